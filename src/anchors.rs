@@ -0,0 +1,206 @@
+use crate::plain_text::heading_text;
+use crate::search_index::json_string;
+
+/// A heading anchor of a generated page, so release tooling and other scripts can
+/// deep-link to a section.
+///
+/// # Fields
+///
+/// - `text`: The plain text of the heading.
+/// - `id`: The id of the heading section, after duplicate ids got their `-1`, `-2`...
+/// - `file`: The generated page holding the heading, relative to the output
+///   directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anchor {
+    pub text: String,
+    pub id: String,
+    pub file: String,
+}
+
+/// Lists the heading anchors of a converted page, in document order.
+///
+/// The anchors are read back from the `<section id="...">` elements of the HTML,
+/// so they are the ids the page really has: headings only get one with
+/// `ParserOptions::sections`, otherwise the page has no anchors.
+///
+/// # Example
+///
+/// ```rust
+/// let options = md2html::ParserOptions {
+///     sections: true,
+///     ..Default::default()
+/// };
+/// let lines = vec![String::from("# Setup"), String::from("## Setup")];
+/// let html = md2html::tokenize_text_with_options(lines.into_iter(), &options).html_lines;
+/// let anchors = md2html::anchors::page_anchors("guide.html", &html);
+/// assert_eq!(anchors[1].text, "Setup");
+/// assert_eq!(anchors[1].id, "setup-1");
+/// assert_eq!(anchors[1].file, "guide.html");
+/// ```
+pub fn page_anchors(file: &str, html_lines: &[String]) -> Vec<Anchor> {
+    let mut anchors = Vec::new();
+    for (i, line) in html_lines.iter().enumerate() {
+        let Some(id) = line
+            .strip_prefix("<section id=\"")
+            .and_then(|rest| rest.strip_suffix("\">"))
+        else {
+            continue;
+        };
+        // The heading follows its section, on several lines when it was wrapped
+        let mut heading = String::new();
+        for heading_line in &html_lines[i + 1..] {
+            heading.push_str(heading_line);
+            if heading_line.contains("</h") {
+                break;
+            }
+            heading.push(' ');
+        }
+        anchors.push(Anchor {
+            text: heading_text(&heading),
+            id: id.to_string(),
+            file: file.to_string(),
+        });
+    }
+    anchors
+}
+
+/// Serializes anchors as a JSON array of `{"text", "id", "file"}` objects.
+///
+/// # Example
+///
+/// ```rust
+/// let anchor = md2html::anchors::Anchor {
+///     text: String::from("Version 1.2"),
+///     id: String::from("version-12"),
+///     file: String::from("changelog.html"),
+/// };
+/// assert_eq!(
+///     md2html::anchors::to_json(&[anchor]),
+///     "[\n  {\"text\": \"Version 1.2\", \"id\": \"version-12\", \"file\": \"changelog.html\"}\n]\n"
+/// );
+/// ```
+pub fn to_json(anchors: &[Anchor]) -> String {
+    let entries: Vec<String> = anchors
+        .iter()
+        .map(|anchor| {
+            format!(
+                "  {{\"text\": {}, \"id\": {}, \"file\": {}}}",
+                json_string(&anchor.text),
+                json_string(&anchor.id),
+                json_string(&anchor.file)
+            )
+        })
+        .collect();
+    match entries.is_empty() {
+        true => String::from("[]\n"),
+        false => format!("[\n{}\n]\n", entries.join(",\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tokenize_text_with_options, ParserOptions};
+
+    fn sections_html(markdown: &str, options: &ParserOptions) -> Vec<String> {
+        let options = ParserOptions {
+            sections: true,
+            ..options.clone()
+        };
+        tokenize_text_with_options(markdown.lines().map(String::from), &options).html_lines
+    }
+
+    /// Checks that every anchor is an id of the HTML, and every id an anchor.
+    fn assert_matches_html(anchors: &[Anchor], html_lines: &[String]) {
+        let html_ids: Vec<&str> = html_lines
+            .iter()
+            .filter_map(|line| line.split_once(" id=\"").map(|(_, rest)| rest))
+            .filter_map(|rest| rest.split_once('"').map(|(id, _)| id))
+            .collect();
+        let anchor_ids: Vec<&str> = anchors.iter().map(|anchor| anchor.id.as_str()).collect();
+        assert_eq!(anchor_ids, html_ids);
+    }
+
+    #[test]
+    fn test_single_document_anchors() {
+        let markdown = "# Changelog\n\n## v1.2.0 — *2024-05-01*\n\n### Fixed\n\n- things\n\n\
+                        ## v1.1.0\n\n### Fixed\n\n```\n## not a heading\n```\n\n### Größe";
+        let html = sections_html(markdown, &ParserOptions::default());
+        let anchors = page_anchors("CHANGELOG.html", &html);
+        let pairs: Vec<(&str, &str)> = anchors
+            .iter()
+            .map(|anchor| (anchor.text.as_str(), anchor.id.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("Changelog", "changelog"),
+                ("v1.2.0 — 2024-05-01", "v120--2024-05-01"),
+                ("Fixed", "fixed"),
+                ("v1.1.0", "v110"),
+                ("Fixed", "fixed-1"),
+                ("Größe", "größe"),
+            ]
+        );
+        assert!(anchors.iter().all(|anchor| anchor.file == "CHANGELOG.html"));
+        assert_matches_html(&anchors, &html);
+
+        // The ids follow the slug options and wrapped headings are still read whole
+        let options = ParserOptions {
+            ascii_slugs: true,
+            wrap_width: Some(12),
+            ..Default::default()
+        };
+        let html = sections_html("## A long heading about Größe", &options);
+        let anchors = page_anchors("page.html", &html);
+        assert_eq!(anchors[0].text, "A long heading about Größe");
+        assert_eq!(anchors[0].id, "a-long-heading-about-grosse");
+        assert_matches_html(&anchors, &html);
+    }
+
+    #[test]
+    fn test_split_document_anchors() {
+        // A document split into pages, every page has its own ids
+        let pages = [
+            ("index.html", "# Release notes\n\n## Upgrading"),
+            ("v2.html", "# Version 2\n\n## Upgrading\n\n## Upgrading"),
+            ("v1.html", "# Version 1\n\n## Upgrading"),
+        ];
+        let mut anchors = Vec::new();
+        for (file, markdown) in pages {
+            let html = sections_html(markdown, &ParserOptions::default());
+            let page = page_anchors(file, &html);
+            assert_matches_html(&page, &html);
+            anchors.extend(page);
+        }
+        let entries: Vec<(&str, &str)> = anchors
+            .iter()
+            .map(|anchor| (anchor.file.as_str(), anchor.id.as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("index.html", "release-notes"),
+                ("index.html", "upgrading"),
+                ("v2.html", "version-2"),
+                ("v2.html", "upgrading"),
+                ("v2.html", "upgrading-1"),
+                ("v1.html", "version-1"),
+                ("v1.html", "upgrading"),
+            ]
+        );
+        assert!(to_json(&anchors)
+            .contains("{\"text\": \"Upgrading\", \"id\": \"upgrading-1\", \"file\": \"v2.html\"}"));
+    }
+
+    #[test]
+    fn test_no_anchors_without_sections() {
+        let html = tokenize_text_with_options(
+            ["# Title", "## Part"].iter().map(|line| line.to_string()),
+            &ParserOptions::default(),
+        )
+        .html_lines;
+        assert!(page_anchors("page.html", &html).is_empty());
+        assert_eq!(to_json(&[]), "[]\n");
+    }
+}
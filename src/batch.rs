@@ -0,0 +1,431 @@
+use crate::output;
+use crate::search_index::json_string;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// How many outputs in a row can fail to be written before a batch stops.
+pub const DEFAULT_MAX_WRITE_FAILURES: usize = 5;
+
+/// Recursively collects every markdown file under `dir`, in the order of
+/// `sort_inputs` whatever order the filesystem lists them in.
+///
+/// # Example
+///
+/// ```rust
+/// let dir = std::env::temp_dir().join("md2html-doc-batch");
+/// std::fs::create_dir_all(dir.join("guide")).unwrap();
+/// std::fs::write(dir.join("index.md"), "# Home").unwrap();
+/// std::fs::write(dir.join("guide/setup.md"), "# Setup").unwrap();
+///
+/// let files = md2html::batch::collect_markdown_files(&dir).unwrap();
+/// assert_eq!(files, vec![dir.join("guide/setup.md"), dir.join("index.md")]);
+/// ```
+pub fn collect_markdown_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    collect_markdown_files_excluding(dir, None)
+}
+
+/// Same as `collect_markdown_files`, but the `excluded` folder is skipped, e.g. an
+/// output folder created inside the input folder.
+pub fn collect_markdown_files_excluding(
+    dir: &Path,
+    excluded: Option<&Path>,
+) -> io::Result<Vec<PathBuf>> {
+    let excluded = excluded.map(output::resolve_path);
+    let mut files = Vec::new();
+    collect_into(dir, excluded.as_deref(), &mut files)?;
+    sort_inputs(&mut files);
+    Ok(files)
+}
+
+fn collect_into(dir: &Path, excluded: Option<&Path>, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if excluded.is_some_and(|excluded| output::resolve_path(&path) == excluded) {
+                continue;
+            }
+            collect_into(&path, excluded, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Sorts input paths lexicographically, comparing their components one by one
+/// so the order doesn't depend on the platform path separator.
+pub fn sort_inputs(paths: &mut [PathBuf]) {
+    paths.sort_by(|a, b| {
+        let components = |path: &PathBuf| {
+            path.components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+        };
+        components(a).cmp(&components(b))
+    });
+}
+
+/// An output of a batch that couldn't be written.
+///
+/// # Fields
+///
+/// - `path`: The file that was being written.
+/// - `error`: The OS error that stopped it (no space left, permission denied...).
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// What became of the outputs of a batch, see `BatchWriter`.
+///
+/// # Fields
+///
+/// - `written`: The files written, in order.
+/// - `failures`: The files that couldn't be written, in order.
+/// - `aborted`: Whether the batch stopped early after too many failures in a row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchReport {
+    pub written: Vec<PathBuf>,
+    pub failures: Vec<WriteFailure>,
+    pub aborted: bool,
+}
+
+impl BatchReport {
+    /// The summary of the batch, one line per failed output after the counts.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use md2html::batch::{BatchReport, WriteFailure};
+    ///
+    /// let report = BatchReport {
+    ///     written: vec!["index.html".into()],
+    ///     failures: vec![WriteFailure {
+    ///         path: "guide.html".into(),
+    ///         error: String::from("No space left on device (os error 28)"),
+    ///     }],
+    ///     aborted: false,
+    /// };
+    /// assert_eq!(
+    ///     report.summary(),
+    ///     [
+    ///         "1 file(s) written, 1 failed:",
+    ///         "  guide.html: No space left on device (os error 28)",
+    ///     ]
+    /// );
+    /// ```
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} file(s) written, {} failed{}",
+            self.written.len(),
+            self.failures.len(),
+            if self.failures.is_empty() { "" } else { ":" }
+        )];
+        lines.extend(
+            self.failures
+                .iter()
+                .map(|failure| format!("  {}: {}", failure.path.display(), failure.error)),
+        );
+        lines
+    }
+
+    /// The report as a JSON object, `{"written": [...], "failed": [{"path": ...,
+    /// "error": ...}], "aborted": false}`.
+    pub fn to_json(&self) -> String {
+        let path = |path: &Path| json_string(&path.display().to_string());
+        let written: Vec<String> = self.written.iter().map(|written| path(written)).collect();
+        let failed: Vec<String> = self
+            .failures
+            .iter()
+            .map(|failure| {
+                format!(
+                    "{{\"path\": {}, \"error\": {}}}",
+                    path(&failure.path),
+                    json_string(&failure.error)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"written\": [{}], \"failed\": [{}], \"aborted\": {}}}\n",
+            written.join(", "),
+            failed.join(", "),
+            self.aborted
+        )
+    }
+}
+
+/// The error stopping a batch once too many outputs in a row failed to be written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TooManyFailures {
+    pub count: usize,
+}
+
+impl fmt::Display for TooManyFailures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "aborting after {} consecutive write failures",
+            self.count
+        )
+    }
+}
+
+impl std::error::Error for TooManyFailures {}
+
+/// Writes the outputs of a batch, recording the ones that fail instead of giving
+/// up on the whole batch: a full disk or a read-only folder costs those files only.
+///
+/// Every file is written atomically (see `output::write_atomically`), so a write
+/// failing midway never leaves a truncated page behind. Once
+/// `max_consecutive_failures` files in a row have failed, the next writes are
+/// pointless and `write` returns `TooManyFailures`; `0` never stops.
+///
+/// # Example
+///
+/// ```rust
+/// let dir = std::env::temp_dir().join("md2html-doc-batch-writer");
+/// let mut writer = md2html::batch::BatchWriter::new(3);
+/// writer.write(&dir.join("index.html"), b"<p>Home</p>").unwrap();
+/// let report = writer.into_report();
+/// assert_eq!(report.written, [dir.join("index.html")]);
+/// assert!(report.failures.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct BatchWriter {
+    max_consecutive_failures: usize,
+    consecutive_failures: usize,
+    report: BatchReport,
+}
+
+impl BatchWriter {
+    pub fn new(max_consecutive_failures: usize) -> Self {
+        BatchWriter {
+            max_consecutive_failures,
+            consecutive_failures: 0,
+            report: BatchReport::default(),
+        }
+    }
+
+    /// Writes `contents` to `path`, see `write_with`.
+    pub fn write(&mut self, path: &Path, contents: &[u8]) -> Result<(), TooManyFailures> {
+        self.write_with(path, |file| file.write_all(contents))
+    }
+
+    /// Writes to `path` what `write` writes to the file it's given. A failure is
+    /// recorded in the report, and only returned once it makes too many in a row.
+    pub fn write_with(
+        &mut self,
+        path: &Path,
+        write: impl FnOnce(&mut File) -> io::Result<()>,
+    ) -> Result<(), TooManyFailures> {
+        match output::write_atomically_with(path, write) {
+            Ok(()) => {
+                self.consecutive_failures = 0;
+                self.report.written.push(path.to_path_buf());
+            }
+            Err(err) => {
+                self.consecutive_failures += 1;
+                self.report.failures.push(WriteFailure {
+                    path: path.to_path_buf(),
+                    error: err.source.to_string(),
+                });
+                if self.consecutive_failures == self.max_consecutive_failures {
+                    self.report.aborted = true;
+                    return Err(TooManyFailures {
+                        count: self.consecutive_failures,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The report of the writes so far.
+    pub fn report(&self) -> &BatchReport {
+        &self.report
+    }
+
+    pub fn into_report(self) -> BatchReport {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_index::{build_search_page, to_json, DEFAULT_BODY_LIMIT};
+    use crate::tokenize_text_with_options;
+
+    /// Converts the files of `root` in the order of `inputs`, the way directory
+    /// mode does, returning every generated artifact.
+    fn convert_tree(root: &Path, mut inputs: Vec<PathBuf>) -> Vec<(PathBuf, String)> {
+        sort_inputs(&mut inputs);
+        let mut artifacts = Vec::new();
+        let mut search_pages = Vec::new();
+        for path in inputs {
+            let markdown = fs::read_to_string(&path).unwrap();
+            let result =
+                tokenize_text_with_options(markdown.lines().map(String::from), &Default::default());
+            let relative = path.strip_prefix(root).unwrap().with_extension("html");
+            search_pages.push(build_search_page(
+                "",
+                &relative.to_string_lossy(),
+                &result.html_lines,
+                DEFAULT_BODY_LIMIT,
+            ));
+            artifacts.push((relative, result.html_lines.join("\n")));
+        }
+        artifacts.push((PathBuf::from("search-index.json"), to_json(&search_pages)));
+        artifacts
+    }
+
+    #[test]
+    fn test_shuffled_discovery_gives_identical_artifacts() {
+        let root = std::env::temp_dir().join("md2html-batch-reproducible");
+        let _ = fs::remove_dir_all(&root);
+        for (path, markdown) in [
+            ("index.md", "# Home\nWelcome"),
+            ("b.md", "# B"),
+            ("a/z.md", "# Z\n- item"),
+            ("a/b.md", "---\ndate: 2024-06-01\n---\n# AB"),
+            ("a-b.md", "# Dash"),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, markdown).unwrap();
+        }
+
+        let discovered = collect_markdown_files(&root).unwrap();
+        let names: Vec<String> = discovered
+            .iter()
+            .map(|path| path.strip_prefix(&root).unwrap().display().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["a/b.md", "a/z.md", "a-b.md", "b.md", "index.md"]
+        );
+
+        let mut shuffled = discovered.clone();
+        shuffled.reverse();
+        shuffled.swap(0, 2);
+        assert_eq!(
+            convert_tree(&root, discovered),
+            convert_tree(&root, shuffled)
+        );
+    }
+
+    #[test]
+    fn test_nested_output_root_excluded() {
+        let root = std::env::temp_dir().join("md2html-batch-nested-output");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("site/guide")).unwrap();
+        fs::write(root.join("index.md"), "# Home").unwrap();
+        fs::write(root.join("site/guide/copied.md"), "# Copied").unwrap();
+
+        let files = collect_markdown_files_excluding(&root, Some(&root.join("./site"))).unwrap();
+        assert_eq!(files, vec![root.join("index.md")]);
+        assert_eq!(collect_markdown_files(&root).unwrap().len(), 2);
+    }
+
+    /// A writer accepting `left` bytes, then failing like a full disk.
+    struct FailAfter<'a> {
+        inner: &'a mut File,
+        left: usize,
+    }
+
+    impl Write for FailAfter<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.left == 0 {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "no space left"));
+            }
+            let length = buf.len().min(self.left);
+            self.left -= length;
+            self.inner.write(&buf[..length])
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    fn write_page(
+        writer: &mut BatchWriter,
+        path: &Path,
+        left: usize,
+    ) -> Result<(), TooManyFailures> {
+        writer.write_with(path, |file| {
+            FailAfter { inner: file, left }.write_all(b"<h1>A page long enough</h1>\n")
+        })
+    }
+
+    fn batch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("md2html-batch-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_failures_recorded_without_partial_files() {
+        let dir = batch_dir("write-failures");
+        let mut writer = BatchWriter::new(DEFAULT_MAX_WRITE_FAILURES);
+        write_page(&mut writer, &dir.join("a.html"), usize::MAX).unwrap();
+        write_page(&mut writer, &dir.join("b.html"), 10).unwrap();
+        write_page(&mut writer, &dir.join("c/d.html"), usize::MAX).unwrap();
+
+        let report = writer.into_report();
+        assert_eq!(report.written, [dir.join("a.html"), dir.join("c/d.html")]);
+        assert_eq!(
+            report.failures,
+            [WriteFailure {
+                path: dir.join("b.html"),
+                error: String::from("no space left"),
+            }]
+        );
+        assert!(!report.aborted);
+        let summary = report.summary();
+        assert_eq!(summary[0], "2 file(s) written, 1 failed:");
+        assert_eq!(
+            summary[1],
+            format!("  {}: no space left", dir.join("b.html").display())
+        );
+        assert!(report.to_json().contains(&format!(
+            "\"failed\": [{{\"path\": {}, \"error\": \"no space left\"}}]",
+            json_string(&dir.join("b.html").display().to_string())
+        )));
+
+        // The failed page left nothing behind, not even its temporary file
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.html", "c"]);
+    }
+
+    #[test]
+    fn test_consecutive_write_failures_abort() {
+        let dir = batch_dir("write-abort");
+        let mut writer = BatchWriter::new(2);
+        write_page(&mut writer, &dir.join("a.html"), 0).unwrap();
+        write_page(&mut writer, &dir.join("b.html"), usize::MAX).unwrap();
+        // A success in between starts the count again
+        write_page(&mut writer, &dir.join("c.html"), 3).unwrap();
+        let err = write_page(&mut writer, &dir.join("d.html"), 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "aborting after 2 consecutive write failures"
+        );
+
+        let report = writer.report();
+        assert!(report.aborted);
+        assert_eq!(report.written, [dir.join("b.html")]);
+        assert_eq!(report.failures.len(), 3);
+        assert_eq!(report.summary().len(), 4);
+        assert!(report.to_json().ends_with("\"aborted\": true}\n"));
+        assert!(!dir.join("d.html").exists());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::escape::escape_text;
+use regex::Regex;
+
+/// A chapter listed in a book's `SUMMARY.md`.
+///
+/// # Fields
+///
+/// - `title`: The link text of the chapter in the summary.
+/// - `path`: The markdown file of the chapter, relative to the book directory.
+/// - `depth`: How deep the chapter is nested in the summary, `0` for top-level chapters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    pub path: String,
+    pub depth: usize,
+}
+
+impl Chapter {
+    /// The URL of the generated page, relative to the output directory.
+    pub fn url(&self) -> String {
+        match self.path.strip_suffix(".md") {
+            Some(stem) => format!("{}.html", stem),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// Parses the chapters of a `SUMMARY.md`, in reading order.
+///
+/// Every list item (`-`, `*`, `+` or `1.`) made of a single link is a chapter, and
+/// its indentation gives the nesting. Lines that aren't list items (e.g. the
+/// `# Summary` title) are ignored, as are draft chapters with an empty link
+/// (`- [Later]()`).
+///
+/// # Example
+///
+/// ```rust
+/// let summary = "# Summary\n- [Intro](intro.md)\n  - [Setup](guide/setup.md)";
+/// let chapters = md2html::book::parse_summary(summary);
+/// assert_eq!(chapters[1].path, "guide/setup.md");
+/// assert_eq!(chapters[1].depth, 1);
+/// ```
+pub fn parse_summary(summary: &str) -> Vec<Chapter> {
+    let item_re = Regex::new(r"^(\s*)(?:[-*+]|\d{1,9}\.)\s+\[([^\]]*)\]\(([^)]*)\)\s*$").unwrap();
+    let mut chapters = Vec::new();
+    // Indentation widths of the enclosing list levels
+    let mut indents: Vec<usize> = Vec::new();
+
+    for line in summary.lines() {
+        let Some(groups) = item_re.captures(line) else {
+            continue;
+        };
+        let indent = groups[1].replace('\t', "    ").len();
+        while indents.last().is_some_and(|&open| open > indent) {
+            indents.pop();
+        }
+        if indents.last().is_none_or(|&open| open < indent) {
+            indents.push(indent);
+        }
+
+        let path = groups[3].trim();
+        if path.is_empty() {
+            continue;
+        }
+        chapters.push(Chapter {
+            title: groups[2].trim().to_string(),
+            path: path.trim_start_matches("./").to_string(),
+            depth: indents.len() - 1,
+        });
+    }
+    chapters
+}
+
+/// Renders the sidebar navigation tree of the page of `chapters[current]`.
+///
+/// The tree mirrors the nesting of the summary with nested `<ol>` lists. Links are
+/// relative to the current page, which is highlighted with `class="active"` and
+/// `aria-current="page"`.
+///
+/// # Example
+///
+/// ```rust
+/// let chapters = md2html::book::parse_summary("- [Intro](intro.md)\n- [Usage](usage.md)");
+/// let sidebar = md2html::book::sidebar_html(&chapters, 1);
+/// assert!(sidebar.contains("<li><a href=\"intro.html\">Intro</a>"));
+/// assert!(sidebar.contains("<a href=\"usage.html\" class=\"active\" aria-current=\"page\">Usage</a>"));
+/// ```
+pub fn sidebar_html(chapters: &[Chapter], current: usize) -> String {
+    let current_url = chapters[current].url();
+    let mut lines = vec![
+        String::from("<nav class=\"sidebar\" aria-label=\"Chapters\">"),
+        String::from("<ol>"),
+    ];
+    // Depth of every open <li>, and whether its nested <ol> is open
+    let mut open_items: Vec<(usize, bool)> = Vec::new();
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        while open_items
+            .last()
+            .is_some_and(|&(depth, _)| depth >= chapter.depth)
+        {
+            close_item(open_items.pop().unwrap(), &mut lines);
+        }
+        if let Some(parent) = open_items.last_mut() {
+            if !parent.1 {
+                parent.1 = true;
+                lines.push(String::from("<ol>"));
+            }
+        }
+
+        let href = relative_url(&current_url, &chapter.url());
+        let title = escape_text(&chapter.title);
+        if index == current {
+            lines.push(format!(
+                "<li><a href=\"{}\" class=\"active\" aria-current=\"page\">{}</a>",
+                href, title
+            ));
+        } else {
+            lines.push(format!("<li><a href=\"{}\">{}</a>", href, title));
+        }
+        open_items.push((chapter.depth, false));
+    }
+
+    while let Some(item) = open_items.pop() {
+        close_item(item, &mut lines);
+    }
+    lines.push(String::from("</ol>"));
+    lines.push(String::from("</nav>"));
+    lines.join("\n")
+}
+
+fn close_item((_, has_children): (usize, bool), lines: &mut Vec<String>) {
+    if has_children {
+        lines.push(String::from("</ol>"));
+    }
+    lines.push(String::from("</li>"));
+}
+
+/// Returns the URL of `to` relative to the page `from`, both relative to the site root.
+///
+/// # Example
+///
+/// ```rust
+/// assert_eq!(md2html::book::relative_url("guide/setup.html", "intro.html"), "../intro.html");
+/// assert_eq!(md2html::book::relative_url("guide/setup.html", "guide/usage.html"), "usage.html");
+/// ```
+pub fn relative_url(from: &str, to: &str) -> String {
+    let from_dirs: Vec<&str> = from.split('/').collect();
+    let from_dirs = &from_dirs[..from_dirs.len() - 1];
+    let to_parts: Vec<&str> = to.split('/').collect();
+
+    let common = from_dirs
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts = vec![".."; from_dirs.len() - common];
+    parts.extend(&to_parts[common..]);
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::standalone::{wrap_page, PageOptions};
+
+    const SUMMARY: &str = "# Summary
+
+- [Introduction](./intro.md)
+- [User Guide](guide/index.md)
+    - [Setup](guide/setup.md)
+- [Later chapter]()
+";
+
+    #[test]
+    fn test_parse_summary() {
+        let chapters = parse_summary(SUMMARY);
+        assert_eq!(
+            chapters,
+            vec![
+                Chapter {
+                    title: String::from("Introduction"),
+                    path: String::from("intro.md"),
+                    depth: 0,
+                },
+                Chapter {
+                    title: String::from("User Guide"),
+                    path: String::from("guide/index.md"),
+                    depth: 0,
+                },
+                Chapter {
+                    title: String::from("Setup"),
+                    path: String::from("guide/setup.md"),
+                    depth: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_summary_ordered_and_dedent() {
+        let chapters =
+            parse_summary("1. [A](a.md)\n   1. [B](b.md)\n      - [C](c.md)\n2. [D](d.md)");
+        let depths: Vec<usize> = chapters.iter().map(|c| c.depth).collect();
+        assert_eq!(depths, vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_book_pages_sidebar() {
+        let chapters = parse_summary(SUMMARY);
+        let pages: Vec<Vec<String>> = (0..chapters.len())
+            .map(|index| {
+                let options = PageOptions {
+                    title: chapters[index].title.clone(),
+                    sidebar_html: Some(sidebar_html(&chapters, index)),
+                    ..Default::default()
+                };
+                wrap_page(&[String::from("<p>Text</p>")], &options)
+            })
+            .collect();
+
+        let nav_start = |page: &Vec<String>| {
+            page.iter()
+                .position(|line| line.starts_with("<nav class=\"sidebar\""))
+                .unwrap()
+        };
+        assert_eq!(
+            pages[2][nav_start(&pages[2])..nav_start(&pages[2]) + 12],
+            [
+                "<nav class=\"sidebar\" aria-label=\"Chapters\">",
+                "<ol>",
+                "<li><a href=\"../intro.html\">Introduction</a>",
+                "</li>",
+                "<li><a href=\"index.html\">User Guide</a>",
+                "<ol>",
+                "<li><a href=\"setup.html\" class=\"active\" aria-current=\"page\">Setup</a>",
+                "</li>",
+                "</ol>",
+                "</li>",
+                "</ol>",
+                "</nav>",
+            ]
+        );
+        for (index, page) in pages.iter().enumerate() {
+            let active: Vec<&String> = page
+                .iter()
+                .filter(|l| l.contains("class=\"active\""))
+                .collect();
+            assert_eq!(active.len(), 1);
+            assert!(active[0].ends_with(&format!(">{}</a>", chapters[index].title)));
+            // The sidebar comes before the content
+            assert!(nav_start(page) < page.iter().position(|l| l.starts_with("<main")).unwrap());
+        }
+        assert!(pages[0].contains(&String::from("<li><a href=\"guide/setup.html\">Setup</a>")));
+    }
+}
@@ -0,0 +1,460 @@
+use crate::sections::SlugStyle;
+use crate::soft_breaks::SoftBreak;
+use crate::ParserOptions;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The name of the configuration files looked up in the input directory of a batch
+/// conversion and in each of its subdirectories.
+pub const CONFIG_FILE_NAME: &str = "md2html.toml";
+
+/// The settings of a `md2html.toml` file.
+///
+/// Every setting is optional: a file only overrides what it sets, everything else
+/// comes from the configuration files of the parent directories (see `resolve`).
+/// The keys are the names of the `ParserOptions` fields they set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub wrap_width: Option<usize>,
+    pub link_endnotes: Option<bool>,
+    pub media_embeds: Option<bool>,
+    pub embeds: Option<bool>,
+    pub sections: Option<bool>,
+    pub literal_paths: Option<bool>,
+    pub autolink_bare_urls: Option<bool>,
+    pub quote_attribution: Option<bool>,
+    pub allow_raw: Option<bool>,
+    pub allow_raw_html: Option<bool>,
+    pub ascii_slugs: Option<bool>,
+    pub slug_style: Option<SlugStyle>,
+    pub toc: Option<bool>,
+    pub toc_depth: Option<u8>,
+    pub heading_anchors: Option<bool>,
+    pub normalize_unicode: Option<bool>,
+    pub soft_break_default: Option<SoftBreak>,
+    pub soft_break_in_quotes: Option<SoftBreak>,
+    pub lint: Option<bool>,
+    pub highlight: Option<bool>,
+    pub headings: Option<bool>,
+    pub blockquotes: Option<bool>,
+    pub lists: Option<bool>,
+    pub code_fences: Option<bool>,
+    pub horizontal_rules: Option<bool>,
+    pub tables: Option<bool>,
+}
+
+/// A line of a configuration file that couldn't be read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Config {
+    /// Layers `other` over this configuration: the settings `other` has win, the
+    /// others are kept.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use md2html::config::Config;
+    ///
+    /// let root = Config { sections: Some(true), embeds: Some(true), ..Default::default() };
+    /// let api = Config { sections: Some(false), ..Default::default() };
+    /// let merged = root.merge(&api);
+    /// assert_eq!(merged.sections, Some(false));
+    /// assert_eq!(merged.embeds, Some(true));
+    /// ```
+    pub fn merge(&self, other: &Config) -> Config {
+        Config {
+            wrap_width: other.wrap_width.or(self.wrap_width),
+            link_endnotes: other.link_endnotes.or(self.link_endnotes),
+            media_embeds: other.media_embeds.or(self.media_embeds),
+            embeds: other.embeds.or(self.embeds),
+            sections: other.sections.or(self.sections),
+            literal_paths: other.literal_paths.or(self.literal_paths),
+            autolink_bare_urls: other.autolink_bare_urls.or(self.autolink_bare_urls),
+            quote_attribution: other.quote_attribution.or(self.quote_attribution),
+            allow_raw: other.allow_raw.or(self.allow_raw),
+            allow_raw_html: other.allow_raw_html.or(self.allow_raw_html),
+            ascii_slugs: other.ascii_slugs.or(self.ascii_slugs),
+            slug_style: other.slug_style.or(self.slug_style),
+            toc: other.toc.or(self.toc),
+            toc_depth: other.toc_depth.or(self.toc_depth),
+            heading_anchors: other.heading_anchors.or(self.heading_anchors),
+            normalize_unicode: other.normalize_unicode.or(self.normalize_unicode),
+            soft_break_default: other.soft_break_default.or(self.soft_break_default),
+            soft_break_in_quotes: other.soft_break_in_quotes.or(self.soft_break_in_quotes),
+            lint: other.lint.or(self.lint),
+            highlight: other.highlight.or(self.highlight),
+            headings: other.headings.or(self.headings),
+            blockquotes: other.blockquotes.or(self.blockquotes),
+            lists: other.lists.or(self.lists),
+            code_fences: other.code_fences.or(self.code_fences),
+            horizontal_rules: other.horizontal_rules.or(self.horizontal_rules),
+            tables: other.tables.or(self.tables),
+        }
+    }
+
+    /// Returns `options` with the settings of this configuration.
+    pub fn apply(&self, options: &ParserOptions) -> ParserOptions {
+        ParserOptions {
+            wrap_width: self.wrap_width.or(options.wrap_width),
+            link_endnotes: self.link_endnotes.unwrap_or(options.link_endnotes),
+            media_embeds: self.media_embeds.unwrap_or(options.media_embeds),
+            embeds: self.embeds.unwrap_or(options.embeds),
+            sections: self.sections.unwrap_or(options.sections),
+            literal_paths: self.literal_paths.unwrap_or(options.literal_paths),
+            autolink_bare_urls: self
+                .autolink_bare_urls
+                .unwrap_or(options.autolink_bare_urls),
+            quote_attribution: self.quote_attribution.unwrap_or(options.quote_attribution),
+            allow_raw: self.allow_raw.unwrap_or(options.allow_raw),
+            allow_raw_html: self.allow_raw_html.unwrap_or(options.allow_raw_html),
+            ascii_slugs: self.ascii_slugs.unwrap_or(options.ascii_slugs),
+            slug_style: self.slug_style.unwrap_or(options.slug_style),
+            toc: self.toc.unwrap_or(options.toc),
+            toc_depth: self.toc_depth.unwrap_or(options.toc_depth),
+            heading_anchors: self.heading_anchors.unwrap_or(options.heading_anchors),
+            normalize_unicode: self.normalize_unicode.unwrap_or(options.normalize_unicode),
+            soft_break_default: self
+                .soft_break_default
+                .unwrap_or(options.soft_break_default),
+            soft_break_in_quotes: self.soft_break_in_quotes.or(options.soft_break_in_quotes),
+            lint: self.lint.unwrap_or(options.lint),
+            highlight: self.highlight.unwrap_or(options.highlight),
+            headings: self.headings.unwrap_or(options.headings),
+            blockquotes: self.blockquotes.unwrap_or(options.blockquotes),
+            lists: self.lists.unwrap_or(options.lists),
+            code_fences: self.code_fences.unwrap_or(options.code_fences),
+            horizontal_rules: self.horizontal_rules.unwrap_or(options.horizontal_rules),
+            tables: self.tables.unwrap_or(options.tables),
+            ..options.clone()
+        }
+    }
+}
+
+/// Merges configuration layers, from the lowest precedence to the highest: the
+/// root configuration first, then the ones of deeper directories, and the command
+/// line flags last.
+pub fn resolve(layers: &[Config]) -> Config {
+    layers
+        .iter()
+        .fold(Config::default(), |merged, layer| merged.merge(layer))
+}
+
+/// Parses the content of a `md2html.toml` file.
+///
+/// Only the subset of TOML the settings need is supported: `key = value` lines
+/// with boolean, integer and string values, comments and blank lines. Unknown keys are
+/// errors, so a typo doesn't silently leave a setting out.
+///
+/// # Example
+///
+/// ```rust
+/// let config = md2html::config::parse_config("# Docs\nsections = true\nwrap_width = 80").unwrap();
+/// assert_eq!(config.sections, Some(true));
+/// assert_eq!(config.wrap_width, Some(80));
+/// assert!(md2html::config::parse_config("section = true").is_err());
+/// ```
+pub fn parse_config(text: &str) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ConfigError {
+            line: index + 1,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(format!("expected `key = value`, found \"{}\"", line)));
+        };
+        let key = key.trim();
+        // A comment may follow the value
+        let value = value.split('#').next().unwrap().trim();
+        let boolean = || match value {
+            "true" => Ok(Some(true)),
+            "false" => Ok(Some(false)),
+            _ => Err(error(format!("{} must be true or false", key))),
+        };
+        match key {
+            "wrap_width" => {
+                let width = value
+                    .parse()
+                    .map_err(|_| error(format!("{} must be a number", key)))?;
+                config.wrap_width = Some(width);
+            }
+            "toc_depth" => {
+                let depth = value
+                    .parse()
+                    .map_err(|_| error(format!("{} must be a number", key)))?;
+                config.toc_depth = Some(depth);
+            }
+            "toc" => config.toc = boolean()?,
+            "heading_anchors" => config.heading_anchors = boolean()?,
+            "normalize_unicode" => config.normalize_unicode = boolean()?,
+            "link_endnotes" => config.link_endnotes = boolean()?,
+            "media_embeds" => config.media_embeds = boolean()?,
+            "embeds" => config.embeds = boolean()?,
+            "sections" => config.sections = boolean()?,
+            "literal_paths" => config.literal_paths = boolean()?,
+            "autolink_bare_urls" => config.autolink_bare_urls = boolean()?,
+            "quote_attribution" => config.quote_attribution = boolean()?,
+            "allow_raw" => config.allow_raw = boolean()?,
+            "allow_raw_html" => config.allow_raw_html = boolean()?,
+            "ascii_slugs" => config.ascii_slugs = boolean()?,
+            "slug_style" => {
+                let style = value.trim_matches('"').parse().map_err(error)?;
+                config.slug_style = Some(style);
+            }
+            "soft_break_default" => {
+                let policy = value.trim_matches('"').parse().map_err(error)?;
+                config.soft_break_default = Some(policy);
+            }
+            "soft_break_in_quotes" => {
+                let policy = value.trim_matches('"').parse().map_err(error)?;
+                config.soft_break_in_quotes = Some(policy);
+            }
+            "lint" => config.lint = boolean()?,
+            "highlight" => config.highlight = boolean()?,
+            "headings" => config.headings = boolean()?,
+            "blockquotes" => config.blockquotes = boolean()?,
+            "lists" => config.lists = boolean()?,
+            "code_fences" => config.code_fences = boolean()?,
+            "horizontal_rules" => config.horizontal_rules = boolean()?,
+            "tables" => config.tables = boolean()?,
+            _ => return Err(error(format!("unknown setting \"{}\"", key))),
+        }
+    }
+    Ok(config)
+}
+
+/// The block constructs `parse_disabled` knows, named like their settings.
+pub const BLOCK_CONSTRUCTS: &[&str] = &[
+    "headings",
+    "blockquotes",
+    "lists",
+    "code_fences",
+    "horizontal_rules",
+    "tables",
+];
+
+/// Parses a comma separated list of block constructs (`blockquotes,headings`) into
+/// the configuration disabling them, as given to `--disable`.
+///
+/// # Example
+///
+/// ```rust
+/// let config = md2html::config::parse_disabled("blockquotes, headings").unwrap();
+/// assert_eq!(config.blockquotes, Some(false));
+/// assert_eq!(config.headings, Some(false));
+/// assert_eq!(config.lists, None);
+/// assert!(md2html::config::parse_disabled("quotes").is_err());
+/// ```
+pub fn parse_disabled(list: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    for name in list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let setting = match name {
+            "headings" => &mut config.headings,
+            "blockquotes" => &mut config.blockquotes,
+            "lists" => &mut config.lists,
+            "code_fences" => &mut config.code_fences,
+            "horizontal_rules" => &mut config.horizontal_rules,
+            "tables" => &mut config.tables,
+            _ => {
+                return Err(format!(
+                    "unknown construct \"{}\", expected one of {}",
+                    name,
+                    BLOCK_CONSTRUCTS.join(", ")
+                ))
+            }
+        };
+        *setting = Some(false);
+    }
+    Ok(config)
+}
+
+/// Reads the configuration files that apply to the markdown file `relative_path`
+/// of the batch in `root`: the one of `root` and the ones of every directory down
+/// to the file, in that order, as expected by `resolve`.
+///
+/// Directories without a configuration file are skipped. Errors name the file
+/// that couldn't be read.
+pub fn directory_configs(root: &Path, relative_path: &Path) -> Result<Vec<Config>, String> {
+    let mut configs = Vec::new();
+    let mut dir = root.to_path_buf();
+    let mut dirs = vec![dir.clone()];
+    if let Some(parent) = relative_path.parent() {
+        for component in parent.components() {
+            dir.push(component);
+            dirs.push(dir.clone());
+        }
+    }
+    for dir in dirs {
+        let path = dir.join(CONFIG_FILE_NAME);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        };
+        configs.push(parse_config(&text).map_err(|err| format!("{}: {}", path.display(), err))?);
+    }
+    Ok(configs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Converter;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(
+            "# Root settings\n\nsections = true  # wrap headings\n  embeds=false\nwrap_width = 72\n\
+             slug_style = \"github\"\nsoft_break_in_quotes = \"break\"\nsoft_break_default = \"space\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                sections: Some(true),
+                embeds: Some(false),
+                wrap_width: Some(72),
+                slug_style: Some(SlugStyle::GitHub),
+                soft_break_default: Some(SoftBreak::Space),
+                soft_break_in_quotes: Some(SoftBreak::Break),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        let error = |text| parse_config(text).unwrap_err();
+        assert_eq!(error("sections = true\nsection = true").line, 2);
+        assert!(error("sections = yes").message.contains("true or false"));
+        assert!(error("wrap_width = -1").message.contains("number"));
+        assert!(error("slug_style = gitlab")
+            .message
+            .contains("default or github"));
+        assert!(error("soft_break_in_quotes = \"newline\"")
+            .message
+            .contains("space or break"));
+        assert!(error("[options]").message.contains("key = value"));
+    }
+
+    #[test]
+    fn test_merge_keeps_unset_settings() {
+        let base = Config {
+            sections: Some(true),
+            wrap_width: Some(80),
+            ..Default::default()
+        };
+        assert_eq!(base.merge(&Config::default()), base);
+        assert_eq!(Config::default().merge(&base), base);
+        let over = Config {
+            wrap_width: Some(100),
+            embeds: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            base.merge(&over),
+            Config {
+                sections: Some(true),
+                wrap_width: Some(100),
+                embeds: Some(false),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let root = parse_config("sections = true\nembeds = true\nmedia_embeds = true").unwrap();
+        let api = parse_config("sections = false\nembeds = false").unwrap();
+        let api_v2 = parse_config("embeds = true").unwrap();
+        let cli = Config {
+            sections: Some(true),
+            ..Default::default()
+        };
+        // Deeper directories override the root, flags override everything
+        let merged = resolve(&[root.clone(), api.clone(), api_v2]);
+        assert_eq!(merged.sections, Some(false));
+        assert_eq!(merged.embeds, Some(true));
+        assert_eq!(merged.media_embeds, Some(true));
+        assert_eq!(resolve(&[root, api, cli]).sections, Some(true));
+        assert_eq!(resolve(&[]), Config::default());
+    }
+
+    #[test]
+    fn test_apply_overrides_only_set_options() {
+        let options = ParserOptions {
+            max_line_length: 10,
+            sections: true,
+            ..Default::default()
+        };
+        let config = Config {
+            sections: Some(false),
+            allow_raw: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.apply(&options),
+            ParserOptions {
+                max_line_length: 10,
+                sections: false,
+                allow_raw: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(Config::default().apply(&options), options);
+    }
+
+    #[test]
+    fn test_directory_override() {
+        let root = std::env::temp_dir().join("md2html-config-override");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("api/v1")).unwrap();
+        fs::write(root.join(CONFIG_FILE_NAME), "sections = true\n").unwrap();
+        fs::write(
+            root.join("api").join(CONFIG_FILE_NAME),
+            "sections = false\n",
+        )
+        .unwrap();
+
+        let convert = |relative_path: &str| {
+            let configs = directory_configs(&root, &PathBuf::from(relative_path)).unwrap();
+            let options = resolve(&configs).apply(&ParserOptions::default());
+            Converter::new()
+                .options(options)
+                .convert_str("# Usage\nRun it.")
+        };
+        assert!(convert("index.md").contains("<section id=\"usage\">"));
+        assert!(convert("guide/setup.md").contains("<section id=\"usage\">"));
+        assert!(!convert("api/index.md").contains("<section"));
+        assert!(!convert("api/v1/ref.md").contains("<section"));
+
+        fs::write(
+            root.join("api").join(CONFIG_FILE_NAME),
+            "sections = maybe\n",
+        )
+        .unwrap();
+        let error = directory_configs(&root, Path::new("api/index.md")).unwrap_err();
+        assert!(error.contains("md2html.toml: line 1"));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::front_matter::FrontMatter;
+use crate::{Converter, ParseWarning, ParserOptions};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Statistics about a finished `convert` call.
+///
+/// # Fields
+///
+/// - `bytes_written`: How many bytes of HTML were written to the output.
+/// - `warnings`: The warnings raised while parsing the input.
+/// - `front_matter`: The front matter block of the input, if it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionStats {
+    pub bytes_written: usize,
+    pub warnings: Vec<ParseWarning>,
+    pub front_matter: Option<FrontMatter>,
+}
+
+/// An error that stopped a `convert` call.
+///
+/// # Variants
+///
+/// - `Read`: The input could not be read (including input that isn't valid UTF-8).
+/// - `Write`: The output could not be written.
+#[derive(Debug)]
+pub enum ConvertError {
+    Read(io::Error),
+    Write(io::Error),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::Read(err) => write!(f, "could not read input: {}", err),
+            ConvertError::Write(err) => write!(f, "could not write output: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConvertError::Read(err) | ConvertError::Write(err) => Some(err),
+        }
+    }
+}
+
+/// Converts markdown read from `input` into HTML written to `output`.
+///
+/// This is `Converter::convert_reader_writer` with the default settings besides
+/// `opts`: the lines of `input` are parsed as they are read, and every generated
+/// HTML line is followed by a `\n`. The output is flushed before returning, I/O
+/// errors from either side are returned as a `ConvertError`.
+///
+/// # Arguments
+///
+/// * `input` - Where the markdown is read from, e.g. a `BufReader<File>` or `Stdin`.
+/// * `output` - Where the HTML is written to, e.g. a `File` or an HTTP response body.
+/// * `opts` - The parser options.
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::Cursor;
+///
+/// let mut html = Vec::new();
+/// let stats = md2html::convert(
+///     Cursor::new("# Title\nSome **text**"),
+///     &mut html,
+///     &md2html::ParserOptions::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(html, b"<h1>Title</h1>\n<p>Some <strong>text</strong></p>\n");
+/// assert_eq!(stats.bytes_written, html.len());
+/// ```
+pub fn convert<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    opts: &ParserOptions,
+) -> Result<ConversionStats, ConvertError> {
+    Converter::new()
+        .options(opts.clone())
+        .convert_reader_writer(input, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A writer that fails once more than `capacity` bytes are written to it.
+    struct FailingWriter {
+        written: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written.len() + buf.len() > self.capacity {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_convert_cursor_to_cursor() {
+        let input = Cursor::new("# Title\n\n- item\n1. first\n");
+        let mut output = Cursor::new(Vec::new());
+        let stats = convert(input, &mut output, &ParserOptions::default()).unwrap();
+
+        let html = String::from_utf8(output.into_inner()).unwrap();
+        assert_eq!(
+            html,
+            "<h1>Title</h1>\n<br/>\n<ul>\n<li>item</li>\n</ul>\n<ol>\n<li>first</li>\n</ol>\n"
+        );
+        assert_eq!(stats.bytes_written, html.len());
+        assert!(stats.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_convert_reports_warnings() {
+        let options = ParserOptions {
+            max_line_length: 3,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let stats = convert(Cursor::new("ok\ntoo long"), &mut output, &options).unwrap();
+        assert_eq!(stats.warnings.len(), 1);
+        assert_eq!(stats.warnings[0].line, 2);
+    }
+
+    #[test]
+    fn test_convert_propagates_write_errors() {
+        let mut output = FailingWriter {
+            written: Vec::new(),
+            capacity: 20,
+        };
+        let input = Cursor::new("# Title\nA paragraph long enough to overflow");
+        let result = convert(input, &mut output, &ParserOptions::default());
+        assert!(matches!(result, Err(ConvertError::Write(_))));
+        assert_eq!(output.written, b"<h1>Title</h1>\n");
+    }
+
+    #[test]
+    fn test_convert_propagates_read_errors() {
+        let input = Cursor::new(vec![b'#', b' ', 0xff, 0xfe]);
+        let result = convert(input, Vec::new(), &ParserOptions::default());
+        match result {
+            Err(ConvertError::Read(err)) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+            _ => panic!("Expected a read error for invalid UTF-8"),
+        }
+    }
+}
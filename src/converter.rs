@@ -0,0 +1,593 @@
+use crate::convert::{ConversionStats, ConvertError};
+use crate::front_matter::{self, DocumentDate, FrontMatter};
+use crate::standalone::{self, PageMeta, PageOptions, WrapDiv};
+use crate::urls::{UrlContext, UrlRewriter};
+use crate::{output, tokenize_text_with_rewriters, HtmlRenderer, ParseWarning, ParserOptions};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+/// A configured markdown to HTML conversion, built once and reused for every
+/// document.
+///
+/// Every setting has a builder method, a fresh `Converter` converts HTML
+/// fragments with the default `ParserOptions`.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::{Converter, HtmlRenderer, ParserOptions};
+///
+/// let converter = Converter::new()
+///     .options(ParserOptions {
+///         sections: true,
+///         ..Default::default()
+///     })
+///     .standalone(true)
+///     .title("Docs")
+///     .renderer(HtmlRenderer::default());
+/// let html = converter.convert_str("# Usage\nRun it.");
+/// assert!(html.contains("<title>Docs</title>"));
+/// assert!(html.contains("<section id=\"usage\">"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Converter {
+    options: ParserOptions,
+    standalone: bool,
+    title: Option<String>,
+    header_html: Option<String>,
+    footer_html: Option<String>,
+    date_format: String,
+    reproducible: bool,
+    wrap_div: Option<WrapDiv>,
+    meta_tags: bool,
+    url_rewriters: Vec<UrlRewriter>,
+    renderer: HtmlRenderer,
+}
+
+/// A converted document, before it is written anywhere.
+///
+/// # Fields
+///
+/// - `html_lines`: The HTML of the document content, one line per block.
+/// - `warnings`: The warnings raised while parsing the document.
+/// - `front_matter`: The front matter block of the document, if it has one.
+/// - `title`: The page title: the one given to the `Converter`, or else the text of
+///   the first `<h1>`, or else the `title` of the front matter, or else the file
+///   name of the source.
+/// - `date`: The date of the document (see `front_matter::document_date`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub html_lines: Vec<String>,
+    pub warnings: Vec<ParseWarning>,
+    pub front_matter: Option<FrontMatter>,
+    pub title: String,
+    pub date: Option<DocumentDate>,
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Converter::new()
+    }
+}
+
+impl Converter {
+    /// Creates a converter of HTML fragments with the default options.
+    pub fn new() -> Self {
+        Converter {
+            options: ParserOptions::default(),
+            standalone: false,
+            title: None,
+            header_html: None,
+            footer_html: None,
+            date_format: String::from(front_matter::DEFAULT_DATE_FORMAT),
+            reproducible: false,
+            wrap_div: None,
+            meta_tags: true,
+            url_rewriters: Vec::new(),
+            renderer: HtmlRenderer::default(),
+        }
+    }
+
+    /// Sets the parser options.
+    pub fn options(mut self, options: ParserOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Writes complete HTML pages instead of fragments (see `standalone::wrap_page`).
+    pub fn standalone(mut self, standalone: bool) -> Self {
+        self.standalone = standalone;
+        self
+    }
+
+    /// Sets the title of every page, instead of deriving it from the document.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sets the HTML put in the `<header>` of standalone pages, `{{date}}` being
+    /// replaced by the date of the document.
+    pub fn header_html(mut self, header_html: impl Into<String>) -> Self {
+        self.header_html = Some(header_html.into());
+        self
+    }
+
+    /// Sets the HTML put in the `<footer>` of standalone pages, `{{date}}` being
+    /// replaced by the date of the document.
+    pub fn footer_html(mut self, footer_html: impl Into<String>) -> Self {
+        self.footer_html = Some(footer_html.into());
+        self
+    }
+
+    /// Sets how `{{date}}` is formatted (see `front_matter::DocumentDate::format`).
+    pub fn date_format(mut self, date_format: impl Into<String>) -> Self {
+        self.date_format = date_format.into();
+        self
+    }
+
+    /// Never uses file modification times, so the output only depends on the
+    /// input documents.
+    pub fn reproducible(mut self, reproducible: bool) -> Self {
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// Wraps fragments into a `<div>` (see `standalone::wrap_fragment`), to embed
+    /// them in existing pages. Standalone pages are never wrapped.
+    pub fn wrap_div(mut self, wrap_div: WrapDiv) -> Self {
+        self.wrap_div = Some(wrap_div);
+        self
+    }
+
+    /// Whether standalone pages get the description and open graph `<meta>` tags
+    /// (see `page_meta`), on by default.
+    pub fn meta_tags(mut self, meta_tags: bool) -> Self {
+        self.meta_tags = meta_tags;
+        self
+    }
+
+    /// Adds a function rewriting the URL of every link and image, given where the
+    /// URL is emitted (see `urls::UrlContext`).
+    ///
+    /// Rewriters run in the order they are added, each one getting the URL the
+    /// previous one returned, and before links become endnotes. `urls::md_to_html`
+    /// and `urls::base_url` are built-in rewriters.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use md2html::urls;
+    ///
+    /// let converter = md2html::Converter::new()
+    ///     .url_rewriter(urls::md_to_html)
+    ///     .url_rewriter(urls::base_url("/docs/"));
+    /// assert_eq!(
+    ///     converter.convert_str("[Setup](setup.md#install)"),
+    ///     "<p><a href=\"/docs/setup.html#install\">Setup</a></p>\n"
+    /// );
+    /// ```
+    pub fn url_rewriter(
+        mut self,
+        rewriter: impl Fn(UrlContext, &str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.url_rewriters.push(UrlRewriter::new(rewriter));
+        self
+    }
+
+    /// Sets how the HTML lines are written (see `HtmlRenderer`).
+    pub fn renderer(mut self, renderer: HtmlRenderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+
+    /// The parser options.
+    pub fn parser_options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    /// How the HTML lines are written.
+    pub fn html_renderer(&self) -> &HtmlRenderer {
+        &self.renderer
+    }
+
+    /// Whether complete HTML pages are written.
+    pub fn is_standalone(&self) -> bool {
+        self.standalone
+    }
+
+    /// Converts a markdown document.
+    ///
+    /// `source_path` is the markdown file, if any: its name is the fallback title
+    /// and its modification time the fallback date.
+    pub fn convert_document(&self, markdown: &str, source_path: Option<&Path>) -> Document {
+        self.convert_lines(markdown.lines().map(String::from), source_path)
+    }
+
+    /// Converts the markdown `lines` of a document, see `convert_document`.
+    fn convert_lines(
+        &self,
+        lines: impl Iterator<Item = String>,
+        source_path: Option<&Path>,
+    ) -> Document {
+        let result = tokenize_text_with_rewriters(lines, &self.options, &self.url_rewriters);
+        let title = match &self.title {
+            Some(title) => title.clone(),
+            None => {
+                let fallback = result
+                    .front_matter
+                    .as_ref()
+                    .and_then(|front_matter| front_matter.metadata().title)
+                    .or_else(|| {
+                        source_path
+                            .and_then(Path::file_stem)
+                            .map(|stem| stem.to_string_lossy().to_string())
+                    })
+                    .unwrap_or_default();
+                standalone::page_title(&fallback, &result.html_lines)
+            }
+        };
+        let date = front_matter::document_date(
+            result.front_matter.as_ref(),
+            source_path.filter(|_| !self.reproducible),
+        );
+        Document {
+            html_lines: result.html_lines,
+            warnings: result.warnings,
+            front_matter: result.front_matter,
+            title,
+            date,
+        }
+    }
+
+    /// The standalone page options of `document`, before any navigation is added.
+    pub fn page_options(&self, document: &Document) -> PageOptions {
+        let date = document
+            .date
+            .as_ref()
+            .map(|date| date.format(&self.date_format));
+        let expand = |html: &String| front_matter::expand_date(html, date.as_deref());
+        let metadata = document
+            .front_matter
+            .as_ref()
+            .map(FrontMatter::metadata)
+            .unwrap_or_default();
+        PageOptions {
+            title: document.title.clone(),
+            lang: metadata.lang,
+            header_html: self.header_html.as_ref().map(expand),
+            footer_html: self.footer_html.as_ref().map(expand),
+            meta: self.meta_tags.then(|| page_meta(document)),
+            ..Default::default()
+        }
+    }
+
+    /// Renders `document` as a standalone page or as a fragment.
+    pub fn render(&self, document: &Document) -> Vec<String> {
+        if self.standalone {
+            standalone::wrap_page(&document.html_lines, &self.page_options(document))
+        } else {
+            self.wrap_fragment(document.html_lines.clone())
+        }
+    }
+
+    /// Wraps the lines of a fragment into the `wrap_div` container, if any.
+    pub fn wrap_fragment(&self, html_lines: Vec<String>) -> Vec<String> {
+        match &self.wrap_div {
+            Some(wrap_div) => standalone::wrap_fragment(html_lines, wrap_div),
+            None => html_lines,
+        }
+    }
+
+    /// Converts a markdown string into HTML, every line followed by the line ending
+    /// of the renderer (`\n` by default).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let html = md2html::Converter::new().convert_str("Some **text**");
+    /// assert_eq!(html, "<p>Some <strong>text</strong></p>\n");
+    /// ```
+    pub fn convert_str(&self, markdown: &str) -> String {
+        self.renderer
+            .join_lines(&self.render(&self.convert_document(markdown, None)))
+    }
+
+    /// Converts the markdown read from `input` into HTML written to `output`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::io::Cursor;
+    ///
+    /// let mut html = Vec::new();
+    /// let stats = md2html::Converter::new()
+    ///     .convert_reader_writer(Cursor::new("# Title"), &mut html)
+    ///     .unwrap();
+    /// assert_eq!(html, b"<h1>Title</h1>\n");
+    /// assert_eq!(stats.bytes_written, html.len());
+    /// ```
+    pub fn convert_reader_writer<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+    ) -> Result<ConversionStats, ConvertError> {
+        self.convert_reader_writer_with_path(input, output, None)
+    }
+
+    /// Converts the markdown read from `input` into HTML written to `output`, like
+    /// `convert_reader_writer`.
+    ///
+    /// `source_path` is the markdown file `input` reads, if any: its name is the
+    /// fallback title and its modification time the fallback date.
+    ///
+    /// The lines of `input` go to the parser as they are read, the first read error
+    /// (including input that isn't valid UTF-8) stopping the conversion before
+    /// anything is written.
+    pub fn convert_reader_writer_with_path<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+        source_path: Option<&Path>,
+    ) -> Result<ConversionStats, ConvertError> {
+        let mut read_error = None;
+        let lines = BufReader::new(input)
+            .lines()
+            .map_while(|line| line.map_err(|err| read_error = Some(err)).ok());
+        let document = self.convert_lines(lines, source_path);
+        match read_error {
+            Some(err) => Err(ConvertError::Read(err)),
+            None => self.write_document(document, output),
+        }
+    }
+
+    /// Converts the markdown file `input` into the HTML file `output`, creating the
+    /// missing folders of `output`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let dir = std::env::temp_dir().join("md2html-doc-converter");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("notes.md"), "Some text").unwrap();
+    ///
+    /// let converter = md2html::Converter::new().standalone(true);
+    /// converter
+    ///     .convert_file(&dir.join("notes.md"), &dir.join("site/notes.html"))
+    ///     .unwrap();
+    /// let html = std::fs::read_to_string(dir.join("site/notes.html")).unwrap();
+    /// assert!(html.contains("<title>notes</title>"));
+    /// ```
+    pub fn convert_file(
+        &self,
+        input: &Path,
+        output: &Path,
+    ) -> Result<ConversionStats, ConvertError> {
+        let markdown = File::open(input).map_err(ConvertError::Read)?;
+        // The output file is only created once the input is read
+        let mut html = Vec::new();
+        let stats = self.convert_reader_writer_with_path(markdown, &mut html, Some(input))?;
+        let mut file = output::create_file(output).map_err(|err| {
+            ConvertError::Write(io::Error::new(err.source.kind(), err.to_string()))
+        })?;
+        file.write_all(&html).map_err(ConvertError::Write)?;
+        Ok(stats)
+    }
+
+    fn write_document<W: Write>(
+        &self,
+        document: Document,
+        output: W,
+    ) -> Result<ConversionStats, ConvertError> {
+        let bytes_written = self
+            .renderer
+            .write_lines(output, &self.render(&document))
+            .map_err(ConvertError::Write)?;
+        Ok(ConversionStats {
+            bytes_written,
+            warnings: document.warnings,
+            front_matter: document.front_matter,
+        })
+    }
+}
+
+/// The `<meta>` tags of `document`, from the `description`, `image` and `author`
+/// front matter keys.
+///
+/// The `og:title` is left to the page title, which already resolves the first
+/// `<h1>` against the `title` key, so the two always agree. Without a
+/// `description`, the start of the first paragraph is used (see
+/// `standalone::page_description`).
+fn page_meta(document: &Document) -> PageMeta {
+    let front_matter = |key: &str| {
+        document
+            .front_matter
+            .as_ref()
+            .and_then(|front_matter| front_matter.get(key))
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+    };
+    PageMeta {
+        title: None,
+        description: front_matter("description")
+            .or_else(|| standalone::page_description(&document.html_lines)),
+        image: front_matter("image"),
+        author: front_matter("author"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_converter_reused_across_documents() {
+        let converter = Converter::new()
+            .standalone(true)
+            .footer_html("<p>Updated {{date}}</p>")
+            .date_format("%d/%m/%Y");
+        let first = converter.convert_str("---\ndate: 2024-06-01\n---\n# First");
+        let second = converter.convert_str("# Second");
+        assert!(first.contains("<title>First</title>"));
+        assert!(first.contains("<p>Updated 01/06/2024</p>"));
+        assert!(second.contains("<title>Second</title>"));
+        assert!(second.contains("<p>Updated </p>"));
+    }
+
+    #[test]
+    fn test_convert_reader_writer_matches_convert() {
+        let markdown = "# Title\n- a\n  - b\n\n| x | y |\n|---|---|\n| 1 | 2 |";
+        let mut expected = Vec::new();
+        crate::convert(Cursor::new(markdown), &mut expected, &Default::default()).unwrap();
+        let mut html = Vec::new();
+        let stats = Converter::new()
+            .convert_reader_writer(Cursor::new(markdown), &mut html)
+            .unwrap();
+        assert_eq!(html, expected);
+        assert_eq!(stats.bytes_written, html.len());
+    }
+
+    #[test]
+    fn test_convert_file() {
+        let dir = std::env::temp_dir().join("md2html-converter-file");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("guide.md"), "Intro\n#### Deep").unwrap();
+
+        let converter = Converter::new().standalone(true).title("Docs");
+        let stats = converter
+            .convert_file(&dir.join("guide.md"), &dir.join("out/guide.html"))
+            .unwrap();
+        let html = std::fs::read_to_string(dir.join("out/guide.html")).unwrap();
+        assert!(html.contains("<title>Docs</title>"));
+        assert!(html.contains("<h4>Deep</h4>"));
+        assert_eq!(stats.bytes_written, html.len());
+
+        let missing = converter.convert_file(&dir.join("missing.md"), &dir.join("x.html"));
+        assert!(matches!(missing, Err(ConvertError::Read(_))));
+    }
+
+    #[test]
+    fn test_open_blocks_closed_before_footer() {
+        let converter = Converter::new()
+            .standalone(true)
+            .footer_html("<p>Footer</p>");
+        let page = converter.convert_str("# Notes\n\n```rust\nfn main() {");
+        let closed = page.find("</code></pre>").unwrap();
+        assert!(closed < page.find("<p>Footer</p>").unwrap());
+        let warnings = converter
+            .convert_document("# Notes\n\n```rust\nfn main() {", None)
+            .warnings;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 3);
+
+        let page = converter.convert_str("1. one\n  1. nested");
+        let footer = page.find("<p>Footer</p>").unwrap();
+        assert_eq!(page[..footer].matches("<ol>").count(), 2);
+        assert_eq!(page[..footer].matches("</ol>").count(), 2);
+    }
+
+    #[test]
+    fn test_reproducible_ignores_modification_time() {
+        let dir = std::env::temp_dir().join("md2html-converter-reproducible");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("page.md");
+        std::fs::write(&path, "# Page").unwrap();
+
+        let converter = Converter::new();
+        assert!(converter
+            .convert_document("# Page", Some(&path))
+            .date
+            .is_some());
+        let converter = converter.reproducible(true);
+        assert_eq!(converter.convert_document("# Page", Some(&path)).date, None);
+    }
+
+    #[test]
+    fn test_wrap_div() {
+        let markdown = "# Title\nText";
+        let wrapped = |spec: &str| {
+            Converter::new()
+                .wrap_div(spec.parse().unwrap())
+                .convert_str(markdown)
+        };
+        assert_eq!(
+            wrapped("article-body"),
+            "<div class=\"article-body\">\n<h1>Title</h1>\n<p>Text</p>\n</div>\n"
+        );
+        assert_eq!(
+            wrapped("#content.article-body"),
+            "<div id=\"content\" class=\"article-body\">\n<h1>Title</h1>\n<p>Text</p>\n</div>\n"
+        );
+        assert_eq!(
+            Converter::new().convert_str(markdown),
+            "<h1>Title</h1>\n<p>Text</p>\n"
+        );
+        // Standalone pages already have their own container
+        let page = Converter::new()
+            .standalone(true)
+            .wrap_div("article-body".parse().unwrap())
+            .convert_str(markdown);
+        assert!(!page.contains("article-body"));
+    }
+
+    #[test]
+    fn test_meta_tags_from_front_matter() {
+        let converter = Converter::new().standalone(true);
+        let page = converter.convert_str(
+            "---\ntitle: The Guide\ndescription: How to use it\nimage: /cover.png\n---\n# Guide\nText",
+        );
+        // The first `<h1>` is the title of the page and of its link previews
+        assert!(page.contains("<title>Guide</title>"));
+        assert!(page.contains("<meta name=\"description\" content=\"How to use it\">"));
+        assert!(page.contains("<meta property=\"og:title\" content=\"Guide\">"));
+        assert!(page.contains("<meta property=\"og:description\" content=\"How to use it\">"));
+        assert!(page.contains("<meta property=\"og:image\" content=\"/cover.png\">"));
+        assert!(!page.contains("<meta name=\"author\""));
+
+        let page = converter.convert_str("---\ntitle: The Guide\n---\nText");
+        assert!(page.contains("<title>The Guide</title>"));
+        assert!(page.contains("<meta property=\"og:title\" content=\"The Guide\">"));
+
+        let page = converter
+            .meta_tags(false)
+            .convert_str("---\ntitle: The Guide\n---\nText");
+        assert!(!page.contains("<meta name=\"description\""));
+        assert!(!page.contains("og:title"));
+    }
+
+    #[test]
+    fn test_front_matter_metadata_in_page() {
+        let converter = Converter::new().standalone(true);
+        let markdown = "---\ntitle: Meeting notes\nauthor: Ada & Grace\nlang: fr\n---\nNo heading";
+        let document = converter.convert_document(markdown, Some(Path::new("notes.md")));
+        assert_eq!(document.title, "Meeting notes");
+        let page = converter.render(&document).join("\n");
+        assert!(page.contains("<html lang=\"fr\">"));
+        assert!(page.contains("<title>Meeting notes</title>"));
+        assert!(page.contains("<meta name=\"author\" content=\"Ada &amp; Grace\">"));
+        assert!(!page.contains("author: "));
+        // The first `<h1>` still wins, and the page stays in English by default
+        let document = converter.convert_document(
+            "---\ntitle: Notes\n---\n# Heading",
+            Some(Path::new("notes.md")),
+        );
+        assert_eq!(document.title, "Heading");
+        assert!(converter
+            .render(&document)
+            .contains(&String::from("<html lang=\"en\">")));
+    }
+
+    #[test]
+    fn test_meta_description_from_first_paragraph() {
+        let page = Converter::new()
+            .standalone(true)
+            .convert_str("# Guide\n> Quoted\n\nThe *first* \"paragraph\".\n\nThe second.");
+        assert!(page
+            .contains("<meta name=\"description\" content=\"The first &quot;paragraph&quot;.\">"));
+        assert!(page.contains("<meta property=\"og:title\" content=\"Guide\">"));
+        assert!(!page.contains("og:image"));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{parse_with_options, render_html, ParserOptions};
+use std::time::{Duration, Instant};
+
+/// A kind of generated markdown document, each stressing a different part of the
+/// parser.
+///
+/// # Variants
+///
+/// - `Paragraphs`: Headings and paragraphs of plain words.
+/// - `Lists`: Nested unordered, ordered and task lists.
+/// - `Code`: Fenced code blocks between short paragraphs.
+/// - `Inline`: Paragraphs full of emphasis, code spans, links and images.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Corpus {
+    Paragraphs,
+    Lists,
+    Code,
+    Inline,
+}
+
+impl Corpus {
+    /// Every corpus, in the order they are benchmarked.
+    pub const ALL: [Corpus; 4] = [
+        Corpus::Paragraphs,
+        Corpus::Lists,
+        Corpus::Code,
+        Corpus::Inline,
+    ];
+
+    /// The name of the corpus in benchmark reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Corpus::Paragraphs => "paragraphs",
+            Corpus::Lists => "lists",
+            Corpus::Code => "code",
+            Corpus::Inline => "inline",
+        }
+    }
+}
+
+const WORDS: [&str; 16] = [
+    "markdown", "parser", "token", "render", "the", "a", "of", "document", "quick", "line", "list",
+    "value", "table", "heading", "and", "output",
+];
+
+/// A small linear congruential generator, so corpora don't depend on a random
+/// crate and are the same on every run.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 33) % bound as u64) as usize
+    }
+
+    fn words(&mut self, count: usize) -> String {
+        (0..count)
+            .map(|_| WORDS[self.next(WORDS.len())])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Generates a `corpus` document of exactly `line_count` lines.
+///
+/// The output only depends on the arguments, so benchmark runs are comparable.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::corpus::{generate, Corpus};
+///
+/// let lines = generate(Corpus::Lists, 100);
+/// assert_eq!(lines.len(), 100);
+/// assert_eq!(lines, generate(Corpus::Lists, 100));
+/// ```
+pub fn generate(corpus: Corpus, line_count: usize) -> Vec<String> {
+    let mut rng = Lcg(corpus as u64 + 1);
+    let mut lines = Vec::with_capacity(line_count + 16);
+    while lines.len() < line_count {
+        match corpus {
+            Corpus::Paragraphs => {
+                if rng.next(5) == 0 {
+                    lines.push(format!("## {}", rng.words(3)));
+                }
+                for _ in 0..3 + rng.next(4) {
+                    let word_count = 8 + rng.next(8);
+                    lines.push(rng.words(word_count));
+                }
+            }
+            Corpus::Lists => {
+                for index in 1..=3 + rng.next(6) {
+                    let indent = "  ".repeat(rng.next(3));
+                    lines.push(match rng.next(3) {
+                        0 => format!("{}- {}", indent, rng.words(4)),
+                        1 => format!("{}{}. {}", indent, index, rng.words(4)),
+                        _ => format!("{}- [{}] {}", indent, ["x", " "][rng.next(2)], rng.words(4)),
+                    });
+                }
+            }
+            Corpus::Code => {
+                lines.push(rng.words(6));
+                lines.push(String::new());
+                lines.push(String::from("```"));
+                for _ in 0..4 + rng.next(8) {
+                    lines.push(format!(
+                        "let {} = {}({}) * 2; // <{}>",
+                        WORDS[rng.next(WORDS.len())],
+                        WORDS[rng.next(WORDS.len())],
+                        rng.next(100),
+                        rng.words(2)
+                    ));
+                }
+                lines.push(String::from("```"));
+            }
+            Corpus::Inline => {
+                for _ in 0..2 + rng.next(3) {
+                    lines.push(format!(
+                        "{} **{}** *{}* `{}` [{}](https://example.com/{}) ![{}](img/{}.png)",
+                        rng.words(3),
+                        rng.words(2),
+                        rng.words(2),
+                        rng.words(1),
+                        rng.words(2),
+                        rng.next(1000),
+                        rng.words(1),
+                        rng.next(1000)
+                    ));
+                }
+            }
+        }
+        lines.push(String::new());
+    }
+    lines.truncate(line_count);
+    lines
+}
+
+/// The timing of a corpus conversion.
+///
+/// # Fields
+///
+/// - `corpus`: The corpus converted.
+/// - `lines`: How many lines were converted, over all the iterations.
+/// - `peak_tokens`: The highest number of tokens a single parse produced.
+/// - `elapsed`: The time spent parsing and rendering, without generating the corpus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub corpus: Corpus,
+    pub lines: usize,
+    pub peak_tokens: usize,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// The conversion speed, in markdown lines per second.
+    pub fn lines_per_second(&self) -> f64 {
+        self.lines as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Generates a `corpus` document of `line_count` lines and times `iterations`
+/// parses and renders of it with `options`.
+pub fn run_benchmark(
+    corpus: Corpus,
+    line_count: usize,
+    iterations: usize,
+    options: &ParserOptions,
+) -> BenchResult {
+    let lines = generate(corpus, line_count);
+    let mut peak_tokens = 0;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let document = parse_with_options(lines.iter().cloned(), options);
+        peak_tokens = peak_tokens.max(document.tokens.len());
+        std::hint::black_box(render_html(&document.tokens));
+    }
+    BenchResult {
+        corpus,
+        lines: line_count * iterations,
+        peak_tokens,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        for corpus in Corpus::ALL {
+            let lines = generate(corpus, 500);
+            assert_eq!(lines.len(), 500, "{}", corpus.name());
+            assert_eq!(lines, generate(corpus, 500), "{}", corpus.name());
+            // A bigger corpus starts with the smaller one
+            assert_eq!(
+                generate(corpus, 1000)[..500],
+                lines[..],
+                "{}",
+                corpus.name()
+            );
+        }
+        assert_ne!(generate(Corpus::Code, 50), generate(Corpus::Inline, 50));
+    }
+
+    #[test]
+    fn test_benchmark_runs_every_corpus() {
+        for corpus in Corpus::ALL {
+            let result = run_benchmark(corpus, 60, 2, &ParserOptions::default());
+            assert_eq!(result.lines, 120);
+            assert!(result.peak_tokens > 0, "{}", corpus.name());
+            assert!(result.lines_per_second() > 0.0);
+        }
+    }
+}
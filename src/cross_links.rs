@@ -0,0 +1,328 @@
+use crate::fences::FenceTracker;
+use regex::Regex;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// A markdown document of a batch conversion, as seen by the link checker. Only
+/// its links are kept, not its text, so a large batch fits in memory.
+///
+/// # Fields
+///
+/// - `path`: The path of the markdown file, relative to the batch input directory.
+/// - `links`: The links of the document (see `find_links`).
+/// - `heading_ids`: The ids of the document headings (see
+///   `sections::heading_ids_with_options`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchDocument {
+    pub path: PathBuf,
+    pub links: Vec<LinkSite>,
+    pub heading_ids: Vec<String>,
+}
+
+/// A link or image of a markdown document, found by `find_links`.
+///
+/// # Fields
+///
+/// - `line`: The line of the link, starting at 1.
+/// - `target`: The link destination as written in the markdown.
+/// - `source_line`: The markdown line holding the link, to point at it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkSite {
+    pub line: usize,
+    pub target: String,
+    pub source_line: String,
+}
+
+/// Finds the inline links and images of `markdown_lines`, leaving out the ones
+/// inside code.
+///
+/// # Example
+///
+/// ```rust
+/// let lines = vec![String::from("See [usage](#usage) and `[code](x.md)`.")];
+/// let links = md2html::cross_links::find_links(&lines);
+/// assert_eq!(links.len(), 1);
+/// assert_eq!((links[0].line, links[0].target.as_str()), (1, "#usage"));
+/// ```
+pub fn find_links(markdown_lines: &[String]) -> Vec<LinkSite> {
+    let link_re = Regex::new(r#"\]\(([^()\s]+)(?:\s+"[^"]*")?\)"#).unwrap();
+    let code_span_re = Regex::new(r"`+[^`]*`+").unwrap();
+    let mut fences = FenceTracker::default();
+    let mut links = Vec::new();
+    for (index, source_line) in markdown_lines.iter().enumerate() {
+        if fences.is_code(source_line) {
+            continue;
+        }
+        let line = code_span_re.replace_all(source_line, "");
+        for groups in link_re.captures_iter(&line) {
+            links.push(LinkSite {
+                line: index + 1,
+                target: groups[1].to_string(),
+                source_line: source_line.clone(),
+            });
+        }
+    }
+    links
+}
+
+/// Why a link is broken.
+///
+/// # Variants
+///
+/// - `MissingFile`: The linked file is neither part of the batch nor on disk.
+/// - `MissingFragment`: The linked document has no heading with the `#fragment` id.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BrokenLinkReason {
+    MissingFile,
+    MissingFragment(String),
+}
+
+/// A link between documents that doesn't resolve.
+///
+/// # Fields
+///
+/// - `source`: The document containing the link, relative to the batch input directory.
+/// - `line`: The line of the link in `source`, starting at 1.
+/// - `target`: The link destination as written in the markdown.
+/// - `reason`: Why the link is broken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    pub source: PathBuf,
+    pub line: usize,
+    pub target: String,
+    pub reason: BrokenLinkReason,
+}
+
+impl BrokenLink {
+    /// Describes the problem, without the location of the link.
+    pub fn message(&self) -> String {
+        match &self.reason {
+            BrokenLinkReason::MissingFile => {
+                format!("broken link to {}: no such file", self.target)
+            }
+            BrokenLinkReason::MissingFragment(id) => format!(
+                "broken link to {}: no heading with the id \"{}\"",
+                self.target, id
+            ),
+        }
+    }
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.source.display(),
+            self.line,
+            self.message()
+        )
+    }
+}
+
+/// Checks the relative links and images of every document of a batch.
+///
+/// A link to another document of the batch must name a heading id of that document
+/// when it has a `#fragment`, and `#fragment`-only links must name a heading of the
+/// document itself. Links to files outside the batch only need to exist under
+/// `root`. External URLs and absolute paths aren't checked, nor is anything inside
+/// code.
+///
+/// # Arguments
+///
+/// * `root` - The batch input directory, the document paths are relative to it.
+/// * `documents` - Every document of the batch.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::cross_links::{check_links, find_links, BatchDocument};
+/// use std::path::{Path, PathBuf};
+///
+/// let documents = vec![BatchDocument {
+///     path: PathBuf::from("index.md"),
+///     links: find_links(&[String::from("See [usage](#usage).")]),
+///     heading_ids: vec![String::from("intro")],
+/// }];
+/// let broken = check_links(Path::new("docs"), &documents);
+/// assert_eq!(
+///     broken[0].to_string(),
+///     "index.md:1: broken link to #usage: no heading with the id \"usage\""
+/// );
+/// ```
+pub fn check_links(root: &Path, documents: &[BatchDocument]) -> Vec<BrokenLink> {
+    let mut broken = Vec::new();
+    for document in documents {
+        for link in &document.links {
+            if let Some(reason) = check_target(root, documents, document, &link.target) {
+                broken.push(BrokenLink {
+                    source: document.path.clone(),
+                    line: link.line,
+                    target: link.target.clone(),
+                    reason,
+                });
+            }
+        }
+    }
+    broken
+}
+
+/// Checks a single link destination of `document`, see `check_links`.
+fn check_target(
+    root: &Path,
+    documents: &[BatchDocument],
+    document: &BatchDocument,
+    target: &str,
+) -> Option<BrokenLinkReason> {
+    if target.contains("://") || target.starts_with("mailto:") || target.starts_with('/') {
+        return None;
+    }
+    let (path, fragment) = match target.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (target, None),
+    };
+
+    let linked_document = if path.is_empty() {
+        Some(document)
+    } else {
+        let linked_path = document.path.parent().unwrap_or(Path::new("")).join(path);
+        let linked_path = normalize(&linked_path);
+        match documents.iter().find(|other| other.path == linked_path) {
+            Some(other) => Some(other),
+            None if root.join(&linked_path).exists() => None,
+            None => return Some(BrokenLinkReason::MissingFile),
+        }
+    };
+
+    // Like browsers, a fragment matches an id as written or percent-decoded
+    // (`#%C3%BCberblick` targets `überblick`)
+    let decoded = fragment.and_then(percent_decode);
+    match (linked_document, fragment) {
+        (Some(linked), Some(id))
+            if !linked
+                .heading_ids
+                .iter()
+                .any(|known| known == id || decoded.as_ref() == Some(known)) =>
+        {
+            Some(BrokenLinkReason::MissingFragment(id.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes the `%XX` sequences of `text`, returning `None` when they aren't valid
+/// UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        let hex = after
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, hex) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Resolves the `.` and `..` components of a relative path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(path: &str, markdown: &str, heading_ids: &[&str]) -> BatchDocument {
+        BatchDocument {
+            path: PathBuf::from(path),
+            links: find_links(&markdown.lines().map(String::from).collect::<Vec<_>>()),
+            heading_ids: heading_ids.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_check_links_three_documents() {
+        let documents = vec![
+            document(
+                "index.md",
+                "# Home\nStart with [the setup](guide/setup.md#prereqs).\nOr [usage](usage.md).",
+                &["home"],
+            ),
+            document(
+                "guide/setup.md",
+                "# Setup\n## Prereqs\nBack [home](../index.md#welcome).\n```\n[not a link](missing.md)\n```",
+                &["setup", "prereqs"],
+            ),
+            document(
+                "guide/faq.md",
+                "# FAQ\nSee `[code](nope.md)` and [the site](https://example.com/a.md).",
+                &["faq"],
+            ),
+        ];
+        let root = std::env::temp_dir().join("md2html-cross-links-fixture");
+        let broken = check_links(&root, &documents);
+        assert_eq!(
+            broken,
+            vec![
+                BrokenLink {
+                    source: PathBuf::from("index.md"),
+                    line: 3,
+                    target: String::from("usage.md"),
+                    reason: BrokenLinkReason::MissingFile,
+                },
+                BrokenLink {
+                    source: PathBuf::from("guide/setup.md"),
+                    line: 3,
+                    target: String::from("../index.md#welcome"),
+                    reason: BrokenLinkReason::MissingFragment(String::from("welcome")),
+                },
+            ]
+        );
+        assert_eq!(
+            broken[0].to_string(),
+            "index.md:3: broken link to usage.md: no such file"
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_fragments() {
+        let documents = vec![document(
+            "index.md",
+            "# Überblick\n[a](#überblick) [b](#%C3%BCberblick) [c](#%C3berblick) [d](#%E6%97%A5)",
+            &["überblick", "%E6%97%A5"],
+        )];
+        let broken = check_links(Path::new("."), &documents);
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].target, "#%C3berblick");
+        assert_eq!(percent_decode("%e6%97%a5%"), Some(String::from("日%")));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("guide/./../api/ref.md")),
+            PathBuf::from("api/ref.md")
+        );
+    }
+}
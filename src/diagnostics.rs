@@ -0,0 +1,352 @@
+use crate::search_index::json_string;
+use colored::Colorize;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    /// The label of the severity in reports.
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn colored_label(self, color: bool) -> String {
+        match (self, color) {
+            (_, false) => self.label().to_string(),
+            (Severity::Error, true) => self.label().bold().red().to_string(),
+            (Severity::Warning, true) => self.label().bold().truecolor(255, 165, 0).to_string(),
+        }
+    }
+}
+
+/// A problem found by a check (heading structure, broken links...) in a markdown
+/// file.
+///
+/// # Fields
+///
+/// - `file`: The file the problem is in, as shown to the user.
+/// - `line`: The line of the problem, starting at 1.
+/// - `column`: The column the problem starts at, starting at 1, when it is known.
+/// - `length`: How many characters the problem spans from `column`, at least 1 is
+///   underlined.
+/// - `span`: The byte range of the problem in `source_line`, when it is known.
+/// - `severity`: How serious the problem is.
+/// - `message`: What the problem is.
+/// - `source_line`: The markdown of `line`, shown under the message when known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub length: usize,
+    pub span: Option<Range<usize>>,
+    pub severity: Severity,
+    pub message: String,
+    pub source_line: Option<String>,
+}
+
+/// How diagnostics are printed (`--message-format`).
+///
+/// # Variants
+///
+/// - `Short`: One `file:line:column: severity: message` line per diagnostic.
+/// - `Pretty`: Diagnostics grouped by file, with the source line and a caret under
+///   the problem.
+/// - `Json`: One JSON object per line, for tools.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MessageFormat {
+    Short,
+    #[default]
+    Pretty,
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "short" => Ok(MessageFormat::Short),
+            "pretty" => Ok(MessageFormat::Pretty),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!(
+                "unknown message format \"{}\", expected short, pretty or json",
+                format
+            )),
+        }
+    }
+}
+
+/// Decides whether diagnostics are colored, like most command line tools do: a
+/// non-empty `NO_COLOR` turns colors off, then a `FORCE_COLOR` other than `0`
+/// turns them on, otherwise only a terminal gets colors.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::diagnostics::use_color;
+///
+/// assert!(!use_color(Some("1"), Some("1"), true));
+/// assert!(use_color(None, Some("1"), false));
+/// assert!(!use_color(None, Some("0"), true));
+/// assert!(use_color(Some(""), None, true));
+/// ```
+pub fn use_color(no_color: Option<&str>, force_color: Option<&str>, is_terminal: bool) -> bool {
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    match force_color {
+        Some(value) if !value.is_empty() => value != "0",
+        _ => is_terminal,
+    }
+}
+
+/// Renders `diagnostics` in `format`, colored when `color` is set (JSON never is).
+///
+/// The diagnostics of a file are kept together, in the order they were found,
+/// and files come in the order of their first diagnostic.
+pub fn render(diagnostics: &[Diagnostic], format: MessageFormat, color: bool) -> String {
+    let mut files: Vec<(&str, Vec<&Diagnostic>)> = Vec::new();
+    for diagnostic in diagnostics {
+        match files.iter_mut().find(|(file, _)| *file == diagnostic.file) {
+            Some((_, file_diagnostics)) => file_diagnostics.push(diagnostic),
+            None => files.push((&diagnostic.file, vec![diagnostic])),
+        }
+    }
+
+    let mut output = String::new();
+    for (file, file_diagnostics) in files {
+        if format == MessageFormat::Pretty {
+            let header = format!("{}:", file);
+            output += &if color {
+                header.bold().to_string()
+            } else {
+                header
+            };
+            output.push('\n');
+        }
+        for diagnostic in file_diagnostics {
+            match format {
+                MessageFormat::Short => {
+                    output += &format!("{}:{}", diagnostic.file, diagnostic.line);
+                    if let Some(column) = diagnostic.column {
+                        output += &format!(":{}", column);
+                    }
+                    output += &format!(
+                        ": {}: {}\n",
+                        diagnostic.severity.colored_label(color),
+                        diagnostic.message
+                    );
+                }
+                MessageFormat::Pretty => output += &render_pretty(diagnostic, color),
+                MessageFormat::Json => output += &render_json(diagnostic),
+            }
+        }
+    }
+    output
+}
+
+/// Renders a diagnostic with its source line and a caret under the problem.
+fn render_pretty(diagnostic: &Diagnostic, color: bool) -> String {
+    let location = match diagnostic.column {
+        Some(column) => format!("{}:{}", diagnostic.line, column),
+        None => diagnostic.line.to_string(),
+    };
+    let mut output = format!(
+        "  {} {}: {}\n",
+        location,
+        diagnostic.severity.colored_label(color),
+        diagnostic.message
+    );
+    let Some(source_line) = &diagnostic.source_line else {
+        return output;
+    };
+    let gutter = " ".repeat(diagnostic.line.to_string().len());
+    output += &format!("  {} | {}\n", diagnostic.line, source_line);
+    if let Some(column) = diagnostic.column {
+        // Tabs are kept and wide characters padded twice so the caret lines up in
+        // the terminal
+        let padding: String = source_line
+            .chars()
+            .take(column.saturating_sub(1))
+            .map(|c| match c {
+                '\t' => String::from("\t"),
+                c => " ".repeat(display_width(c)),
+            })
+            .collect();
+        let underlined: usize = source_line
+            .chars()
+            .skip(column.saturating_sub(1))
+            .take(diagnostic.length)
+            .map(display_width)
+            .sum();
+        let carets = "^".repeat(underlined.max(1));
+        let carets = match (color, diagnostic.severity) {
+            (false, _) => carets,
+            (true, Severity::Error) => carets.bold().red().to_string(),
+            (true, Severity::Warning) => carets.bold().truecolor(255, 165, 0).to_string(),
+        };
+        output += &format!("  {} | {}{}\n", gutter, padding, carets);
+    }
+    output
+}
+
+/// How many terminal columns `c` takes: none for combining marks, two for wide
+/// East Asian characters and emoji, one otherwise.
+fn display_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// Renders a diagnostic as a single line JSON object, `byte_start` and `byte_end`
+/// being the byte range of the problem in its line.
+fn render_json(diagnostic: &Diagnostic) -> String {
+    let null_or =
+        |value: Option<usize>| value.map_or(String::from("null"), |value| value.to_string());
+    let span = diagnostic.span.as_ref();
+    format!(
+        "{{\"file\": {}, \"line\": {}, \"column\": {}, \"length\": {}, \"byte_start\": {}, \"byte_end\": {}, \"severity\": \"{}\", \"message\": {}}}\n",
+        json_string(&diagnostic.file),
+        diagnostic.line,
+        null_or(diagnostic.column),
+        diagnostic.length,
+        null_or(span.map(|span| span.start)),
+        null_or(span.map(|span| span.end)),
+        diagnostic.severity.label(),
+        json_string(&diagnostic.message)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        vec![
+            Diagnostic {
+                file: String::from("guide.md"),
+                line: 3,
+                column: Some(9),
+                length: 7,
+                span: Some(8..15),
+                severity: Severity::Warning,
+                message: String::from("broken link to setup.md: no such file"),
+                source_line: Some(String::from("See the [setup](setup.md) page")),
+            },
+            Diagnostic {
+                file: String::from("index.md"),
+                line: 12,
+                column: None,
+                length: 1,
+                span: None,
+                severity: Severity::Error,
+                message: String::from("heading level jumps from h2 to h4, use h3 instead"),
+                source_line: Some(String::from("#### Details")),
+            },
+            Diagnostic {
+                file: String::from("guide.md"),
+                line: 5,
+                column: None,
+                length: 1,
+                span: None,
+                severity: Severity::Warning,
+                message: String::from("another h1"),
+                source_line: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_short() {
+        assert_eq!(
+            render(&diagnostics(), MessageFormat::Short, false),
+            "guide.md:3:9: warning: broken link to setup.md: no such file\n\
+             guide.md:5: warning: another h1\n\
+             index.md:12: error: heading level jumps from h2 to h4, use h3 instead\n"
+        );
+    }
+
+    #[test]
+    fn test_render_pretty() {
+        assert_eq!(
+            render(&diagnostics(), MessageFormat::Pretty, false),
+            "guide.md:\n\
+             \x20 3:9 warning: broken link to setup.md: no such file\n\
+             \x20 3 | See the [setup](setup.md) page\n\
+             \x20   |         ^^^^^^^\n\
+             \x20 5 warning: another h1\n\
+             index.md:\n\
+             \x20 12 error: heading level jumps from h2 to h4, use h3 instead\n\
+             \x20 12 | #### Details\n"
+        );
+    }
+
+    #[test]
+    fn test_render_pretty_wide_characters() {
+        let source_line = "日本語 [リンク](https://example.com";
+        let start = source_line.find('[').unwrap();
+        let diagnostic = Diagnostic {
+            file: String::from("notes.md"),
+            line: 2,
+            column: Some(source_line[..start].chars().count() + 1),
+            length: source_line[start..].chars().count(),
+            span: Some(start..source_line.len()),
+            severity: Severity::Warning,
+            message: String::from("link destination is never closed, missing `)`"),
+            source_line: Some(String::from(source_line)),
+        };
+        let output = render(&[diagnostic], MessageFormat::Pretty, false);
+        // Every CJK character takes two columns, so does its padding or caret
+        assert_eq!(
+            output.lines().nth(3).unwrap(),
+            format!("    |        {}", "^".repeat(2 * 3 + 22))
+        );
+    }
+
+    #[test]
+    fn test_render_json() {
+        let json = render(&diagnostics()[..2], MessageFormat::Json, true);
+        assert_eq!(
+            json.lines().next().unwrap(),
+            "{\"file\": \"guide.md\", \"line\": 3, \"column\": 9, \"length\": 7, \
+             \"byte_start\": 8, \"byte_end\": 15, \"severity\": \"warning\", \"message\": \"broken link to setup.md: no such file\"}"
+        );
+        assert!(json.lines().nth(1).unwrap().contains("\"column\": null"));
+        assert!(json
+            .lines()
+            .nth(1)
+            .unwrap()
+            .contains("\"byte_start\": null"));
+    }
+
+    #[test]
+    fn test_message_format_from_str() {
+        assert_eq!("json".parse(), Ok(MessageFormat::Json));
+        assert!("xml".parse::<MessageFormat>().is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use regex::Regex;
+
+/// A video provider whose links can be embedded as an `<iframe>`.
+///
+/// # Fields
+///
+/// - `name`: The provider name used by the `{{name ID}}` shortcode.
+/// - `url_patterns`: Regexes matching the provider's page URLs, the first capture
+///   group being the video id.
+/// - `embed_url`: The iframe URL, `{id}` is replaced by the video id.
+struct Provider {
+    name: &'static str,
+    url_patterns: &'static [&'static str],
+    embed_url: &'static str,
+}
+
+const PROVIDERS: &[Provider] = &[
+    Provider {
+        name: "youtube",
+        url_patterns: &[
+            r"^https?://(?:www\.|m\.)?youtube\.com/watch\?(?:[^#]*&)?v=([\w-]{11})",
+            r"^https?://youtu\.be/([\w-]{11})",
+        ],
+        embed_url: "https://www.youtube-nocookie.com/embed/{id}",
+    },
+    Provider {
+        name: "vimeo",
+        url_patterns: &[r"^https?://(?:www\.)?vimeo\.com/(\d+)"],
+        embed_url: "https://player.vimeo.com/video/{id}",
+    },
+];
+
+/// What a line on its own turns into when embeds are enabled.
+#[derive(Debug, PartialEq)]
+pub enum EmbedLine {
+    /// A known provider's video, holding the iframe URL.
+    Video(String),
+    /// A bare URL of an unknown provider, rendered as a normal link.
+    Link(String),
+}
+
+/// Recognizes a line consisting solely of a video URL or a `{{provider ID}}` shortcode.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::embeds::{embed_line, EmbedLine};
+///
+/// assert_eq!(
+///     embed_line("{{youtube dQw4w9WgXcQ}}"),
+///     Some(EmbedLine::Video(String::from("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ")))
+/// );
+/// assert_eq!(embed_line("Some text"), None);
+/// ```
+pub fn embed_line(line: &str) -> Option<EmbedLine> {
+    let line = line.trim();
+
+    let shortcode_re = Regex::new(r"^\{\{(\w+)\s+([\w-]+)\}\}$").unwrap();
+    if let Some(groups) = shortcode_re.captures(line) {
+        let provider = PROVIDERS.iter().find(|p| p.name == &groups[1])?;
+        return Some(EmbedLine::Video(
+            provider.embed_url.replace("{id}", &groups[2]),
+        ));
+    }
+
+    let is_bare_url = (line.starts_with("https://") || line.starts_with("http://"))
+        && !line.contains(char::is_whitespace);
+    if !is_bare_url {
+        return None;
+    }
+    for provider in PROVIDERS {
+        for pattern in provider.url_patterns {
+            if let Some(groups) = Regex::new(pattern).unwrap().captures(line) {
+                return Some(EmbedLine::Video(
+                    provider.embed_url.replace("{id}", &groups[1]),
+                ));
+            }
+        }
+    }
+    Some(EmbedLine::Link(line.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_youtube_watch_url() {
+        assert_eq!(
+            embed_line("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"),
+            Some(EmbedLine::Video(String::from(
+                "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_youtube_short_url() {
+        assert_eq!(
+            embed_line("https://youtu.be/dQw4w9WgXcQ"),
+            Some(EmbedLine::Video(String::from(
+                "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_shortcode() {
+        assert_eq!(
+            embed_line("{{vimeo 76979871}}"),
+            Some(EmbedLine::Video(String::from(
+                "https://player.vimeo.com/video/76979871"
+            )))
+        );
+        assert_eq!(embed_line("{{dailymotion x7tgad0}}"), None);
+    }
+
+    #[test]
+    fn test_unknown_provider_is_a_link() {
+        assert_eq!(
+            embed_line("https://example.com/video/42"),
+            Some(EmbedLine::Link(String::from(
+                "https://example.com/video/42"
+            )))
+        );
+        assert_eq!(embed_line("Watch https://youtu.be/dQw4w9WgXcQ"), None);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::entities::escape_text;
+use crate::footnotes;
+use crate::inline::Inline;
+use crate::tables::TableCell;
+use crate::Token;
+
+/// Rewrites the links of `tokens` as numbered endnotes, for print output.
+///
+/// Every link is replaced by its text followed by a superscript number, and a
+/// "Links" section listing each URL under its number is appended at the end of the
+/// document, rendered like the footnotes (see `footnotes::notes_section`). The
+/// numbers go on from the last footnote, so the two never share one. Repeated URLs
+/// share the same number. Fragment-only (`#section`) and `mailto:` links are left
+/// as they are, as are code blocks.
+///
+/// # Example
+///
+/// ```rust
+/// let tokens = md2html::parse(vec![String::from("See [docs](https://example.com).")].into_iter());
+/// let html = md2html::render_html(&md2html::endnotes::links_to_endnotes(tokens));
+/// assert_eq!(
+///     html,
+///     vec![
+///         "<p>See docs<sup>1</sup>.</p>",
+///         "<section class=\"footnotes endnotes\">",
+///         "<h2>Links</h2>",
+///         "<ol>",
+///         "<li id=\"endnote-1\">https://example.com</li>",
+///         "</ol>",
+///         "</section>",
+///     ]
+/// );
+/// ```
+pub fn links_to_endnotes(tokens: Vec<Token>) -> Vec<Token> {
+    let mut urls = Endnotes {
+        first: footnotes::footnote_count(&tokens) + 1,
+        urls: Vec::new(),
+    };
+
+    let mut result: Vec<Token> = tokens
+        .into_iter()
+        .map(|token| replace_links(token, &mut urls))
+        .collect();
+
+    if !urls.urls.is_empty() {
+        let items = urls
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| {
+                format!(
+                    "<li id=\"endnote-{}\">{}</li>",
+                    urls.first + index,
+                    escape_text(url)
+                )
+            })
+            .collect();
+        result.extend(footnotes::notes_section(
+            "footnotes endnotes",
+            Some("Links"),
+            urls.first,
+            items,
+        ));
+    }
+    result
+}
+
+/// The URLs turned into endnotes so far, in order.
+///
+/// # Fields
+///
+/// - `first`: The number of the first endnote, right after the last footnote.
+/// - `urls`: The URL of every endnote.
+struct Endnotes {
+    first: usize,
+    urls: Vec<String>,
+}
+
+impl Endnotes {
+    /// The number of the endnote of `url`, given on its first link.
+    fn number(&mut self, url: String) -> usize {
+        let index = match self.urls.iter().position(|known| *known == url) {
+            Some(index) => index,
+            None => {
+                self.urls.push(url);
+                self.urls.len() - 1
+            }
+        };
+        self.first + index
+    }
+}
+
+/// Replaces the links of a single token, collecting their URLs in `urls`.
+fn replace_links(token: Token, urls: &mut Endnotes) -> Token {
+    match token {
+        Token::Header { level, text } => Token::Header {
+            level,
+            text: replace_inline_links(text, urls),
+        },
+        Token::Paragraph { text } => Token::Paragraph {
+            text: replace_inline_links(text, urls),
+        },
+        Token::UListItem { depth, text } => Token::UListItem {
+            depth,
+            text: replace_inline_links(text, urls),
+        },
+        Token::ListItemStart { text } => Token::ListItemStart {
+            text: replace_inline_links(text, urls),
+        },
+        Token::OListItem {
+            depth,
+            number,
+            text,
+        } => Token::OListItem {
+            depth,
+            number,
+            text: replace_inline_links(text, urls),
+        },
+        Token::TableRow { cells } => Token::TableRow {
+            cells: cells
+                .into_iter()
+                .map(|cell| TableCell {
+                    text: replace_inline_links(cell.text, urls),
+                    ..cell
+                })
+                .collect(),
+        },
+        Token::QuoteAttribution { text } => Token::QuoteAttribution {
+            text: replace_inline_links(text, urls),
+        },
+        Token::DefinitionTerm { text } => Token::DefinitionTerm {
+            text: replace_inline_links(text, urls),
+        },
+        Token::Definition { text } => Token::Definition {
+            text: replace_inline_links(text, urls),
+        },
+        Token::Quote { text, nested_token } => {
+            let text = replace_inline_links(text, urls);
+            Token::Quote {
+                text,
+                nested_token: Box::new(replace_links(*nested_token, urls)),
+            }
+        }
+        // SimpleText only holds raw code block lines
+        token => token,
+    }
+}
+
+/// Replaces every link of `inlines` by its content followed by its endnote number.
+fn replace_inline_links(inlines: Vec<Inline>, urls: &mut Endnotes) -> Vec<Inline> {
+    let mut result = Vec::with_capacity(inlines.len());
+    for inline in inlines {
+        match inline {
+            Inline::Link {
+                children,
+                url,
+                title,
+            } => {
+                let children = replace_inline_links(children, urls);
+                if url.starts_with('#') || url.starts_with("mailto:") {
+                    result.push(Inline::Link {
+                        children,
+                        url,
+                        title,
+                    });
+                    continue;
+                }
+                let number = urls.number(url);
+                result.extend(children);
+                result.push(Inline::Html(format!("<sup>{}</sup>", number)));
+            }
+            Inline::Strong(children) => {
+                result.push(Inline::Strong(replace_inline_links(children, urls)))
+            }
+            Inline::Emph(children) => {
+                result.push(Inline::Emph(replace_inline_links(children, urls)))
+            }
+            Inline::Strikethrough(children) => {
+                result.push(Inline::Strikethrough(replace_inline_links(children, urls)))
+            }
+            inline => result.push(inline),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, render_html, tokenize_text_with_options, ParserOptions};
+
+    #[test]
+    fn test_endnotes_numbering_with_duplicate() {
+        let lines = vec![
+            String::from("Read [the guide](https://a.example) and [the API](https://b.example)."),
+            String::from("- Again, [the guide](https://a.example)"),
+            String::from("Or [mail us](mailto:me@example.com), see [below](#faq), [the blog](https://c.example)."),
+        ];
+        let html = render_html(&links_to_endnotes(parse(lines.into_iter())));
+        assert_eq!(
+            html,
+            vec![
+                "<p>Read the guide<sup>1</sup> and the API<sup>2</sup>.</p>",
+                "<ul>",
+                "<li>Again, the guide<sup>1</sup></li>",
+                "</ul>",
+                "<p>Or <a href=\"mailto:me@example.com\">mail us</a>, see <a href=\"#faq\">below</a>, the blog<sup>3</sup>.</p>",
+                "<section class=\"footnotes endnotes\">",
+                "<h2>Links</h2>",
+                "<ol>",
+                "<li id=\"endnote-1\">https://a.example</li>",
+                "<li id=\"endnote-2\">https://b.example</li>",
+                "<li id=\"endnote-3\">https://c.example</li>",
+                "</ol>",
+                "</section>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_endnotes_numbered_after_footnotes() {
+        let markdown = "A claim[^src], see [the data](https://data.example).\n\n\
+                        [^src]: From [the paper](https://paper.example).";
+        let options = ParserOptions {
+            link_endnotes: true,
+            ..Default::default()
+        };
+        let html =
+            tokenize_text_with_options(markdown.lines().map(String::from), &options).html_lines;
+        assert_eq!(
+            html[0],
+            "<p>A claim<sup><a href=\"#fn-1\" id=\"fnref-1\">1</a></sup>, see the data<sup>2</sup>.</p>"
+        );
+        assert_eq!(
+            html[html.len() - 5..],
+            [
+                "<h2>Links</h2>",
+                "<ol start=\"2\">",
+                "<li id=\"endnote-2\">https://data.example</li>",
+                "</ol>",
+                "</section>",
+            ]
+        );
+        assert_eq!(
+            html.iter()
+                .filter(|line| line.starts_with("<section"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_endnotes_without_links() {
+        let lines = vec![String::from("No links, only [a fragment](#top).")];
+        let tokens = parse(lines.into_iter());
+        let expected = render_html(&tokens);
+        assert_eq!(render_html(&links_to_endnotes(tokens)), expected);
+    }
+}
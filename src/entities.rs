@@ -0,0 +1,156 @@
+/// The named character references recognized in markdown text.
+///
+/// This is the commonly used subset of the HTML5 named references, any other name
+/// is treated as plain text and has its `&` escaped.
+const NAMED_REFERENCES: &[&str] = &[
+    "amp", "lt", "gt", "quot", "apos", "nbsp", "ensp", "emsp", "thinsp", "zwnj", "zwj", "shy",
+    "ndash", "mdash", "hellip", "lsquo", "rsquo", "sbquo", "ldquo", "rdquo", "bdquo", "laquo",
+    "raquo", "lsaquo", "rsaquo", "prime", "Prime", "bull", "middot", "dagger", "Dagger", "para",
+    "sect", "copy", "reg", "trade", "deg", "plusmn", "times", "divide", "minus", "frac12",
+    "frac14", "frac34", "sup1", "sup2", "sup3", "micro", "permil", "infin", "ne", "le", "ge",
+    "asymp", "equiv", "sum", "prod", "radic", "part", "nabla", "isin", "notin", "cap", "cup",
+    "sub", "sup", "and", "or", "not", "forall", "exist", "empty", "larr", "rarr", "uarr", "darr",
+    "harr", "lArr", "rArr", "uArr", "dArr", "hArr", "crarr", "cent", "pound", "euro", "yen",
+    "curren", "iexcl", "iquest", "ordf", "ordm", "acute", "cedil", "uml", "macr", "brvbar",
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+    "lambda", "mu", "nu", "xi", "omicron", "pi", "rho", "sigma", "tau", "upsilon", "phi", "chi",
+    "psi", "omega", "Alpha", "Beta", "Gamma", "Delta", "Theta", "Lambda", "Pi", "Sigma", "Phi",
+    "Psi", "Omega", "spades", "clubs", "hearts", "diams", "loz", "check", "cross", "star", "starf",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil", "Egrave", "Eacute",
+    "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml", "Ntilde", "Ograve", "Oacute", "Ocirc",
+    "Otilde", "Ouml", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute", "szlig", "agrave",
+    "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil", "egrave", "eacute", "ecirc",
+    "euml", "igrave", "iacute", "icirc", "iuml", "ntilde", "ograve", "oacute", "ocirc", "otilde",
+    "ouml", "oslash", "ugrave", "uacute", "ucirc", "uuml", "yacute", "yuml", "eth", "ETH", "thorn",
+    "THORN",
+];
+
+/// Returns the name of the reference-shaped text at the start of `text` (`ndash` for
+/// `&ndash;`), or `None` when `text` doesn't look like a reference at all (a lone `&`).
+fn reference_name(text: &str) -> Option<&str> {
+    let end = text.find(';')?;
+    let name = text.get(1..end)?;
+    let looks_like_reference =
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '#');
+    looks_like_reference.then_some(name)
+}
+
+/// Checks whether `name` is a valid named (`ndash`) or numeric (`#8212`, `#x2014`)
+/// character reference.
+fn is_valid_reference(name: &str) -> bool {
+    if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+        (1..=6).contains(&hex.len()) && u32::from_str_radix(hex, 16).is_ok_and(is_valid_code_point)
+    } else if let Some(decimal) = name.strip_prefix('#') {
+        (1..=7).contains(&decimal.len())
+            && decimal.chars().all(|c| c.is_ascii_digit())
+            && decimal.parse().is_ok_and(is_valid_code_point)
+    } else {
+        NAMED_REFERENCES.contains(&name)
+    }
+}
+
+fn is_valid_code_point(code_point: u32) -> bool {
+    code_point != 0 && char::from_u32(code_point).is_some()
+}
+
+/// Escapes the `&` of every invalid character reference in `text`.
+///
+/// Valid named (`&hellip;`) and numeric (`&#8212;`, `&#x2014;`) references are passed
+/// through unchanged so the browser renders them, invalid ones like `&notareference;`
+/// get `&amp;` and are displayed as typed. A `&` that doesn't look like a reference
+/// is left alone.
+///
+/// # Example
+///
+/// ```rust
+/// let html = md2html::entities::escape_invalid_references("&ndash; &foo; & &#169;");
+/// assert_eq!(html, "&ndash; &amp;foo; & &#169;");
+/// ```
+pub fn escape_invalid_references(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        match reference_name(rest) {
+            Some(name) if !is_valid_reference(name) => result.push_str("&amp;"),
+            _ => result.push('&'),
+        }
+        rest = &rest[1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Escapes the `<` and `>` of `text`, and every `&` that doesn't start a valid
+/// character reference, so the text can't inject markup but references are still
+/// rendered.
+///
+/// # Example
+///
+/// ```rust
+/// let html = md2html::entities::escape_text("5 < 10 && x > 3 &copy; &foo;");
+/// assert_eq!(html, "5 &lt; 10 &amp;&amp; x &gt; 3 &copy; &amp;foo;");
+/// ```
+pub fn escape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for (index, c) in text.char_indices() {
+        match c {
+            '&' => match reference_name(&text[index..]) {
+                Some(name) if is_valid_reference(name) => result.push('&'),
+                _ => result.push_str("&amp;"),
+            },
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Escapes `text` for a double-quoted attribute value: `"`, `<` and `>` are
+/// escaped, and so is the `&` of invalid character references.
+pub(crate) fn escape_attribute(text: &str) -> String {
+    escape_invalid_references(text)
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escapes every `&` of `text`, used for code where references are displayed as typed.
+pub fn escape_all_references(text: &str) -> String {
+    text.replace('&', "&amp;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_reference_passes_through() {
+        assert_eq!(
+            escape_invalid_references("a &ndash; b&hellip;"),
+            "a &ndash; b&hellip;"
+        );
+    }
+
+    #[test]
+    fn test_numeric_references_pass_through() {
+        assert_eq!(
+            escape_invalid_references("&#8212; &#x2014;"),
+            "&#8212; &#x2014;"
+        );
+        assert_eq!(
+            escape_invalid_references("&#0; &#x110000;"),
+            "&amp;#0; &amp;#x110000;"
+        );
+    }
+
+    #[test]
+    fn test_invalid_reference_is_escaped() {
+        assert_eq!(
+            escape_invalid_references("&notareference; & AT&T &#xZZ;"),
+            "&amp;notareference; & AT&T &amp;#xZZ;"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::borrow::Cow;
+
+/// Escapes the `&`, `<` and `>` of `text`, for HTML text content.
+///
+/// Every `&` is escaped, so character references are displayed as typed (see
+/// `entities::escape_text` to render them). `text` is borrowed when there is
+/// nothing to escape.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::escape::escape_text;
+/// use std::borrow::Cow;
+///
+/// assert_eq!(escape_text("a < b && c"), "a &lt; b &amp;&amp; c");
+/// assert!(matches!(escape_text("plain text"), Cow::Borrowed(_)));
+/// ```
+pub fn escape_text(text: &str) -> Cow<'_, str> {
+    escape_with(text, |c| match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        _ => None,
+    })
+}
+
+/// Escapes `text` for an HTML attribute value, quoted with `"` or `'`: the quotes
+/// are escaped too.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::escape::escape_attr;
+///
+/// assert_eq!(escape_attr("Ada's \"notes\""), "Ada&#39;s &quot;notes&quot;");
+/// ```
+pub fn escape_attr(text: &str) -> Cow<'_, str> {
+    escape_with(text, |c| match c {
+        '"' => Some("&quot;"),
+        '\'' => Some("&#39;"),
+        c => html_text_entity(c),
+    })
+}
+
+/// Escapes `text` for XML text or attribute values, with the five entities XML
+/// predefines.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::escape::escape_xml;
+///
+/// assert_eq!(escape_xml("Q&A: 'why' <now>"), "Q&amp;A: &apos;why&apos; &lt;now&gt;");
+/// ```
+pub fn escape_xml(text: &str) -> Cow<'_, str> {
+    escape_with(text, |c| match c {
+        '"' => Some("&quot;"),
+        '\'' => Some("&apos;"),
+        c => html_text_entity(c),
+    })
+}
+
+fn html_text_entity(c: char) -> Option<&'static str> {
+    match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        _ => None,
+    }
+}
+
+/// Replaces every character of `text` that `entity` has a replacement for, only
+/// allocating once the first one is found.
+fn escape_with(text: &str, entity: impl Fn(char) -> Option<&'static str>) -> Cow<'_, str> {
+    let Some(first) = text.find(|c| entity(c).is_some()) else {
+        return Cow::Borrowed(text);
+    };
+    let mut escaped = String::with_capacity(text.len() + 8);
+    escaped.push_str(&text[..first]);
+    for c in text[first..].chars() {
+        match entity(c) {
+            Some(replacement) => escaped.push_str(replacement),
+            None => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every string of up to 4 characters made of the special characters and a
+    /// letter, as the inputs of the property tests.
+    fn inputs() -> Vec<String> {
+        let alphabet = ['&', '<', '>', '"', '\'', 'a', ';'];
+        let mut inputs = vec![String::new()];
+        let mut previous = vec![String::new()];
+        for _ in 0..4 {
+            previous = previous
+                .iter()
+                .flat_map(|text| alphabet.iter().map(move |c| format!("{}{}", text, c)))
+                .collect();
+            inputs.extend(previous.iter().cloned());
+        }
+        inputs
+    }
+
+    fn unescape(text: &str) -> String {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    }
+
+    /// Checks that no raw `specials` are left, every `&` starts one of the
+    /// entities, and the original text can be recovered.
+    fn assert_safe(escape: fn(&str) -> Cow<'_, str>, specials: &[char], entities: &[&str]) {
+        for input in inputs() {
+            let escaped = escape(&input);
+            assert!(!escaped.contains(specials), "{:?} -> {:?}", input, escaped);
+            for (index, _) in escaped.match_indices('&') {
+                let rest = &escaped[index..];
+                assert!(
+                    entities.iter().any(|entity| rest.starts_with(entity)),
+                    "{:?} -> {:?}",
+                    input,
+                    escaped
+                );
+            }
+            assert_eq!(unescape(&escaped), input);
+        }
+    }
+
+    #[test]
+    fn test_escape_text_is_safe() {
+        assert_safe(escape_text, &['<', '>'], &["&amp;", "&lt;", "&gt;"]);
+    }
+
+    #[test]
+    fn test_escape_attr_is_safe() {
+        assert_safe(
+            escape_attr,
+            &['<', '>', '"', '\''],
+            &["&amp;", "&lt;", "&gt;", "&quot;", "&#39;"],
+        );
+    }
+
+    #[test]
+    fn test_escape_xml_is_safe() {
+        assert_safe(
+            escape_xml,
+            &['<', '>', '"', '\''],
+            &["&amp;", "&lt;", "&gt;", "&quot;", "&apos;"],
+        );
+    }
+
+    #[test]
+    fn test_borrowed_when_clean() {
+        for escape in [escape_text, escape_attr, escape_xml] {
+            for text in ["", "plain text", "naïve 日本語", "a;b#c"] {
+                assert!(matches!(escape(text), Cow::Borrowed(borrowed) if borrowed == text));
+            }
+            assert!(matches!(escape("a & b"), Cow::Owned(_)));
+        }
+        // Quotes are only special in attributes
+        assert!(matches!(escape_text("\"quoted\""), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::search_index::json_string;
+use crate::ParserOptions;
+
+/// The version of md2html, following semantic versioning.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The output formats md2html can render.
+const OUTPUTS: &[&str] = &["html"];
+
+/// What this build of md2html can do, for scripts checking a capability before
+/// using it.
+///
+/// # Fields
+///
+/// - `version`: The version of md2html.
+/// - `cargo_features`: The optional cargo features md2html was built with, e.g.
+///   `highlight`. A feature left out at build time isn't listed.
+/// - `outputs`: The output formats.
+/// - `extensions`: The syntax extensions and block constructs, with whether they
+///   are on by default (see `ParserOptions::extensions`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureReport {
+    pub version: &'static str,
+    pub cargo_features: Vec<&'static str>,
+    pub outputs: Vec<&'static str>,
+    pub extensions: Vec<(&'static str, bool)>,
+}
+
+/// The capabilities of this build.
+///
+/// # Example
+///
+/// ```rust
+/// let report = md2html::features::report();
+/// assert_eq!(report.version, env!("CARGO_PKG_VERSION"));
+/// assert!(report.outputs.contains(&"html"));
+/// assert!(report.extensions.contains(&("tables", true)));
+/// ```
+pub fn report() -> FeatureReport {
+    let mut cargo_features = Vec::new();
+    if cfg!(feature = "highlight") {
+        cargo_features.push("highlight");
+    }
+    FeatureReport {
+        version: VERSION,
+        cargo_features,
+        outputs: OUTPUTS.to_vec(),
+        extensions: ParserOptions::default().extensions(),
+    }
+}
+
+impl FeatureReport {
+    /// The report as text, one capability per line.
+    pub fn to_text(&self) -> String {
+        let list = |names: &[&str]| match names.is_empty() {
+            true => String::from("none"),
+            false => names.join(", "),
+        };
+        let mut text = format!(
+            "md2html {}\ncargo features: {}\noutputs: {}\nextensions:\n",
+            self.version,
+            list(&self.cargo_features),
+            list(&self.outputs)
+        );
+        for (name, enabled) in &self.extensions {
+            let state = if *enabled { "on" } else { "off" };
+            text += &format!("  {} ({} by default)\n", name, state);
+        }
+        text
+    }
+
+    /// The report as a JSON object, `{"version": "0.1.1", "cargo_features": [...],
+    /// "outputs": ["html"], "extensions": [{"name": "tables", "default": true}, ...]}`.
+    pub fn to_json(&self) -> String {
+        let strings = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| json_string(name))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        let extensions: Vec<String> = self
+            .extensions
+            .iter()
+            .map(|(name, enabled)| {
+                format!(
+                    "{{\"name\": {}, \"default\": {}}}",
+                    json_string(name),
+                    enabled
+                )
+            })
+            .collect();
+        format!(
+            "{{\"version\": {}, \"cargo_features\": [{}], \"outputs\": [{}], \"extensions\": [{}]}}\n",
+            json_string(self.version),
+            strings(&self.cargo_features),
+            strings(&self.outputs),
+            extensions.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_entries() {
+        let report = report();
+        assert_eq!(report.outputs, ["html"]);
+        assert!(report.extensions.contains(&("tables", true)));
+        assert!(report.extensions.contains(&("autolink_bare_urls", false)));
+
+        let json = report.to_json();
+        assert!(json.contains("\"outputs\": [\"html\"]"), "{}", json);
+        assert!(json.contains("{\"name\": \"tables\", \"default\": true}"));
+        assert!(json.contains("{\"name\": \"toc\", \"default\": false}"));
+        let text = report.to_text();
+        assert!(text.starts_with(&format!("md2html {}\n", VERSION)));
+        assert!(
+            text.contains("\n  code_fences (on by default)\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_report_cargo_features() {
+        let report = report();
+        assert_eq!(
+            report.cargo_features.contains(&"highlight"),
+            cfg!(feature = "highlight")
+        );
+        if !cfg!(feature = "highlight") {
+            assert!(report.to_json().contains("\"cargo_features\": []"));
+            assert!(report.to_text().contains("cargo features: none\n"));
+        }
+    }
+}
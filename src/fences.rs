@@ -0,0 +1,74 @@
+/// The opening or closing line of a fenced code block: a run of at least three
+/// backticks or tildes.
+///
+/// # Fields
+///
+/// - `marker`: The fence character, `` ` `` or `~`.
+/// - `length`: The number of fence characters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fence {
+    pub marker: char,
+    pub length: usize,
+}
+
+impl Fence {
+    /// Parses the fence `line` starts with, returning it with the info string
+    /// that follows it (`rust` in `` ```rust ``).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use md2html::fences::Fence;
+    ///
+    /// let (fence, info) = Fence::parse("~~~~markdown").unwrap();
+    /// assert_eq!(fence, Fence { marker: '~', length: 4 });
+    /// assert_eq!(info, "markdown");
+    /// assert_eq!(Fence::parse("``not a fence"), None);
+    /// ```
+    pub fn parse(line: &str) -> Option<(Fence, &str)> {
+        let marker = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+        let info = line.trim_start_matches(marker);
+        let length = line.len() - info.len();
+        (length >= 3).then_some((Fence { marker, length }, info))
+    }
+
+    /// Whether `other` ends the code block this fence opened: it has to use the
+    /// same character and be at least as long, so a ```` block can show ```.
+    pub fn is_closed_by(&self, other: &Fence) -> bool {
+        other.marker == self.marker && other.length >= self.length
+    }
+}
+
+/// Follows the fenced code blocks of a document, line by line.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::fences::FenceTracker;
+///
+/// let mut fences = FenceTracker::default();
+/// let code: Vec<bool> = ["text", "````", "```", "````", "text"]
+///     .iter()
+///     .map(|line| fences.is_code(line))
+///     .collect();
+/// assert_eq!(code, [false, true, true, true, false]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FenceTracker {
+    open: Option<Fence>,
+}
+
+impl FenceTracker {
+    /// Reads the next `line`, returning whether it is code: a line inside a code
+    /// block or one of its fences.
+    pub fn is_code(&mut self, line: &str) -> bool {
+        let fence = Fence::parse(line).map(|(fence, _)| fence);
+        match (self.open, fence) {
+            (Some(open), Some(fence)) if open.is_closed_by(&fence) => self.open = None,
+            (Some(_), _) => (),
+            (None, Some(fence)) => self.open = Some(fence),
+            (None, None) => return false,
+        }
+        true
+    }
+}
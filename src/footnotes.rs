@@ -0,0 +1,403 @@
+use crate::fences::FenceTracker;
+use crate::inline::{self, Inline};
+use crate::tables::TableCell;
+use crate::Token;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// The definition of a footnote, `[^label]: text`.
+///
+/// # Fields
+///
+/// - `text`: The markdown text of the footnote, its continuation lines joined
+///   with spaces.
+/// - `line`: The 1-based line of the definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FootnoteDefinition {
+    pub text: String,
+    pub line: usize,
+}
+
+/// The footnote definitions of a document, found by `collect_footnotes`.
+///
+/// # Fields
+///
+/// - `definitions`: The definitions by label, the first one wins when a label is
+///   defined twice.
+/// - `is_definition`: Whether each line of the document belongs to a definition,
+///   its first line or a continuation, and so is left out of the text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollectedFootnotes {
+    pub definitions: HashMap<String, FootnoteDefinition>,
+    pub is_definition: Vec<bool>,
+}
+
+/// Parses the first line of a footnote definition (`[^1]: The source.`) into its
+/// label and text.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::footnotes::parse_definition;
+///
+/// assert_eq!(
+///     parse_definition("[^note]: The source."),
+///     Some((String::from("note"), String::from("The source.")))
+/// );
+/// assert_eq!(parse_definition("[note]: https://example.com"), None);
+/// ```
+pub fn parse_definition(line: &str) -> Option<(String, String)> {
+    let definition_re = Regex::new(r"^ {0,3}\[\^([^\]\s]+)\]:\s*(.*)$").unwrap();
+    let groups = definition_re.captures(line)?;
+    Some((groups[1].to_string(), groups[2].trim_end().to_string()))
+}
+
+/// Whether `line` continues a footnote definition: it is indented by four spaces
+/// or a tab.
+fn is_continuation(line: &str) -> bool {
+    (line.starts_with("    ") || line.starts_with('\t')) && !line.trim().is_empty()
+}
+
+/// Collects the footnote definitions of a document, skipping fenced code blocks.
+///
+/// A definition goes on over the lines below it indented by four spaces or a tab,
+/// blank lines between them included.
+///
+/// # Example
+///
+/// ```rust
+/// let lines: Vec<String> = ["Text[^1]", "", "[^1]: The source,", "    page 12."]
+///     .iter()
+///     .map(|line| line.to_string())
+///     .collect();
+/// let footnotes = md2html::footnotes::collect_footnotes(&lines);
+/// assert_eq!(footnotes.definitions["1"].text, "The source, page 12.");
+/// assert_eq!(footnotes.is_definition, [false, false, true, true]);
+/// ```
+pub fn collect_footnotes(lines: &[String]) -> CollectedFootnotes {
+    let mut footnotes = CollectedFootnotes {
+        definitions: HashMap::new(),
+        is_definition: vec![false; lines.len()],
+    };
+    let mut fences = FenceTracker::default();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        i += 1;
+        if fences.is_code(line) {
+            continue;
+        }
+        let Some((label, text)) = parse_definition(line) else {
+            continue;
+        };
+        footnotes.is_definition[i - 1] = true;
+        let line_number = i;
+        let mut text = vec![text];
+        while i < lines.len() {
+            let next_text = lines[i..].iter().position(|line| !line.trim().is_empty());
+            match next_text {
+                Some(offset) if is_continuation(&lines[i + offset]) => {
+                    footnotes.is_definition[i..=i + offset].fill(true);
+                    text.push(lines[i + offset].trim().to_string());
+                    i += offset + 1;
+                }
+                _ => break,
+            }
+        }
+        let definition = FootnoteDefinition {
+            text: text.join(" ").trim().to_string(),
+            line: line_number,
+        };
+        footnotes.definitions.entry(label).or_insert(definition);
+    }
+    footnotes
+}
+
+/// Links the footnote references (`[^label]`) of `tokens` to their footnotes, and
+/// appends the footnotes section listing them.
+///
+/// `notes` holds the rendered text of every defined footnote by label. The
+/// footnotes are numbered in the order they are first referenced, each reference
+/// becomes `<sup><a href="#fn-1" id="fnref-1">1</a></sup>`, and every footnote ends
+/// with a link back to its first reference. References to labels without a
+/// definition stay literal text, and so do code spans.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::inline::Inline;
+/// use std::collections::HashMap;
+///
+/// let tokens = md2html::parse(vec![String::from("A claim[^src].")].into_iter());
+/// let notes = HashMap::from([(String::from("src"), vec![Inline::Text(String::from("The source."))])]);
+/// let html = md2html::render_html(&md2html::footnotes::link_footnotes(tokens, &notes));
+/// assert_eq!(html[0], "<p>A claim<sup><a href=\"#fn-1\" id=\"fnref-1\">1</a></sup>.</p>");
+/// assert_eq!(html[3], "<li id=\"fn-1\">The source. <a href=\"#fnref-1\" class=\"footnote-backref\">↩</a></li>");
+/// ```
+pub fn link_footnotes(tokens: Vec<Token>, notes: &HashMap<String, Vec<Inline>>) -> Vec<Token> {
+    if notes.is_empty() {
+        return tokens;
+    }
+    let mut references = References {
+        notes,
+        order: Vec::new(),
+        counts: Vec::new(),
+    };
+    let mut result: Vec<Token> = tokens
+        .into_iter()
+        .map(|token| link_token(token, &mut references))
+        .collect();
+
+    if !references.order.is_empty() {
+        let items = references
+            .order
+            .iter()
+            .enumerate()
+            .map(|(index, label)| {
+                format!(
+                    "<li id=\"fn-{}\">{} <a href=\"#fnref-{}\" class=\"footnote-backref\">↩</a></li>",
+                    index + 1,
+                    inline::to_html(&notes[label]),
+                    index + 1
+                )
+            })
+            .collect();
+        result.extend(notes_section(FOOTNOTES_CLASS, None, 1, items));
+    }
+    result
+}
+
+/// The class of the `<section>` listing the footnotes.
+const FOOTNOTES_CLASS: &str = "footnotes";
+
+/// The tokens of a section listing numbered notes at the end of a document, like
+/// the footnotes: a `<section class="{class}">` holding an `<h2>` when `heading`
+/// is set, then an `<ol>` of the `items`, the HTML of their `<li>` elements,
+/// numbered from `start`.
+///
+/// # Example
+///
+/// ```rust
+/// let items = vec![String::from("<li>https://example.com</li>")];
+/// let tokens = md2html::footnotes::notes_section("footnotes endnotes", Some("Links"), 3, items);
+/// assert_eq!(
+///     md2html::render_html(&tokens),
+///     [
+///         "<section class=\"footnotes endnotes\">",
+///         "<h2>Links</h2>",
+///         "<ol start=\"3\">",
+///         "<li>https://example.com</li>",
+///         "</ol>",
+///         "</section>",
+///     ]
+/// );
+/// ```
+pub fn notes_section(
+    class: &str,
+    heading: Option<&str>,
+    start: usize,
+    items: Vec<String>,
+) -> Vec<Token> {
+    let mut html = vec![format!("<section class=\"{}\">", class)];
+    html.extend(heading.map(|heading| format!("<h2>{}</h2>", heading)));
+    html.push(match start {
+        1 => String::from("<ol>"),
+        start => format!("<ol start=\"{}\">", start),
+    });
+    html.extend(items);
+    html.push(String::from("</ol>"));
+    html.push(String::from("</section>"));
+    html.into_iter()
+        .map(|html| Token::RawHtml { html })
+        .collect()
+}
+
+/// How many footnotes `tokens` list in their footnotes section, see `link_footnotes`.
+pub fn footnote_count(tokens: &[Token]) -> usize {
+    let section = format!("<section class=\"{}\">", FOOTNOTES_CLASS);
+    let Some(start) = tokens
+        .iter()
+        .position(|token| matches!(token, Token::RawHtml { html } if *html == section))
+    else {
+        return 0;
+    };
+    tokens[start..]
+        .iter()
+        .take_while(|token| !matches!(token, Token::RawHtml { html } if html == "</section>"))
+        .filter(|token| matches!(token, Token::RawHtml { html } if html.starts_with("<li ")))
+        .count()
+}
+
+/// The footnotes referenced so far, in order, with how many times each was.
+struct References<'a> {
+    notes: &'a HashMap<String, Vec<Inline>>,
+    order: Vec<String>,
+    counts: Vec<usize>,
+}
+
+impl References<'_> {
+    /// The HTML of a reference to `label`, numbering its footnote on its first
+    /// reference. Later references get their own id, the back-link goes to the first.
+    fn reference(&mut self, label: &str) -> String {
+        let index = match self.order.iter().position(|known| known == label) {
+            Some(index) => {
+                self.counts[index] += 1;
+                index
+            }
+            None => {
+                self.order.push(label.to_string());
+                self.counts.push(1);
+                self.order.len() - 1
+            }
+        };
+        let number = index + 1;
+        let id = match self.counts[index] {
+            1 => format!("fnref-{}", number),
+            count => format!("fnref-{}-{}", number, count),
+        };
+        format!(
+            "<sup><a href=\"#fn-{}\" id=\"{}\">{}</a></sup>",
+            number, id, number
+        )
+    }
+}
+
+/// Links the footnote references of a single token.
+fn link_token(token: Token, references: &mut References) -> Token {
+    match token {
+        Token::Header { level, text } => Token::Header {
+            level,
+            text: link_inlines(text, references),
+        },
+        Token::Paragraph { text } => Token::Paragraph {
+            text: link_inlines(text, references),
+        },
+        Token::UListItem { depth, text } => Token::UListItem {
+            depth,
+            text: link_inlines(text, references),
+        },
+        Token::ListItemStart { text } => Token::ListItemStart {
+            text: link_inlines(text, references),
+        },
+        Token::OListItem {
+            depth,
+            number,
+            text,
+        } => Token::OListItem {
+            depth,
+            number,
+            text: link_inlines(text, references),
+        },
+        Token::TableRow { cells } => Token::TableRow {
+            cells: cells
+                .into_iter()
+                .map(|cell| TableCell {
+                    text: link_inlines(cell.text, references),
+                    ..cell
+                })
+                .collect(),
+        },
+        Token::QuoteAttribution { text } => Token::QuoteAttribution {
+            text: link_inlines(text, references),
+        },
+        Token::DefinitionTerm { text } => Token::DefinitionTerm {
+            text: link_inlines(text, references),
+        },
+        Token::Definition { text } => Token::Definition {
+            text: link_inlines(text, references),
+        },
+        Token::Quote { text, nested_token } => {
+            let text = link_inlines(text, references);
+            Token::Quote {
+                text,
+                nested_token: Box::new(link_token(*nested_token, references)),
+            }
+        }
+        // SimpleText only holds raw code block lines
+        token => token,
+    }
+}
+
+/// Replaces the footnote references in the text of `inlines`.
+fn link_inlines(inlines: Vec<Inline>, references: &mut References) -> Vec<Inline> {
+    let reference_re = Regex::new(r"\[\^([^\]\s]+)\]").unwrap();
+    let mut result = Vec::with_capacity(inlines.len());
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => {
+                let mut rest = 0;
+                for groups in reference_re.captures_iter(&text) {
+                    let (whole, label) = (groups.get(0).unwrap(), &groups[1]);
+                    if !references.notes.contains_key(label) {
+                        continue;
+                    }
+                    if whole.start() > rest {
+                        result.push(Inline::Text(text[rest..whole.start()].to_string()));
+                    }
+                    result.push(Inline::Html(references.reference(label)));
+                    rest = whole.end();
+                }
+                if rest < text.len() {
+                    result.push(Inline::Text(text[rest..].to_string()));
+                }
+            }
+            Inline::Strong(children) => {
+                result.push(Inline::Strong(link_inlines(children, references)))
+            }
+            Inline::Emph(children) => result.push(Inline::Emph(link_inlines(children, references))),
+            Inline::Strikethrough(children) => {
+                result.push(Inline::Strikethrough(link_inlines(children, references)))
+            }
+            inline => result.push(inline),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tokenize_text;
+
+    fn html(markdown: &str) -> Vec<String> {
+        tokenize_text(markdown.lines().map(String::from))
+    }
+
+    #[test]
+    fn test_footnotes_numbered_by_first_reference() {
+        let html = html(
+            "Some claim[^b], *another*[^a] and again[^b].\n\n\
+             [^a]: First *defined*.\n\
+             [^b]: The source,\n    page 12.\n\n    Second paragraph.\n\
+             After the notes",
+        );
+        assert_eq!(
+            html,
+            [
+                "<p>Some claim<sup><a href=\"#fn-1\" id=\"fnref-1\">1</a></sup>, \
+                 <i>another</i><sup><a href=\"#fn-2\" id=\"fnref-2\">2</a></sup> and again\
+                 <sup><a href=\"#fn-1\" id=\"fnref-1-2\">1</a></sup>.</p>",
+                "<br/>",
+                "<p>After the notes</p>",
+                "<section class=\"footnotes\">",
+                "<ol>",
+                "<li id=\"fn-1\">The source, page 12. Second paragraph. \
+                 <a href=\"#fnref-1\" class=\"footnote-backref\">↩</a></li>",
+                "<li id=\"fn-2\">First <i>defined</i>. \
+                 <a href=\"#fnref-2\" class=\"footnote-backref\">↩</a></li>",
+                "</ol>",
+                "</section>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undefined_and_literal_references() {
+        let html =
+            html("Missing[^x], code `a[^1]` and\n\n```\n[^1]: in code\n```\n\n[^1]: Unused.");
+        assert_eq!(html[0], "<p>Missing[^x], code <code>a[^1]</code> and</p>");
+        assert!(html.contains(&String::from("[^1]: in code")));
+        // A footnote never referenced isn't listed
+        assert!(!html.iter().any(|line| line.contains("footnotes")));
+        assert!(!html.iter().any(|line| line.contains("Unused")));
+    }
+}
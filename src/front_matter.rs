@@ -0,0 +1,442 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use regex::Regex;
+use std::fmt::Write;
+use std::path::Path;
+
+/// The default format of `{{date}}`, an ISO date.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The `key: value` lines of a front matter block, in the order they are written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontMatter {
+    pub fields: Vec<(String, String)>,
+}
+
+impl FrontMatter {
+    /// Returns the value of `key`, if the front matter has it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The keys describing the document, see `Metadata`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let lines: Vec<String> = ["---", "title: Notes", "lang: fr", "---"].map(String::from).to_vec();
+    /// let (front_matter, _) = md2html::front_matter::split_front_matter(&lines).unwrap();
+    /// let metadata = front_matter.metadata();
+    /// assert_eq!(metadata.title.as_deref(), Some("Notes"));
+    /// assert_eq!(metadata.lang.as_deref(), Some("fr"));
+    /// assert_eq!(metadata.author, None);
+    /// ```
+    pub fn metadata(&self) -> Metadata {
+        let field = |key: &str| {
+            self.get(key)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+        Metadata {
+            title: field("title"),
+            author: field("author"),
+            date: field("date"),
+            lang: field("lang"),
+        }
+    }
+}
+
+/// The front matter keys describing a document, empty values left out.
+///
+/// # Fields
+///
+/// - `title`: The page `<title>` of a document without `<h1>`, and its `og:title`.
+/// - `author`: The `<meta name="author">` of the page.
+/// - `date`: The date as written, see `document_date` for the parsed one.
+/// - `lang`: The language of the page, its `<html lang>`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    pub lang: Option<String>,
+}
+
+/// A front matter block that couldn't be read, see `parse_front_matter`.
+///
+/// # Fields
+///
+/// - `line`: The 1-based line that isn't a `key: value` pair.
+/// - `line_count`: How many lines the block spans, delimiters included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MalformedFrontMatter {
+    pub line: usize,
+    pub line_count: usize,
+}
+
+/// Splits the front matter block off the start of a document, see
+/// `parse_front_matter`. A malformed block is left to the parser.
+///
+/// Returns the front matter and how many lines it spans, delimiters included.
+///
+/// # Example
+///
+/// ```rust
+/// let lines: Vec<String> = ["---", "date: 2024-06-01", "---", "# Title"]
+///     .iter()
+///     .map(|line| line.to_string())
+///     .collect();
+/// let (front_matter, line_count) = md2html::front_matter::split_front_matter(&lines).unwrap();
+/// assert_eq!(front_matter.get("date"), Some("2024-06-01"));
+/// assert_eq!(line_count, 3);
+/// ```
+pub fn split_front_matter(lines: &[String]) -> Option<(FrontMatter, usize)> {
+    parse_front_matter(lines)?.ok()
+}
+
+/// Parses the front matter block at the start of a document, as written by Jekyll
+/// or Obsidian.
+///
+/// A front matter block starts with a `---` line as the very first line and ends
+/// with the next `---` line. The lines in between are `key: value` pairs, blank
+/// lines and `#` comments; a key without a value can be followed by indented
+/// lines or `- item` lines, joined with `, ` into its value (`tags:` then `- rust`).
+/// Quotes around a value are removed, other YAML is kept as written.
+///
+/// Returns `None` when the document has no front matter: the first line isn't
+/// `---`, no `---` line closes it, or it has no `key: value` pair at all (e.g. a
+/// horizontal rule above a setext heading). A block with pairs and some other line
+/// is malformed, and returned as an error spanning the whole block.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::front_matter::{parse_front_matter, MalformedFrontMatter};
+///
+/// let lines: Vec<String> = ["---", "title: Notes", "tags:", "  - rust", "  - web", "---"]
+///     .map(String::from)
+///     .to_vec();
+/// let (front_matter, _) = parse_front_matter(&lines).unwrap().unwrap();
+/// assert_eq!(front_matter.get("tags"), Some("rust, web"));
+///
+/// let lines: Vec<String> = ["---", "title: Notes", "not a pair", "---"].map(String::from).to_vec();
+/// assert_eq!(parse_front_matter(&lines), Some(Err(MalformedFrontMatter { line: 3, line_count: 4 })));
+/// ```
+pub fn parse_front_matter(
+    lines: &[String],
+) -> Option<Result<(FrontMatter, usize), MalformedFrontMatter>> {
+    if lines.first().map(|line| line.trim_end()) != Some("---") {
+        return None;
+    }
+    let field_re = Regex::new(r"^([A-Za-z0-9_-]+):\s*(.*)$").unwrap();
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut malformed_line = None;
+    // Whether the last key has no value yet, so list items can follow it
+    let mut open_key = false;
+
+    for (index, line) in lines.iter().enumerate().skip(1) {
+        let line = line.trim_end();
+        if line == "---" {
+            if fields.is_empty() {
+                return None;
+            }
+            return Some(match malformed_line {
+                Some(line) => Err(MalformedFrontMatter {
+                    line,
+                    line_count: index + 1,
+                }),
+                None => Ok((FrontMatter { fields }, index + 1)),
+            });
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(groups) = field_re.captures(line) {
+            let value = groups[2].trim();
+            open_key = value.is_empty();
+            fields.push((groups[1].to_string(), unquote(value).to_string()));
+            continue;
+        }
+        let item = line.trim_start();
+        let item = item.strip_prefix("- ").unwrap_or(item);
+        match fields.last_mut() {
+            Some((_, value))
+                if open_key && (line.starts_with([' ', '\t']) || line.starts_with("- ")) =>
+            {
+                if !value.is_empty() {
+                    value.push_str(", ");
+                }
+                value.push_str(unquote(item));
+            }
+            _ => {
+                malformed_line.get_or_insert(index + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Whether `lines` start with a `---` line that no other `---` line closes: a
+/// front matter block left open, which is rendered as a horizontal rule instead.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::front_matter::is_unclosed;
+///
+/// let lines: Vec<String> = ["---", "title: Notes", "# Notes"].map(String::from).to_vec();
+/// assert!(is_unclosed(&lines));
+/// let lines: Vec<String> = ["---", "title: Notes", "---"].map(String::from).to_vec();
+/// assert!(!is_unclosed(&lines));
+/// ```
+pub fn is_unclosed(lines: &[String]) -> bool {
+    let mut delimiters = lines.iter().filter(|line| line.trim_end() == "---");
+    lines.first().is_some_and(|line| line.trim_end() == "---") && delimiters.nth(1).is_none()
+}
+
+/// `value` without the quotes around it, if any.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .or_else(|| {
+            value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+        })
+        .unwrap_or(value)
+}
+
+/// The date of a document, as expanded by `{{date}}`.
+///
+/// # Variants
+///
+/// - `Parsed`: A date read from the front matter or the file modification time.
+/// - `Raw`: A front matter date that couldn't be parsed, kept as written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentDate {
+    Parsed(NaiveDateTime),
+    Raw(String),
+}
+
+impl DocumentDate {
+    /// Formats the date with a `strftime`-like `format` (e.g. `"%B %e, %Y"`), see
+    /// `chrono::format::strftime`. An invalid format falls back to
+    /// `DEFAULT_DATE_FORMAT`, raw dates are returned as they are.
+    pub fn format(&self, format: &str) -> String {
+        match self {
+            DocumentDate::Parsed(date) => {
+                let mut formatted = String::new();
+                match write!(formatted, "{}", date.format(format)) {
+                    Ok(()) => formatted,
+                    Err(_) => date.format(DEFAULT_DATE_FORMAT).to_string(),
+                }
+            }
+            DocumentDate::Raw(raw) => raw.clone(),
+        }
+    }
+}
+
+/// Parses an ISO date (`2024-06-01`) or date and time (`2024-06-01T08:30:00`,
+/// `2024-06-01 08:30`, `2024-06-01T08:30:00+02:00`).
+///
+/// Datetimes with an offset keep their local time, the offset is dropped.
+pub fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.naive_local());
+    }
+    [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+/// Finds the date of a document: the `date` key of its front matter, or else the
+/// modification time of its source file, in UTC so the output doesn't depend on
+/// the timezone of the machine.
+///
+/// Returns `None` when neither is available, e.g. for markdown read from stdin.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::front_matter::{document_date, split_front_matter, DocumentDate};
+///
+/// let lines = vec![String::from("---"), String::from("date: June 1st"), String::from("---")];
+/// let (front_matter, _) = split_front_matter(&lines).unwrap();
+/// assert_eq!(
+///     document_date(Some(&front_matter), None),
+///     Some(DocumentDate::Raw(String::from("June 1st")))
+/// );
+/// ```
+pub fn document_date(
+    front_matter: Option<&FrontMatter>,
+    source_path: Option<&Path>,
+) -> Option<DocumentDate> {
+    if let Some(value) = front_matter.and_then(|front_matter| front_matter.get("date")) {
+        return Some(match parse_date(value) {
+            Some(date) => DocumentDate::Parsed(date),
+            None => DocumentDate::Raw(value.to_string()),
+        });
+    }
+    let modified = source_path?.metadata().ok()?.modified().ok()?;
+    Some(DocumentDate::Parsed(
+        DateTime::<Utc>::from(modified).naive_utc(),
+    ))
+}
+
+/// Replaces every `{{date}}` of `template` with `date`, or with nothing when the
+/// document has no date.
+pub fn expand_date(template: &str, date: Option<&str>) -> String {
+    template.replace("{{date}}", date.unwrap_or(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn front_matter(date: &str) -> FrontMatter {
+        FrontMatter {
+            fields: vec![(String::from("date"), date.to_string())],
+        }
+    }
+
+    #[test]
+    fn test_iso_date() {
+        let date = document_date(Some(&front_matter("2024-06-01")), None).unwrap();
+        assert_eq!(date.format("%B %e, %Y"), "June  1, 2024");
+        assert_eq!(date.format(DEFAULT_DATE_FORMAT), "2024-06-01");
+    }
+
+    #[test]
+    fn test_datetime() {
+        for value in [
+            "2024-06-01T08:30:00",
+            "2024-06-01 08:30",
+            "2024-06-01T08:30:00+02:00",
+        ] {
+            let date = document_date(Some(&front_matter(value)), None).unwrap();
+            assert_eq!(
+                date.format("%Y-%m-%d %H:%M"),
+                "2024-06-01 08:30",
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_date_uses_modification_time() {
+        let path = std::env::temp_dir().join("md2html-front-matter-mtime.md");
+        std::fs::write(&path, "# No date").unwrap();
+        let modified = path.metadata().unwrap().modified().unwrap();
+        let expected = DateTime::<Utc>::from(modified).naive_utc();
+
+        let without_date = FrontMatter {
+            fields: vec![(String::from("title"), String::from("Post"))],
+        };
+        assert_eq!(
+            document_date(Some(&without_date), Some(&path)),
+            Some(DocumentDate::Parsed(expected))
+        );
+        assert_eq!(
+            document_date(None, Some(&path)),
+            Some(DocumentDate::Parsed(expected))
+        );
+        assert_eq!(document_date(None, None), None);
+    }
+
+    #[test]
+    fn test_invalid_date_is_kept_raw() {
+        let date = document_date(Some(&front_matter("2024-13-45")), None).unwrap();
+        assert_eq!(date, DocumentDate::Raw(String::from("2024-13-45")));
+        assert_eq!(date.format("%B %e, %Y"), "2024-13-45");
+    }
+
+    #[test]
+    fn test_invalid_format_falls_back() {
+        let date = DocumentDate::Parsed(parse_date("2024-06-01").unwrap());
+        assert_eq!(date.format("%Q"), "2024-06-01");
+    }
+
+    #[test]
+    fn test_yaml_front_matter() {
+        let lines: Vec<String> = [
+            "---",
+            "# Exported from Obsidian",
+            "title: \"Weekly: notes\"",
+            "author: 'Ada'",
+            "aliases:",
+            "  - weekly",
+            "  - \"notes\"",
+            "date: 2024-06-01",
+            "lang: en-GB",
+            "---",
+            "Text",
+        ]
+        .map(String::from)
+        .to_vec();
+        let (front_matter, line_count) = parse_front_matter(&lines).unwrap().unwrap();
+        assert_eq!(line_count, 10);
+        assert_eq!(front_matter.get("aliases"), Some("weekly, notes"));
+        assert_eq!(
+            front_matter.metadata(),
+            Metadata {
+                title: Some(String::from("Weekly: notes")),
+                author: Some(String::from("Ada")),
+                date: Some(String::from("2024-06-01")),
+                lang: Some(String::from("en-GB")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_front_matter() {
+        let parse = |lines: &[&str]| {
+            let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+            parse_front_matter(&lines)
+        };
+        // A list item needs a key without value above it
+        assert_eq!(
+            parse(&["---", "title: Post", "- item", "{broken", "---", "Text"]),
+            Some(Err(MalformedFrontMatter {
+                line: 3,
+                line_count: 5
+            }))
+        );
+        assert_eq!(
+            parse(&["---", "title: Post", "  indented", "---"]),
+            Some(Err(MalformedFrontMatter {
+                line: 3,
+                line_count: 4
+            }))
+        );
+        // Without any pair it's no front matter but a horizontal rule
+        assert_eq!(parse(&["---", "Heading", "---"]), None);
+    }
+
+    #[test]
+    fn test_split_front_matter_requires_fields() {
+        let lines: Vec<String> = ["---", "Some text", "---"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(split_front_matter(&lines), None);
+        let lines: Vec<String> = ["---", "title: 'Post'", ""]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(split_front_matter(&lines), None);
+    }
+}
@@ -0,0 +1,153 @@
+/// The theme of highlighted code blocks when none is chosen.
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// A code block highlighted with inline styles, so the page needs no JavaScript.
+///
+/// # Fields
+///
+/// - `start_html`: The opening `<pre><code>` tags, the `<pre>` having the
+///   background of the theme.
+/// - `lines`: The HTML of every line of code, made of styled `<span>`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HighlightedBlock {
+    pub start_html: String,
+    pub lines: Vec<String>,
+}
+
+/// Whether code blocks can be highlighted: md2html has to be built with the
+/// `highlight` cargo feature.
+pub fn is_available() -> bool {
+    cfg!(feature = "highlight")
+}
+
+/// The names of the themes `highlight_code` accepts, empty when highlighting isn't
+/// available.
+pub fn theme_names() -> Vec<String> {
+    #[cfg(feature = "highlight")]
+    {
+        syntect_sets::themes().themes.keys().cloned().collect()
+    }
+    #[cfg(not(feature = "highlight"))]
+    {
+        Vec::new()
+    }
+}
+
+/// Highlights the `lines` of a code block written in `language` (a name or file
+/// extension such as `rust` or `rs`) with the colors of `theme`.
+///
+/// Returns `None` when the language or the theme is unknown, or when highlighting
+/// isn't available (see `is_available`), the block is then output as plain
+/// escaped code.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::highlight::{highlight_code, DEFAULT_THEME};
+///
+/// let lines = vec![String::from("let x = 1;")];
+/// assert_eq!(highlight_code("no-such-language", &lines, DEFAULT_THEME), None);
+/// if md2html::highlight::is_available() {
+///     let block = highlight_code("rust", &lines, DEFAULT_THEME).unwrap();
+///     assert!(block.start_html.starts_with("<pre style=\"background-color:"));
+///     assert!(block.lines[0].contains("<span style=\""));
+/// }
+/// ```
+pub fn highlight_code(language: &str, lines: &[String], theme: &str) -> Option<HighlightedBlock> {
+    #[cfg(feature = "highlight")]
+    {
+        syntect_sets::highlight(language, lines, theme)
+    }
+    #[cfg(not(feature = "highlight"))]
+    {
+        let _ = (language, lines, theme);
+        None
+    }
+}
+
+#[cfg(feature = "highlight")]
+mod syntect_sets {
+    use super::HighlightedBlock;
+    use crate::escape::escape_attr;
+    use std::sync::OnceLock;
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::ThemeSet;
+    use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+    use syntect::parsing::SyntaxSet;
+
+    // Loading the definitions takes a while, they are shared by every block
+    fn syntaxes() -> &'static SyntaxSet {
+        static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+        SYNTAXES.get_or_init(SyntaxSet::load_defaults_nonewlines)
+    }
+
+    pub(super) fn themes() -> &'static ThemeSet {
+        static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+        THEMES.get_or_init(ThemeSet::load_defaults)
+    }
+
+    pub(super) fn highlight(
+        language: &str,
+        lines: &[String],
+        theme: &str,
+    ) -> Option<HighlightedBlock> {
+        let syntaxes = syntaxes();
+        let syntax = syntaxes.find_syntax_by_token(language)?;
+        let theme = themes().themes.get(theme)?;
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        let lines = lines
+            .iter()
+            .map(|line| {
+                let regions = highlighter.highlight_line(line, syntaxes).ok()?;
+                styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok()
+            })
+            .collect::<Option<Vec<String>>>()?;
+
+        let background = theme.settings.background.map_or(String::new(), |color| {
+            format!(
+                " style=\"background-color:#{:02x}{:02x}{:02x}\"",
+                color.r, color.g, color.b
+            )
+        });
+        Some(HighlightedBlock {
+            start_html: format!(
+                "<pre{}><code class=\"language-{}\">",
+                background,
+                escape_attr(language)
+            ),
+            lines,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "highlight"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_rust() {
+        let lines = vec![
+            String::from("fn main() {"),
+            String::from("    let s = \"<b>\";"),
+            String::from("}"),
+        ];
+        let block = highlight_code("rust", &lines, DEFAULT_THEME).unwrap();
+        assert_eq!(
+            block.start_html,
+            "<pre style=\"background-color:#ffffff\"><code class=\"language-rust\">"
+        );
+        assert_eq!(block.lines.len(), 3);
+        assert!(block.lines[0].starts_with("<span style=\""));
+        // The code is still escaped
+        assert!(block.lines[1].contains("&lt;b&gt;"));
+        assert!(!block.lines[1].contains("<b>"));
+    }
+
+    #[test]
+    fn test_unknown_language_or_theme() {
+        let lines = vec![String::from("x")];
+        assert_eq!(highlight_code("klingon", &lines, DEFAULT_THEME), None);
+        assert_eq!(highlight_code("rust", &lines, "no-such-theme"), None);
+        assert!(theme_names().contains(&String::from(DEFAULT_THEME)));
+    }
+}
@@ -0,0 +1,350 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// An inline element of a block (header, paragraph, list item...).
+///
+/// Text is kept as HTML-ready text: special characters are already escaped and
+/// entity references (`&copy;`) are kept as written, so rendering the elements gives
+/// back the exact HTML of the inline converter.
+///
+/// # Variants
+///
+/// - `Text`: Plain text.
+/// - `Strong`: Bold content (`**text**`).
+/// - `Emph`: Italic content (`*text*`).
+/// - `Strikethrough`: Struck content (`~~text~~`).
+/// - `Code`: The content of an inline code span (`` `code` ``).
+/// - `Link`: A link (`[text](url)`), with its content, URL and optional title.
+/// - `Image`: An image (`![alt](url)`), with its alt text, URL and optional title.
+/// - `Break`: A hard line break.
+/// - `Checkbox`: The checkbox of a task list item (`- [ ]` or `- [x]`).
+/// - `Html`: Markup kept as it is, such as sized images, media players or raw HTML
+///   written in the markdown.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Strong(Vec<Inline>),
+    Emph(Vec<Inline>),
+    Strikethrough(Vec<Inline>),
+    Code(String),
+    Link {
+        children: Vec<Inline>,
+        url: String,
+        title: Option<String>,
+    },
+    Image {
+        alt: String,
+        url: String,
+        title: Option<String>,
+    },
+    Break,
+    Checkbox {
+        checked: bool,
+    },
+    Html(String),
+}
+
+impl fmt::Display for Inline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inline::Text(text) => write!(f, "{}", text),
+            Inline::Strong(children) => write!(f, "<strong>{}</strong>", to_html(children)),
+            Inline::Emph(children) => write!(f, "<i>{}</i>", to_html(children)),
+            Inline::Strikethrough(children) => write!(f, "<del>{}</del>", to_html(children)),
+            Inline::Code(code) => write!(f, "<code>{}</code>", code),
+            Inline::Link {
+                children,
+                url,
+                title,
+            } => match title {
+                Some(title) => write!(
+                    f,
+                    "<a href=\"{}\" title=\"{}\">{}</a>",
+                    url,
+                    title,
+                    to_html(children)
+                ),
+                None => write!(f, "<a href=\"{}\">{}</a>", url, to_html(children)),
+            },
+            Inline::Image { alt, url, title } => match title {
+                Some(title) => write!(
+                    f,
+                    "<img src=\"{}\" alt=\"{}\" title=\"{}\">",
+                    url, alt, title
+                ),
+                None => write!(f, "<img src=\"{}\" alt=\"{}\">", url, alt),
+            },
+            Inline::Break => write!(f, "<br/>"),
+            Inline::Checkbox { checked: true } => {
+                write!(f, "<input type=\"checkbox\" checked disabled>")
+            }
+            Inline::Checkbox { checked: false } => write!(f, "<input type=\"checkbox\" disabled>"),
+            Inline::Html(html) => write!(f, "{}", html),
+        }
+    }
+}
+
+/// Renders inline elements as HTML.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::inline::{to_html, Inline};
+///
+/// let inlines = vec![
+///     Inline::Text(String::from("Some ")),
+///     Inline::Strong(vec![Inline::Text(String::from("bold"))]),
+/// ];
+/// assert_eq!(to_html(&inlines), "Some <strong>bold</strong>");
+/// ```
+pub fn to_html(inlines: &[Inline]) -> String {
+    inlines.iter().map(Inline::to_string).collect()
+}
+
+/// Renders the visible text of inline elements, without any markup.
+///
+/// # Example
+///
+/// ```rust
+/// let inlines = md2html::inline::from_html("See <a href=\"/a\"><strong>the</strong> docs</a><br/>");
+/// assert_eq!(md2html::inline::to_plain_text(&inlines), "See the docs\n");
+/// ```
+pub fn to_plain_text(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(content) | Inline::Code(content) => text += content,
+            Inline::Strong(children) | Inline::Emph(children) | Inline::Strikethrough(children) => {
+                text += &to_plain_text(children)
+            }
+            Inline::Link { children, .. } => text += &to_plain_text(children),
+            Inline::Image { alt, .. } => text += alt,
+            Inline::Break => text.push('\n'),
+            Inline::Checkbox { .. } | Inline::Html(_) => (),
+        }
+    }
+    text
+}
+
+/// How deep `from_html` nests elements, deeper tags are kept as `Html` so rendering
+/// can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+/// An element being parsed by `from_html`, waiting for its closing tag.
+struct OpenElement {
+    open_tag: String,
+    children: Vec<Inline>,
+}
+
+/// Builds the inline elements of a line of HTML produced by the inline converter.
+///
+/// Only the exact tags the converter emits are turned into elements, any other
+/// markup (and tags that are never closed) is kept as `Html`, so `to_html` always
+/// gives back the input unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::inline::{from_html, Inline};
+///
+/// let html = "A <a href=\"https://example.com\">link</a>";
+/// let inlines = from_html(html);
+/// assert_eq!(
+///     inlines[1],
+///     Inline::Link {
+///         children: vec![Inline::Text(String::from("link"))],
+///         url: String::from("https://example.com"),
+///         title: None,
+///     }
+/// );
+/// assert_eq!(md2html::inline::to_html(&inlines), html);
+/// ```
+pub fn from_html(html: &str) -> Vec<Inline> {
+    // Compiled once, this runs for every block and image alt text
+    static LINK_RE: OnceLock<Regex> = OnceLock::new();
+    static IMAGE_RE: OnceLock<Regex> = OnceLock::new();
+    let link_re = LINK_RE
+        .get_or_init(|| Regex::new(r#"^<a href="([^"<>]*)"(?: title="([^"<>]*)")?>$"#).unwrap());
+    let image_re = IMAGE_RE.get_or_init(|| {
+        Regex::new(r#"^<img src="([^"<>]*)" alt="([^"<>]*)"(?: title="([^"<>]*)")?>$"#).unwrap()
+    });
+
+    // The innermost element is last, the root holds the result
+    let mut stack = vec![OpenElement {
+        open_tag: String::new(),
+        children: Vec::new(),
+    }];
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(tag) = next_tag(rest) else {
+            // Text up to the next possible tag
+            let first_len = rest.chars().next().unwrap().len_utf8();
+            let text_end = rest[first_len..]
+                .find('<')
+                .map_or(rest.len(), |i| i + first_len);
+            push_text(&mut stack.last_mut().unwrap().children, &rest[..text_end]);
+            rest = &rest[text_end..];
+            continue;
+        };
+        rest = &rest[tag.len()..];
+
+        // Code spans hold raw text up to their closing tag
+        if tag == "<code>" {
+            if let Some(code_end) = rest.find("</code>") {
+                let code = Inline::Code(rest[..code_end].to_string());
+                stack.last_mut().unwrap().children.push(code);
+                rest = &rest[code_end + "</code>".len()..];
+                continue;
+            }
+        }
+
+        let closes_current = match stack.last().unwrap().open_tag.as_str() {
+            "<strong>" => tag == "</strong>",
+            "<i>" => tag == "</i>",
+            "<del>" => tag == "</del>",
+            open_tag if open_tag.starts_with("<a ") => tag == "</a>",
+            _ => false,
+        };
+        if closes_current {
+            let element = stack.pop().unwrap();
+            let inline = match element.open_tag.as_str() {
+                "<strong>" => Inline::Strong(element.children),
+                "<i>" => Inline::Emph(element.children),
+                "<del>" => Inline::Strikethrough(element.children),
+                open_tag => {
+                    let groups = link_re.captures(open_tag).unwrap();
+                    Inline::Link {
+                        children: element.children,
+                        url: groups[1].to_string(),
+                        title: groups.get(2).map(|title| title.as_str().to_string()),
+                    }
+                }
+            };
+            stack.last_mut().unwrap().children.push(inline);
+        } else if (matches!(tag, "<strong>" | "<i>" | "<del>") || link_re.is_match(tag))
+            && stack.len() <= MAX_DEPTH
+        {
+            stack.push(OpenElement {
+                open_tag: tag.to_string(),
+                children: Vec::new(),
+            });
+        } else if let Some(groups) = image_re.captures(tag) {
+            stack.last_mut().unwrap().children.push(Inline::Image {
+                alt: groups[2].to_string(),
+                url: groups[1].to_string(),
+                title: groups.get(3).map(|title| title.as_str().to_string()),
+            });
+        } else if tag == "<br/>" {
+            stack.last_mut().unwrap().children.push(Inline::Break);
+        } else {
+            stack
+                .last_mut()
+                .unwrap()
+                .children
+                .push(Inline::Html(tag.to_string()));
+        }
+    }
+
+    // Elements never closed are kept as raw tags followed by their content, every
+    // element still open was opened after all the content of the one below it
+    let mut stack = stack.into_iter();
+    let mut inlines = stack.next().unwrap().children;
+    for element in stack {
+        inlines.push(Inline::Html(element.open_tag));
+        for child in element.children {
+            match child {
+                Inline::Text(text) => push_text(&mut inlines, &text),
+                child => inlines.push(child),
+            }
+        }
+    }
+    inlines
+}
+
+/// Returns the tag `html` starts with, e.g. `<strong>`, if it starts with one.
+fn next_tag(html: &str) -> Option<&str> {
+    let after_bracket = html.strip_prefix('<')?;
+    if !after_bracket.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!') {
+        return None;
+    }
+    let tag_end = html.find('>')?;
+    if html[1..tag_end].contains('<') {
+        return None;
+    }
+    Some(&html[..=tag_end])
+}
+
+/// Appends text to `inlines`, merging it with the text right before it.
+fn push_text(inlines: &mut Vec<Inline>, text: &str) {
+    match inlines.last_mut() {
+        Some(Inline::Text(previous)) => previous.push_str(text),
+        _ => inlines.push(Inline::Text(text.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_inline_markdown;
+
+    #[test]
+    fn test_from_html_structure() {
+        let html = convert_inline_markdown(&mut String::from(
+            "Read **the [guide](/guide)** and *run* `cargo *build*` ![logo](logo.png) ~~old~~",
+        ));
+        assert_eq!(
+            from_html(&html),
+            vec![
+                Inline::Text(String::from("Read ")),
+                Inline::Strong(vec![
+                    Inline::Text(String::from("the ")),
+                    Inline::Link {
+                        children: vec![Inline::Text(String::from("guide"))],
+                        url: String::from("/guide"),
+                        title: None,
+                    },
+                ]),
+                Inline::Text(String::from(" and ")),
+                Inline::Emph(vec![Inline::Text(String::from("run"))]),
+                Inline::Text(String::from(" ")),
+                Inline::Code(String::from("cargo *build*")),
+                Inline::Text(String::from(" ")),
+                Inline::Image {
+                    alt: String::from("logo"),
+                    url: String::from("logo.png"),
+                    title: None,
+                },
+                Inline::Text(String::from(" ")),
+                Inline::Strikethrough(vec![Inline::Text(String::from("old"))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_html_round_trips_odd_markup() {
+        for html in [
+            "a < b and c > d",
+            "<strong>never closed <i>nor this",
+            "</i>closing first<i>",
+            "<strong><i>crossed</strong></i>",
+            "<img src=\"a.png\" alt=\"a\" width=\"10\"> <video controls src=\"v.mp4\">v</video>",
+            "<code>unterminated",
+            "<A HREF=\"x\">upper</A> <!-- comment --> &copy;",
+            "<",
+            "é < ü",
+            "",
+        ] {
+            assert_eq!(to_html(&from_html(html)), html);
+        }
+    }
+
+    #[test]
+    fn test_from_html_deep_nesting() {
+        let html = "<i>".repeat(100_000);
+        assert_eq!(to_html(&from_html(&html)), html);
+        let html = format!("{}x{}", "<i>".repeat(100_000), "</i>".repeat(100_000));
+        assert_eq!(to_html(&from_html(&html)), html);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::find_code_span;
+use std::ops::Range;
+
+/// A problem with the inline markup of a line, found by `check_line`.
+///
+/// # Fields
+///
+/// - `span`: The byte range of the problem in the line.
+/// - `message`: What the problem is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineProblem {
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// Finds inline markup of `line` that is kept as text because it is incomplete:
+/// a `**` or `__` that is never closed, or a link whose destination has no `)`.
+///
+/// Code spans, backslash escapes and filesystem paths (`src/**/*.rs`) are skipped,
+/// their characters are never markup.
+///
+/// # Example
+///
+/// ```rust
+/// let problems = md2html::inline_checks::check_line("Some **bold text");
+/// assert_eq!(problems[0].span, 5..7);
+/// assert_eq!(problems[0].message, "`**` is never closed, kept as text");
+/// assert!(md2html::inline_checks::check_line("Some **bold** text").is_empty());
+/// ```
+pub fn check_line(line: &str) -> Vec<InlineProblem> {
+    let masked = mask_literals(line);
+    let mut problems = unclosed_links(&masked);
+    problems.extend(unclosed_delimiters(&mask_paths(masked)));
+    problems.sort_by_key(|problem| problem.span.start);
+    problems
+}
+
+/// The bytes of `line` with code spans and escaped characters blanked out, so
+/// their punctuation isn't taken for markup. Offsets are kept.
+fn mask_literals(line: &str) -> Vec<u8> {
+    let mut masked = line.as_bytes().to_vec();
+    let mut offset = 0;
+    while let Some((span_start, _, _, span_end)) = find_code_span(&line[offset..]) {
+        masked[offset + span_start..offset + span_end].fill(b' ');
+        offset += span_end;
+    }
+    let mut i = 0;
+    while i + 1 < masked.len() {
+        if masked[i] == b'\\' && masked[i + 1].is_ascii_punctuation() {
+            masked[i..i + 2].fill(b' ');
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    masked
+}
+
+/// Blanks out the words of `masked` that are paths or URLs, whose `*` and `_`
+/// are kept as typed.
+fn mask_paths(mut masked: Vec<u8>) -> Vec<u8> {
+    let mut start = 0;
+    while start < masked.len() {
+        let end = masked[start..]
+            .iter()
+            .position(u8::is_ascii_whitespace)
+            .map_or(masked.len(), |length| start + length);
+        let word = &masked[start..end];
+        if word.contains(&b'/') || word.contains(&b'\\') {
+            masked[start..end].fill(b' ');
+        }
+        start = end + 1;
+    }
+    masked
+}
+
+/// Finds the `**`, `__` (and longer) runs that open strong emphasis without any
+/// run closing it on the line.
+fn unclosed_delimiters(masked: &[u8]) -> Vec<InlineProblem> {
+    // Other bytes of multibyte characters count as word characters
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || !b.is_ascii();
+    let mut open: Vec<(u8, Range<usize>)> = Vec::new();
+    let mut i = 0;
+    while i < masked.len() {
+        let delimiter = masked[i];
+        if delimiter != b'*' && delimiter != b'_' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < masked.len() && masked[i] == delimiter {
+            i += 1;
+        }
+        if i - start < 2 {
+            continue;
+        }
+        let before = start.checked_sub(1).map(|j| masked[j]);
+        let after = masked.get(i).copied();
+        // `snake__case` words have no emphasis
+        if delimiter == b'_' && before.is_some_and(is_word) && after.is_some_and(is_word) {
+            continue;
+        }
+        let can_open = after.is_some_and(|b| !b.is_ascii_whitespace());
+        let can_close = before.is_some_and(|b| !b.is_ascii_whitespace());
+        let opener = open
+            .iter()
+            .rposition(|(open_delimiter, _)| *open_delimiter == delimiter);
+        match opener {
+            Some(index) if can_close => {
+                open.remove(index);
+            }
+            _ if can_open => open.push((delimiter, start..i)),
+            _ => (),
+        }
+    }
+    open.into_iter()
+        .map(|(delimiter, span)| InlineProblem {
+            message: format!(
+                "`{}` is never closed, kept as text",
+                String::from(delimiter as char).repeat(span.len())
+            ),
+            span,
+        })
+        .collect()
+}
+
+/// Finds the `[text](destination` links whose destination is never closed by a
+/// `)`, the span goes from the `[` (or `![`) to the end of the line.
+fn unclosed_links(masked: &[u8]) -> Vec<InlineProblem> {
+    let mut problems = Vec::new();
+    let end = masked.len()
+        - masked
+            .iter()
+            .rev()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+    let mut i = 0;
+    while i + 1 < masked.len() {
+        if masked[i] != b']' || masked[i + 1] != b'(' {
+            i += 1;
+            continue;
+        }
+        let mut depth = 0;
+        let close = masked[i + 2..].iter().position(|b| match b {
+            b'(' => {
+                depth += 1;
+                false
+            }
+            b')' if depth == 0 => true,
+            b')' => {
+                depth -= 1;
+                false
+            }
+            _ => false,
+        });
+        if let Some(close) = close {
+            i += 2 + close + 1;
+            continue;
+        }
+        let mut start = link_text_start(masked, i);
+        if start > 0 && masked[start - 1] == b'!' {
+            start -= 1;
+        }
+        problems.push(InlineProblem {
+            span: start..end,
+            message: String::from("link destination is never closed, missing `)`"),
+        });
+        // The rest of the line belongs to the broken destination
+        break;
+    }
+    problems
+}
+
+/// The offset of the `[` matching the `]` at `close`, or `close` itself when the
+/// bracket has no opening one.
+fn link_text_start(masked: &[u8], close: usize) -> usize {
+    let mut depth = 0;
+    for j in (0..close).rev() {
+        match masked[j] {
+            b']' => depth += 1,
+            b'[' if depth == 0 => return j,
+            b'[' => depth -= 1,
+            _ => (),
+        }
+    }
+    close
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(line: &str) -> Vec<(&str, String)> {
+        check_line(line)
+            .into_iter()
+            .map(|problem| (&line[problem.span], problem.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_unclosed_strong_mid_line() {
+        assert_eq!(
+            spans("Install it **now and restart, or `**` not"),
+            [("**", String::from("`**` is never closed, kept as text"))]
+        );
+        assert_eq!(check_line("Install it **now and restart")[0].span, 11..13);
+        assert_eq!(
+            spans("A __strong__ and __weak one"),
+            [("__", String::from("`__` is never closed, kept as text"))]
+        );
+    }
+
+    #[test]
+    fn test_closed_or_literal_delimiters() {
+        for line in [
+            "**bold** and __bold__ and ***both***",
+            "Nested **bold *and* italic** text",
+            "A lone ** in prose, 2 ** 3",
+            "Globs like src/**/*.rs and C:\\Users\\**",
+            "Escaped \\*\\* stars and `**code**` spans",
+            "snake__case__names stay text",
+        ] {
+            assert!(check_line(line).is_empty(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_unclosed_link_after_cjk_text() {
+        let line = "日本語のテキスト [リンク](https://example.com の説明";
+        let problems = check_line(line);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(
+            &line[problems[0].span.clone()],
+            "[リンク](https://example.com の説明"
+        );
+        assert_eq!(problems[0].span.start, "日本語のテキスト ".len());
+        assert_eq!(
+            problems[0].message,
+            "link destination is never closed, missing `)`"
+        );
+
+        assert_eq!(spans("See ![logo](logo.png").len(), 1);
+        assert_eq!(spans("See ![logo](logo.png")[0].0, "![logo](logo.png");
+        assert_eq!(spans("Not a link ](")[0].0, "](");
+        assert!(check_line("[a](b) and [c](d (e))").is_empty());
+        assert!(check_line("[a](b) then `[c](d`").is_empty());
+    }
+}
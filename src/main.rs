@@ -1,136 +1,1607 @@
-mod parser;
 mod simple_log;
 
 use colored::Colorize;
 use core::panic;
-use parser::tokenize_text;
+use md2html::anchors::{self, Anchor};
+use md2html::batch::{self, BatchReport, BatchWriter};
+use md2html::book::{self, Chapter};
+use md2html::config::{self, Config};
+use md2html::corpus::{self, Corpus};
+use md2html::cross_links::{self, BatchDocument, BrokenLink};
+use md2html::diagnostics::{self, Diagnostic, MessageFormat, Severity};
+use md2html::features;
+use md2html::front_matter::{self, DocumentDate};
+use md2html::highlight;
+use md2html::manifest::{self, PageMetadata};
+use md2html::renderer::LineEnding;
+use md2html::search_index::{self, SearchPage};
+use md2html::sections::{self, SlugStyle};
+use md2html::site_diff::{self, FileChange, FileDiff};
+use md2html::sniff::{self, Content};
+use md2html::soft_breaks::SoftBreak;
+use md2html::standalone::{self, PageLink, PageOptions, WrapDiv};
+use md2html::{
+    output, Converter, Document, HtmlRenderer, ParseWarning, ParserOptions, WarningKind,
+};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str::FromStr;
-use std::sync::OnceLock;
 use std::{env, fs};
 
-// Use OnceCell if you want:
-// A global value
-// Assigned at runtime
-// Immutable after assignment (like const)
-static INPUT_FILE_PATH: std::sync::OnceLock<String> = OnceLock::new();
-static OUTPUT_FILE_PATH: std::sync::OnceLock<String> = OnceLock::new();
 const SOFTWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEFAULT_MAX_INPUT_SIZE: u64 = 64 * 1024 * 1024;
+const DEFAULT_BENCH_LINES: usize = 1_000;
+const DEFAULT_ANCHORS_FILE: &str = "anchors.json";
 
-fn read_lines_file(path: &PathBuf) -> Result<impl Iterator<Item = String>, ()> {
-    let file: File = match File::open(&path) {
+/// The files named on the command line, `-` standing for stdin or stdout.
+///
+/// # Fields
+///
+/// - `input`: The markdown file or directory of `--input`.
+/// - `output`: The HTML file or directory of `--output`.
+/// - `stdin_filename`: The file of `--stdin-filename`, naming what is read from stdin.
+#[derive(Debug, Default)]
+struct Paths {
+    input: Option<String>,
+    output: Option<String>,
+    stdin_filename: Option<String>,
+}
+
+/// The flags of the command line other than the files, as read by `process_args`.
+/// A flag left out is `false` or `None`, its default is applied where it's read.
+///
+/// # Fields
+///
+/// - `book`: Build a book with the `book` command (see `convert_book`).
+/// - `diff_trees`: The old and new output folders of `md2html diff`.
+/// - `diff_against`: The output folder the pages of a batch are compared with,
+///   instead of being written.
+/// - `diff_report`: The JSON file of `--report`, describing a comparison.
+/// - `write_report`: The JSON file of `--write-report`, describing the outputs of a
+///   batch.
+/// - `search_index`, `search_index_limit`: Write search-index.json in directory
+///   mode, keeping up to `search_index_limit` characters of each page body.
+/// - `emit_anchors`: Write the heading anchors, to the path given to
+///   `--emit-anchors` or else to anchors.json (see `write_anchors`).
+/// - `page_nav`: Add previous/next links to the pages of a batch.
+/// - `max_input_size`, `max_write_failures`, `max_open_files`: The limits on the
+///   inputs and outputs of a run.
+/// - `strict`, `heading_checks`: What makes the conversion fail (see
+///   `check_warnings`).
+/// - `in_place`: Allow the output to overwrite the input.
+/// - `force_text`: Convert inputs that look binary (see `not_text_reason`).
+/// - `standalone`, `reproducible`, `no_meta`, `header`, `footer`, `date_format`,
+///   `wrap_div`, `line_ending`: How the pages are rendered (see `converter`).
+/// - `max_line_length` to `disabled`: The parser settings (see `parser_options`
+///   and `cli_config`).
+/// - `message_format`, `color`: How diagnostics are printed, `color` being decided
+///   by `main` from the environment.
+/// - `features`, `json`: Print the features md2html was built with, as JSON with
+///   `json`.
+/// - `bench_internal`, `bench_lines`: Run the benchmarks on corpora of
+///   `bench_lines` lines.
+#[derive(Debug, Default)]
+struct Flags {
+    book: bool,
+    diff_trees: Option<(String, String)>,
+    diff_against: Option<String>,
+    diff_report: Option<String>,
+    write_report: Option<String>,
+    search_index: bool,
+    search_index_limit: Option<usize>,
+    emit_anchors: Option<Option<String>>,
+    page_nav: bool,
+    max_input_size: Option<u64>,
+    max_write_failures: Option<usize>,
+    max_open_files: Option<usize>,
+    strict: bool,
+    heading_checks: bool,
+    in_place: bool,
+    force_text: bool,
+    standalone: bool,
+    reproducible: bool,
+    no_meta: bool,
+    header: Option<String>,
+    footer: Option<String>,
+    date_format: Option<String>,
+    wrap_div: Option<WrapDiv>,
+    line_ending: Option<LineEnding>,
+    max_line_length: Option<usize>,
+    max_inline_delimiters: Option<usize>,
+    max_nesting_depth: Option<usize>,
+    wrap_width: Option<usize>,
+    link_endnotes: bool,
+    media_embeds: bool,
+    embeds: bool,
+    sections: bool,
+    autolink_bare_urls: bool,
+    quote_attribution: bool,
+    allow_raw: bool,
+    allow_raw_html: bool,
+    lint: bool,
+    ascii_slugs: bool,
+    slug_style: Option<SlugStyle>,
+    soft_break_default: Option<SoftBreak>,
+    soft_break_in_quotes: Option<SoftBreak>,
+    toc: bool,
+    toc_depth: Option<u8>,
+    heading_anchors: bool,
+    anchor_symbol: Option<String>,
+    strip_regions: Option<Vec<String>>,
+    keep_regions: Option<Vec<String>>,
+    normalize_unicode: bool,
+    verify_code_blocks: bool,
+    highlight: bool,
+    highlight_theme: Option<String>,
+    disabled: Option<Config>,
+    message_format: Option<MessageFormat>,
+    color: bool,
+    features: bool,
+    json: bool,
+    bench_internal: bool,
+    bench_lines: Option<usize>,
+}
+
+fn parser_options(flags: &Flags) -> ParserOptions {
+    let defaults = ParserOptions::default();
+    let disabled = flags.disabled.clone().unwrap_or_default();
+    ParserOptions {
+        max_line_length: flags.max_line_length.unwrap_or(defaults.max_line_length),
+        max_inline_delimiters: flags
+            .max_inline_delimiters
+            .unwrap_or(defaults.max_inline_delimiters),
+        max_nesting_depth: flags
+            .max_nesting_depth
+            .unwrap_or(defaults.max_nesting_depth),
+        wrap_width: flags.wrap_width,
+        link_endnotes: flags.link_endnotes,
+        media_embeds: flags.media_embeds,
+        embeds: flags.embeds,
+        // The anchors map lists the section ids, so --emit-anchors needs them
+        sections: flags.sections || flags.emit_anchors.is_some(),
+        literal_paths: defaults.literal_paths,
+        autolink_bare_urls: flags.autolink_bare_urls,
+        quote_attribution: flags.quote_attribution,
+        allow_raw: flags.allow_raw,
+        allow_raw_html: flags.allow_raw_html,
+        ascii_slugs: flags.ascii_slugs,
+        slug_style: flags.slug_style.unwrap_or_default(),
+        toc: flags.toc,
+        toc_depth: flags.toc_depth.unwrap_or(defaults.toc_depth),
+        heading_anchors: flags.heading_anchors,
+        anchor_symbol: flags
+            .anchor_symbol
+            .clone()
+            .unwrap_or(defaults.anchor_symbol),
+        strip_regions: flags.strip_regions.clone().unwrap_or_default(),
+        keep_regions: flags.keep_regions.clone().unwrap_or_default(),
+        normalize_unicode: flags.normalize_unicode,
+        verify_code_blocks: flags.verify_code_blocks,
+        soft_break_default: flags
+            .soft_break_default
+            .unwrap_or(defaults.soft_break_default),
+        soft_break_in_quotes: flags.soft_break_in_quotes,
+        lint: flags.lint,
+        highlight: flags.highlight,
+        highlight_theme: flags
+            .highlight_theme
+            .clone()
+            .unwrap_or(defaults.highlight_theme),
+        headings: disabled.headings.unwrap_or(defaults.headings),
+        blockquotes: disabled.blockquotes.unwrap_or(defaults.blockquotes),
+        lists: disabled.lists.unwrap_or(defaults.lists),
+        code_fences: disabled.code_fences.unwrap_or(defaults.code_fences),
+        horizontal_rules: disabled
+            .horizontal_rules
+            .unwrap_or(defaults.horizontal_rules),
+        tables: disabled.tables.unwrap_or(defaults.tables),
+    }
+}
+
+/// Splits the comma separated region names of `--strip-regions` and `--keep-regions`.
+fn region_names(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// The `--max-input-size` limit, in bytes.
+fn max_input_size(flags: &Flags) -> u64 {
+    flags.max_input_size.unwrap_or(DEFAULT_MAX_INPUT_SIZE)
+}
+
+/// Opens the markdown file at `path` for reading.
+///
+/// Exits with an error when the file is bigger than the `--max-input-size` limit.
+fn open_input(path: &Path, flags: &Flags) -> BufReader<File> {
+    let max_input_size = max_input_size(flags);
+    let file: File = match File::open(path) {
         Err(why) => panic!("couldn't open {}: {}", path.display(), why),
         Ok(file) => file,
     };
-    let reader = BufReader::new(file);
-    Ok(reader.lines().filter_map(Result::ok))
+    let input_size = match file.metadata() {
+        Err(why) => panic!("couldn't open {}: {}", path.display(), why),
+        Ok(metadata) => metadata.len(),
+    };
+    if input_size > max_input_size {
+        log!(
+            error,
+            "{} is {} bytes, over the --max-input-size limit of {} bytes",
+            path.display(),
+            input_size,
+            max_input_size
+        );
+        exit(1);
+    }
+    BufReader::new(file)
 }
 
-fn write_result(html_lines: Vec<String>) {
-    let mut working_path: PathBuf;
-    if OUTPUT_FILE_PATH.get().is_none() {
-        working_path = env::current_dir().unwrap();
-        let folder_name = String::from("output");
-        working_path.push(folder_name);
-    } else {
-        match PathBuf::from_str(OUTPUT_FILE_PATH.get().unwrap()) {
-            Ok(p) => working_path = p,
-            Err(e) => panic!("Error: {}", e),
+/// Reads the HTML injected by `--header`/`--footer`, exiting with an error if the
+/// file can't be read.
+fn read_injected_html(path: Option<&String>) -> Option<String> {
+    let path = path?;
+    match fs::read_to_string(path) {
+        Ok(html) => Some(html),
+        Err(err) => {
+            log!(error, "Could not read {}: {}", path, err);
+            exit(1);
         }
     }
+}
+
+/// The settings given by command line flags, they win over every `md2html.toml`.
+fn cli_config(flags: &Flags) -> Config {
+    let flag = |set: bool| set.then_some(true);
+    let disabled = flags.disabled.clone().unwrap_or_default();
+    Config {
+        wrap_width: flags.wrap_width,
+        link_endnotes: flag(flags.link_endnotes),
+        media_embeds: flag(flags.media_embeds),
+        embeds: flag(flags.embeds),
+        sections: flag(flags.sections || flags.emit_anchors.is_some()),
+        literal_paths: None,
+        autolink_bare_urls: flag(flags.autolink_bare_urls),
+        quote_attribution: flag(flags.quote_attribution),
+        allow_raw: flag(flags.allow_raw),
+        allow_raw_html: flag(flags.allow_raw_html),
+        ascii_slugs: flag(flags.ascii_slugs),
+        slug_style: flags.slug_style,
+        toc: flag(flags.toc),
+        toc_depth: flags.toc_depth,
+        heading_anchors: flag(flags.heading_anchors),
+        normalize_unicode: flag(flags.normalize_unicode),
+        soft_break_default: flags.soft_break_default,
+        soft_break_in_quotes: flags.soft_break_in_quotes,
+        lint: flag(flags.lint),
+        highlight: flag(flags.highlight),
+        headings: disabled.headings,
+        blockquotes: disabled.blockquotes,
+        lists: disabled.lists,
+        code_fences: disabled.code_fences,
+        horizontal_rules: disabled.horizontal_rules,
+        tables: disabled.tables,
+    }
+}
+
+/// Returns `converter` with the settings of the `md2html.toml` files that apply to
+/// the markdown file `relative_path` of the batch in `input_dir`.
+///
+/// Exits with an error if one of them can't be read.
+fn directory_converter(
+    converter: &Converter,
+    input_dir: &Path,
+    relative_path: &Path,
+    flags: &Flags,
+) -> Converter {
+    let mut layers = match config::directory_configs(input_dir, relative_path) {
+        Ok(configs) => configs,
+        Err(err) => {
+            log!(error, "{}", err);
+            exit(1);
+        }
+    };
+    layers.push(cli_config(flags));
+    let options = config::resolve(&layers).apply(converter.parser_options());
+    converter.clone().options(options)
+}
+
+/// Builds the converter configured by the command line flags, reused for every
+/// document of the run.
+fn converter(flags: &Flags) -> Converter {
+    let mut converter = Converter::new()
+        .options(parser_options(flags))
+        .standalone(flags.standalone)
+        .reproducible(flags.reproducible)
+        .meta_tags(!flags.no_meta)
+        .renderer(HtmlRenderer {
+            line_ending: flags.line_ending.unwrap_or_default(),
+        });
+    if let Some(header_html) = read_injected_html(flags.header.as_ref()) {
+        converter = converter.header_html(header_html);
+    }
+    if let Some(footer_html) = read_injected_html(flags.footer.as_ref()) {
+        converter = converter.footer_html(footer_html);
+    }
+    if let Some(date_format) = flags.date_format.as_ref() {
+        converter = converter.date_format(date_format);
+    }
+    if let Some(wrap_div) = flags.wrap_div.as_ref() {
+        converter = converter.wrap_div(wrap_div.clone());
+    }
+    converter
+}
+
+/// Renders a converted document: a standalone page with `--standalone`, and the
+/// HTML followed by the page navigation (if any) otherwise, both in the
+/// `--wrap-div` container if there is one.
+fn render_page(converter: &Converter, document: Document, options: &PageOptions) -> Vec<String> {
+    if converter.is_standalone() {
+        return standalone::wrap_page(&document.html_lines, options);
+    }
+    let mut lines = document.html_lines;
+    if let Some(page_nav_html) = &options.page_nav_html {
+        lines.extend(page_nav_html.lines().map(String::from));
+    }
+    converter.wrap_fragment(lines)
+}
 
-    log!(info, "Creating folders for {}", working_path.display());
+/// Writes `lines` to `output` with `renderer`, exiting with an error if the output
+/// can't be written.
+fn write_lines(renderer: &HtmlRenderer, output: impl Write, lines: &[String], target_name: &str) {
+    if let Err(err) = renderer.write_lines(output, lines) {
+        log!(error, "Could not write {}: {}", target_name, err);
+        exit(1);
+    }
+}
 
-    // Create folder path
-    if let Err(err) = fs::create_dir_all(&(working_path.clone())) {
-        panic!(
-            "Error: Could not create output directory {}\nReason:{}",
-            working_path.display(),
-            err
+/// Writes the converted `html` to `output`, exiting with an error if the output
+/// can't be written.
+fn write_html(mut output: impl Write, html: &[u8], target_name: &str) {
+    if let Err(err) = output.write_all(html).and_then(|_| output.flush()) {
+        log!(error, "Could not write {}: {}", target_name, err);
+        exit(1);
+    }
+}
+
+/// Reads the markdown of `input`, exiting with an error if it can't be read, if
+/// it is bigger than the `--max-input-size` limit, if it isn't valid UTF-8, or if
+/// it doesn't look like text (see `not_text_reason`).
+///
+/// Files are checked by `open_input` before being read, the limit is what stops
+/// stdin.
+fn read_markdown(
+    input: impl Read,
+    source_name: &str,
+    path: Option<&Path>,
+    flags: &Flags,
+) -> String {
+    let max_input_size = max_input_size(flags);
+    let bytes = read_input(input.take(max_input_size + 1), source_name);
+    if bytes.len() as u64 > max_input_size {
+        log!(
+            error,
+            "{} is over the --max-input-size limit of {} bytes",
+            source_name,
+            max_input_size
+        );
+        exit(1);
+    }
+    if let Some(reason) = not_text_reason(&bytes, path, flags) {
+        log!(
+            error,
+            "{}: {}, pass --force-text to convert it anyway",
+            source_name,
+            reason
         );
+        exit(1);
     }
+    decode_markdown(bytes, source_name)
+}
 
-    // Create the file
-    let filename_out: String = String::from("out.html");
-    working_path.push(filename_out);
+/// Reads all of `input`, exiting with an error if it can't be read.
+fn read_input(mut input: impl Read, source_name: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if let Err(err) = input.read_to_end(&mut bytes) {
+        log!(error, "{}: could not read input: {}", source_name, err);
+        exit(1);
+    }
+    bytes
+}
 
-    log!(info, "Writing file {}", working_path.display());
+/// Decodes the markdown read by `read_input`, exiting with an error if it isn't
+/// valid UTF-8.
+fn decode_markdown(bytes: Vec<u8>, source_name: &str) -> String {
+    match String::from_utf8(bytes) {
+        Ok(markdown) => markdown,
+        Err(err) => {
+            log!(error, "{}: could not read input: {}", source_name, err);
+            exit(1);
+        }
+    }
+}
 
-    let mut file = match File::create(&working_path) {
-        Err(err) => panic!(
-            "Error: Could not create file {}\nReason:{}",
-            working_path.display(),
-            err
-        ),
+/// Why the input read from `path` isn't converted: it has the extension of a
+/// binary format, or its first bytes aren't UTF-8 text. `None` when it looks like
+/// markdown, or with `--force-text`.
+fn not_text_reason(bytes: &[u8], path: Option<&Path>, flags: &Flags) -> Option<String> {
+    if flags.force_text {
+        return None;
+    }
+    if let Some(path) = path.filter(|path| sniff::has_binary_extension(path)) {
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        return Some(format!("a .{} file is not markdown", extension));
+    }
+    match sniff::sniff(bytes) {
+        Content::Text => None,
+        Content::Utf16 => Some(String::from(
+            "looks like UTF-16, convert it to UTF-8 first (e.g. iconv -f UTF-16 -t UTF-8)",
+        )),
+        Content::Binary => Some(String::from(
+            "contains NUL bytes, it looks like a binary file",
+        )),
+    }
+}
+
+/// Prints `diagnostics` to stderr in the `--message-format` format.
+fn report(diagnostics: &[Diagnostic], flags: &Flags) {
+    let format = flags.message_format.unwrap_or_default();
+    eprint!("{}", diagnostics::render(diagnostics, format, flags.color));
+}
+
+/// Turns the broken links of a batch in `input_dir` into diagnostics pointing at
+/// the link target.
+fn broken_link_diagnostics(
+    input_dir: &Path,
+    broken_links: &[BrokenLink],
+    documents: &[BatchDocument],
+    severity: Severity,
+) -> Vec<Diagnostic> {
+    broken_links
+        .iter()
+        .map(|broken_link| {
+            let source_line = documents
+                .iter()
+                .find(|document| document.path == broken_link.source)
+                .and_then(|document| {
+                    document.links.iter().find(|link| {
+                        link.line == broken_link.line && link.target == broken_link.target
+                    })
+                })
+                .map(|link| link.source_line.clone());
+            let start = source_line
+                .as_ref()
+                .and_then(|line| line.find(&broken_link.target));
+            let column = source_line
+                .as_ref()
+                .zip(start)
+                .map(|(line, start)| line[..start].chars().count() + 1);
+            Diagnostic {
+                file: input_dir.join(&broken_link.source).display().to_string(),
+                line: broken_link.line,
+                column,
+                length: broken_link.target.chars().count(),
+                span: start.map(|start| start..start + broken_link.target.len()),
+                severity,
+                message: broken_link.message(),
+                source_line,
+            }
+        })
+        .collect()
+}
+
+/// Converts `markdown` with `converter`, reporting the parse warnings (see
+/// `check_warnings`).
+///
+/// `source_name` names the input in the logs and `source_path` is the markdown
+/// file.
+fn convert_markdown(
+    converter: &Converter,
+    markdown: &str,
+    source_name: &str,
+    source_path: Option<&Path>,
+    flags: &Flags,
+) -> Document {
+    let document = converter.convert_document(markdown, source_path);
+    check_warnings(
+        &document.warnings,
+        document.date.as_ref(),
+        markdown,
+        source_name,
+        flags,
+    );
+    document
+}
+
+/// Reports the parse `warnings` of `markdown` and its invalid front matter `date`.
+///
+/// `source_name` names the input in the logs. Exits with an error if headings skip
+/// levels or repeat the `<h1>` with `--heading-checks` or `--strict`, or if a line
+/// couldn't be tokenized with `--strict`.
+fn check_warnings(
+    warnings: &[ParseWarning],
+    date: Option<&DocumentDate>,
+    markdown: &str,
+    source_name: &str,
+    flags: &Flags,
+) {
+    let strict = flags.strict;
+    let fail_on_headings = flags.heading_checks || strict;
+    let fails = |kind: WarningKind| match kind {
+        WarningKind::HeadingStructure => fail_on_headings,
+        // A corrupted code block is a bug, the output can't be trusted
+        WarningKind::CodeBlock => true,
+        WarningKind::Tokenize
+        | WarningKind::UnclosedFence
+        | WarningKind::UnclosedContainer
+        | WarningKind::Region => strict,
+        WarningKind::Limit
+        | WarningKind::RawBlock
+        | WarningKind::Reference
+        | WarningKind::Inline
+        | WarningKind::ZeroWidth
+        | WarningKind::FrontMatter => false,
+    };
+    let diagnostics: Vec<Diagnostic> = warnings
+        .iter()
+        .map(|warning| {
+            let source_line = markdown.lines().nth(warning.line - 1).map(String::from);
+            // Heading and fence problems point at the `#` or fence marker
+            let marker = match warning.kind {
+                WarningKind::HeadingStructure => Some('#'),
+                WarningKind::UnclosedFence => {
+                    source_line.as_ref().and_then(|line| line.chars().next())
+                }
+                _ => None,
+            };
+            let marker_length = marker
+                .zip(source_line.as_ref())
+                .map_or(0, |(marker, line)| {
+                    line.chars().take_while(|c| *c == marker).count()
+                });
+            // Inline problems know their byte range, columns count characters
+            let span = match &warning.span {
+                Some(span) => Some(span.clone()),
+                None => (marker_length > 0).then_some(0..marker_length),
+            };
+            let (column, length) = match (&span, &source_line) {
+                (Some(span), Some(line)) if line.get(span.clone()).is_some() => (
+                    Some(line[..span.start].chars().count() + 1),
+                    line[span.clone()].chars().count(),
+                ),
+                _ => (None, 1),
+            };
+            Diagnostic {
+                file: source_name.to_string(),
+                line: warning.line,
+                column,
+                length: length.max(1),
+                span,
+                severity: if fails(warning.kind) {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                },
+                message: warning.message.clone(),
+                source_line,
+            }
+        })
+        .collect();
+    report(&diagnostics, flags);
+    if let Some(DocumentDate::Raw(raw)) = date {
+        log!(
+            warning,
+            "{}: invalid date \"{}\" in the front matter, kept as written",
+            source_name,
+            raw
+        );
+    }
+    // The whole document is checked first, so every problem is reported at once
+    let problems = warnings
+        .iter()
+        .filter(|warning| fails(warning.kind))
+        .count();
+    if problems > 0 {
+        log!(error, "{}: {} problem(s) found", source_name, problems);
+        exit(1);
+    }
+}
+
+/// Parses the numeric value of a CLI flag, exiting with an error if it is invalid.
+fn parse_number_arg<T: FromStr>(flag: &str, param: &str) -> T {
+    match param.parse::<T>() {
+        Ok(value) => value,
+        Err(_) => {
+            log!(error, "invalid value for {}: {}", flag, param);
+            exit(1);
+        }
+    }
+}
+
+/// The output file or directory of `--output`, `output/` in the current
+/// directory by default.
+fn output_dir(output: Option<&str>) -> PathBuf {
+    PathBuf::from(output.unwrap_or("output"))
+}
+
+/// Creates `file_name`, relative to `output_dir`, along with its folders.
+fn create_output_file(output_dir: &Path, file_name: &Path) -> File {
+    create_file_or_exit(&output_dir.join(file_name))
+}
+
+/// Creates the file at `path` and its missing parent folders, exiting with an
+/// error naming the failing path when that isn't possible.
+fn create_file_or_exit(path: &Path) -> File {
+    log!(info, "Writing file {}", path.display());
+    match output::create_file(path) {
         Ok(file) => file,
+        Err(err) => {
+            log!(error, "{}", err);
+            exit(1);
+        }
+    }
+}
+
+/// Recursively collects every markdown file under `dir`, see
+/// `batch::collect_markdown_files`.
+///
+/// An output folder inside `dir` is skipped, so files generated or copied there
+/// are never converted again.
+fn collect_markdown_files(dir: &Path, output_dir: &Path) -> Vec<PathBuf> {
+    let nested_output = output::is_inside(output_dir, dir) && !output::same_file(output_dir, dir);
+    if nested_output {
+        log!(
+            info,
+            "Skipping {}, it is the output folder",
+            output_dir.display()
+        );
+    }
+    match batch::collect_markdown_files_excluding(dir, nested_output.then_some(output_dir)) {
+        Err(why) => panic!("couldn't read directory {}: {}", dir.display(), why),
+        Ok(files) => files,
+    }
+}
+
+/// The previous/next links of `pages[current]`, when `--page-nav` is set.
+fn page_nav_html(pages: &[PageLink], current: usize, flags: &Flags) -> Option<String> {
+    if !flags.page_nav {
+        return None;
+    }
+    standalone::page_nav_html(pages, current)
+}
+
+/// Writes the heading anchors of the converted pages when `--emit-anchors` is set,
+/// to the path given to it or else to anchors.json in `output_dir`.
+fn write_anchors(anchors: &[Anchor], output_dir: &Path, flags: &Flags) {
+    let Some(path) = flags.emit_anchors.as_ref() else {
+        return;
+    };
+    let (mut file, path) = match path {
+        Some(path) => (create_file_or_exit(Path::new(path)), PathBuf::from(path)),
+        None => (
+            create_output_file(output_dir, Path::new(DEFAULT_ANCHORS_FILE)),
+            output_dir.join(DEFAULT_ANCHORS_FILE),
+        ),
     };
+    if let Err(err) = file.write_all(anchors::to_json(anchors).as_bytes()) {
+        log!(error, "Could not write {}: {}", path.display(), err);
+        exit(1);
+    }
+}
+
+/// Converts every markdown file under `input_dir`, mirroring the folder structure
+/// inside the output directory (`guide/setup.md` -> `guide/setup.html`).
+///
+/// A first pass reads the manifest of the batch: the metadata of every document,
+/// only up to its first `<h1>`, on up to `--max-open-files` files at a time. The
+/// page navigation takes the titles of the neighbouring pages from it, so the
+/// second pass converts and writes each page on its own, keeping only its links
+/// and heading ids to check the links between documents. Broken links are
+/// logged, and stop the conversion with `--strict`: then nothing is written until
+/// they're checked, and the pages are converted again in a third pass.
+fn convert_directory(converter: &Converter, input_dir: &Path, output_dir: &Path, flags: &Flags) {
+    let markdown_paths = collect_markdown_files(input_dir, output_dir);
+    let converters: Vec<Converter> = markdown_paths
+        .iter()
+        .map(|path| {
+            directory_converter(
+                converter,
+                input_dir,
+                path.strip_prefix(input_dir).unwrap(),
+                flags,
+            )
+        })
+        .collect();
+    let max_open_files = flags
+        .max_open_files
+        .unwrap_or(manifest::DEFAULT_MAX_OPEN_FILES);
+    let manifest = manifest::build_manifest(&markdown_paths, max_open_files, |index, path| {
+        read_page_metadata(path, input_dir, &converters[index], flags)
+    });
+    let pages: Vec<(&PathBuf, &Converter, PageMetadata)> = markdown_paths
+        .iter()
+        .zip(&converters)
+        .zip(manifest)
+        .filter_map(|((path, converter), metadata)| Some((path, converter, metadata?)))
+        .collect();
+    let page_links: Vec<PageLink> = pages
+        .iter()
+        .map(|(_, _, metadata)| PageLink {
+            title: metadata.page_title(),
+            url: metadata.url(),
+        })
+        .collect();
+
+    let strict = flags.strict;
+    let mut search_pages: Vec<SearchPage> = Vec::new();
+    let mut heading_anchors: Vec<Anchor> = Vec::new();
+    let body_limit = flags
+        .search_index_limit
+        .unwrap_or(search_index::DEFAULT_BODY_LIMIT);
+    let mut writer = BatchWriter::new(
+        flags
+            .max_write_failures
+            .unwrap_or(batch::DEFAULT_MAX_WRITE_FAILURES),
+    );
+    // With --diff-against the pages are compared with that folder, not written
+    let mut generated: Option<Vec<(PathBuf, String)>> =
+        flags.diff_against.as_ref().map(|_| Vec::new());
+    let mut write_page = |index: usize, document: Document| {
+        let page_link = &page_links[index];
+        if flags.search_index {
+            search_pages.push(search_index::build_search_page(
+                &page_link.title,
+                &page_link.url,
+                &document.html_lines,
+                body_limit,
+            ));
+        }
+        heading_anchors.extend(anchors::page_anchors(&page_link.url, &document.html_lines));
 
-    // Write inside the file
-    for mut line in html_lines {
-        line.push('\n');
-        let bytes_written = match file.write(line.as_bytes()) {
-            Err(_) => panic!(
-                "Error: Could not write bytes to file {}",
-                working_path.display()
+        let options = PageOptions {
+            page_nav_html: page_nav_html(&page_links, index, flags),
+            ..converter.page_options(&document)
+        };
+        let html_path = pages[index].2.output_path();
+        let lines = render_page(converter, document, &options);
+        if let Some(generated) = generated.as_mut() {
+            generated.push((html_path, lines.join("\n")));
+            return;
+        }
+        let html_file = output_dir.join(html_path);
+        log!(info, "Writing file {}", html_file.display());
+        if let Err(err) = writer.write_with(&html_file, |file| {
+            converter
+                .html_renderer()
+                .write_lines(BufWriter::new(file), &lines)
+                .map(|_| ())
+        }) {
+            abort_batch(writer.report(), err, flags);
+        }
+    };
+
+    let mut batch_documents: Vec<BatchDocument> = Vec::new();
+    for (index, (markdown_path, document_converter, metadata)) in pages.iter().enumerate() {
+        log!(info, "Starting conversion of {}", markdown_path.display());
+        let source_name = markdown_path.display().to_string();
+        let markdown = read_markdown(open_input(markdown_path, flags), &source_name, None, flags);
+        let document = convert_markdown(
+            document_converter,
+            &markdown,
+            &source_name,
+            Some(markdown_path),
+            flags,
+        );
+        let lines: Vec<String> = markdown.lines().map(String::from).collect();
+        batch_documents.push(BatchDocument {
+            path: metadata.path.clone(),
+            links: cross_links::find_links(&lines),
+            heading_ids: sections::heading_ids_with_options(
+                &document.html_lines,
+                document_converter.parser_options(),
             ),
-            Ok(bytes_written) => bytes_written,
+        });
+        if !strict {
+            write_page(index, document);
+        }
+    }
+
+    let broken_links = cross_links::check_links(input_dir, &batch_documents);
+    let severity = if strict {
+        Severity::Error
+    } else {
+        Severity::Warning
+    };
+    report(
+        &broken_link_diagnostics(input_dir, &broken_links, &batch_documents, severity),
+        flags,
+    );
+    if strict && !broken_links.is_empty() {
+        log!(
+            error,
+            "{} broken link(s) found, nothing was written (--strict)",
+            broken_links.len()
+        );
+        exit(1);
+    }
+    if strict {
+        // The warnings were reported by the second pass already
+        for (index, (markdown_path, document_converter, _)) in pages.iter().enumerate() {
+            let source_name = markdown_path.display().to_string();
+            let markdown =
+                read_markdown(open_input(markdown_path, flags), &source_name, None, flags);
+            write_page(
+                index,
+                document_converter.convert_document(&markdown, Some(markdown_path)),
+            );
+        }
+    }
+
+    if let (Some(generated), Some(old_dir)) = (generated, flags.diff_against.as_ref()) {
+        let old = read_html_tree(Path::new(old_dir));
+        report_site_diff(&site_diff::diff_trees(&old, &generated), flags);
+    }
+    if flags.search_index {
+        let json = search_index::to_json(&search_pages);
+        if let Err(err) = writer.write(&output_dir.join("search-index.json"), json.as_bytes()) {
+            abort_batch(writer.report(), err, flags);
+        }
+    }
+    write_anchors(&heading_anchors, output_dir, flags);
+    finish_batch(writer.report(), flags);
+}
+
+/// Reads the HTML files of the output folder `dir`, exiting with an error if it
+/// can't be read.
+fn read_html_tree(dir: &Path) -> Vec<(PathBuf, String)> {
+    match site_diff::read_html_files(dir) {
+        Ok(files) => files,
+        Err(err) => {
+            log!(error, "Could not read {}: {}", dir.display(), err);
+            exit(1);
+        }
+    }
+}
+
+/// Prints the comparison of two outputs to stdout, one line per file followed by
+/// the lines of the changed ones, and writes its `--report`. Exits with 1 when
+/// they differ, with 0 otherwise.
+fn report_site_diff(diffs: &[FileDiff], flags: &Flags) -> ! {
+    let mut lines: Vec<String> = Vec::new();
+    for diff in diffs {
+        lines.push(format!("{:<9} {}", diff.change.name(), diff.path.display()));
+        if let FileChange::Changed(diff_lines) = &diff.change {
+            lines.extend(diff_lines.iter().map(|line| format!("    {}", line)));
+        }
+    }
+    lines.push(site_diff::summary(diffs));
+    write_lines(
+        &HtmlRenderer::default(),
+        io::stdout().lock(),
+        &lines,
+        "stdout",
+    );
+    if let Some(path) = flags.diff_report.as_ref() {
+        let json = site_diff::to_json(diffs);
+        if let Err(err) = output::write_atomically(Path::new(path), json.as_bytes()) {
+            log!(error, "{}", err);
+            exit(1);
+        }
+    }
+    let identical = diffs
+        .iter()
+        .all(|diff| diff.change == FileChange::Identical);
+    exit(if identical { 0 } else { 1 });
+}
+
+/// Reads the manifest entry of the markdown file at `path`, under `input_dir`,
+/// with the options of its `converter`. `None` when the file is skipped for not
+/// being text.
+fn read_page_metadata(
+    path: &Path,
+    input_dir: &Path,
+    converter: &Converter,
+    flags: &Flags,
+) -> Option<PageMetadata> {
+    let source_name = path.display().to_string();
+    // The sniffed bytes are the start of what's read, so they're only read once
+    let mut reader = open_input(path, flags);
+    let head = match reader.fill_buf() {
+        Ok(head) => head,
+        Err(err) => {
+            log!(error, "{}: could not read input: {}", source_name, err);
+            exit(1);
+        }
+    };
+    if let Some(reason) = not_text_reason(head, Some(path), flags) {
+        log!(debug, "Skipping {}: {}", source_name, reason);
+        return None;
+    }
+    match manifest::read_metadata(reader, converter.parser_options()) {
+        Ok(metadata) => Some(PageMetadata {
+            path: path.strip_prefix(input_dir).unwrap().to_path_buf(),
+            ..metadata
+        }),
+        Err(err) => {
+            log!(error, "{}: could not read input: {}", source_name, err);
+            exit(1);
+        }
+    }
+}
+
+/// Stops a batch after too many outputs in a row failed to be written, with the
+/// summary of what was written so far.
+fn abort_batch(report: &BatchReport, err: batch::TooManyFailures, flags: &Flags) -> ! {
+    log!(error, "{}", err);
+    finish_batch(report, flags);
+    exit(1);
+}
+
+/// Logs the summary of the outputs of a batch and writes its `--write-report`,
+/// exiting with an error when some outputs failed.
+fn finish_batch(report: &BatchReport, flags: &Flags) {
+    let summary = report.summary();
+    if report.failures.is_empty() {
+        log!(info, "{}", summary[0]);
+    } else {
+        log!(error, "{}", summary.join("\n"));
+    }
+    if let Some(path) = flags.write_report.as_ref() {
+        if let Err(err) = output::write_atomically(Path::new(path), report.to_json().as_bytes()) {
+            log!(error, "{}", err);
+        }
+    }
+    if !report.failures.is_empty() {
+        exit(1);
+    }
+}
+
+/// Builds the book described by `book_dir/SUMMARY.md`: every chapter becomes a
+/// standalone page with a sidebar listing all the chapters.
+///
+/// Exits with an error when the summary links to missing files. Markdown files
+/// that aren't in the summary are skipped with a warning.
+fn convert_book(converter: &Converter, book_dir: &Path, output_dir: &Path, flags: &Flags) {
+    let summary_path = book_dir.join("SUMMARY.md");
+    let summary = match fs::read_to_string(&summary_path) {
+        Ok(summary) => summary,
+        Err(err) => {
+            log!(error, "Could not read {}: {}", summary_path.display(), err);
+            exit(1);
+        }
+    };
+    let chapters: Vec<Chapter> = book::parse_summary(&summary);
+
+    let missing: Vec<&Chapter> = chapters
+        .iter()
+        .filter(|chapter| !book_dir.join(&chapter.path).is_file())
+        .collect();
+    for chapter in &missing {
+        log!(
+            error,
+            "{} links to a missing file: {}",
+            summary_path.display(),
+            chapter.path
+        );
+    }
+    if !missing.is_empty() {
+        exit(1);
+    }
+
+    for markdown_path in collect_markdown_files(book_dir, output_dir) {
+        let relative_path = markdown_path.strip_prefix(book_dir).unwrap();
+        let in_summary = chapters
+            .iter()
+            .any(|chapter| Path::new(&chapter.path) == relative_path);
+        if !in_summary && markdown_path != summary_path {
+            log!(
+                warning,
+                "Skipping {}, it is not listed in SUMMARY.md",
+                markdown_path.display()
+            );
+        }
+    }
+
+    let page_links: Vec<PageLink> = chapters
+        .iter()
+        .map(|chapter| PageLink {
+            title: chapter.title.clone(),
+            url: chapter.url(),
+        })
+        .collect();
+
+    let mut heading_anchors: Vec<Anchor> = Vec::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        let markdown_path = book_dir.join(&chapter.path);
+        log!(info, "Starting conversion of {}", markdown_path.display());
+        let source_name = markdown_path.display().to_string();
+        let markdown = read_markdown(
+            open_input(&markdown_path, flags),
+            &source_name,
+            Some(&markdown_path),
+            flags,
+        );
+        let document = convert_markdown(
+            converter,
+            &markdown,
+            &source_name,
+            Some(&markdown_path),
+            flags,
+        );
+        heading_anchors.extend(anchors::page_anchors(&chapter.url(), &document.html_lines));
+
+        let options = PageOptions {
+            title: chapter.title.clone(),
+            sidebar_html: Some(book::sidebar_html(&chapters, index)),
+            page_nav_html: page_nav_html(&page_links, index, flags),
+            ..converter.page_options(&document)
         };
-        log!(debug, "Written {}bytes", bytes_written);
+        let html_path = Path::new(&chapter.url()).to_path_buf();
+        let output = BufWriter::new(create_output_file(output_dir, &html_path));
+        write_lines(
+            converter.html_renderer(),
+            output,
+            &standalone::wrap_page(&document.html_lines, &options),
+            &html_path.display().to_string(),
+        );
+    }
+    write_anchors(&heading_anchors, output_dir, flags);
+}
+
+/// Times the conversion of every generated corpus (see `md2html::corpus`) and
+/// prints the speed and the peak token count of each.
+fn run_benchmarks(flags: &Flags) {
+    const ITERATIONS: usize = 3;
+    let line_count = flags.bench_lines.unwrap_or(DEFAULT_BENCH_LINES);
+    let options = parser_options(flags);
+    println!("{} lines per corpus, {} iterations", line_count, ITERATIONS);
+    for corpus in Corpus::ALL {
+        let result = corpus::run_benchmark(corpus, line_count, ITERATIONS, &options);
+        println!(
+            "{:<12} {:>12.0} lines/s {:>10} peak tokens {:>10.1} ms",
+            corpus.name(),
+            result.lines_per_second(),
+            result.peak_tokens,
+            result.elapsed.as_secs_f64() * 1000.0
+        );
     }
 }
 
 fn main() {
-    process_args();
-    let mut working_path;
-    let path_str: String;
-    if INPUT_FILE_PATH.get().is_none() {
-        working_path = env::current_dir().unwrap();
-        path_str = format!("input{}in.md", std::path::MAIN_SEPARATOR_STR).to_string();
-        working_path.push(Path::new(&path_str));
-    } else {
-        match PathBuf::from_str(INPUT_FILE_PATH.get().unwrap()) {
-            Ok(p) => working_path = p,
-            Err(e) => panic!("Error: {}", e),
+    let (paths, mut flags) = process_args();
+    if flags.features {
+        let report = features::report();
+        match flags.json {
+            true => print!("{}", report.to_json()),
+            false => print!("{}", report.to_text()),
+        }
+        return;
+    }
+    let color = diagnostics::use_color(
+        env::var("NO_COLOR").ok().as_deref(),
+        env::var("FORCE_COLOR").ok().as_deref(),
+        io::stderr().is_terminal(),
+    );
+    colored::control::set_override(color);
+    flags.color = color;
+    let flags = &flags;
+    if flags.bench_internal {
+        run_benchmarks(flags);
+        return;
+    }
+    if let Some((old_dir, new_dir)) = flags.diff_trees.as_ref() {
+        let old = read_html_tree(Path::new(old_dir));
+        let new = read_html_tree(Path::new(new_dir));
+        report_site_diff(&site_diff::diff_trees(&old, &new), flags);
+    }
+    // input/in.md in the current directory by default
+    let working_path = match &paths.input {
+        Some(input) => PathBuf::from(input),
+        None => Path::new("input").join("in.md"),
+    };
+    let output_dir = output_dir(paths.output.as_deref());
+
+    if flags.wrap_div.is_some() && (flags.standalone || flags.book) {
+        log!(
+            warning,
+            "--wrap-div is only used for fragments, not with --standalone or books"
+        );
+    }
+
+    if flags.highlight && !highlight::is_available() {
+        log!(
+            warning,
+            "md2html was built without the highlight feature, code blocks are not highlighted"
+        );
+    }
+    if let Some(theme) = flags.highlight_theme.as_ref() {
+        let themes = highlight::theme_names();
+        if highlight::is_available() && !themes.contains(theme) {
+            log!(
+                error,
+                "unknown highlight theme \"{}\", expected one of {}",
+                theme,
+                themes.join(", ")
+            );
+            exit(1);
         }
     }
 
-    println!("Starting conversion of {}", working_path.display());
+    if flags.book {
+        if !working_path.is_dir() {
+            log!(error, "book needs --input to be a directory");
+            exit(1);
+        }
+        convert_book(&converter(flags), &working_path, &output_dir, flags);
+        return;
+    }
+
+    if working_path.is_dir() {
+        if paths.output.as_deref() == Some("-") {
+            log!(
+                error,
+                "--output - can't be used when --input is a directory"
+            );
+            exit(1);
+        }
+        convert_directory(&converter(flags), &working_path, &output_dir, flags);
+        return;
+    }
 
-    let str_iter = match read_lines_file(&working_path) {
-        Err(_) => panic!("Error: Could not read lines of file"),
-        Ok(str_iter) => str_iter,
+    if flags.search_index {
+        log!(
+            warning,
+            "--search-index is only used when --input is a directory"
+        );
+    }
+    if flags.page_nav {
+        log!(
+            warning,
+            "--page-nav is only used when --input is a directory"
+        );
+    }
+
+    // "-" reads the markdown from stdin and/or writes the HTML to stdout
+    let from_stdin = paths.input.as_deref() == Some("-");
+    let to_stdout = paths.output.as_deref() == Some("-");
+    if paths.stdin_filename.is_some() && !from_stdin {
+        log!(
+            warning,
+            "--stdin-filename is only used when reading from stdin (--input -)"
+        );
+    }
+    // The file piped into stdin, only used to name it
+    let stdin_path = paths
+        .stdin_filename
+        .as_deref()
+        .filter(|_| from_stdin)
+        .map(Path::new);
+    // --output names the HTML file itself, e.g. build/site/page.html
+    let output_path = match (output::is_file_path(&output_dir), stdin_path) {
+        (true, _) => output_dir.clone(),
+        (false, Some(stdin_path)) => output_dir
+            .join(Path::new(stdin_path.file_stem().unwrap_or_default()).with_extension("html")),
+        (false, None) => output_dir.join("out.html"),
     };
+    let in_place = !from_stdin && !to_stdout && output::same_file(&working_path, &output_path);
+    if in_place && !flags.in_place {
+        log!(
+            error,
+            "{} is both the input and the output, pass --in-place to overwrite it",
+            working_path.display()
+        );
+        exit(1);
+    }
 
-    let tokenized_text_lines = tokenize_text(str_iter);
-    write_result(tokenized_text_lines);
+    let (markdown, source_name) = if from_stdin {
+        let source_name =
+            stdin_path.map_or(String::from("<stdin>"), |path| path.display().to_string());
+        log!(info, "Starting conversion of {} (stdin)", source_name);
+        (
+            read_markdown(io::stdin().lock(), &source_name, None, flags),
+            source_name,
+        )
+    } else {
+        log!(info, "Starting conversion of {}", working_path.display());
+        let source_name = working_path.display().to_string();
+        (
+            read_markdown(
+                open_input(&working_path, flags),
+                &source_name,
+                Some(&working_path),
+                flags,
+            ),
+            source_name,
+        )
+    };
 
-    // for line in tokenized_text_lines {
-    //     println!("{}", line);
-    // }
+    let converter = converter(flags);
+    let source_path = match from_stdin {
+        true => stdin_path,
+        false => Some(working_path.as_path()),
+    };
+    // The HTML is only written out once the warnings are checked
+    let mut html = Vec::new();
+    let stats = match converter.convert_reader_writer_with_path(
+        markdown.as_bytes(),
+        &mut html,
+        source_path,
+    ) {
+        Ok(stats) => stats,
+        Err(err) => {
+            log!(error, "{}: {}", source_name, err);
+            exit(1);
+        }
+    };
+    check_warnings(
+        &stats.warnings,
+        front_matter::document_date(stats.front_matter.as_ref(), None).as_ref(),
+        &markdown,
+        &source_name,
+        flags,
+    );
+    if flags.emit_anchors.is_some() {
+        // The anchors file goes next to the HTML file, or in the current
+        // directory when the HTML is written to stdout
+        let (page, anchors_dir) = match to_stdout {
+            true => (String::from("-"), PathBuf::new()),
+            false => (
+                output_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                output_path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            ),
+        };
+        let html_lines: Vec<String> = String::from_utf8_lossy(&html)
+            .lines()
+            .map(String::from)
+            .collect();
+        write_anchors(
+            &anchors::page_anchors(&page, &html_lines),
+            &anchors_dir,
+            flags,
+        );
+    }
+    if to_stdout {
+        write_html(io::stdout().lock(), &html, &source_name);
+    } else if in_place {
+        // The whole input is already read, the file is only replaced once the
+        // HTML is fully written
+        log!(info, "Overwriting {}", output_path.display());
+        if let Err(err) = output::write_atomically(&output_path, &html) {
+            log!(error, "{}", err);
+            exit(1);
+        }
+    } else {
+        write_html(create_file_or_exit(&output_path), &html, &source_name);
+    }
 }
 
-fn process_args() {
-    let mut args: env::Args = env::args();
+/// Reads the command line flags, returning the files it names and the other flags.
+fn process_args() -> (Paths, Flags) {
+    let mut paths = Paths::default();
+    let mut flags = Flags::default();
+    let mut args = env::args().peekable();
     while let Some(curr) = args.next() {
         // Define here your CLI commands
         match curr.as_str() {
-            "--input" => match args.next() {
-                Some(param) => match INPUT_FILE_PATH.set(param) {
-                    Err(e) => panic!("Error: {}", e),
-                    _ => (),
-                },
-                _ => (),
-            },
-            "--output" => match args.next() {
-                Some(param) => match OUTPUT_FILE_PATH.set(param) {
-                    Err(e) => panic!("Error: {}", e),
-                    _ => (),
-                },
-                _ => (),
-            },
+            "--input" => {
+                if let Some(param) = args.next() {
+                    paths.input.get_or_insert(param);
+                }
+            }
+            "--output" => {
+                if let Some(param) = args.next() {
+                    paths.output.get_or_insert(param);
+                }
+            }
+            "book" => {
+                flags.book = true;
+            }
+            "diff" => {
+                if let (Some(old), Some(new)) = (args.next(), args.next()) {
+                    flags.diff_trees.get_or_insert((old, new));
+                }
+            }
+            "--diff-against" => {
+                if let Some(param) = args.next() {
+                    flags.diff_against.get_or_insert(param);
+                }
+            }
+            "--report" => {
+                if let Some(param) = args.next() {
+                    flags.diff_report.get_or_insert(param);
+                }
+            }
+            "--search-index" => {
+                flags.search_index = true;
+            }
+            "--emit-anchors" => {
+                // The path is optional, the next argument may be another flag
+                let path = args.next_if(|next| {
+                    !next.starts_with('-') && !["book", "diff"].contains(&next.as_str())
+                });
+                flags.emit_anchors.get_or_insert(path);
+            }
+            "--search-index-limit" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .search_index_limit
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--max-write-failures" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .max_write_failures
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--max-open-files" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .max_open_files
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--write-report" => {
+                if let Some(param) = args.next() {
+                    flags.write_report.get_or_insert(param);
+                }
+            }
+            "--max-input-size" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .max_input_size
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--max-line-length" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .max_line_length
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--max-inline-delimiters" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .max_inline_delimiters
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--max-nesting-depth" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .max_nesting_depth
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--link-endnotes" => {
+                flags.link_endnotes = true;
+            }
+            "--media" => {
+                flags.media_embeds = true;
+            }
+            "--embeds" => {
+                flags.embeds = true;
+            }
+            "--sections" => {
+                flags.sections = true;
+            }
+            "--strict" => {
+                flags.strict = true;
+            }
+            "--heading-checks" => {
+                flags.heading_checks = true;
+            }
+            "--page-nav" => {
+                flags.page_nav = true;
+            }
+            "--standalone" => {
+                flags.standalone = true;
+            }
+            "--header" => {
+                if let Some(param) = args.next() {
+                    flags.header.get_or_insert(param);
+                }
+            }
+            "--footer" => {
+                if let Some(param) = args.next() {
+                    flags.footer.get_or_insert(param);
+                }
+            }
+            "--reproducible" => {
+                flags.reproducible = true;
+            }
+            "--no-meta" => {
+                flags.no_meta = true;
+            }
+            "--stdin-filename" => {
+                if let Some(param) = args.next() {
+                    paths.stdin_filename.get_or_insert(param);
+                }
+            }
+            "--in-place" => {
+                flags.in_place = true;
+            }
+            "--force-text" => {
+                flags.force_text = true;
+            }
+            "--autolink-bare-urls" => {
+                flags.autolink_bare_urls = true;
+            }
+            "--quote-attribution" => {
+                flags.quote_attribution = true;
+            }
+            "--allow-raw" => {
+                flags.allow_raw = true;
+            }
+            "--allow-raw-html" => {
+                flags.allow_raw_html = true;
+            }
+            "--lint" => {
+                flags.lint = true;
+            }
+            "--ascii-slugs" => {
+                flags.ascii_slugs = true;
+            }
+            "--toc" => {
+                flags.toc = true;
+            }
+            "--toc-depth" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .toc_depth
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--heading-anchors" => {
+                flags.heading_anchors = true;
+            }
+            "--anchor-symbol" => {
+                if let Some(param) = args.next() {
+                    flags.anchor_symbol.get_or_insert(param);
+                }
+            }
+            "--strip-regions" => {
+                if let Some(param) = args.next() {
+                    flags.strip_regions.get_or_insert(region_names(&param));
+                }
+            }
+            "--keep-regions" => {
+                if let Some(param) = args.next() {
+                    flags.keep_regions.get_or_insert(region_names(&param));
+                }
+            }
+            "--normalize-unicode" => {
+                flags.normalize_unicode = true;
+            }
+            "--verify-code-blocks" => {
+                flags.verify_code_blocks = true;
+            }
+            "--slug-style" => {
+                if let Some(param) = args.next() {
+                    match param.parse() {
+                        Ok(style) => {
+                            flags.slug_style.get_or_insert(style);
+                        }
+                        Err(err) => {
+                            log!(error, "invalid value for --slug-style: {}", err);
+                            exit(1);
+                        }
+                    }
+                }
+            }
+            "--soft-breaks" | "--soft-breaks-in-quotes" => {
+                if let Some(param) = args.next() {
+                    match param.parse() {
+                        Ok(policy) => {
+                            match curr.as_str() {
+                                "--soft-breaks" => flags.soft_break_default.get_or_insert(policy),
+                                _ => flags.soft_break_in_quotes.get_or_insert(policy),
+                            };
+                        }
+                        Err(err) => {
+                            log!(error, "invalid value for {}: {}", curr, err);
+                            exit(1);
+                        }
+                    }
+                }
+            }
+            "--line-ending" => {
+                if let Some(param) = args.next() {
+                    match param.parse() {
+                        Ok(line_ending) => {
+                            flags.line_ending.get_or_insert(line_ending);
+                        }
+                        Err(err) => {
+                            log!(error, "invalid value for --line-ending: {}", err);
+                            exit(1);
+                        }
+                    }
+                }
+            }
+            "--bench-internal" => {
+                flags.bench_internal = true;
+            }
+            "--bench-lines" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .bench_lines
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--message-format" => {
+                if let Some(param) = args.next() {
+                    match param.parse() {
+                        Ok(format) => {
+                            flags.message_format.get_or_insert(format);
+                        }
+                        Err(err) => {
+                            log!(error, "{}", err);
+                            exit(1);
+                        }
+                    }
+                }
+            }
+            "--highlight" => {
+                flags.highlight = true;
+            }
+            "--highlight-theme" => {
+                if let Some(param) = args.next() {
+                    flags.highlight_theme.get_or_insert(param);
+                }
+            }
+            "--disable" => {
+                if let Some(param) = args.next() {
+                    match config::parse_disabled(&param) {
+                        Ok(disabled) => {
+                            flags.disabled.get_or_insert(disabled);
+                        }
+                        Err(err) => {
+                            log!(error, "invalid value for --disable: {}", err);
+                            exit(1);
+                        }
+                    }
+                }
+            }
+            "--wrap-div" => {
+                if let Some(param) = args.next() {
+                    match param.parse() {
+                        Ok(wrap_div) => {
+                            flags.wrap_div.get_or_insert(wrap_div);
+                        }
+                        Err(err) => {
+                            log!(error, "invalid value for --wrap-div: {}", err);
+                            exit(1);
+                        }
+                    }
+                }
+            }
+            "--date-format" => {
+                if let Some(param) = args.next() {
+                    flags.date_format.get_or_insert(param);
+                }
+            }
+            "--wrap" => {
+                if let Some(param) = args.next() {
+                    flags
+                        .wrap_width
+                        .get_or_insert(parse_number_arg(&curr, &param));
+                }
+            }
+            "--features" => {
+                flags.features = true;
+            }
+            "--json" => {
+                flags.json = true;
+            }
             "--version" => {
                 println!("v{}", SOFTWARE_VERSION);
                 exit(0);
@@ -142,6 +1613,7 @@ fn process_args() {
             _ => (),
         }
     }
+    (paths, flags)
 }
 
 fn print_help() {
@@ -150,12 +1622,171 @@ fn print_help() {
 
     USAGE:
         md2html --input <FILE> --output <FILE>
+        md2html --input <DIR> --output <DIR> [--search-index]
+        md2html book --input <DIR> --output <DIR>
+        md2html diff <OLD_DIR> <NEW_DIR> [--report <FILE>]
+
+    COMMANDS:
+        book                 Build the book described by <DIR>/SUMMARY.md,
+                             one page per chapter with a navigation sidebar
+        diff                 Compare the HTML files of two output folders:
+                             identical, changed (with the changed lines),
+                             added or removed. Exits with 1 if they differ
 
     OPTIONS:
-        --input <FILE>       Absolute path to the input Markdown file
-        --output <FILE>      Absolute path to the output HTML file
+        --input <FILE>       Absolute path to the input Markdown file, or a
+                             directory to convert every .md file inside it,
+                             or - to read from stdin
+        --output <FILE>      Path to the output HTML file (missing folders
+                             are created), a directory to write out.html
+                             into, or - to write to stdout
+        --stdin-filename <FILE>
+                             Name of the markdown read from stdin, shown in
+                             messages and giving the page title, the output
+                             file name and the file date
+        --in-place           Allow --output to be the input file itself, which
+                             is replaced once the HTML is fully written
+        --force-text         Convert the input even when it looks like a
+                             binary file (NUL bytes, .png, .pdf...), which
+                             is refused, or skipped in directory mode
+        --search-index       In directory mode, also write search-index.json
+        --diff-against <DIR> In directory mode, compare the pages with the
+                             ones of DIR instead of writing them, like diff
+        --report <FILE>      With diff or --diff-against, also write the
+                             comparison to FILE as JSON
+        --emit-anchors [FILE]
+                             Also write the heading text, id and page of
+                             every heading section as JSON, to FILE or to
+                             anchors.json next to the HTML
+        --search-index-limit <N>
+                             Maximum characters of page body kept in the
+                             search index (default: 5000)
+        --max-write-failures <N>
+                             In directory mode, stop after N pages in a
+                             row fail to be written (default: 5, 0 never
+                             stops), the others are listed at the end
+        --max-open-files <N>
+                             In directory mode, read the titles of at most
+                             N files at a time before converting them
+                             (default: 64)
+        --write-report <FILE>
+                             In directory mode, write the written and
+                             failed output files to FILE as JSON
+        --max-input-size <BYTES>
+                             Refuse input files, or stdin, bigger than this
+                             (default: 67108864, 64 MiB)
+        --max-line-length <N>
+                             Lines longer than this skip inline formatting
+                             (default: 100000)
+        --max-inline-delimiters <N>
+                             Lines with more inline delimiters than this
+                             skip inline formatting (default: 10000)
+        --max-nesting-depth <N>
+                             How deep quotes can be nested before their
+                             markers are kept as text (default: 32)
+        --wrap <N>           Soft-wrap the text of the generated HTML at N
+                             columns, without breaking tags or code
+        --link-endnotes      Render links as numbered endnotes listed in a
+                             "Links" section, for printing
+        --media              Render images of audio/video files as <audio>
+                             and <video> players
+        --embeds             Render YouTube/Vimeo URLs alone on a line, and
+                             {{youtube ID}} shortcodes, as embedded players
+        --autolink-bare-urls Turn bare http(s):// and www. URLs into links
+        --quote-attribution  Render a last quote line starting with an em
+                             dash or -- as the <cite> of the quote
+        --allow-raw          Output ```=html and ```raw fenced blocks as
+                             they are, without escaping
+        --allow-raw-html     Keep HTML written in the text as markup instead
+                             of escaping <, > and &
+        --sections           Wrap every heading and its content in a
+                             <section> carrying the heading id
+        --ascii-slugs        Make heading ids plain ASCII (Größe -> grosse),
+                             other letters are percent-encoded
+        --slug-style <STYLE> How heading ids are made: default, or github
+                             for the ids GitHub gives the same headings
+        --soft-breaks <POLICY>
+                             What the line break inside a paragraph becomes:
+                             space (default) to reflow, or break for a <br/>
+        --soft-breaks-in-quotes <POLICY>
+                             The same inside blockquotes, e.g. break to keep
+                             the lines of a chat transcript
+        --line-ending <ENDING>
+                             What ends the lines of the HTML: lf (default)
+                             or crlf
+        --toc                Put a table of contents before the first
+                             heading, a [TOC] line places it anywhere.
+                             Implies --sections for the heading ids
+        --toc-depth <N>      Deepest heading level in the table of
+                             contents (default: 3)
+        --heading-anchors    Append a # link to its section to every
+                             heading, implies --sections
+        --anchor-symbol <TEXT>
+                             Text of the --heading-anchors links
+                             (default: #)
+        --highlight          Color the code of fenced blocks with a known
+                             language using inline styles, no JavaScript
+                             needed (needs the highlight cargo feature)
+        --highlight-theme <NAME>
+                             Colors of --highlight (default:
+                             InspiredGitHub), e.g. base16-ocean.dark
+        --strip-regions <LIST>
+                             Leave out the regions between <!-- NAME:start -->
+                             and <!-- NAME:end --> lines, for the listed
+                             comma separated names
+        --keep-regions <LIST>
+                             Output the listed regions as they are, without
+                             converting their markdown
+        --normalize-unicode  Put the text in Unicode normalization form C,
+                             e.g. e followed by a combining accent becomes
+                             the single letter é
+        --verify-code-blocks Check that every code block of the output
+                             holds its source byte for byte, failing with
+                             a diff when one doesn't
+        --disable <LIST>     Treat the listed block constructs as text:
+                             headings, blockquotes, lists, code_fences,
+                             horizontal_rules or tables, comma separated
+        --strict             Fail when headings skip levels or repeat the
+                             h1, a code fence or ::: container is never
+                             closed, and in directory mode when links
+                             between the documents are broken
+        --heading-checks     Fail when headings skip levels (## then ####)
+                             or a document has more than one h1
+        --lint               Also warn about link reference definitions
+                             that are never used, and about a ** or a
+                             link destination never closed
+        --message-format <F> How warnings and check failures are printed:
+                             pretty (default), short or json. Colors
+                             follow NO_COLOR and FORCE_COLOR
+        --page-nav           In directory and book mode, add links to the
+                             previous and next pages at the bottom of pages
+        --standalone         Write complete HTML pages, with the content in
+                             <main> and a "skip to content" link
+        --header <FILE>      With --standalone, HTML put in a <header> at
+                             the top of every page
+        --footer <FILE>      With --standalone, HTML put in a <footer> at
+                             the bottom of every page
+        --no-meta            With --standalone, leave out the description
+                             and og:title/og:description/og:image tags
+        --wrap-div <SPEC>    Without --standalone, wrap the output in a
+                             <div>: article-body sets its class and
+                             #content.article-body its id and class
+        --date-format <FMT>  Format of {{date}} in the header and footer
+                             (default: %Y-%m-%d), e.g. "%B %e, %Y"
+        --reproducible       Keep the output free of anything depending on
+                             when or where it is built, e.g. dates taken
+                             from file modification times
+        --bench-internal     Time the conversion of generated documents
+                             (paragraphs, lists, code, inline formatting)
+                             and print lines/second for each
+        --bench-lines <N>    Lines of every --bench-internal document
+                             (default: 1000)
         --help               Show this help message and exit
         --version            Show version information and exit
+        --features           Show the version, cargo features, output
+                             formats and extensions (with their default
+                             state) of this build and exit, as JSON with
+                             --json
     "#;
     println!("{}", HELP_MESSAGE);
 }
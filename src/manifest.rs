@@ -0,0 +1,346 @@
+use crate::front_matter::parse_front_matter;
+use crate::standalone::page_title;
+use crate::{tokenize_text_with_options, ParserOptions};
+use std::io::{self, BufRead};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// How many files a batch reads at the same time by default, see `build_manifest`.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 64;
+
+/// What a batch needs to know about a document before converting it, read by
+/// `read_metadata` without the rest of the document.
+///
+/// # Fields
+///
+/// - `path`: The markdown file, relative to the batch input directory.
+/// - `title`: The text of the first `<h1>`, if the document has one.
+/// - `front_matter_title`: The `title` of the front matter.
+/// - `date`: The `date` of the front matter, as written.
+/// - `draft`: Whether the front matter has `draft: true`.
+/// - `permalink`: The `permalink` of the front matter.
+/// - `lines_read`: How many lines were read to find all this.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageMetadata {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub front_matter_title: Option<String>,
+    pub date: Option<String>,
+    pub draft: bool,
+    pub permalink: Option<String>,
+    pub lines_read: usize,
+}
+
+impl PageMetadata {
+    /// The page title: the first `<h1>`, or else the front matter title, or else
+    /// the file name without its extension, like `Converter::convert_document`
+    /// gives it.
+    pub fn page_title(&self) -> String {
+        let fallback = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        self.title
+            .clone()
+            .or_else(|| self.front_matter_title.clone())
+            .unwrap_or_else(|| fallback.to_string())
+    }
+
+    /// Where the page is written, relative to the batch output directory: its
+    /// `permalink`, or else the markdown path with an `.html` extension.
+    ///
+    /// A permalink ending with `/` gets an `index.html`, and one without an
+    /// extension gets `.html`. Permalinks leaving the output directory (with `..`)
+    /// are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use md2html::manifest::PageMetadata;
+    /// use std::path::PathBuf;
+    ///
+    /// let page = |permalink: Option<&str>| PageMetadata {
+    ///     path: PathBuf::from("guide/setup.md"),
+    ///     permalink: permalink.map(String::from),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(page(None).output_path(), PathBuf::from("guide/setup.html"));
+    /// assert_eq!(page(Some("/install/")).output_path(), PathBuf::from("install/index.html"));
+    /// assert_eq!(page(Some("/docs/install")).output_path(), PathBuf::from("docs/install.html"));
+    /// assert_eq!(page(Some("/")).output_path(), PathBuf::from("index.html"));
+    /// assert_eq!(page(Some("../setup.html")).output_path(), PathBuf::from("guide/setup.html"));
+    /// ```
+    pub fn output_path(&self) -> PathBuf {
+        let permalink = self
+            .permalink
+            .as_deref()
+            .map(str::trim)
+            .filter(|permalink| !permalink.is_empty())
+            .map(|permalink| permalink.trim_start_matches('/'))
+            .filter(|permalink| {
+                Path::new(permalink)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)))
+            });
+        match permalink {
+            Some(permalink) if permalink.is_empty() || permalink.ends_with('/') => {
+                Path::new(permalink).join("index.html")
+            }
+            Some(permalink) if Path::new(permalink).extension().is_none() => {
+                Path::new(permalink).with_extension("html")
+            }
+            Some(permalink) => PathBuf::from(permalink),
+            None => self.path.with_extension("html"),
+        }
+    }
+
+    /// The URL of the page relative to the batch output directory, the
+    /// `output_path` with forward slashes whatever the platform separator is.
+    pub fn url(&self) -> String {
+        self.output_path()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Reads the metadata of the markdown document `reader` reads, stopping after its
+/// front matter and first `<h1>`: only a document without one is read to its end.
+///
+/// The title is found by converting the lines read so far with `options`, so it is
+/// the one of the full conversion, except when the heading uses a link reference
+/// or footnote defined below it.
+///
+/// # Example
+///
+/// ```rust
+/// let markdown = "---\ndate: 2024-06-01\ndraft: true\n---\nIntro\n# The **Guide**\n\nA long text";
+/// let metadata = md2html::manifest::read_metadata(markdown.as_bytes(), &Default::default()).unwrap();
+/// assert_eq!(metadata.title.as_deref(), Some("The Guide"));
+/// assert_eq!(metadata.date.as_deref(), Some("2024-06-01"));
+/// assert!(metadata.draft);
+/// assert_eq!(metadata.lines_read, 6);
+/// ```
+pub fn read_metadata(
+    mut reader: impl BufRead,
+    options: &ParserOptions,
+) -> io::Result<PageMetadata> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut read_line = |lines: &mut Vec<String>| -> io::Result<bool> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+        let length = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(length);
+        lines.push(line);
+        Ok(true)
+    };
+
+    // The front matter goes until its closing `---`
+    let mut title = None;
+    if read_line(&mut lines)? && lines[0].trim_end() == "---" {
+        while read_line(&mut lines)? && lines.last().unwrap().trim_end() != "---" {}
+    }
+    let (front_matter, body_start) = match parse_front_matter(&lines) {
+        Some(Ok((front_matter, line_count))) => (Some(front_matter), line_count),
+        // A malformed block is left out of the document
+        Some(Err(malformed)) => (None, malformed.line_count),
+        None => (None, 0),
+    };
+    let mut checked = body_start;
+    loop {
+        // Converting is only worth it once a line could have made an `<h1>`
+        if lines[checked..].iter().any(|line| could_be_h1(line)) {
+            let html = tokenize_text_with_options(lines.iter().cloned(), options).html_lines;
+            // Only the first `<h1>` counts, an empty one leaves the page untitled
+            if html.iter().any(|line| line.starts_with("<h1>")) {
+                title = Some(page_title("", &html)).filter(|title| !title.is_empty());
+                break;
+            }
+        }
+        checked = lines.len();
+        if !read_line(&mut lines)? {
+            break;
+        }
+    }
+
+    let field = |key: &str| {
+        front_matter
+            .as_ref()
+            .and_then(|front_matter| front_matter.get(key))
+            .map(String::from)
+    };
+    Ok(PageMetadata {
+        path: PathBuf::new(),
+        title,
+        front_matter_title: front_matter
+            .as_ref()
+            .and_then(|front_matter| front_matter.metadata().title),
+        date: field("date"),
+        draft: field("draft").is_some_and(|draft| draft == "true"),
+        permalink: field("permalink"),
+        lines_read: lines.len(),
+    })
+}
+
+/// Whether `line` could make an `<h1>`: a `#` heading, or the `===` line under a
+/// setext one, in a blockquote or not.
+fn could_be_h1(line: &str) -> bool {
+    let line = line.trim_start_matches([' ', '>']);
+    let atx = line
+        .strip_prefix('#')
+        .is_some_and(|rest| !rest.starts_with('#'));
+    let setext = line.starts_with('=') && line.trim_end().chars().all(|c| c == '=');
+    atx || setext
+}
+
+/// Runs `read` on every path of `paths`, given with its index, on several threads
+/// but never more than `max_open_files` at a time, returning the results in the
+/// order of `paths`.
+///
+/// `read` is expected to open its file and close it before returning, so at most
+/// `max_open_files` files are open at once. 0 reads them one at a time.
+///
+/// # Example
+///
+/// ```rust
+/// use std::path::PathBuf;
+///
+/// let paths = vec![PathBuf::from("a.md"), PathBuf::from("b.md")];
+/// let lengths = md2html::manifest::build_manifest(&paths, 8, |_, path| path.as_os_str().len());
+/// assert_eq!(lengths, [4, 4]);
+/// ```
+pub fn build_manifest<T, F>(paths: &[PathBuf], max_open_files: usize, read: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &Path) -> T + Sync,
+{
+    let available = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let workers = max_open_files.max(1).min(available).min(paths.len());
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new(paths.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                let result = read(index, path);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every path is read"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Converter;
+    use std::io::{BufReader, Read};
+
+    /// A reader counting the lines read from it, like a file would be.
+    struct CountingReader<'a> {
+        lines: std::vec::IntoIter<String>,
+        read: &'a AtomicUsize,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            // One line per read, so the buffer never gets ahead of the lines asked for
+            let Some(line) = self.lines.next() else {
+                return Ok(0);
+            };
+            self.read.fetch_add(1, Ordering::Relaxed);
+            let bytes = format!("{}\n", line).into_bytes();
+            buf[..bytes.len()].copy_from_slice(&bytes);
+            Ok(bytes.len())
+        }
+    }
+
+    fn read_counting(markdown: &str) -> (PageMetadata, usize) {
+        let read = AtomicUsize::new(0);
+        let reader = CountingReader {
+            lines: markdown
+                .lines()
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            read: &read,
+        };
+        let metadata = read_metadata(BufReader::new(reader), &ParserOptions::default()).unwrap();
+        (metadata, read.into_inner())
+    }
+
+    const DOCUMENTS: [&str; 9] = [
+        "---\ntitle: ignored\ndate: 2024-06-01\npermalink: /guide/\n---\n# Guide\nBody\nmore body",
+        "Intro text\n\n```sh\n# a comment\n```\n## Not the title\n\nSetext *title*\n===\n\n# Later",
+        "No title here\n## Only a level 2\n\ntext",
+        "---\nnot front matter\n---\n# Title after a rule",
+        "    # indented code\n\n> # [Linked](https://example.com) `code`\nrest",
+        "Text\n#\n# Not the title either",
+        "---\ndraft: true\n---",
+        "---\ntitle: From the front matter\n---\nNo heading",
+        "---\ntitle: Broken\nnot a pair\n---\n# Real title",
+    ];
+
+    #[test]
+    fn test_first_pass_stops_after_the_title() {
+        let expected_lines = [6, 9, 4, 4, 3, 2, 3, 4, 5];
+        for (markdown, expected) in DOCUMENTS.iter().zip(expected_lines) {
+            let (metadata, read) = read_counting(markdown);
+            assert_eq!(metadata.lines_read, expected, "{}", markdown);
+            assert_eq!(read, expected, "{}", markdown);
+        }
+        let (metadata, _) = read_counting(DOCUMENTS[0]);
+        assert_eq!(metadata.date.as_deref(), Some("2024-06-01"));
+        assert_eq!(metadata.permalink.as_deref(), Some("/guide/"));
+        assert!(!metadata.draft);
+        assert!(read_counting(DOCUMENTS[6]).0.draft);
+    }
+
+    #[test]
+    fn test_manifest_titles_match_full_conversion() {
+        let options = ParserOptions {
+            sections: true,
+            heading_anchors: true,
+            ..Default::default()
+        };
+        for (index, markdown) in DOCUMENTS.iter().enumerate() {
+            let path = PathBuf::from(format!("page-{}.md", index));
+            let metadata = PageMetadata {
+                path: path.clone(),
+                ..read_metadata(markdown.as_bytes(), &options).unwrap()
+            };
+            let document = Converter::new()
+                .options(options.clone())
+                .convert_document(markdown, Some(&path));
+            assert_eq!(metadata.page_title(), document.title, "{}", markdown);
+        }
+    }
+
+    #[test]
+    fn test_build_manifest_bounds_open_files() {
+        let paths: Vec<PathBuf> = (0..200)
+            .map(|i| PathBuf::from(format!("{}.md", i)))
+            .collect();
+        let (open, most_open) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let names = build_manifest(&paths, 3, |_, path| {
+            let now_open = open.fetch_add(1, Ordering::SeqCst) + 1;
+            most_open.fetch_max(now_open, Ordering::SeqCst);
+            std::thread::yield_now();
+            open.fetch_sub(1, Ordering::SeqCst);
+            path.display().to_string()
+        });
+        assert!(most_open.into_inner() <= 3);
+        assert_eq!(names[199], "199.md");
+        assert_eq!(names.len(), 200);
+        assert!(build_manifest(&[], 3, |_, _| ()).is_empty());
+    }
+}
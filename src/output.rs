@@ -0,0 +1,253 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// An error that stopped the creation of an output file.
+///
+/// # Fields
+///
+/// - `path`: The path that failed, either the file itself or the first folder of its
+///   parent path that couldn't be created.
+/// - `source`: The underlying I/O error (permission denied, read-only filesystem...).
+#[derive(Debug)]
+pub struct OutputError {
+    pub path: PathBuf,
+    pub source: io::Error,
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "could not create {}: {}",
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for OutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Checks whether an `--output` path names a file (`site/page.html`) rather than a
+/// folder to write into.
+///
+/// A path is a file path when it has an extension and isn't an existing folder.
+///
+/// # Example
+///
+/// ```rust
+/// use std::path::Path;
+///
+/// assert!(md2html::output::is_file_path(Path::new("build/site/page.html")));
+/// assert!(!md2html::output::is_file_path(Path::new("build/site")));
+/// ```
+pub fn is_file_path(path: &Path) -> bool {
+    path.extension().is_some() && !path.is_dir()
+}
+
+/// Creates the file at `path`, along with the missing folders of its parent path.
+///
+/// Only the parent folders are created, never a folder named like the file. When a
+/// folder can't be created, e.g. because a file of the same name is in the way, the
+/// error names that folder.
+///
+/// # Example
+///
+/// ```rust
+/// let path = std::env::temp_dir().join("md2html-doc-output/nested/page.html");
+/// md2html::output::create_file(&path).unwrap();
+/// assert!(path.is_file());
+/// ```
+pub fn create_file(path: &Path) -> Result<File, OutputError> {
+    if let Some(parent) = path.parent() {
+        create_folders(parent)?;
+    }
+    File::create(path).map_err(|source| OutputError {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Creates `folder` and its missing ancestors, one at a time so a failure can name
+/// the folder that couldn't be created.
+fn create_folders(folder: &Path) -> Result<(), OutputError> {
+    let mut ancestors: Vec<&Path> = folder
+        .ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .collect();
+    ancestors.reverse();
+
+    for ancestor in ancestors {
+        if ancestor.is_dir() {
+            continue;
+        }
+        let error = |source| OutputError {
+            path: ancestor.to_path_buf(),
+            source,
+        };
+        if ancestor.exists() {
+            return Err(error(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                "a file is in the way of the folder",
+            )));
+        }
+        fs::create_dir(ancestor).map_err(error)?;
+    }
+    Ok(())
+}
+
+/// Resolves `path` to an absolute path without `.`, `..` or symlinks, even when
+/// it doesn't exist yet: the existing part is canonicalized and the `..` of the
+/// missing part remove the folder before them.
+pub fn resolve_path(path: &Path) -> PathBuf {
+    let absolute = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => std::env::current_dir().unwrap_or_default().join(path),
+    };
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => {
+                resolved.push(component);
+                if let Ok(canonical) = resolved.canonicalize() {
+                    resolved = canonical;
+                }
+            }
+        }
+    }
+    resolved
+}
+
+/// Checks whether `a` and `b` are the same file once resolved (see `resolve_path`),
+/// e.g. `notes.md` and `./docs/../notes.md`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::path::Path;
+///
+/// assert!(md2html::output::same_file(Path::new("notes.md"), Path::new("./docs/../notes.md")));
+/// assert!(!md2html::output::same_file(Path::new("notes.md"), Path::new("notes.html")));
+/// ```
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    resolve_path(a) == resolve_path(b)
+}
+
+/// Checks whether `path` is `root` or inside it, once both are resolved.
+pub fn is_inside(path: &Path, root: &Path) -> bool {
+    resolve_path(path).starts_with(resolve_path(root))
+}
+
+/// Replaces the file at `path` with `contents` as a whole: they are written to a
+/// temporary file next to it which is then renamed over it, so a failure never
+/// leaves a truncated file behind.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), OutputError> {
+    write_atomically_with(path, |file| file.write_all(contents))
+}
+
+/// Replaces the file at `path` with what `write` writes to the file it's given,
+/// atomically like `write_atomically`: when `write` fails nothing is left behind.
+pub fn write_atomically_with(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> Result<(), OutputError> {
+    let path = &resolve_path(path);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.md2html-tmp", file_name));
+    let error = |source| OutputError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let result = create_file(&temp_path).and_then(|mut file| {
+        write(&mut file)
+            .and_then(|_| file.sync_all())
+            .map_err(error)
+    });
+    if let Err(err) = result.and_then(|_| fs::rename(&temp_path, path).map_err(error)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder for a single test, removed first if a previous run left it.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("md2html-output-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_create_file_deep_missing_parent() {
+        let dir = test_dir("deep");
+        let path = dir.join("build/site/docs/page.html");
+        create_file(&path).unwrap();
+        assert!(path.is_file());
+        assert!(path.parent().unwrap().is_dir());
+    }
+
+    #[test]
+    fn test_create_file_existing_parent() {
+        let dir = test_dir("existing");
+        let path = dir.join("page.html");
+        create_file(&path).unwrap();
+        assert!(path.is_file());
+        // Overwriting an existing file works too
+        create_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_create_file_parent_is_a_file() {
+        let dir = test_dir("parent-file");
+        File::create(dir.join("build")).unwrap();
+        let err = create_file(&dir.join("build/docs/page.html")).unwrap_err();
+        assert_eq!(err.path, dir.join("build"));
+        assert_eq!(err.source.kind(), io::ErrorKind::NotADirectory);
+        assert!(err.to_string().starts_with(&format!(
+            "could not create {}: ",
+            dir.join("build").display()
+        )));
+    }
+
+    #[test]
+    fn test_same_file_collision() {
+        let dir = test_dir("same-file");
+        fs::write(dir.join("notes.md"), "# Notes").unwrap();
+        assert!(same_file(
+            &dir.join("notes.md"),
+            &dir.join("missing/./../notes.md")
+        ));
+        assert!(!same_file(&dir.join("notes.md"), &dir.join("notes.html")));
+        // An output that doesn't exist yet still resolves
+        assert!(is_inside(&dir.join("out/site"), &dir));
+        assert!(!is_inside(&dir, &dir.join("out")));
+    }
+
+    #[test]
+    fn test_write_atomically_replaces_input() {
+        let dir = test_dir("in-place");
+        let path = dir.join("notes.md");
+        fs::write(&path, "# Notes\nA long line of text").unwrap();
+        let markdown = fs::read_to_string(&path).unwrap();
+        write_atomically(&path, markdown.to_uppercase().as_bytes()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# NOTES\nA LONG LINE OF TEXT"
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+}
@@ -1,7 +1,349 @@
+use entities::{escape_attribute, escape_invalid_references, escape_text};
+use fences::{Fence, FenceTracker};
+use front_matter::FrontMatter;
+use inline::Inline;
+use regex::Regex;
+use sections::SlugStyle;
+use soft_breaks::{SoftBreak, SoftBreakScope};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::sync::OnceLock;
+use tables::TableCell;
+use urls::UrlRewriter;
+
+pub mod anchors;
+pub mod batch;
+pub mod book;
+pub mod config;
+mod convert;
+mod converter;
+pub mod corpus;
+pub mod cross_links;
+pub mod diagnostics;
+pub mod embeds;
+pub mod endnotes;
+pub mod entities;
+pub mod escape;
+pub mod features;
+pub mod fences;
+pub mod footnotes;
+pub mod front_matter;
+pub mod highlight;
+pub mod inline;
+pub mod inline_checks;
+pub mod manifest;
+pub mod output;
+pub mod plain_text;
+pub mod references;
+pub mod regions;
+pub mod renderer;
+pub mod search_index;
+pub mod sections;
+pub mod site_diff;
+pub mod sniff;
+pub mod soft_breaks;
+pub mod standalone;
+pub mod tables;
+pub mod toc;
+pub mod unicode;
+pub mod urls;
+pub mod verify;
+pub mod wrap;
+
+pub use convert::{convert, ConversionStats, ConvertError};
+pub use converter::{Converter, Document};
+pub use renderer::HtmlRenderer;
+
+/// Options that tune how markdown is parsed.
+///
+/// # Fields
+///
+/// - `max_line_length`: Lines longer than this (in characters) skip inline formatting
+///   and are only escaped.
+/// - `max_inline_delimiters`: Lines with more inline delimiters (`*`, `_`, `` ` ``, `~`,
+///   brackets and parentheses) than this skip inline formatting and are only escaped.
+/// - `max_nesting_depth`: How deep containers (e.g. quotes) can be nested, deeper
+///   markers are kept as literal text.
+/// - `wrap_width`: When set, the rendered HTML is soft-wrapped at this many columns
+///   (see `wrap::wrap_html`).
+/// - `link_endnotes`: Render links as numbered endnotes for print output
+///   (see `endnotes::links_to_endnotes`).
+/// - `media_embeds`: Render images pointing to audio/video files as `<audio>` and
+///   `<video>` elements.
+/// - `embeds`: Render a paragraph made only of a YouTube/Vimeo URL, or a
+///   `{{youtube ID}}` shortcode, as an embedded player (see `embeds::embed_line`).
+///   Off by default since the page then loads content from the provider.
+/// - `sections`: Wrap every heading and its content in a `<section>` carrying the
+///   heading id (see `sections::wrap_sections`).
+/// - `literal_paths`: Keep filesystem paths (`C:\Users\*.md`, `src/**/*.rs`) and
+///   `scheme://` URIs as typed, so their `*` never starts emphasis. Turn it off
+///   for strict CommonMark emphasis.
+/// - `autolink_bare_urls`: Turn bare `http(s)://` and `www.` URLs into links, like
+///   GitHub does. `<https://example.com>` autolinks are always converted.
+/// - `allow_raw`: Output the content of fenced blocks marked `=html` or `raw` as
+///   it is, without `<pre>` nor escaping. Off by default since it lets the
+///   markdown inject any HTML in the page.
+/// - `allow_raw_html`: Keep HTML written in the text (`<kbd>Ctrl</kbd>`) as markup.
+///   By default `<`, `>` and `&` are escaped and displayed as typed.
+/// - `quote_attribution`: Render the last line of a blockquote starting with an em
+///   dash or `--` as its `<footer><cite>` attribution.
+/// - `ascii_slugs`: Make heading ids plain ASCII, transliterating common Latin
+///   diacritics and percent-encoding the other non-ASCII letters (see
+///   `sections::slugify_with_options`).
+/// - `slug_style`: The rules turning headings into ids, md2html's own or GitHub's
+///   (see `sections::SlugStyle`).
+/// - `toc`: Put a table of contents linking to the headings before the first
+///   heading. A `[TOC]` line puts it at its place, even without `toc`. Headings
+///   get their ids from sections, so a table of contents implies `sections`.
+/// - `toc_depth`: The deepest heading level listed in the table of contents.
+/// - `heading_anchors`: Append a permalink to its section to every heading, like
+///   GitHub's link icon (see `sections::add_heading_anchors`). Headings get their
+///   ids from sections, so anchors imply `sections`.
+/// - `anchor_symbol`: The text of the heading permalinks, `#` by default.
+/// - `strip_regions`: The names of the regions left out of the output, from their
+///   `<!-- name:start -->` line to their `<!-- name:end -->` one, e.g. a table of
+///   contents kept up to date by another tool (see `regions::classify_lines`).
+/// - `keep_regions`: The names of the regions output as they are, without being
+///   converted, e.g. a table of badges written in HTML.
+/// - `normalize_unicode`: Put the text in Unicode normalization form C, so a letter
+///   followed by a combining accent becomes the precomposed letter (see
+///   `unicode::nfc`). Code blocks are left as typed.
+/// - `verify_code_blocks`: Check that every code block of the output holds its
+///   source byte for byte, reporting the ones that don't with a diff (see
+///   `verify::check_code_blocks`). Always on in the unit tests, which panic on a
+///   mismatch.
+/// - `soft_break_default`: What the line break between two lines of a paragraph
+///   becomes, a space by default so the text reflows (see `soft_breaks::resolve`).
+/// - `soft_break_in_quotes`: The same inside blockquotes, e.g. `Break` to keep the
+///   lines of a chat transcript. `None` follows `soft_break_default`.
+/// - `lint`: Also warn about problems that don't change the output, such as link
+///   reference definitions that are never used, and about inline markup kept as
+///   text because it is incomplete (`**` never closed).
+/// - `highlight`: Highlight code blocks with a known language using inline styles
+///   (see `highlight::highlight_code`), if md2html is built with the `highlight`
+///   feature.
+/// - `highlight_theme`: The colors of highlighted code blocks, one of
+///   `highlight::theme_names`.
+/// - `headings`, `blockquotes`, `lists`, `code_fences`, `horizontal_rules`,
+///   `tables`: Whether the block construct is recognized, on by default. The lines
+///   of a disabled construct are text of a paragraph, e.g. `>` lines of a shell
+///   transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserOptions {
+    pub max_line_length: usize,
+    pub max_inline_delimiters: usize,
+    pub max_nesting_depth: usize,
+    pub wrap_width: Option<usize>,
+    pub link_endnotes: bool,
+    pub media_embeds: bool,
+    pub embeds: bool,
+    pub sections: bool,
+    pub literal_paths: bool,
+    pub autolink_bare_urls: bool,
+    pub quote_attribution: bool,
+    pub allow_raw: bool,
+    pub allow_raw_html: bool,
+    pub ascii_slugs: bool,
+    pub slug_style: SlugStyle,
+    pub toc: bool,
+    pub toc_depth: u8,
+    pub heading_anchors: bool,
+    pub anchor_symbol: String,
+    pub strip_regions: Vec<String>,
+    pub keep_regions: Vec<String>,
+    pub normalize_unicode: bool,
+    pub verify_code_blocks: bool,
+    pub soft_break_default: SoftBreak,
+    pub soft_break_in_quotes: Option<SoftBreak>,
+    pub lint: bool,
+    pub highlight: bool,
+    pub highlight_theme: String,
+    pub headings: bool,
+    pub blockquotes: bool,
+    pub lists: bool,
+    pub code_fences: bool,
+    pub horizontal_rules: bool,
+    pub tables: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            max_line_length: 100_000,
+            max_inline_delimiters: 10_000,
+            max_nesting_depth: 32,
+            wrap_width: None,
+            link_endnotes: false,
+            media_embeds: false,
+            embeds: false,
+            sections: false,
+            literal_paths: true,
+            autolink_bare_urls: false,
+            quote_attribution: false,
+            allow_raw: false,
+            allow_raw_html: false,
+            ascii_slugs: false,
+            slug_style: SlugStyle::Default,
+            toc: false,
+            toc_depth: 3,
+            heading_anchors: false,
+            anchor_symbol: String::from(sections::DEFAULT_ANCHOR_SYMBOL),
+            strip_regions: Vec::new(),
+            keep_regions: Vec::new(),
+            normalize_unicode: false,
+            verify_code_blocks: false,
+            soft_break_default: SoftBreak::Space,
+            soft_break_in_quotes: None,
+            lint: false,
+            highlight: false,
+            highlight_theme: String::from(highlight::DEFAULT_THEME),
+            headings: true,
+            blockquotes: true,
+            lists: true,
+            code_fences: true,
+            horizontal_rules: true,
+            tables: true,
+        }
+    }
+}
+
+impl ParserOptions {
+    /// The syntax extensions and block constructs turned on or off by these
+    /// options, by the name of their field, for `features::report`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let extensions = md2html::ParserOptions::default().extensions();
+    /// assert!(extensions.contains(&("tables", true)));
+    /// assert!(extensions.contains(&("toc", false)));
+    /// ```
+    pub fn extensions(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            ("headings", self.headings),
+            ("blockquotes", self.blockquotes),
+            ("lists", self.lists),
+            ("code_fences", self.code_fences),
+            ("horizontal_rules", self.horizontal_rules),
+            ("tables", self.tables),
+            ("literal_paths", self.literal_paths),
+            ("autolink_bare_urls", self.autolink_bare_urls),
+            ("link_endnotes", self.link_endnotes),
+            ("media_embeds", self.media_embeds),
+            ("embeds", self.embeds),
+            ("quote_attribution", self.quote_attribution),
+            ("allow_raw", self.allow_raw),
+            ("allow_raw_html", self.allow_raw_html),
+            ("sections", self.sections),
+            ("toc", self.toc),
+            ("heading_anchors", self.heading_anchors),
+            ("highlight", self.highlight),
+        ]
+    }
+}
+
+/// What a `ParseWarning` is about.
+///
+/// # Variants
+///
+/// - `Limit`: A line went over a safety limit of `ParserOptions`.
+/// - `HeadingStructure`: A heading skips a level (`##` followed by `####`) or is
+///   another `<h1>` of a document that already has one.
+/// - `RawBlock`: A raw fenced block was rendered as code because raw output isn't
+///   allowed (see `ParserOptions::allow_raw`).
+/// - `Tokenize`: A line couldn't be tokenized and was kept as escaped text.
+/// - `Reference`: A link reference definition repeats a label already defined, or
+///   is never used (only reported with `ParserOptions::lint`).
+/// - `UnclosedFence`: A code fence has no closing fence anywhere below it, so the
+///   rest of the document became code.
+/// - `Inline`: Inline markup is kept as text because it is incomplete, such as a
+///   `**` never closed (only reported with `ParserOptions::lint`, see
+///   `inline_checks::check_line`).
+/// - `ZeroWidth`: Zero-width characters were removed from the document, pointing
+///   at the first line that had one (see `unicode::is_zero_width`).
+/// - `UnclosedContainer`: A `:::` container is never closed, it ends with the
+///   document.
+/// - `CodeBlock`: With `verify_code_blocks`, the output of a code block doesn't
+///   hold its source exactly. This is a bug of md2html, not of the document.
+/// - `FrontMatter`: The front matter block has a line that isn't a `key: value`
+///   pair, so the block is left out without being read (see
+///   `front_matter::parse_front_matter`).
+/// - `Region`: A marker of a region to strip or keep has no matching start or end
+///   marker, so the lines are converted as usual (see `regions::classify_lines`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WarningKind {
+    Limit,
+    HeadingStructure,
+    RawBlock,
+    Tokenize,
+    Reference,
+    UnclosedFence,
+    Inline,
+    Region,
+    ZeroWidth,
+    CodeBlock,
+    UnclosedContainer,
+    FrontMatter,
+}
+
+/// A non fatal problem found while parsing, reported with its 1-based line number
+/// and, for a problem inside the line, the byte range it spans in the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning {
+    pub line: usize,
+    pub span: Option<Range<usize>>,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Why `tokenize_line` couldn't tokenize a line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenizeError {
+    pub message: String,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The output of `parse_with_options`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedDocument {
+    pub tokens: Vec<Token>,
+    pub warnings: Vec<ParseWarning>,
+    pub front_matter: Option<FrontMatter>,
+    pub code_blocks: Vec<verify::CodeBlockLines>,
+}
+
+/// The output of `tokenize_text_with_options`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseResult {
+    pub html_lines: Vec<String>,
+    pub warnings: Vec<ParseWarning>,
+    pub front_matter: Option<FrontMatter>,
+}
+
+/// Matches the marker of an ordered list item, capped at 9 digits like CommonMark.
+fn ordered_list_marker_re() -> &'static Regex {
+    static ORDERED_LIST_MARKER_RE: OnceLock<Regex> = OnceLock::new();
+    ORDERED_LIST_MARKER_RE.get_or_init(|| Regex::new(r"^(\d{1,9})\.\s").unwrap())
+}
+
 /// Represents various types of tokens that can be extracted from a markdown input.
 ///
 /// This enum is used to categorize and store different markdown elements such as headers,
-/// paragraphs, lists, inline formatting, and more. Each variant corresponds to a specific
+/// paragraphs, lists, tables, and more. Inline formatting is held by the `Inline`
+/// content of the variants with text. Each variant corresponds to a specific
 /// markdown construct and may contain associated data relevant to that construct.
 ///
 /// # Variants
@@ -15,110 +357,233 @@
 ///   Represents a paragraph of text. Contains:
 ///   - `text`: The content of the paragraph.
 ///
+/// - `ULStart`:
+///   Marks the start of an unordered list.
+///
+/// - `ULEnd`:
+///   Marks the end of an unordered list.
+///
 /// - `UListItem`:
 ///   Represents an unordered list item (e.g., `- Item`). Contains:
+///   - `depth`: The nesting depth given by the indentation, one level per 2 columns.
+///   - `text`: The content of the list item.
+///
+/// - `ListItemStart`:
+///   Represents a list item whose nested list follows it, so it is left open. Contains:
 ///   - `text`: The content of the list item.
 ///
+/// - `ListItemEnd`:
+///   Closes a `ListItemStart` after its nested list.
+///
 /// - `OLStart`:
-///   Marks the start of an ordered list.
+///   Marks the start of an ordered list. Contains:
+///   - `start`: The number of the first item, rendered as a `start` attribute when
+///     it isn't 1.
 ///
 /// - `OLEnd`:
 ///   Marks the end of an ordered list.
 ///
 /// - `OListItem`:
 ///   Represents an ordered list item (e.g., `1. Item`). Contains:
+///   - `depth`: The nesting depth given by the indentation, one level per 2 columns.
+///   - `number`: The number of the item marker (`5` for `5. Item`).
 ///   - `text`: The content of the list item.
 ///
 /// - `SimpleText`:
-///   Represents plain text without any formatting. Contains:
-///   - `text`: The content of the text.
-///
-/// - `InlineCode`:
-///   Represents inline code (e.g., `` `code` ``). Contains:
-///   - `text`: The content of the inline code.
+///   Represents a line of a code block, kept as written. Contains:
+///   - `text`: The line, escaped.
 ///
 /// - `Quote`:
 ///   Represents a blockquote (e.g., `> Quote`). Contains:
-///   - `text`: The content of the quote.
-///   - `nested_token`: A nested token inside the quote.
-///
-/// - `Bold`:
-///   Represents bold text (e.g., `**bold**`). Contains:
-///   - `text`: The content of the bold text.
-///
-/// - `Italic`:
-///   Represents italic text (e.g., `*italic*`). Contains:
-///   - `text`: The content of the italic text.
-///
-/// - `Strikethrough`:
-///   Represents strikethrough text (e.g., `~~text~~`). Contains:
-///   - `text`: The content of the strikethrough text.
-///
-/// - `Link`:
-///   Represents a hyperlink (e.g., `[text](url)`). Contains:
-///   - `text`: The display text of the link.
-///   - `url`: The URL of the link.
+///   - `text`: The paragraph text of the quote, empty when it holds another block.
+///   - `nested_token`: The block inside the quote (e.g. a header or another quote),
+///     `None` when the quote holds paragraph text.
 ///
 /// - `CodeBlockStart`:
-///   Marks the start of a code block (e.g., `` ``` ``).
+///   Marks the start of a code block (e.g., `` ```rust ``). Contains:
+///   - `language`: The first word of the fence info string, if any, rendered as the
+///     `language-*` class highlighters look for.
 ///
 /// - `CodeBlockEnd`:
 ///   Marks the end of a code block (e.g., `` ``` ``).
 ///
+/// - `CodeBlock`:
+///   Represents a code fence line (e.g., `` ```rust ``), replaced by `CodeBlockStart`
+///   or `CodeBlockEnd` once the fences are paired. Contains:
+///   - `language`: The first word of the fence info string, if any.
+///   - `fence`: The fence characters, to find the one closing the block.
+///
+/// - `RawHtml`:
+///   Represents a line of a raw fenced block (`` ```=html ``), output as it is.
+///   Contains:
+///   - `html`: The line, unescaped.
+///
 /// - `HorizLine`:
 ///   Represents a horizontal line (e.g., `---`).
 ///
 /// - `BreakLine`:
 ///   Represents a line break.
 ///
+/// - `Embed`:
+///   Represents an embedded video (e.g. a YouTube URL on its own line). Contains:
+///   - `src`: The URL of the embedding iframe.
+///
+/// - `SectionStart`:
+///   Marks the start of a heading section (see `sections::wrap_sections`). Contains:
+///   - `id`: The id of the section.
+///
+/// - `SectionEnd`:
+///   Marks the end of a heading section.
+///
+/// - `TableStart`, `TableEnd`:
+///   Mark the start and end of a table.
+///
+/// - `TableHeadStart`, `TableHeadEnd`:
+///   Mark the start and end of the header row of a table. `TableHeadEnd` stands for
+///   the delimiter row (`|---|:-:|`).
+///
+/// - `TableBodyStart`, `TableBodyEnd`:
+///   Mark the start and end of the data rows of a table.
+///
+/// - `TableRow`:
+///   Represents a row of a table (e.g., `| a | b |`). Contains:
+///   - `cells`: The cells of the row, as many as the table has columns.
+///
+/// - `QuoteStart`, `QuoteEnd`:
+///   Mark the start and end of a blockquote grouping consecutive `Quote` lines.
+///
+/// - `AdmonitionStart`:
+///   Marks the start of a callout, a blockquote whose first line is a GitHub alert
+///   marker (`> [!NOTE]`) rendered as a titled `<div>`. Contains:
+///   - `kind`: The lowercase kind of callout, one of `ADMONITION_KINDS`.
+///
+/// - `AdmonitionEnd`:
+///   Marks the end of a callout.
+///
+/// - `ContainerStart`:
+///   Marks the start of a custom container, a `::: warning` line, rendered as a
+///   `<div>`. Contains:
+///   - `class`: The class names given after the colons, if any.
+///
+/// - `ContainerEnd`:
+///   Marks the end of a custom container, a bare `:::` line.
+///
+/// - `DetailsStart`:
+///   Marks the start of a collapsible section, a `:::details Summary` line,
+///   rendered as `<details>`. Contains:
+///   - `summary`: The inline content of the `<summary>`, empty when none is given.
+///
+/// - `DetailsEnd`:
+///   Marks the end of a collapsible section, a bare `:::` line.
+///
+/// - `QuoteAttribution`:
+///   Represents the source of a blockquote (`> — Ada Lovelace`). Contains:
+///   - `text`: The inline content of the attribution, without its dash.
+///
+/// - `DefinitionListStart`, `DefinitionListEnd`:
+///   Mark the start and end of a definition list grouping consecutive terms and
+///   definitions.
+///
+/// - `DefinitionTerm`:
+///   Represents a term of a definition list, the line of text right above a
+///   `: definition` line. Contains:
+///   - `text`: The inline content of the term.
+///
+/// - `Definition`:
+///   Represents a definition of the term above it (e.g., `: A definition`). Contains:
+///   - `text`: The inline content of the definition, without its `: `.
+///
 /// - `None`:
 ///   Represents an empty or unrecognized token.
-use regex::Regex;
-use std::cmp::min;
-use std::fmt;
-
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Token {
     Header {
         level: u8,
-        text: String,
+        text: Vec<Inline>,
     },
     Paragraph {
-        text: String,
+        text: Vec<Inline>,
     },
+    ULStart {},
+    ULEnd {},
     UListItem {
-        text: String,
+        depth: u8,
+        text: Vec<Inline>,
+    },
+    ListItemStart {
+        text: Vec<Inline>,
+    },
+    ListItemEnd {},
+    OLStart {
+        start: u32,
     },
-    OLStart {},
     OLEnd {},
     OListItem {
-        text: String,
+        depth: u8,
+        number: u32,
+        text: Vec<Inline>,
     },
     SimpleText {
         text: String,
     },
     Quote {
-        text: String,
+        text: Vec<Inline>,
         nested_token: Box<Token>,
     },
-    // InlineCode {
-    //     text: String,
-    // },
-    // Strikethrough {
-    //     text: String,
-    // },
-    // CodeBlock {
-    //     text: String,
-    // },
-    // Link {
-    //     text: String,
-    //     url: String,
-    // },
-    CodeBlockStart {},
+    CodeBlockStart {
+        language: Option<String>,
+    },
     CodeBlockEnd {},
-    CodeBlock {},
+    CodeBlock {
+        language: Option<String>,
+        fence: Fence,
+    },
+    RawHtml {
+        html: String,
+    },
     HorizLine {},
     BreakLine {},
+    Embed {
+        src: String,
+    },
+    SectionStart {
+        id: String,
+    },
+    SectionEnd {},
+    TableStart {},
+    TableEnd {},
+    TableHeadStart {},
+    TableHeadEnd {},
+    TableBodyStart {},
+    TableBodyEnd {},
+    TableRow {
+        cells: Vec<TableCell>,
+    },
+    QuoteStart {},
+    QuoteEnd {},
+    AdmonitionStart {
+        kind: String,
+    },
+    AdmonitionEnd {},
+    ContainerStart {
+        class: Option<String>,
+    },
+    ContainerEnd {},
+    DetailsStart {
+        summary: Vec<Inline>,
+    },
+    DetailsEnd {},
+    QuoteAttribution {
+        text: Vec<Inline>,
+    },
+    DefinitionListStart {},
+    DefinitionListEnd {},
+    DefinitionTerm {
+        text: Vec<Inline>,
+    },
+    Definition {
+        text: Vec<Inline>,
+    },
     None {},
 }
 
@@ -130,21 +595,71 @@ impl Clone for Token {
                 text: text.clone(),
             },
             Token::Paragraph { text } => Token::Paragraph { text: text.clone() },
-            Token::UListItem { text } => Token::UListItem { text: text.clone() },
-            Token::OListItem { text } => Token::OListItem { text: text.clone() },
+            Token::UListItem { depth, text } => Token::UListItem {
+                depth: *depth,
+                text: text.clone(),
+            },
+            Token::ListItemStart { text } => Token::ListItemStart { text: text.clone() },
+            Token::ListItemEnd {} => Token::ListItemEnd {},
+            Token::OListItem {
+                depth,
+                number,
+                text,
+            } => Token::OListItem {
+                depth: *depth,
+                number: *number,
+                text: text.clone(),
+            },
             Token::SimpleText { text } => Token::SimpleText { text: text.clone() },
             Token::Quote { text, nested_token } => Token::Quote {
                 text: text.clone(),
                 nested_token: nested_token.clone(),
             },
-            Token::OLStart {} => Token::OLStart {},
+            Token::ULStart {} => Token::ULStart {},
+            Token::ULEnd {} => Token::ULEnd {},
+            Token::OLStart { start } => Token::OLStart { start: *start },
             Token::OLEnd {} => Token::OLEnd {},
-            Token::CodeBlockStart {} => Token::CodeBlockStart {},
+            Token::CodeBlockStart { language } => Token::CodeBlockStart {
+                language: language.clone(),
+            },
             Token::CodeBlockEnd {} => Token::CodeBlockEnd {},
             Token::HorizLine {} => Token::HorizLine {},
             Token::BreakLine {} => Token::BreakLine {},
             Token::None {} => Token::None {},
-            Token::CodeBlock {} => Token::CodeBlock {},
+            Token::CodeBlock { language, fence } => Token::CodeBlock {
+                language: language.clone(),
+                fence: *fence,
+            },
+            Token::RawHtml { html } => Token::RawHtml { html: html.clone() },
+            Token::Embed { src } => Token::Embed { src: src.clone() },
+            Token::SectionStart { id } => Token::SectionStart { id: id.clone() },
+            Token::SectionEnd {} => Token::SectionEnd {},
+            Token::TableStart {} => Token::TableStart {},
+            Token::QuoteStart {} => Token::QuoteStart {},
+            Token::QuoteEnd {} => Token::QuoteEnd {},
+            Token::AdmonitionStart { kind } => Token::AdmonitionStart { kind: kind.clone() },
+            Token::AdmonitionEnd {} => Token::AdmonitionEnd {},
+            Token::ContainerStart { class } => Token::ContainerStart {
+                class: class.clone(),
+            },
+            Token::ContainerEnd {} => Token::ContainerEnd {},
+            Token::DetailsStart { summary } => Token::DetailsStart {
+                summary: summary.clone(),
+            },
+            Token::DetailsEnd {} => Token::DetailsEnd {},
+            Token::QuoteAttribution { text } => Token::QuoteAttribution { text: text.clone() },
+            Token::DefinitionListStart {} => Token::DefinitionListStart {},
+            Token::DefinitionListEnd {} => Token::DefinitionListEnd {},
+            Token::DefinitionTerm { text } => Token::DefinitionTerm { text: text.clone() },
+            Token::Definition { text } => Token::Definition { text: text.clone() },
+            Token::TableEnd {} => Token::TableEnd {},
+            Token::TableHeadStart {} => Token::TableHeadStart {},
+            Token::TableHeadEnd {} => Token::TableHeadEnd {},
+            Token::TableBodyStart {} => Token::TableBodyStart {},
+            Token::TableBodyEnd {} => Token::TableBodyEnd {},
+            Token::TableRow { cells } => Token::TableRow {
+                cells: cells.clone(),
+            },
         }
     }
 }
@@ -152,21 +667,105 @@ impl Clone for Token {
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Token::Header { level, text } => write!(f, "<h{0}>{1}</h{0}>", level, text),
-            Token::Paragraph { text } => write!(f, "<p>{}</p>", text),
-            Token::UListItem { text } => write!(f, "<li>{}</li>", text),
-            Token::OListItem { text } => write!(f, "<li>{}</li>", text),
+            Token::Header { level, text } => {
+                write!(f, "<h{0}>{1}</h{0}>", level, inline::to_html(text))
+            }
+            Token::Paragraph { text } => write!(f, "<p>{}</p>", inline::to_html(text)),
+            Token::UListItem { text, .. } => write!(f, "<li>{}</li>", inline::to_html(text)),
+            Token::ListItemStart { text } => write!(f, "<li>{}", inline::to_html(text)),
+            Token::ListItemEnd {} => write!(f, "</li>"),
+            Token::OListItem { text, .. } => write!(f, "<li>{}</li>", inline::to_html(text)),
             Token::Quote { text, nested_token } => {
-                write!(f, "<q>{}{}</q>", text, nested_token.as_ref())
+                write!(f, "<blockquote>")?;
+                if !text.is_empty() {
+                    write!(f, "<p>{}</p>", inline::to_html(text))?;
+                }
+                // A list item on its own still needs its list
+                match nested_token.as_ref() {
+                    item @ Token::UListItem { .. } => write!(f, "<ul>{}</ul>", item)?,
+                    item @ Token::OListItem { number, .. } => {
+                        write!(f, "{}{}</ol>", Token::OLStart { start: *number }, item)?
+                    }
+                    nested_token => write!(f, "{}", nested_token)?,
+                }
+                write!(f, "</blockquote>")
             }
-            Token::OLStart {} => write!(f, "<ol>"),
+            Token::ULStart {} => write!(f, "<ul>"),
+            Token::ULEnd {} => write!(f, "</ul>"),
+            Token::OLStart { start: 1 } => write!(f, "<ol>"),
+            Token::OLStart { start } => write!(f, "<ol start=\"{}\">", start),
             Token::OLEnd {} => write!(f, "</ol>"),
-            Token::CodeBlock {} => write!(f, ""),
-            Token::CodeBlockStart {} => write!(f, "<pre><code>"),
+            Token::CodeBlock { .. } => write!(f, ""),
+            Token::CodeBlockStart { language: None } => write!(f, "<pre><code>"),
+            Token::CodeBlockStart {
+                language: Some(language),
+            } => write!(
+                f,
+                "<pre><code class=\"language-{}\">",
+                escape::escape_attr(language)
+            ),
             Token::CodeBlockEnd {} => write!(f, "</code></pre>"),
+            Token::RawHtml { html } => write!(f, "{}", html),
             Token::SimpleText { text } => write!(f, "{}", text),
             Token::HorizLine {} => write!(f, "<hr>"),
             Token::BreakLine {} => write!(f, "<br/>"),
+            Token::Embed { src } => write!(
+                f,
+                "<div class=\"video-embed\"><iframe src=\"{}\" allowfullscreen></iframe></div>",
+                escape::escape_attr(src)
+            ),
+            Token::SectionStart { id } => write!(f, "<section id=\"{}\">", id),
+            Token::SectionEnd {} => write!(f, "</section>"),
+            Token::TableStart {} => write!(f, "<table>"),
+            Token::QuoteStart {} => write!(f, "<blockquote>"),
+            Token::QuoteEnd {} => write!(f, "</blockquote>"),
+            Token::AdmonitionStart { kind } => {
+                let mut title = kind.chars();
+                write!(
+                    f,
+                    "<div class=\"admonition {}\"><p class=\"admonition-title\">{}{}</p>",
+                    kind,
+                    title
+                        .next()
+                        .map(|c| c.to_ascii_uppercase())
+                        .unwrap_or_default(),
+                    title.as_str()
+                )
+            }
+            Token::AdmonitionEnd {} => write!(f, "</div>"),
+            Token::ContainerStart { class: None } => write!(f, "<div>"),
+            Token::ContainerStart { class: Some(class) } => {
+                write!(f, "<div class=\"{}\">", escape::escape_attr(class))
+            }
+            Token::ContainerEnd {} => write!(f, "</div>"),
+            Token::DetailsStart { summary } if summary.is_empty() => write!(f, "<details>"),
+            Token::DetailsStart { summary } => {
+                write!(
+                    f,
+                    "<details><summary>{}</summary>",
+                    inline::to_html(summary)
+                )
+            }
+            Token::DetailsEnd {} => write!(f, "</details>"),
+            Token::QuoteAttribution { text } => {
+                write!(f, "<footer><cite>{}</cite></footer>", inline::to_html(text))
+            }
+            Token::DefinitionListStart {} => write!(f, "<dl>"),
+            Token::DefinitionListEnd {} => write!(f, "</dl>"),
+            Token::DefinitionTerm { text } => write!(f, "<dt>{}</dt>", inline::to_html(text)),
+            Token::Definition { text } => write!(f, "<dd>{}</dd>", inline::to_html(text)),
+            Token::TableEnd {} => write!(f, "</table>"),
+            Token::TableHeadStart {} => write!(f, "<thead>"),
+            Token::TableHeadEnd {} => write!(f, "</thead>"),
+            Token::TableBodyStart {} => write!(f, "<tbody>"),
+            Token::TableBodyEnd {} => write!(f, "</tbody>"),
+            Token::TableRow { cells } => {
+                write!(f, "<tr>")?;
+                for cell in cells {
+                    write!(f, "{}", cell)?;
+                }
+                write!(f, "</tr>")
+            }
             Token::None {} => write!(f, ""),
         }
     }
@@ -181,9 +780,18 @@ impl fmt::Display for Token {
 ///
 /// # Supported Inline Markdown Syntax
 ///
-/// - **Bold**: `**text**` is converted to `<strong>text</strong>`.
-/// - *Italic*: `*text*` is converted to `<i>text</i>`.
-/// - [Links](url): `[text](url)` is converted to `<a href="url">text</a>`.
+/// - **Bold**: `**text**` or `__text__` is converted to `<strong>text</strong>`.
+/// - *Italic*: `*text*` or `_text_` is converted to `<i>text</i>`.
+/// - Strikethrough: `~~text~~` is converted to `<del>text</del>`.
+/// - Inline code: `` `code` `` is converted to `<code>code</code>`, its content
+///   escaped and left unformatted. A span opened by a run of backticks is closed
+///   by a run of the same length, so ``` `` a ` b `` ``` can hold a backtick.
+/// - [Links](url): `[text](url)` is converted to `<a href="url">text</a>`, and
+///   `[text](url "title")` also gets a `title` attribute (images too).
+/// - Images: `![alt](src)` is converted to `<img src="src" alt="alt">`, a
+///   `=WIDTHxHEIGHT` suffix (`![alt](src =640x480)`) sets the image size.
+/// - Autolinks: `<https://example.com>` and `<me@example.com>` become links.
+/// - Escapes: a backslash before punctuation (`\*`) writes the character as is.
 ///
 /// # Arguments
 ///
@@ -197,161 +805,731 @@ impl fmt::Display for Token {
 ///
 /// ```rust
 /// let mut line = String::from("This is **bold**, *italic*, and [a link](https://example.com).");
-/// let html = markdown_parser::convert_inline_markdown(&mut line);
+/// let html = md2html::convert_inline_markdown(&mut line);
 /// assert_eq!(
 ///     html,
 ///     "This is <strong>bold</strong>, <i>italic</i>, and <a href=\"https://example.com\">a link</a>."
 /// );
+/// let mut line = String::from("Use `*ptr` and `a[0](b)`");
+/// assert_eq!(
+///     md2html::convert_inline_markdown(&mut line),
+///     "Use <code>*ptr</code> and <code>a[0](b)</code>"
+/// );
 /// ```
 ///
 /// # Notes
 ///
+/// - Escapes and code spans are found first and swapped for placeholders, so no
+///   other pass formats their content. They are put back at the very end.
 /// - The function processes bold syntax (`**text**`) before italic syntax (`*text*`),
 ///   as bold and italic can overlap in markdown.
 /// - Nested link syntax is supported, allowing for constructs like `[text [nested]](url)`.
 /// - The input string is modified during processing, but the returned string contains
 ///   the final HTML result.
+/// - Delimiters left unpaired, like a `*` without its closing one, stay literal text.
+pub fn convert_inline_markdown(line: &mut String) -> String {
+    convert_inline_markdown_with_options(line, &ParserOptions::default())
+}
+
+/// Converts inline markdown syntax to HTML, honoring `options`.
 ///
-/// # Debugging
+/// This is the configurable version of `convert_inline_markdown`.
+pub fn convert_inline_markdown_with_options(line: &mut String, options: &ParserOptions) -> String {
+    // Backslash escapes and code spans are swapped for placeholders until the very
+    // end, and autolinks, paths and URIs while emphasis is converted
+    let escapes = protect_escapes(line, options);
+    let code_spans = protect_code_spans(line);
+    let literal_spans = protect_literal_spans(line, options);
+
+    // Text is escaped before any tag is added, unless raw HTML is allowed
+    if !options.allow_raw_html {
+        *line = escape_text(line);
+    }
+
+    // Treating bold italic - NEEDS TO BE DONE BEFORE BOLD
+    *line = convert_bold_italic(line);
+
+    // Compiled once, these run for every line of inline text
+    static BOLD_RE: OnceLock<Regex> = OnceLock::new();
+    static ITALIC_RE: OnceLock<Regex> = OnceLock::new();
+    static IMAGE_RE: OnceLock<Regex> = OnceLock::new();
+    static LINK_RE: OnceLock<Regex> = OnceLock::new();
+    static LINK_TEXT_RE: OnceLock<Regex> = OnceLock::new();
+
+    // Treating bold syntax. Every pass rewrites the line in a single scan, so the
+    // work stays linear however many delimiters the line has
+    let re = BOLD_RE.get_or_init(|| Regex::new(r"\*\*(.+?)\*\*").unwrap());
+    let mut resulted_format = re.replace_all(line, "<strong>$1</strong>").to_string();
+
+    // Treating italic - NEEDS TO BE DONE AFTER BOLD
+    let re = ITALIC_RE.get_or_init(|| Regex::new(r"\*(.+?)\*").unwrap());
+    resulted_format = re.replace_all(&resulted_format, "<i>$1</i>").to_string();
+
+    // Treating __bold__ and _italic_, after the asterisk forms so both can nest
+    resulted_format = convert_underscore_emphasis(&resulted_format);
+
+    // Treating strikethrough - after bold and italic so it can be nested in them
+    resulted_format = convert_strikethrough(&resulted_format);
+    resulted_format = restore_spans(&resulted_format, PLACEHOLDER_START, &literal_spans);
+
+    // Treating IMAGES ![alt](src) - NEEDS TO BE DONE BEFORE LINKS
+    let re =
+        IMAGE_RE.get_or_init(|| Regex::new(r"!\[([^\[\]]*)\]\(((?:[^()]|\([^()]*\))*)\)").unwrap());
+    resulted_format = re
+        .replace_all(&resulted_format, |groups: &regex::Captures| {
+            render_image(&groups[1], &groups[2], options)
+        })
+        .to_string();
+
+    // Treating LINK text searches for []() pair allows nesting for the [[]]
+    let re = LINK_RE.get_or_init(|| {
+        Regex::new(r"\[[^\[\]]*(?:\[[^\[\]]*\][^\[\]]*)*\]\((?:[^()]|\([^()]*\))*\)").unwrap()
+    });
+    let text_part_re =
+        LINK_TEXT_RE.get_or_init(|| Regex::new(r"\[[^\[\]]*(?:\[[^\[\]]*\][^\[\]]*)*\]").unwrap());
+    resulted_format = re
+        .replace_all(&resulted_format, |groups: &regex::Captures| {
+            let found_substring = &groups[0]; // [Link Text](url)
+            let text_part_range = text_part_re.find(found_substring).unwrap();
+            let link_text = &found_substring[1..text_part_range.end() - 1]; // The title of the link
+                                                                            // Without the '('  ')'
+            let link_url = &found_substring[text_part_range.end() + 1..found_substring.len() - 1];
+            let (link_url, link_title) = split_link_title(link_url);
+            let title_attribute = link_title
+                .map(|title| format!(" title=\"{}\"", title))
+                .unwrap_or_default();
+            format!(
+                "<a href=\"{}\"{}>{}</a>",
+                escape_url(link_url, options),
+                title_attribute,
+                link_text
+            )
+        })
+        .to_string();
+
+    // Putting back the code spans and the escaped characters
+    let restored = restore_spans(
+        &escape_invalid_references(&resulted_format),
+        CODE_PLACEHOLDER_START,
+        &code_spans,
+    );
+    restore_spans(&restored, ESCAPE_PLACEHOLDER_START, &escapes)
+}
+
+/// Converts the spans opened or closed by a `***` run: `***text***` becomes
+/// `<strong><i>text</i></strong>`. When the run is only one side of the span
+/// (`***bold** italic*`, `*italic **bold***`), the delimiter closed first, or
+/// opened last, is the inner element.
 ///
-/// The function includes debug output for link processing. If a link pattern is detected,
-/// it prints a debug message to the console.
+/// Spans with other asterisks inside are left to the bold and italic passes.
+fn convert_bold_italic(line: &str) -> String {
+    // The text next to an opening or closing run can't be whitespace
+    const INNER: &str = r"([^*\s](?:[^*]*?[^*\s])?)";
+    const HEAD: &str = r"([^*\s][^*]*?)";
+    const TAIL: &str = r"([^*]*?[^*\s])";
+    static PATTERNS: OnceLock<Vec<(Regex, &str)>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            (
+                format!(r"\*\*\*{}\*\*\*", INNER),
+                "<strong><i>$1</i></strong>",
+            ),
+            (
+                format!(r"\*\*\*{}\*\*{}\*", INNER, TAIL),
+                "<i><strong>$1</strong>$2</i>",
+            ),
+            (
+                format!(r"\*\*\*{}\*{}\*\*", INNER, TAIL),
+                "<strong><i>$1</i>$2</strong>",
+            ),
+            (
+                format!(r"\*\*{}\*{}\*\*\*", HEAD, INNER),
+                "<strong>$1<i>$2</i></strong>",
+            ),
+            (
+                format!(r"\*{}\*\*{}\*\*\*", HEAD, INNER),
+                "<i>$1<strong>$2</strong></i>",
+            ),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(&pattern).unwrap(), replacement))
+        .collect()
+    });
+    let mut converted = line.to_string();
+    for (re, replacement) in patterns {
+        converted = re.replace_all(&converted, *replacement).to_string();
+    }
+    converted
+}
+
+/// Converts `~~text~~` into `<del>text</del>`.
 ///
-/// # Limitations
+/// Only runs of exactly two tildes are delimiters, so a single `~` and `~~~` (kept
+/// for tilde code fences) stay as they are. An opening `~~` that is never closed
+/// is literal text.
+fn convert_strikethrough(text: &str) -> String {
+    convert_delimiter_runs(text, '~', 2, "del", true)
+}
+
+/// Converts `__bold__` into `<strong>` and then `_italic_` into `<i>`, the
+/// underscore forms of `**` and `*`.
 ///
-/// - The function assumes valid markdown input and does not handle malformed markdown.
-/// - Inline code syntax (e.g., `` `code` ``) is not currently supported.
-pub fn convert_inline_markdown(line: &mut String) -> String {
-    // Treating bold syntax
-    let mut re = Regex::new(r"\*\*(.+?)\*\*").unwrap();
-    let mut resulted_format = String::new();
-    while re.is_match(line) {
-        // Find the indexes of the first match
-        let found_ind = re.find(line).unwrap();
-        let mut found_substring = (&line[found_ind.range()]).to_string(); // **bolded word**
-        let start_of_string = (&line[0..found_ind.start()]).to_string(); // Whatever is before it
+/// Underscores inside words (`snake_case_name`) are never delimiters.
+fn convert_underscore_emphasis(text: &str) -> String {
+    let bold = convert_delimiter_runs(text, '_', 2, "strong", false);
+    convert_delimiter_runs(&bold, '_', 1, "i", false)
+}
 
-        // Removes the starting ** and the ending **
-        found_substring.replace_range(0..2, "");
-        found_substring.replace_range(found_substring.len() - 2..found_substring.len(), "");
+/// Wraps the text between two runs of exactly `length` `delimiter` characters, `~`
+/// or `_`, in a `tag` element, pairing every opening run with the next closing one.
+///
+/// An opening run can't be followed by whitespace and a closing one can't follow
+/// whitespace. Unless `intraword` is set, an opening run can't follow a letter or
+/// a digit, and a closing one can't be followed by one. Runs left unpaired are
+/// literal text.
+fn convert_delimiter_runs(
+    text: &str,
+    delimiter: char,
+    length: usize,
+    tag: &str,
+    intraword: bool,
+) -> String {
+    // Every run is a single delimiter character repeated, so one pattern serves
+    // all of them
+    static RUNS_RE: OnceLock<Regex> = OnceLock::new();
+    let runs_re = RUNS_RE.get_or_init(|| Regex::new(r"~+|_+").unwrap());
+    let mut converted = String::with_capacity(text.len());
+    let mut last_end = 0;
+    let mut opening: Option<regex::Match> = None;
+    for run in runs_re.find_iter(text).filter(|run| {
+        run.as_str().starts_with(delimiter) && run.len() == length * delimiter.len_utf8()
+    }) {
+        let previous = text[..run.start()].chars().next_back();
+        let next = text[run.end()..].chars().next();
+        let in_word = |c: Option<char>| !intraword && c.is_some_and(char::is_alphanumeric);
+        match opening {
+            Some(open) if previous.is_some_and(|c| !c.is_whitespace()) && !in_word(next) => {
+                converted.push_str(&text[last_end..open.start()]);
+                converted.push_str(&format!("<{}>", tag));
+                converted.push_str(&text[open.end()..run.start()]);
+                converted.push_str(&format!("</{}>", tag));
+                last_end = run.end();
+                opening = None;
+            }
+            Some(_) => (),
+            None => {
+                if next.is_some_and(|c| !c.is_whitespace()) && !in_word(previous) {
+                    opening = Some(run);
+                }
+            }
+        }
+    }
+    converted.push_str(&text[last_end..]);
+    converted
+}
 
-        // Now found_substring contains only the bolded text so we can make it html
-        resulted_format = format!(
-            "{resulted_format}{}<strong>{}</strong>",
-            start_of_string, found_substring
-        );
+/// Mark the start of a placeholder of `protect_literal_spans`, of
+/// `protect_code_spans` and of `protect_escapes`, and the end of all of them, all
+/// private use characters that never appear in real text.
+const PLACEHOLDER_START: char = '\u{E000}';
+const CODE_PLACEHOLDER_START: char = '\u{E002}';
+const ESCAPE_PLACEHOLDER_START: char = '\u{E003}';
+const PLACEHOLDER_END: char = '\u{E001}';
 
-        // We then trim the start of the original line till what we found
-        let _ = &line.replace_range(..found_ind.end(), "");
+/// The placeholder of the `index`th protected span, starting with `start`. Its
+/// digits are private use characters too, so no inline pattern can match part of
+/// a placeholder.
+fn placeholder(start: char, index: usize) -> String {
+    let mut placeholder = String::from(start);
+    for digit in index.to_string().chars() {
+        placeholder.push(char::from_u32(0xE010 + digit.to_digit(10).unwrap()).unwrap());
     }
-    resulted_format = resulted_format + line;
+    placeholder.push(PLACEHOLDER_END);
+    placeholder
+}
 
-    // Treating italic - NEEDS TO BE DONE AFTER BOLD
-    re = Regex::new(r"\*(.+?)\*").unwrap();
-    let mut line_copy = resulted_format.clone();
-    resulted_format = String::new();
-    while re.is_match(&line_copy) {
-        // Find the indexes of the first match
-        let found_ind = re.find(&line_copy).unwrap();
-        let mut found_substring = (&line_copy[found_ind.range()]).to_string(); // *italic word*
-        let start_of_string = (&line_copy[0..found_ind.start()]).to_string(); // Whatever is before it
+/// Replaces the backslash escapes of `line` (`\*`, `\[`, `\\`...) with placeholders,
+/// returning the character every placeholder stands for, escaped for HTML, in
+/// order. Any ASCII punctuation can be escaped, a backslash before anything else
+/// is a literal backslash.
+///
+/// Code spans are skipped, backslashes are literal inside them, but an escaped
+/// backtick doesn't open one. With `options.literal_paths`, paths and URIs are
+/// skipped too so `C:\Users\*.md` stays as typed.
+fn protect_escapes(line: &mut String, options: &ParserOptions) -> Vec<String> {
+    let literal_paths: Vec<(usize, usize)> = match options.literal_paths {
+        true => literal_path_re()
+            .find_iter(line)
+            .map(|found| (found.start(), found.end()))
+            .collect(),
+        false => Vec::new(),
+    };
+    let mut literal_paths = literal_paths.into_iter().peekable();
+    let mut escapes = Vec::new();
+    let mut protected = String::with_capacity(line.len());
+    let mut rest = line.as_str();
+    while let Some(c) = rest.chars().next() {
+        let offset = line.len() - rest.len();
+        // Paths are in order, the ones already passed can't start here
+        while literal_paths
+            .next_if(|(start, _)| *start < offset)
+            .is_some()
+        {}
+        if let Some((_, end)) = literal_paths.next_if(|(start, _)| *start == offset) {
+            protected.push_str(&line[offset..end]);
+            rest = &line[end..];
+            continue;
+        }
+        if c == '`' {
+            let run = rest.len() - rest.trim_start_matches('`').len();
+            let len = match find_code_span(rest) {
+                Some((0, _, _, span_end)) => span_end,
+                _ => run,
+            };
+            protected.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        let escaped = rest[c.len_utf8()..]
+            .chars()
+            .next()
+            .filter(|c| c.is_ascii_punctuation());
+        match (c, escaped) {
+            ('\\', Some(escaped)) => {
+                protected.push_str(&placeholder(ESCAPE_PLACEHOLDER_START, escapes.len()));
+                escapes.push(match escaped {
+                    // A literal backslash at the end of a line must not read as a hard
+                    // break later on
+                    '\\' => String::from("&#92;"),
+                    '"' => String::from("&quot;"),
+                    escaped => escape::escape_text(&escaped.to_string()).into_owned(),
+                });
+                rest = &rest[2..];
+            }
+            (c, _) => {
+                protected.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    *line = protected;
+    escapes
+}
 
-        // Removes the starting * and the ending *
-        found_substring.remove(0);
-        found_substring.remove(found_substring.len() - 1);
+/// Replaces the code spans of `line` with placeholders, returning the `<code>`
+/// element every placeholder stands for, in order. Their content is escaped and
+/// never formatted.
+fn protect_code_spans(line: &mut String) -> Vec<String> {
+    let mut spans = Vec::new();
+    let mut protected = String::with_capacity(line.len());
+    let mut rest = line.as_str();
+    while let Some((span_start, code_start, code_end, span_end)) = find_code_span(rest) {
+        protected.push_str(&rest[..span_start]);
+        protected.push_str(&placeholder(CODE_PLACEHOLDER_START, spans.len()));
+        spans.push(format!(
+            "<code>{}</code>",
+            escape::escape_text(strip_code_span_spaces(&rest[code_start..code_end]))
+        ));
+        rest = &rest[span_end..];
+    }
+    protected.push_str(rest);
+    *line = protected;
+    spans
+}
 
-        // Now found_substring contains only the italic text so we can make it html
-        resulted_format = format!(
-            "{resulted_format}{}<i>{}</i>",
-            start_of_string, found_substring
-        );
+/// Replaces runs of `line` that the emphasis passes must not touch with
+/// placeholders, returning what every placeholder stands for, in order.
+///
+/// - Autolinks (`<https://example.com>`, `<user@example.com>`) become anchors.
+/// - With `options.autolink_bare_urls`, bare `http(s)://` and `www.` URLs become
+///   anchors too, without their trailing punctuation.
+/// - With `options.literal_paths`, filesystem paths and `scheme://` URIs are kept
+///   as typed. Trailing `*` and punctuation are left out of the run, so emphasis
+///   around a path (`*see src/**/*.rs*`) still closes.
+fn protect_literal_spans(line: &mut String, options: &ParserOptions) -> Vec<String> {
+    let mut spans = Vec::new();
 
-        // We then trim the start of the original line till what we found
-        let _ = &line_copy.replace_range(..found_ind.end(), "");
+    static AUTOLINK_RE: OnceLock<Regex> = OnceLock::new();
+    static BARE_URL_RE: OnceLock<Regex> = OnceLock::new();
+    let autolink_re = AUTOLINK_RE.get_or_init(|| {
+        Regex::new(
+        r"<(?:([A-Za-z][A-Za-z0-9+.-]{1,31}:[^\s<>]*)|([A-Za-z0-9.!#$%&'*+/=?^_`{|}~-]+@[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?(?:\.[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?)*))>",
+    )
+    .unwrap()
+    });
+    protect_matches(line, &mut spans, autolink_re, |groups| {
+        let (url, text) = match (groups.get(1), groups.get(2)) {
+            (Some(uri), _) => (uri.as_str().to_string(), uri.as_str()),
+            (None, Some(email)) => (format!("mailto:{}", email.as_str()), email.as_str()),
+            (None, None) => unreachable!(),
+        };
+        Some((groups.get(0).unwrap().range(), anchor(&url, text)))
+    });
+
+    if options.autolink_bare_urls {
+        let bare_url_re = BARE_URL_RE
+            .get_or_init(|| Regex::new(r"(?:^|[\s*_~(])((?:https?://|www\.)[^\s<]*)").unwrap());
+        let text = line.clone();
+        protect_matches(line, &mut spans, bare_url_re, |groups| {
+            let found = groups.get(1).unwrap();
+            // The destination of an inline link is not a bare URL
+            if text[..found.start()].ends_with("](") {
+                return None;
+            }
+            let url = trim_bare_url(found.as_str());
+            if url.ends_with("://") || url == "www." {
+                return None;
+            }
+            let href = match url.starts_with("www.") {
+                true => format!("http://{}", url),
+                false => url.to_string(),
+            };
+            Some((found.start()..found.start() + url.len(), anchor(&href, url)))
+        });
     }
-    resulted_format = resulted_format + &line_copy;
 
-    // Treating LINK text searches for []() pair allows nesting for the [[]]
-    re = Regex::new(r"\[[^\[\]]*(?:\[[^\[\]]*\][^\[\]]*)*\]\([^()]*\)").unwrap();
-    let mut line_copy = resulted_format.clone();
-    resulted_format = String::new();
-
-    while re.is_match(&line_copy) {
-        //println!("[debug] Found a link pattern in line:\n{}\n", line_copy);
-        // Find the indexes of the first match
-        let found_ind = re.find(&line_copy).unwrap();
-        let found_substring = (&line_copy[found_ind.range()]).to_string(); // [Link Text](url)
-        let start_of_string = (&line_copy[0..found_ind.start()]).to_string(); // Whatever is before it
-
-        let text_part_re = Regex::new(r"\[[^\[\]]*(?:\[[^\[\]]*\][^\[\]]*)*\]").unwrap();
-        let text_part_range = text_part_re.find(&found_substring).unwrap();
-        let link_text = (&found_substring[1..text_part_range.end() - 1]).to_string(); // The title of the link
-        let mut link_url =
-            (&found_substring[text_part_range.end()..found_substring.len()]).to_string(); // Still contains the '('  ')'
-
-        link_url.remove(0);
-        link_url.remove(link_url.len() - 1);
-
-        // Now found_substring contains only the italic text so we can make it html
-        resulted_format = format!(
-            "{resulted_format}{}<a href=\"{}\">{}</a>",
-            start_of_string, link_url, link_text
-        );
-
-        // We then trim the start of the original line till what we found
-        let _ = &line_copy.replace_range(..found_ind.end(), "");
-    }
-    resulted_format = resulted_format + &line_copy;
-
-    // Finding inlined code
-    re = Regex::new(r"(`+)([^`]*)(`+)").unwrap(); // Split into 3 capture groups
-    let mut line_copy = resulted_format.clone();
-    let mut resulted_format = String::new();
-    if re.is_match(&line_copy) {
-        let found_ind = re.find(&line_copy).unwrap();
-        let start_of_string = (&line_copy[0..found_ind.start()]).to_string(); // Whatever is before it
-
-        // We use capture groups to find all the parts
-        let Some(groups) = re.captures(&line_copy) else {
-            return line_copy;
-        };
-        let first_backticks = groups[1].to_string();
-        let mut inline_code = groups[2].to_string();
-        let end_backticks = groups[3].to_string();
-
-        if inline_code.len() > 0 {
-            let min_num_backticks = min(first_backticks.len(), end_backticks.len());
-            if min_num_backticks == first_backticks.len()
-                && min_num_backticks != end_backticks.len()
-            {
-                inline_code = format!(
-                    "{}{}",
-                    inline_code,
-                    end_backticks[0..end_backticks.len() - min_num_backticks].to_string()
-                );
-            } else if min_num_backticks == end_backticks.len()
-                && min_num_backticks != first_backticks.len()
-            {
-                inline_code = format!(
-                    "{}{}",
-                    first_backticks[0..first_backticks.len() - min_num_backticks].to_string(),
-                    inline_code
-                );
+    if options.literal_paths {
+        protect_matches(line, &mut spans, literal_path_re(), |groups| {
+            let found = groups.get(0).unwrap();
+            let span = found
+                .as_str()
+                .trim_end_matches(['*', '.', ',', ';', ':', '!', '?']);
+            (!span.is_empty())
+                .then(|| (found.start()..found.start() + span.len(), span.to_string()))
+        });
+    }
+    spans
+}
+
+/// Matches the filesystem paths and `scheme://` URIs kept as typed with
+/// `ParserOptions::literal_paths`.
+fn literal_path_re() -> &'static Regex {
+    static LITERAL_PATH_RE: OnceLock<Regex> = OnceLock::new();
+    LITERAL_PATH_RE.get_or_init(|| {
+        Regex::new(
+            r#"(?:[A-Za-z][A-Za-z0-9+.-]*://|\b[A-Za-z]:\\)[^\s<>()\[\]"']*|[\w.~-]*(?:/[\w.*?~-]+)+"#,
+        )
+        .unwrap()
+    })
+}
+
+/// Replaces the runs of `line` picked by `pick` with placeholders, appending what
+/// each one stands for to `spans`.
+///
+/// `pick` gets every match of `re` and returns the byte range of `line` to replace
+/// along with its replacement, or `None` to leave the match alone.
+fn protect_matches(
+    line: &mut String,
+    spans: &mut Vec<String>,
+    re: &Regex,
+    mut pick: impl FnMut(&regex::Captures) -> Option<(std::ops::Range<usize>, String)>,
+) {
+    let mut protected = String::with_capacity(line.len());
+    let mut last_end = 0;
+    for groups in re.captures_iter(line) {
+        let Some((range, replacement)) = pick(&groups) else {
+            continue;
+        };
+        protected.push_str(&line[last_end..range.start]);
+        protected.push_str(&placeholder(PLACEHOLDER_START, spans.len()));
+        spans.push(replacement);
+        last_end = range.end;
+    }
+    protected.push_str(&line[last_end..]);
+    *line = protected;
+}
+
+/// Drops the trailing punctuation of a bare URL, GFM style: `?!.,:;*_~` and quotes,
+/// and a closing parenthesis that doesn't close one opened inside the URL.
+fn trim_bare_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let mut trimmed =
+            url.trim_end_matches(['?', '!', '.', ',', ':', ';', '*', '_', '~', '\'', '"']);
+        if trimmed.ends_with(')') && trimmed.matches(')').count() > trimmed.matches('(').count() {
+            trimmed = &trimmed[..trimmed.len() - 1];
+        }
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+/// Renders a link to `url` whose text is the URL itself.
+fn anchor(url: &str, text: &str) -> String {
+    format!(
+        "<a href=\"{}\">{}</a>",
+        escape_attribute(url),
+        escape_attribute(text)
+    )
+}
+
+/// Puts back the runs replaced by `protect_literal_spans`, `protect_code_spans` or
+/// `protect_escapes`, whose placeholders start with `start`, in a single scan of
+/// `text`.
+fn restore_spans(text: &str, start: char, spans: &[String]) -> String {
+    let mut restored = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(position) = rest.find(start) {
+        restored.push_str(&rest[..position]);
+        rest = &rest[position + start.len_utf8()..];
+        let digits: String = rest
+            .chars()
+            .map_while(|c| (c as u32).checked_sub(0xE010).filter(|digit| *digit < 10))
+            .map(|digit| char::from_digit(digit, 10).unwrap())
+            .collect();
+        let after_digits = &rest[digits.len() * '\u{E010}'.len_utf8()..];
+        let span = digits
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| spans.get(index));
+        match (span, after_digits.strip_prefix(PLACEHOLDER_END)) {
+            (Some(span), Some(after)) if !digits.is_empty() => {
+                restored.push_str(span);
+                rest = after;
             }
+            _ => restored.push(start),
+        }
+    }
+    restored.push_str(rest);
+    restored
+}
+
+/// Splits a link or image destination into its URL and its optional title,
+/// written after the URL in double quotes, single quotes or parentheses
+/// (`https://x.com "My Title"`).
+///
+/// A backslash escapes a quote inside the title. The returned title is escaped
+/// for an attribute.
+fn split_link_title(destination: &str) -> (&str, Option<String>) {
+    // Compiled once, this runs for every link and image
+    static TITLE_RE: OnceLock<Regex> = OnceLock::new();
+    static UNESCAPE_RE: OnceLock<Regex> = OnceLock::new();
+    let title_re = TITLE_RE.get_or_init(|| {
+        Regex::new(
+            r#"^(.*?)\s+(?:"((?:[^"\\]|\\.)*)"|'((?:[^'\\]|\\.)*)'|\(((?:[^()\\]|\\.)*)\))$"#,
+        )
+        .unwrap()
+    });
+    let destination = destination.trim();
+    match title_re.captures(destination) {
+        Some(groups) if !groups[1].is_empty() => {
+            let title = (2..=4)
+                .find_map(|index| groups.get(index))
+                .unwrap()
+                .as_str();
+            let unescaped = UNESCAPE_RE
+                .get_or_init(|| Regex::new(r#"\\(["'()\\])"#).unwrap())
+                .replace_all(title, "$1");
+            (
+                groups.get(1).unwrap().as_str(),
+                Some(escape_attribute(&unescaped)),
+            )
+        }
+        _ => (destination, None),
+    }
+}
+
+/// Renders an image as an `<img>` tag.
+///
+/// The alt text is the plain text of `alt` (its emphasis is dropped), escaped
+/// for the attribute, so an empty `alt` still gives `alt=""`.
+///
+/// The destination may have a title (see `split_link_title`) and end with a `=WIDTHxHEIGHT` suffix (`img.png =640x480`,
+/// `img.png =640x` or `img.png =x480`) which becomes the `width`/`height` attributes.
+/// A malformed suffix is kept as part of the URL.
+///
+/// With `options.media_embeds`, audio and video files are rendered as `<audio>` and
+/// `<video>` elements instead, with the alt text as their fallback content.
+fn render_image(alt: &str, destination: &str, options: &ParserOptions) -> String {
+    let (destination, title) = split_link_title(destination);
+    let (src, width, height) = split_image_dimensions(destination);
+    let media_element = if options.media_embeds {
+        media_element(src)
+    } else {
+        None
+    };
+    let src = escape_url(src, options);
+    let mut html = match media_element {
+        Some(element) => format!("<{} controls src=\"{}\"", element, src),
+        None => {
+            let alt_text = inline::to_plain_text(&inline::from_html(alt));
+            format!(
+                "<img src=\"{}\" alt=\"{}\"",
+                src,
+                escape_attribute(&alt_text)
+            )
+        }
+    };
+    if let Some(title) = title {
+        html += &format!(" title=\"{}\"", title);
+    }
+    if let Some(width) = width {
+        html += &format!(" width=\"{}\"", width);
+    }
+    if let Some(height) = height {
+        html += &format!(" height=\"{}\"", height);
+    }
+    match media_element {
+        Some(element) => format!("{}>{}</{}>", html, alt, element),
+        None => html + ">",
+    }
+}
+
+/// Escapes the URL of a link or image for its attribute.
+///
+/// Unless raw HTML is allowed, the line was already escaped for text, so its `&`,
+/// `<` and `>` are decoded first and only escaped once.
+fn escape_url(url: &str, options: &ParserOptions) -> String {
+    match options.allow_raw_html {
+        true => escape::escape_attr(url).into_owned(),
+        false => {
+            let decoded = url
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&");
+            escape::escape_attr(&decoded).into_owned()
+        }
+    }
+}
 
-            // Now found_substring contains only the italic text so we can make it html
-            resulted_format = format!(
-                "{resulted_format}{}<code>{}</code>",
-                start_of_string, inline_code
+/// Returns the media element (`video` or `audio`) matching the file extension of `src`.
+fn media_element(src: &str) -> Option<&'static str> {
+    // Ignore the query string and fragment of the URL
+    let path = src.split(['?', '#']).next().unwrap_or(src);
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "mp4" | "webm" | "mov" => Some("video"),
+        "mp3" | "ogg" | "wav" | "m4a" => Some("audio"),
+        _ => None,
+    }
+}
+
+/// Splits the `=WIDTHxHEIGHT` suffix off an image destination.
+fn split_image_dimensions(destination: &str) -> (&str, Option<&str>, Option<&str>) {
+    static DIMENSIONS_RE: OnceLock<Regex> = OnceLock::new();
+    let dimensions_re =
+        DIMENSIONS_RE.get_or_init(|| Regex::new(r"^(.*?)\s+=(\d*)x(\d*)$").unwrap());
+    if let Some(groups) = dimensions_re.captures(destination) {
+        let (width, height) = (groups.get(2).unwrap(), groups.get(3).unwrap());
+        if !width.is_empty() || !height.is_empty() {
+            let non_empty = |m: regex::Match<'_>| (!m.is_empty()).then(|| &destination[m.range()]);
+            return (
+                &destination[groups.get(1).unwrap().range()],
+                non_empty(width),
+                non_empty(height),
             );
+        }
+    }
+    (destination, None, None)
+}
+
+/// Strips one space on both sides of the content of a code span when it has one on
+/// both sides, so `` `` `a` `` `` shows `` `a` ``. Content made of spaces only is
+/// kept as it is.
+fn strip_code_span_spaces(code: &str) -> &str {
+    match code
+        .strip_prefix(' ')
+        .and_then(|code| code.strip_suffix(' '))
+    {
+        Some(stripped) if !code.trim_matches(' ').is_empty() => stripped,
+        _ => code,
+    }
+}
+
+/// Finds the first inline code span in `text`.
+///
+/// Like CommonMark backtick strings, a span opens on a run of backticks and closes
+/// on the next run of *exactly* the same length, so ``` ``a `b` c`` ``` keeps the
+/// single backticks as content. A run that no later run of its length closes is
+/// literal text, e.g. the lone backtick of "press the ` key".
+///
+/// The runs are paired in a single pass from the end of the text, so a line full
+/// of unmatched runs stays linear.
+///
+/// # Returns
+///
+/// `(span_start, code_start, code_end, span_end)` byte offsets, or `None` when no
+/// opening run has a matching closing run.
+pub(crate) fn find_code_span(text: &str) -> Option<(usize, usize, usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let length = bytes[i..].iter().take_while(|b| **b == b'`').count();
+        if length > 0 {
+            runs.push((i, length));
+        }
+        i += length.max(1);
+    }
 
-            // We then trim the start of the original line till what we found
-            let _ = &line_copy.replace_range(..found_ind.end(), "");
+    // The start of the nearest run of every length after the current one
+    let mut next_run: HashMap<usize, usize> = HashMap::new();
+    let mut span = None;
+    for &(start, length) in runs.iter().rev() {
+        if let Some(&close) = next_run.get(&length) {
+            span = Some((start, start + length, close, close + length));
         }
+        next_run.insert(length, start);
+    }
+    span
+}
+
+/// Splits an ATX heading line into its level and the text after its `#`, `None`
+/// when `line` isn't one.
+///
+/// The `#` must be followed by a space or a tab, or end the line: a line of only
+/// `#` (`#`, `##`, `#\t`) is an empty heading. Like CommonMark, seven or more `#`
+/// are just text.
+fn atx_heading(line: &str) -> Option<(u8, &str)> {
+    let text = line.trim_start_matches('#');
+    let level = line.len() - text.len();
+    let heading = (1..=6).contains(&level) && (text.is_empty() || text.starts_with([' ', '\t']));
+    heading.then_some((level as u8, text))
+}
+
+/// Removes the optional closing sequence of an ATX heading: the trailing `#` of
+/// `# Title #`, kept when they aren't preceded by a space (`# C#`).
+fn strip_closing_hashes(text: &str) -> &str {
+    let trimmed = text.trim_end();
+    let without_hashes = trimmed.trim_end_matches('#');
+    if without_hashes.len() == trimmed.len() {
+        return text;
+    }
+    if without_hashes.is_empty() || without_hashes.ends_with([' ', '\t']) {
+        without_hashes
+    } else {
+        text
     }
-    resulted_format = resulted_format + &line_copy;
+}
+
+/// Trims heading text and collapses its runs of whitespace to single spaces.
+///
+/// Code spans are kept as they are, their spacing is part of the code, and so are
+/// no-break spaces, which the author meant to keep.
+fn normalize_heading_text(text: &str) -> String {
+    let collapse_whitespace = |text: &str| -> String {
+        let mut collapsed = String::with_capacity(text.len());
+        for c in text.chars() {
+            if !c.is_whitespace() || c == '\u{A0}' {
+                collapsed.push(c);
+            } else if !collapsed.ends_with(' ') {
+                collapsed.push(' ');
+            }
+        }
+        collapsed
+    };
 
-    return resulted_format;
+    let mut normalized = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((span_start, _, _, span_end)) = find_code_span(rest) {
+        normalized += &collapse_whitespace(&rest[..span_start]);
+        normalized += &rest[span_start..span_end];
+        rest = &rest[span_end..];
+    }
+    normalized += &collapse_whitespace(rest);
+    normalized.trim().to_string()
 }
 
 /// Tokenizes a collection of markdown lines into HTML tokens.
@@ -380,11 +1558,13 @@ pub fn convert_inline_markdown(line: &mut String) -> String {
 ///     String::from("Code block content"),
 ///     String::from("```"),
 /// ];
-/// let html_tokens = markdown_parser::tokenize_text(markdown_lines.into_iter());
+/// let html_tokens = md2html::tokenize_text(markdown_lines.into_iter());
 /// assert_eq!(html_tokens, vec![
 ///     "<h1>Header</h1>",
 ///     "<p>This is a paragraph.</p>",
+///     "<ul>",
 ///     "<li>List item</li>",
+///     "</ul>",
 ///     "<pre><code>",
 ///     "Code block content",
 ///     "</code></pre>",
@@ -393,6 +1573,7 @@ pub fn convert_inline_markdown(line: &mut String) -> String {
 ///
 /// # Notes
 ///
+/// - The function is a thin wrapper composing `parse` and `render_html`.
 /// - The function uses `tokenize_line` to process each line individually.
 /// - Debugging information is printed to the console for tokenized lines.
 /// - Multi-line constructs such as ordered lists and code blocks are handled
@@ -409,200 +1590,1543 @@ pub fn convert_inline_markdown(line: &mut String) -> String {
 /// - Inline markdown elements (e.g., bold, italic, links) are processed by the
 ///   `convert_inline_markdown` function.
 pub fn tokenize_text(str_iter: impl Iterator<Item = String>) -> Vec<String> {
-    let mut string_result: Vec<String> = Vec::new();
-    let input_text: Vec<String> = str_iter.collect();
-
-    let mut token_list: Vec<Token> = Vec::new();
-    let mut token_list_processed = Vec::new();
-
-    // let mut debug_print_str: String = "[ ".to_string();
-    for line in &input_text {
-        let token = tokenize_line(line.clone()).unwrap_or(Token::BreakLine {});
-
-        // Debug printing
-        // debug_print_str += &format!("{:?},\t", token);
-
-        token_list.push(token);
-    }
-    // debug_print_str += " ]\n";
-    // let debugRe = Regex::new(r"\{[^{}]*\}").unwrap();
-    // println!("[DEBUG] {}", debugRe.replace_all(&debug_print_str, ""));
+    render_html(&parse(str_iter))
+}
+
+/// Tokenizes a collection of markdown lines into HTML tokens, honoring `options`.
+///
+/// This is the configurable version of `tokenize_text`, it also returns the warnings
+/// raised while parsing (e.g. lines that exceeded a safety limit).
+///
+/// # Example
+///
+/// ```rust
+/// let options = md2html::ParserOptions {
+///     max_line_length: 10,
+///     ..Default::default()
+/// };
+/// let lines = vec![String::from("A line that is **way** too long")];
+/// let result = md2html::tokenize_text_with_options(lines.into_iter(), &options);
+/// assert_eq!(result.html_lines, vec!["<p>A line that is **way** too long</p>"]);
+/// assert_eq!(result.warnings.len(), 1);
+/// ```
+pub fn tokenize_text_with_options(
+    str_iter: impl Iterator<Item = String>,
+    options: &ParserOptions,
+) -> ParseResult {
+    tokenize_text_with_rewriters(str_iter, options, &[])
+}
+
+/// Converts markdown lines into HTML lines like `tokenize_text_with_options`, the
+/// URLs of links and images going through `rewriters` before endnotes are made.
+pub(crate) fn tokenize_text_with_rewriters(
+    str_iter: impl Iterator<Item = String>,
+    options: &ParserOptions,
+    rewriters: &[UrlRewriter],
+) -> ParseResult {
+    let source: Vec<String> = str_iter.collect();
+    let mut document = parse_with_options(source.iter().cloned(), options);
+    document.tokens = urls::rewrite_urls(document.tokens, rewriters);
+    if options.link_endnotes {
+        document.tokens = endnotes::links_to_endnotes(document.tokens);
+    }
+    // The table of contents and the heading anchors link to the ids of the sections
+    let toc = options.toc || toc::has_marker(&document.tokens);
+    if options.sections || toc || options.heading_anchors {
+        document.tokens = sections::wrap_sections_with_options(document.tokens, options);
+    }
+    if toc {
+        document.tokens = toc::insert_toc(document.tokens, options.toc_depth);
+    }
+    if options.heading_anchors {
+        document.tokens = sections::add_heading_anchors(document.tokens, &options.anchor_symbol);
+    }
+    let mut html_lines = render_html(&document.tokens);
+    if let Some(width) = options.wrap_width {
+        html_lines = wrap::wrap_html(&html_lines, width);
+    }
+    if options.verify_code_blocks {
+        document.warnings.extend(verify::check_code_blocks(
+            &source,
+            &document.code_blocks,
+            &html_lines,
+        ));
+    }
+    ParseResult {
+        html_lines,
+        warnings: document.warnings,
+        front_matter: document.front_matter,
+    }
+}
+
+/// Parses a collection of markdown lines into tokens.
+///
+/// The returned tokens already include the multi-line grouping (e.g. `OLStart` and
+/// `OLEnd` around ordered list items, `CodeBlockStart` and `CodeBlockEnd` around the
+/// raw lines of a code block), so they can be rendered one by one with `render_html`.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::inline::Inline;
+/// use md2html::Token;
+///
+/// let tokens = md2html::parse(vec![String::from("1. item")].into_iter());
+/// assert_eq!(
+///     tokens,
+///     vec![
+///         Token::OLStart { start: 1 },
+///         Token::OListItem {
+///             depth: 0,
+///             number: 1,
+///             text: vec![Inline::Text(String::from("item"))],
+///         },
+///         Token::OLEnd {},
+///     ]
+/// );
+/// ```
+pub fn parse(str_iter: impl Iterator<Item = String>) -> Vec<Token> {
+    parse_with_options(str_iter, &ParserOptions::default()).tokens
+}
+
+/// Renders parsed tokens as HTML, one line per token.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::inline::Inline;
+/// use md2html::Token;
+///
+/// let tokens = vec![Token::Header { level: 1, text: vec![Inline::Text(String::from("Title"))] }];
+/// assert_eq!(md2html::render_html(&tokens), vec!["<h1>Title</h1>"]);
+/// ```
+pub fn render_html(tokens: &[Token]) -> Vec<String> {
+    tokens.iter().map(|token| token.to_string()).collect()
+}
+
+/// Parses a collection of markdown lines into tokens, honoring `options`.
+///
+/// This is the configurable version of `parse`, it also returns the warnings raised
+/// while parsing and the front matter block of the document, which isn't rendered
+/// (see `front_matter::split_front_matter`).
+pub fn parse_with_options(
+    str_iter: impl Iterator<Item = String>,
+    options: &ParserOptions,
+) -> ParsedDocument {
+    let mut warnings: Vec<ParseWarning> = Vec::new();
+    let mut input_text: Vec<String> = str_iter.collect();
+    // A malformed front matter block is left out of the output, but not read
+    let mut front_matter_warning = None;
+    let (front_matter, front_matter_lines) = match front_matter::parse_front_matter(&input_text) {
+        Some(Ok((front_matter, line_count))) => (Some(front_matter), line_count),
+        Some(Err(malformed)) => {
+            front_matter_warning = Some(ParseWarning {
+                line: malformed.line,
+                span: None,
+                kind: WarningKind::FrontMatter,
+                message: String::from(
+                    "front matter line isn't a `key: value` pair, the block is skipped",
+                ),
+            });
+            (None, malformed.line_count)
+        }
+        None => {
+            if front_matter::is_unclosed(&input_text) {
+                front_matter_warning = Some(ParseWarning {
+                    line: 1,
+                    span: None,
+                    kind: WarningKind::FrontMatter,
+                    message: String::from(
+                        "front matter block is never closed by a `---` line, it's rendered as text",
+                    ),
+                });
+            }
+            (None, 0)
+        }
+    };
+    input_text.drain(..front_matter_lines);
+    // Zero-width spaces pasted along with the text are invisible, but split words
+    // and markers apart. Code is left as typed.
+    let mut zero_width: Option<(usize, usize)> = None;
+    let mut zero_width_fences = FenceTracker::default();
+    for (i, line) in input_text.iter_mut().enumerate() {
+        if options.code_fences && zero_width_fences.is_code(line) {
+            continue;
+        }
+        let (stripped, removed) = unicode::strip_zero_width(line);
+        if removed > 0 {
+            *line = stripped.into_owned();
+            zero_width.get_or_insert((i + 1, 0)).1 += removed;
+        }
+    }
+    if let Some((line, count)) = zero_width {
+        warnings.push(ParseWarning {
+            line,
+            span: None,
+            kind: WarningKind::ZeroWidth,
+            message: format!(
+                "{} zero-width character{} removed, the first on this line",
+                count,
+                if count == 1 { "" } else { "s" }
+            ),
+        });
+    }
+    // Reference definitions are collected first, references can come before them
+    let collected_definitions = references::collect_definitions(&input_text);
+    let link_definitions = &collected_definitions.definitions;
+    let is_definition = &collected_definitions.is_definition;
+    let mut used_labels = HashSet::new();
+    let footnotes = footnotes::collect_footnotes(&input_text);
+    let regions = regions::classify_lines(
+        &input_text,
+        &options.strip_regions,
+        &options.keep_regions,
+        options.code_fences,
+    );
+    warnings.extend(regions.problems.into_iter().map(|problem| ParseWarning {
+        line: problem.line,
+        span: None,
+        kind: WarningKind::Region,
+        message: problem.message,
+    }));
+
+    let mut token_list: Vec<Token> = Vec::new();
+    let mut token_list_processed = Vec::new();
+
+    let mut fences = FenceTracker::default();
+    // The column alignments of the table being parsed, if any
+    let mut table_alignments: Option<Vec<tables::Alignment>> = None;
+    // Whether the lines above belong to a list, its indented lines are then item
+    // continuations rather than code
+    let mut in_list = false;
+    // The lines of the `:::` containers open with the token closing them,
+    // innermost last
+    let mut open_containers: Vec<(usize, Token)> = Vec::new();
+    for (i, line) in input_text.iter().enumerate() {
+        // Regions to strip or keep are never parsed, like the content of a fence
+        match regions.lines[i] {
+            regions::RegionLine::Markdown => (),
+            regions::RegionLine::Stripped => {
+                token_list.push(Token::None {});
+                table_alignments = None;
+                continue;
+            }
+            regions::RegionLine::Kept => {
+                token_list.push(Token::RawHtml { html: line.clone() });
+                table_alignments = None;
+                continue;
+            }
+        }
+        // Footnote definitions, continuation lines included, are listed at the end
+        if footnotes.is_definition[i] {
+            token_list.push(Token::None {});
+            table_alignments = None;
+            continue;
+        }
+        // No-break spaces pasted from word processors count as spaces around block
+        // markers, the ones in the text are kept
+        let spaced_line: String;
+        let line = match unicode::block_spaces(line) {
+            Cow::Owned(spaced) => {
+                spaced_line = spaced;
+                &spaced_line
+            }
+            Cow::Borrowed(_) => line,
+        };
+        let inside_fence = options.code_fences && fences.is_code(line);
+        let blank = line.trim().is_empty();
+        if !inside_fence && !blank && strip_code_indent(line).is_none() {
+            in_list = false;
+        }
+
+        // Lines indented by four spaces or a tab after a blank line are code, kept
+        // escaped as they are. Blank lines between them belong to the code block.
+        let previous_code = matches!(token_list.last(), Some(Token::SimpleText { .. }));
+        let indented_code = !inside_fence
+            && !in_list
+            && match strip_code_indent(line) {
+                Some(code) if !code.trim().is_empty() => {
+                    previous_code || matches!(token_list.last(), None | Some(Token::BreakLine {}))
+                }
+                _ => {
+                    previous_code
+                        && blank
+                        && input_text[i + 1..]
+                            .iter()
+                            .find(|next| !next.trim().is_empty())
+                            .is_some_and(|next| strip_code_indent(next).is_some())
+                }
+            };
+        if indented_code {
+            let code = strip_code_indent(line).unwrap_or_default();
+            token_list.push(Token::SimpleText {
+                text: escape::escape_text(code).into_owned(),
+            });
+            table_alignments = None;
+            continue;
+        }
+        let normalized_line: String;
+        let line = match options.normalize_unicode && !inside_fence {
+            true => {
+                normalized_line = unicode::nfc(line);
+                &normalized_line
+            }
+            false => line,
+        };
+
+        // `::: name` opens a container and `:::details Summary` a collapsible
+        // section, a bare `:::` closes the innermost one open
+        if let Some(summary) = details_fence(line).filter(|_| !inside_fence) {
+            let summary = convert_inline_markdown_with_options(&mut summary.to_string(), options);
+            open_containers.push((i, Token::DetailsEnd {}));
+            token_list.push(Token::DetailsStart {
+                summary: inline::from_html(&summary),
+            });
+            table_alignments = None;
+            continue;
+        }
+        if let Some(class) = container_fence(line).filter(|_| !inside_fence) {
+            let closed = match class {
+                None => open_containers.pop(),
+                Some(_) => None,
+            };
+            if let Some((_, end)) = closed {
+                token_list.push(end);
+            } else {
+                open_containers.push((i, Token::ContainerEnd {}));
+                token_list.push(Token::ContainerStart { class });
+            }
+            table_alignments = None;
+            continue;
+        }
+
+        // A line of `=` or `-` under paragraph text turns that text into a heading,
+        // without text above it `---` stays a horizontal rule
+        if let Some(level) = setext_level(line).filter(|_| !inside_fence && options.headings) {
+            let first = token_list
+                .iter()
+                .rposition(|token| !matches!(token, Token::Paragraph { .. }))
+                .map_or(0, |last_other| last_other + 1);
+            if first < token_list.len() {
+                let mut text: Vec<Inline> = Vec::new();
+                for token in &mut token_list[first..] {
+                    if let Token::Paragraph { text: line_text } =
+                        std::mem::replace(token, Token::None {})
+                    {
+                        if !text.is_empty() {
+                            text.push(Inline::Text(String::from(" ")));
+                        }
+                        text.extend(line_text);
+                        // Trailing spaces are no hard break in a heading
+                        if let Some(Inline::Text(last)) = text.last_mut() {
+                            last.truncate(last.trim_end().len());
+                        }
+                    }
+                }
+                token_list[first] = Token::Header { level, text };
+                token_list.push(Token::None {});
+                continue;
+            }
+        }
+
+        // Lines over the safety limits skip inline formatting, so pathological input
+        // can't make the multi-pass converter hang
+        if !inside_fence {
+            if let Some(reason) = exceeded_limit(line, options) {
+                warnings.push(ParseWarning {
+                    line: i + 1,
+                    span: None,
+                    kind: WarningKind::Limit,
+                    message: format!("{}, inline formatting skipped", reason),
+                });
+                token_list.push(Token::Paragraph {
+                    text: vec![Inline::Text(escape::escape_text(line).into_owned())],
+                });
+                table_alignments = None;
+                continue;
+            }
+            if is_definition[i] {
+                token_list.push(Token::None {});
+                table_alignments = None;
+                continue;
+            }
+        }
+        // Offsets are checked on the line as written, before references are resolved
+        let inline_problems = match options.lint && !inside_fence {
+            true => inline_checks::check_line(line),
+            false => Vec::new(),
+        };
+        let resolved_line;
+        let line = if inside_fence {
+            line
+        } else {
+            resolved_line =
+                references::resolve_references_tracking(line, link_definitions, &mut used_labels);
+            &resolved_line
+        };
+
+        // A table starts with a header row followed by a delimiter row, and goes on
+        // until a line without pipes
+        if !inside_fence && options.tables {
+            if let Some(alignments) = &table_alignments {
+                if matches!(token_list.last(), Some(Token::TableRow { cells }) if cells[0].header) {
+                    token_list.push(Token::TableHeadEnd {});
+                    continue;
+                }
+                if line.contains('|') {
+                    token_list.push(Token::TableRow {
+                        cells: tables::row_cells(line, alignments, false, options),
+                    });
+                    continue;
+                }
+                table_alignments = None;
+            } else if let Some(alignments) = input_text
+                .get(i + 1)
+                .and_then(|next_line| tables::table_start(line, next_line))
+            {
+                token_list.push(Token::TableRow {
+                    cells: tables::row_cells(line, &alignments, true, options),
+                });
+                table_alignments = Some(alignments);
+                continue;
+            }
+        }
+
+        // A `: ` line right under a line of text makes that line the term it defines,
+        // further `: ` lines add definitions of the same term
+        let definition = line.strip_prefix(": ").filter(|_| {
+            !inside_fence
+                && matches!(
+                    token_list.last(),
+                    Some(Token::Paragraph { .. } | Token::Definition { .. })
+                )
+        });
+        if let Some(definition) = definition {
+            if let Some(Token::Paragraph { text }) = token_list.last_mut() {
+                let mut text = std::mem::take(text);
+                if let Some(Inline::Text(last)) = text.last_mut() {
+                    last.truncate(last.trim_end().len());
+                }
+                let term = token_list.len() - 1;
+                token_list[term] = Token::DefinitionTerm { text };
+                // A blank line between two groups doesn't end the list
+                if term >= 2
+                    && matches!(token_list[term - 1], Token::BreakLine {})
+                    && matches!(token_list[term - 2], Token::Definition { .. })
+                {
+                    token_list[term - 1] = Token::None {};
+                }
+            }
+            let html =
+                convert_inline_markdown_with_options(&mut definition.trim().to_string(), options);
+            token_list.push(Token::Definition {
+                text: inline::from_html(&html),
+            });
+            for problem in inline_problems {
+                warnings.push(ParseWarning {
+                    line: i + 1,
+                    span: Some(problem.span),
+                    kind: WarningKind::Inline,
+                    message: problem.message,
+                });
+            }
+            continue;
+        }
+
+        let nesting_depth = quote_depth(line);
+        if options.blockquotes && nesting_depth > options.max_nesting_depth {
+            warnings.push(ParseWarning {
+                line: i + 1,
+                span: None,
+                kind: WarningKind::Limit,
+                message: format!(
+                    "nesting depth {} is over the limit of {}, deeper markers kept as text",
+                    nesting_depth, options.max_nesting_depth
+                ),
+            });
+        }
+
+        // A line that can't be tokenized is kept as text, the rest of the document
+        // is still converted
+        let mut token = match tokenize_nested_line(line.clone(), 0, options) {
+            Ok(token) => token,
+            Err(err) => {
+                warnings.push(ParseWarning {
+                    line: i + 1,
+                    span: None,
+                    kind: WarningKind::Tokenize,
+                    message: format!("{}, line kept as text", err),
+                });
+                Token::Paragraph {
+                    text: vec![Inline::Text(escape::escape_text(line).into_owned())],
+                }
+            }
+        };
+
+        // A video URL or shortcode alone in its paragraph becomes an embedded player
+        if options.embeds && matches!(token, Token::Paragraph { .. }) {
+            match embeds::embed_line(line) {
+                Some(embeds::EmbedLine::Video(src)) => token = Token::Embed { src },
+                Some(embeds::EmbedLine::Link(url)) => {
+                    token = Token::Paragraph {
+                        text: vec![Inline::Link {
+                            children: vec![Inline::Text(url.clone())],
+                            url,
+                            title: None,
+                        }],
+                    }
+                }
+                None => (),
+            }
+        }
+
+        // A block that can't interrupt the paragraph above it stays paragraph text,
+        // otherwise prose like "1984. was a year" would turn into a list
+        if matches!(token_list.last(), Some(Token::Paragraph { .. }))
+            && !matches!(token, Token::Paragraph { .. } | Token::BreakLine {})
+            && !interrupts_paragraph(line)
+        {
+            token = Token::Paragraph {
+                text: inline::from_html(&convert_inline_markdown_with_options(
+                    &mut line.clone(),
+                    options,
+                )),
+            };
+        }
+
+        if matches!(
+            token,
+            Token::Paragraph { .. }
+                | Token::Header { .. }
+                | Token::UListItem { .. }
+                | Token::OListItem { .. }
+                | Token::Quote { .. }
+        ) {
+            for problem in inline_problems {
+                warnings.push(ParseWarning {
+                    line: i + 1,
+                    span: Some(problem.span),
+                    kind: WarningKind::Inline,
+                    message: problem.message,
+                });
+            }
+        }
+
+        if matches!(token, Token::UListItem { .. } | Token::OListItem { .. }) {
+            in_list = true;
+        }
+        token_list.push(token);
+    }
+
+    // A backslash or two spaces at the very end of a line is a hard break when the
+    // paragraph continues on the next line. At the end of a paragraph the backslash
+    // stays literal, and trailing spaces are always dropped.
+    // Code block lines are replaced by their raw text later so they're unaffected.
+    for i in 0..token_list.len() {
+        let continues = matches!(token_list.get(i + 1), Some(Token::Paragraph { text }) if !toc::is_marker(text));
+        let Token::Paragraph { text } = &mut token_list[i] else {
+            continue;
+        };
+        let Some(Inline::Text(last_text)) = text.last_mut() else {
+            continue;
+        };
+        let trimmed_len = last_text.trim_end_matches(' ').len();
+        let spaces = last_text.len() - trimmed_len;
+        let backslash = spaces == 0 && last_text.ends_with('\\');
+        if !continues || !(spaces >= 2 || backslash) {
+            last_text.truncate(trimmed_len);
+            if last_text.is_empty() {
+                text.pop();
+            }
+            continue;
+        }
+        last_text.truncate(trimmed_len - usize::from(backslash));
+        if last_text.is_empty() {
+            text.pop();
+        }
+        text.push(Inline::Break);
+    }
 
     // Parse further the token_list for tokens which need multi-line support
-    // eg. code blocks, ordered lists
+    // eg. code blocks, ordered and unordered lists
     // We add special tokens that will "encapsulate" the content that requires multi-line support
-    let mut last_token = Token::None {};
     let mut inside_code_block = false;
+    // The fence of the open code block, only a matching one closes it
+    let mut open_fence: Option<Fence> = None;
+    // Where the open code block starts, in the input and in the output, to
+    // highlight its lines once it ends
+    let mut code_block_start = (0, 0);
+    let mut indented_code_start = 0;
+    let mut code_blocks = Vec::new();
+    // Whether the code block is a raw one, output as it is
+    let mut raw_block = false;
     let mut skip_token = false;
+    let mut open_lists: Vec<OpenList> = Vec::new();
+    let mut open_table: Option<TablePart> = None;
+    let mut outline = HeadingOutline::default();
+    let mut open_quote = OpenQuote::default();
+    // The `:::` containers open, for the soft break policy of their paragraphs
+    let mut scopes: Vec<SoftBreakScope> = Vec::new();
     for (i, token) in token_list.iter().enumerate() {
+        // Link reference definitions leave nothing in the output
+        if matches!(token, Token::None {}) {
+            continue;
+        }
+        match token {
+            Token::ContainerStart { .. } | Token::DetailsStart { .. } => {
+                scopes.push(SoftBreakScope::Container)
+            }
+            Token::ContainerEnd {} | Token::DetailsEnd {} => {
+                scopes.pop();
+            }
+            _ => (),
+        }
+        if let Token::Header { level, .. } = token {
+            if !inside_code_block {
+                warnings.extend(outline.check(*level, i + 1));
+            }
+        }
+
+        // Consecutive quote lines share a blockquote, deeper `> >` lines open nested
+        // ones and any other line closes them all
+        let quote = match token {
+            Token::Quote { .. } if !inside_code_block => Some(quote_content(token)),
+            _ => None,
+        };
+        let quote_depth = quote.as_ref().map_or(0, |(depth, _)| *depth);
+        open_quote.close_to(quote_depth, &mut token_list_processed);
+
         // PUSH ANYTHING BEFORE THE CURRENT TOKEN
-        if matches!(token, Token::OListItem { .. })
-            && !matches!(last_token, Token::OListItem { .. })
+        // Lists nest by indentation, every open list remembers its depth and kind
+        let list_item = match token {
+            Token::UListItem { depth, .. } if !inside_code_block => Some(OpenList {
+                depth: *depth,
+                ordered: false,
+            }),
+            Token::OListItem { depth, .. } if !inside_code_block => Some(OpenList {
+                depth: *depth,
+                ordered: true,
+            }),
+            _ => None,
+        };
+        let switched = close_lists(&mut open_lists, list_item, &mut token_list_processed);
+
+        // Tables get their <table>, <thead> and <tbody> around the rows
+        match token {
+            Token::TableRow { .. } if !inside_code_block => match open_table {
+                None => {
+                    token_list_processed.push(Token::TableStart {});
+                    token_list_processed.push(Token::TableHeadStart {});
+                    open_table = Some(TablePart::Head);
+                }
+                Some(TablePart::AfterHead) => {
+                    token_list_processed.push(Token::TableBodyStart {});
+                    open_table = Some(TablePart::Body);
+                }
+                Some(_) => (),
+            },
+            Token::TableHeadEnd {} if !inside_code_block => open_table = Some(TablePart::AfterHead),
+            _ => close_table(&mut open_table, &mut token_list_processed),
+        }
+
+        // Consecutive terms and definitions share a definition list
+        let definition_item = !inside_code_block && is_definition_item(token);
+        let other = |token: &&Token| !matches!(token, Token::None {});
+        if definition_item
+            && !token_list[..i]
+                .iter()
+                .rev()
+                .find(other)
+                .is_some_and(is_definition_item)
         {
-            // If this is one of the first series of ol's then we add starting tag
-            token_list_processed.push(Token::OLStart {});
+            token_list_processed.push(Token::DefinitionListStart {});
         }
 
-        if matches!(token, Token::CodeBlock {}) {
-            token_list_processed.push(if inside_code_block == false {
-                Token::CodeBlockStart {}
-            } else {
-                Token::CodeBlockEnd {}
+        if let Some((depth, mut content)) = quote {
+            // Only the last line of the quote can be its attribution
+            let last_line = !matches!(token_list.get(i + 1), Some(Token::Quote { .. }));
+            if options.quote_attribution && last_line {
+                if let Token::Paragraph { text } = &content {
+                    if let Some(text) = attribution(text) {
+                        content = Token::QuoteAttribution { text };
+                    }
+                }
+            }
+            let soft_break = soft_breaks::resolve(
+                &[scopes.as_slice(), &[SoftBreakScope::Quote]].concat(),
+                options,
+            );
+            open_quote.push(depth, content, soft_break, &mut token_list_processed);
+            continue;
+        }
+
+        if let Some(item) = list_item {
+            let nests = open_lists
+                .last()
+                .is_some_and(|open| item.depth > open.depth)
+                && matches!(
+                    token_list_processed.last(),
+                    Some(Token::UListItem { .. } | Token::OListItem { .. })
+                );
+            if nests {
+                // The parent item stays open so the nested list ends up inside it
+                if let Some(Token::UListItem { text, .. } | Token::OListItem { text, .. }) =
+                    token_list_processed.last_mut()
+                {
+                    let text = std::mem::take(text);
+                    *token_list_processed.last_mut().unwrap() = Token::ListItemStart { text };
+                }
+            }
+            if nests || switched || open_lists.is_empty() {
+                token_list_processed.push(if item.ordered {
+                    // Like CommonMark, only the first number of the list counts
+                    let start = match token {
+                        Token::OListItem { number, .. } => *number,
+                        _ => 1,
+                    };
+                    Token::OLStart { start }
+                } else {
+                    Token::ULStart {}
+                });
+                open_lists.push(item);
+            }
+        }
+
+        // Inside a code block only a fence closing it counts, the others are code
+        let fence_line = match token {
+            Token::CodeBlock { language, fence }
+                if !inside_code_block
+                    || open_fence.is_some_and(|open| open.is_closed_by(fence)) =>
+            {
+                Some((language, *fence))
+            }
+            _ => None,
+        };
+        if let Some((language, fence)) = fence_line {
+            // A stray fence turns everything below it into code, point at it
+            let closed = token_list[i + 1..].iter().any(|next| {
+                matches!(next, Token::CodeBlock { fence: next, .. } if fence.is_closed_by(next))
             });
+            if !inside_code_block && !closed {
+                warnings.push(ParseWarning {
+                    line: i + 1,
+                    span: None,
+                    kind: WarningKind::UnclosedFence,
+                    message: String::from(
+                        "code fence is never closed, the rest of the document is rendered as code",
+                    ),
+                });
+            }
+            // Raw fences leave no <pre> around their lines
+            let raw_fence = !inside_code_block && is_raw_fence(&input_text[i]);
+            if raw_fence && !options.allow_raw {
+                warnings.push(ParseWarning {
+                    line: i + 1,
+                    span: None,
+                    kind: WarningKind::RawBlock,
+                    message: String::from(
+                        "raw block rendered as code, raw output needs --allow-raw",
+                    ),
+                });
+            }
+            let raw = raw_block || (raw_fence && options.allow_raw);
+            if !raw && !inside_code_block {
+                code_block_start = (i, token_list_processed.len());
+                token_list_processed.push(Token::CodeBlockStart {
+                    language: language.clone(),
+                });
+            } else if !raw {
+                let (line, start) = code_block_start;
+                code_blocks.push(verify::CodeBlockLines {
+                    lines: line + 1..i,
+                    indented: false,
+                });
+                highlight_code_block(
+                    &mut token_list_processed[start..],
+                    &input_text[line + 1..i],
+                    options,
+                );
+                token_list_processed.push(Token::CodeBlockEnd {});
+            }
+            raw_block = raw && !inside_code_block;
             skip_token = true;
+            open_fence = (!inside_code_block).then_some(fence);
             inside_code_block = !inside_code_block;
         }
 
+        // Consecutive lines of text make a single paragraph, a blank line ends it
+        if let Token::Paragraph { text } = token {
+            let continues = i > 0
+                && matches!(&token_list[i - 1], Token::Paragraph { text } if !toc::is_marker(text))
+                && !toc::is_marker(text);
+            if continues && !inside_code_block {
+                if let Some(Token::Paragraph { text: paragraph }) = token_list_processed.last_mut()
+                {
+                    if !matches!(paragraph.last(), Some(Inline::Break)) {
+                        paragraph.push(soft_breaks::resolve(&scopes, options).inline());
+                    }
+                    paragraph.extend(text.iter().cloned());
+                    continue;
+                }
+            }
+        }
+
+        // Indented code lines are escaped already, every run of them is a code block
+        let indented_code = !inside_code_block && matches!(token, Token::SimpleText { .. });
+        if indented_code && (i == 0 || !matches!(token_list[i - 1], Token::SimpleText { .. })) {
+            token_list_processed.push(Token::CodeBlockStart { language: None });
+            indented_code_start = i;
+        }
+
         // PUSH THE CURRENT TOKEN
-        if skip_token == false {
+        if !skip_token {
             token_list_processed.push(token.clone());
         }
+        if indented_code && !matches!(token_list.get(i + 1), Some(Token::SimpleText { .. })) {
+            token_list_processed.push(Token::CodeBlockEnd {});
+            code_blocks.push(verify::CodeBlockLines {
+                lines: indented_code_start..i + 1,
+                indented: true,
+            });
+        }
 
         // Code blocks remove all formatting inside so we'll only use simple_text tokens
-        if inside_code_block && skip_token != true {
-            let raw_line = input_text[i].clone();
+        if inside_code_block && !skip_token {
             token_list_processed.pop();
-            token_list_processed.push(Token::SimpleText { text: raw_line });
+            token_list_processed.push(if raw_block {
+                Token::RawHtml {
+                    html: input_text[i].clone(),
+                }
+            } else {
+                Token::SimpleText {
+                    text: escape::escape_text(&input_text[i]).into_owned(),
+                }
+            });
         }
 
         // PUSH ANYTHING AFTER THE CURRENT TOKEN
-        if !matches!(token, Token::OListItem { .. })
-            && matches!(last_token, Token::OListItem { .. })
+        if definition_item
+            && !token_list[i + 1..]
+                .iter()
+                .find(other)
+                .is_some_and(is_definition_item)
         {
-            // If this is isn't an ol item but last item was one, we add ending tag
-            token_list_processed.push(Token::OLEnd {});
+            token_list_processed.push(Token::DefinitionListEnd {});
         }
-
-        if matches!(token, Token::CodeBlockStart {}) {
+        if matches!(token, Token::CodeBlockStart { .. }) {
             inside_code_block = true;
         }
 
-        last_token = token.clone();
         skip_token = false;
     }
 
-    for token in token_list_processed {
-        string_result.push(token.to_string());
+    // A list, quote or code block that runs until the end of the input still needs
+    // its ending tags
+    if inside_code_block && !raw_block {
+        let (line, start) = code_block_start;
+        code_blocks.push(verify::CodeBlockLines {
+            lines: line + 1..input_text.len(),
+            indented: false,
+        });
+        highlight_code_block(
+            &mut token_list_processed[start..],
+            &input_text[line + 1..],
+            options,
+        );
+        token_list_processed.push(Token::CodeBlockEnd {});
+    }
+    open_quote.close_to(0, &mut token_list_processed);
+    close_lists(&mut open_lists, None, &mut token_list_processed);
+    close_table(&mut open_table, &mut token_list_processed);
+    for (line, end) in open_containers.into_iter().rev() {
+        token_list_processed.push(end);
+        warnings.push(ParseWarning {
+            line: line + 1,
+            span: None,
+            kind: WarningKind::UnclosedContainer,
+            message: String::from(
+                "container is never closed by a `:::` line, it ends with the document",
+            ),
+        });
+    }
+
+    // Footnotes are rendered once the references in the text are known
+    let notes: HashMap<String, Vec<Inline>> = footnotes
+        .definitions
+        .into_iter()
+        .map(|(label, definition)| {
+            let mut text = references::resolve_references_tracking(
+                &definition.text,
+                link_definitions,
+                &mut used_labels,
+            );
+            let html = convert_inline_markdown_with_options(&mut text, options);
+            (label, inline::from_html(&html))
+        })
+        .collect();
+    let token_list_processed = footnotes::link_footnotes(token_list_processed, &notes);
+
+    // The first definition of a label is the one used
+    for duplicate in &collected_definitions.duplicates {
+        warnings.push(ParseWarning {
+            line: duplicate.line,
+            span: None,
+            kind: WarningKind::Reference,
+            message: format!(
+                "link reference [{}] is already defined on line {}, this definition is ignored",
+                duplicate.label,
+                duplicate.first_line + front_matter_lines
+            ),
+        });
+    }
+    if options.lint {
+        for (label, definition) in references::unused_definitions(link_definitions, &used_labels) {
+            warnings.push(ParseWarning {
+                line: definition.line,
+                span: None,
+                kind: WarningKind::Reference,
+                message: format!("link reference [{}] is defined but never used", label),
+            });
+        }
     }
 
-    string_result
+    // Warnings point at lines of the whole document, front matter included
+    for warning in &mut warnings {
+        warning.line += front_matter_lines;
+    }
+    warnings.splice(0..0, front_matter_warning);
+    for block in &mut code_blocks {
+        block.lines = block.lines.start + front_matter_lines..block.lines.end + front_matter_lines;
+    }
+    ParsedDocument {
+        tokens: token_list_processed,
+        warnings,
+        front_matter,
+        code_blocks,
+    }
 }
 
-/// Tokenizes a single line of text.
-///
-/// This function takes a markdown string as input and processes it to extract tokens
-/// based on the implemented parsing logic. It is typically used as part of
-/// a larger markdown parsing pipeline.
-///
-/// # Arguments
-///
-/// * `text` - A `String` containing the line of markdown text to be tokenized.
-///
-/// # Example
+/// Parses the line opening or closing a custom container, three colons or more
+/// followed by optional class names: `Some(Some("warning"))` for `::: warning`,
+/// `Some(None)` for a bare `:::`. Other text after the colons makes it no container
+/// line.
 ///
-/// ```rust
-/// let line = String::from("This is a sample line.");
-/// markdown_parser::tokenize_line(line);
-/// ```
-pub fn tokenize_line(line: String) -> Result<Token, ()> {
-    let token_result;
-    let mut line_copy = line.clone();
-
-    // Not all blocks accept the inline parsing, eg. Code blocks
+/// Checked after `details_fence`, so `:::details` opens a collapsible section.
+fn container_fence(line: &str) -> Option<Option<String>> {
+    let line = line.trim_end();
+    let info = line.trim_start_matches(':');
+    if line.len() - info.len() < 3 {
+        return None;
+    }
+    let is_class = |name: &str| {
+        name.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+    };
+    let classes: Vec<&str> = info.split_whitespace().collect();
+    if !classes.iter().all(|class| is_class(class)) {
+        return None;
+    }
+    Some((!classes.is_empty()).then(|| classes.join(" ")))
+}
 
-    // Line is a Header
-    if line_copy.starts_with("#") {
-        let mut count = 0;
-        while line_copy.starts_with("#") {
-            count += 1;
-            line_copy.remove(0); // Removes the #
-        }
-        // If after all the "#" there isn't a simple space,
-        // we need to skip because it isn't valid markdown
-        if line_copy.chars().nth(0).unwrap() != ' ' {
-            line_copy = line.clone();
-        } else {
-            // Remove the space as well
-            line_copy.remove(0);
+/// Parses the line opening a collapsible section, three colons or more followed by
+/// `details` and the summary: `Some("Click to expand")` for
+/// `:::details Click to expand`, `Some("")` without a summary.
+fn details_fence(line: &str) -> Option<&str> {
+    let info = line.trim_start_matches(':');
+    if line.len() - info.len() < 3 {
+        return None;
+    }
+    let summary = info.trim_start().strip_prefix("details")?;
+    match summary.is_empty() || summary.starts_with(char::is_whitespace) {
+        true => Some(summary.trim()),
+        false => None,
+    }
+}
 
-            let inline_converted_line = convert_inline_markdown(&mut line_copy);
+/// Whether `token` is an item of a definition list, a term or a definition.
+fn is_definition_item(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::DefinitionTerm { .. } | Token::Definition { .. }
+    )
+}
 
-            token_result = Token::Header {
-                level: (count),
-                text: (inline_converted_line),
-            };
-            return Ok(token_result);
-        }
+/// With `options.highlight`, replaces the tokens of a code block, from its
+/// `CodeBlockStart` to its last line, with the highlighted HTML of its source
+/// `lines`. Blocks without a known language are left as plain code.
+fn highlight_code_block(tokens: &mut [Token], lines: &[String], options: &ParserOptions) {
+    let Some(Token::CodeBlockStart {
+        language: Some(language),
+    }) = tokens.first()
+    else {
+        return;
+    };
+    if !options.highlight || tokens.len() != lines.len() + 1 {
+        return;
     }
+    let Some(block) = highlight::highlight_code(language, lines, &options.highlight_theme) else {
+        return;
+    };
+    let html_lines = std::iter::once(block.start_html).chain(block.lines);
+    for (token, html) in tokens.iter_mut().zip(html_lines) {
+        *token = Token::RawHtml { html };
+    }
+}
 
-    // Line is a block quote
-    if line_copy.starts_with("> ") {
-        line_copy.remove(0); // Removes the >
-        line_copy.remove(0); // Removes the space after
-        let inline_converted_line = convert_inline_markdown(&mut line_copy);
-        let nested_token = tokenize_line(inline_converted_line).unwrap();
-        match nested_token {
-            Token::Paragraph { text } => {
-                token_result = Token::Quote {
-                    text: (text),
-                    nested_token: Box::new(Token::None {}),
-                }
+/// The headings seen so far by `parse_with_options`, to check the document outline.
+#[derive(Default)]
+struct HeadingOutline {
+    previous_level: Option<u8>,
+    first_h1_line: Option<usize>,
+}
+
+impl HeadingOutline {
+    /// Checks the heading of `level` found on `line`: it may only go one level
+    /// deeper than the heading before it, and a document has a single `<h1>`.
+    fn check(&mut self, level: u8, line: usize) -> Vec<ParseWarning> {
+        let mut warnings = Vec::new();
+        if let Some(previous) = self.previous_level {
+            if level > previous + 1 {
+                warnings.push(ParseWarning {
+                    line,
+                    span: None,
+                    kind: WarningKind::HeadingStructure,
+                    message: format!(
+                        "heading level jumps from h{} to h{}, use h{} instead",
+                        previous,
+                        level,
+                        previous + 1
+                    ),
+                });
             }
-            _ => {
-                token_result = Token::Quote {
-                    text: ("[DEBUG]").to_owned(),
-                    nested_token: Box::new(nested_token),
-                }
+        }
+        if level == 1 {
+            match self.first_h1_line {
+                Some(first) => warnings.push(ParseWarning {
+                    line,
+                    span: None,
+                    kind: WarningKind::HeadingStructure,
+                    message: format!("another h1, the document already has one on line {}", first),
+                }),
+                None => self.first_h1_line = Some(line),
             }
         }
-
-        return Ok(token_result);
+        self.previous_level = Some(level);
+        warnings
     }
+}
 
-    // Line is 3 dashes -> HorizLine
-    if line_copy.trim() == "---" {
-        token_result = Token::HorizLine {};
-        return Ok(token_result);
+/// Unwraps a quote line into its nesting depth and the block it holds, its
+/// paragraph text becoming a `Paragraph`.
+fn quote_content(token: &Token) -> (usize, Token) {
+    match token {
+        Token::Quote { text, .. } if !text.is_empty() => {
+            (1, Token::Paragraph { text: text.clone() })
+        }
+        Token::Quote { nested_token, .. } => {
+            let (depth, content) = quote_content(nested_token);
+            (depth + 1, content)
+        }
+        token => (0, token.clone()),
     }
+}
 
-    // Line followed by a space is a ListItem
-    if line_copy.starts_with(&['-', '*', '+']) && line_copy.chars().nth(1).unwrap_or('.') == ' ' {
-        line_copy.remove(0); // Removes the - char
-        line_copy.remove(0); // Removes the space after
-        let inline_converted_line = convert_inline_markdown(&mut line_copy);
-        token_result = Token::UListItem {
-            text: (inline_converted_line),
-        };
-        return Ok(token_result);
+/// Returns the content of a quote attribution line (`— Ada Lovelace` or
+/// `-- source`) without its dash, or `None` if `text` isn't one.
+fn attribution(text: &[Inline]) -> Option<Vec<Inline>> {
+    let Some(Inline::Text(first)) = text.first() else {
+        return None;
+    };
+    // `--` needs a space after it, so `--flag` isn't an attribution
+    let rest = match first.strip_prefix('\u{2014}') {
+        Some(rest) => rest,
+        None => first
+            .strip_prefix("--")
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '))?,
     }
-    // Line is codeblock start can follow text after it
-    if line_copy.starts_with("```") {
-        token_result = Token::CodeBlock {};
-        return Ok(token_result);
+    .trim_start();
+    let mut text = text.to_vec();
+    if rest.is_empty() {
+        text.remove(0);
+    } else {
+        text[0] = Inline::Text(rest.to_string());
     }
+    (!text.is_empty()).then_some(text)
+}
 
-    // Line starts with a number followed by a . and a space
-    let re = Regex::new(r"^\d+\.\s").unwrap();
-    if re.is_match(&line_copy) {
-        let re_res = re.find(&line_copy).unwrap();
-        let inlined_text = convert_inline_markdown(&mut line_copy.split_off(re_res.end()));
-        token_result = Token::OListItem {
-            text: (inlined_text),
-        };
+/// The kinds of GitHub alerts rendered as callouts, see `admonition_kind`.
+pub const ADMONITION_KINDS: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
 
-        return Ok(token_result);
-    }
+/// The lowercase kind of the callout started by the first line of a blockquote,
+/// when the line is only a GitHub alert marker such as `[!NOTE]` or `[!warning]`.
+/// Other markers (`[!FOO]`) leave the blockquote as it is.
+fn admonition_kind(text: &[Inline]) -> Option<&'static str> {
+    let [Inline::Text(text)] = text else {
+        return None;
+    };
+    let kind = text.trim().strip_prefix("[!")?.strip_suffix(']')?;
+    ADMONITION_KINDS
+        .into_iter()
+        .find(|known| known.eq_ignore_ascii_case(kind))
+}
 
-    // Line is empty -> BreakLine
-    if line_copy.clone().trim() == "" {
-        token_result = Token::BreakLine {};
-        return Ok(token_result);
-    }
+/// The blockquotes opened by the multi-line pass of `parse_with_options`.
+///
+/// # Fields
+///
+/// - `depth`: How many blockquotes are open.
+/// - `admonitions`: Whether each open blockquote, outermost first, is a callout
+///   (see `admonition_kind`) closed by `AdmonitionEnd`.
+/// - `paragraph`: Whether the last quote line was paragraph text, that the next
+///   one continues.
+/// - `list`: Whether the last quote line was a list item, and of which kind
+///   (`true` for ordered), that the next item joins.
+#[derive(Default)]
+struct OpenQuote {
+    depth: usize,
+    admonitions: Vec<bool>,
+    paragraph: bool,
+    list: Option<bool>,
+}
+
+impl OpenQuote {
+    /// Closes the blockquotes nested deeper than `depth`.
+    fn close_to(&mut self, depth: usize, tokens: &mut Vec<Token>) {
+        if depth == self.depth {
+            return;
+        }
+        while self.depth > depth {
+            tokens.push(match self.admonitions.pop() {
+                Some(true) => Token::AdmonitionEnd {},
+                _ => Token::QuoteEnd {},
+            });
+            self.depth -= 1;
+        }
+        self.paragraph = false;
+        self.list = None;
+    }
+
+    /// Adds the `content` of a quote line `depth` blockquotes deep, opening the
+    /// missing blockquotes. The innermost one is a callout when `content` is its
+    /// alert marker.
+    fn push(
+        &mut self,
+        depth: usize,
+        content: Token,
+        soft_break: SoftBreak,
+        tokens: &mut Vec<Token>,
+    ) {
+        let admonition = match &content {
+            Token::Paragraph { text } if self.depth < depth => admonition_kind(text),
+            _ => None,
+        };
+        while self.depth < depth {
+            self.depth += 1;
+            let callout = self.depth == depth && admonition.is_some();
+            tokens.push(match admonition {
+                Some(kind) if callout => Token::AdmonitionStart {
+                    kind: kind.to_string(),
+                },
+                _ => Token::QuoteStart {},
+            });
+            self.admonitions.push(callout);
+            self.paragraph = false;
+            self.list = None;
+        }
+        // The marker only gives the callout its kind
+        if admonition.is_some() {
+            return;
+        }
+        let paragraph = matches!(content, Token::Paragraph { .. });
+        let list = match content {
+            Token::UListItem { .. } => Some(false),
+            Token::OListItem { .. } => Some(true),
+            _ => None,
+        };
+        match content {
+            // Paragraph text flows across the lines of the quote
+            Token::Paragraph { text } if self.paragraph => {
+                if let Some(Token::Paragraph { text: paragraph }) = tokens.last_mut() {
+                    paragraph.push(soft_break.inline());
+                    paragraph.extend(text);
+                }
+            }
+            Token::UListItem { .. } | Token::OListItem { .. } => {
+                if self.list == list {
+                    // The item joins the list of the previous line
+                    tokens.pop();
+                } else {
+                    tokens.push(match content {
+                        Token::OListItem { number, .. } => Token::OLStart { start: number },
+                        _ => Token::ULStart {},
+                    });
+                }
+                tokens.push(content);
+                tokens.push(match list {
+                    Some(true) => Token::OLEnd {},
+                    _ => Token::ULEnd {},
+                });
+            }
+            // An empty `>` line only separates paragraphs
+            Token::BreakLine {} | Token::None {} => (),
+            content => tokens.push(content),
+        }
+        self.paragraph = paragraph;
+        self.list = list;
+    }
+}
+
+/// Where the multi-line pass of `parse_with_options` is in an open table.
+#[derive(Clone, Copy)]
+enum TablePart {
+    Head,
+    AfterHead,
+    Body,
+}
+
+/// Closes the open table, if any, along with its header or body.
+fn close_table(open_table: &mut Option<TablePart>, tokens: &mut Vec<Token>) {
+    match open_table.take() {
+        None => return,
+        Some(TablePart::Head) => tokens.push(Token::TableHeadEnd {}),
+        Some(TablePart::AfterHead) => (),
+        Some(TablePart::Body) => tokens.push(Token::TableBodyEnd {}),
+    }
+    tokens.push(Token::TableEnd {});
+}
+
+/// A list opened by the multi-line pass of `parse_with_options`.
+#[derive(Clone, Copy)]
+struct OpenList {
+    depth: u8,
+    ordered: bool,
+}
+
+/// Closes the open lists that can't hold the list item `item`, inner lists first, or
+/// all of them when the list ends (`item` is `None`).
+///
+/// A dedent never closes the outermost list, an item less indented than it is still
+/// one of its items. A list of the other kind at the item's depth is closed though,
+/// and then its parent item is left open for the new list: in that case this returns
+/// `true` so the caller opens it.
+fn close_lists(
+    open_lists: &mut Vec<OpenList>,
+    item: Option<OpenList>,
+    tokens: &mut Vec<Token>,
+) -> bool {
+    while let Some(&open) = open_lists.last() {
+        let keep_open = match item {
+            Some(item) => {
+                open.depth < item.depth
+                    || (open.ordered == item.ordered
+                        && (open.depth == item.depth || open_lists.len() == 1))
+            }
+            None => false,
+        };
+        if keep_open {
+            break;
+        }
+        open_lists.pop();
+        tokens.push(if open.ordered {
+            Token::OLEnd {}
+        } else {
+            Token::ULEnd {}
+        });
+        if open_lists.is_empty() {
+            break;
+        }
+        // Switching the kind of a nested list keeps its parent item open
+        if item.is_some_and(|item| item.depth == open.depth) {
+            return true;
+        }
+        // A nested list closes the parent item it was opened in
+        tokens.push(Token::ListItemEnd {});
+    }
+    false
+}
+
+/// Converts the text of a list item after its marker.
+///
+/// A task list item (`[ ] text` or `[x] text`, the `x` in any case) starts with a
+/// disabled checkbox, anything else after a `[` is regular item text.
+fn list_item_text(item_text: &str, options: &ParserOptions) -> Vec<Inline> {
+    static TASK_RE: OnceLock<Regex> = OnceLock::new();
+    let task = TASK_RE.get_or_init(|| Regex::new(r"^\[([ xX])\]\s").unwrap());
+    let Some(groups) = task.captures(item_text) else {
+        let converted = convert_inline_markdown_with_options(&mut item_text.to_string(), options);
+        return inline::from_html(&converted);
+    };
+    let mut rest = item_text[groups.get(0).unwrap().end()..].to_string();
+    let converted = convert_inline_markdown_with_options(&mut rest, options);
+    let mut text = vec![Inline::Checkbox {
+        checked: &groups[1] != " ",
+    }];
+    let mut inlines = inline::from_html(&converted);
+    match inlines.first_mut() {
+        Some(Inline::Text(first)) => first.insert(0, ' '),
+        _ => inlines.insert(0, Inline::Text(String::from(" "))),
+    }
+    text.extend(inlines);
+    text
+}
+
+/// The nesting depth of a list item indented by `indent`, one level per 2 columns
+/// with tabs counting as 4.
+fn indent_depth(indent: &str) -> u8 {
+    let width: usize = indent.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum();
+    (width / 2).min(u8::MAX as usize) as u8
+}
+
+/// The heading level a setext underline gives to the paragraph above it: 1 for a
+/// line of `=`, 2 for a line of `-`, indented by at most three spaces.
+fn setext_level(line: &str) -> Option<u8> {
+    if line.len() - line.trim_start_matches(' ').len() > 3 {
+        return None;
+    }
+    let underline = line.trim();
+    match underline.chars().next()? {
+        '=' if underline.chars().all(|c| c == '=') => Some(1),
+        '-' if underline.chars().all(|c| c == '-') => Some(2),
+        _ => None,
+    }
+}
+
+/// The code of a line of an indented code block, without its indentation of four
+/// spaces or a tab. `None` when `line` is less indented.
+pub(crate) fn strip_code_indent(line: &str) -> Option<&str> {
+    line.strip_prefix("    ")
+        .or_else(|| line.strip_prefix('\t'))
+}
+
+/// Checks whether `line` opens a raw fenced block, whose info string is `=html`
+/// or `raw` (Pandoc's raw attribute).
+fn is_raw_fence(line: &str) -> bool {
+    matches!(line.trim_start_matches(['`', '~']).trim(), "=html" | "raw")
+}
+
+/// Strips the quote marker `line` starts with: a `>` followed by a space, or
+/// by the `>` of a nested quote (`>> text`).
+fn strip_quote_marker(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('>')?;
+    if rest.starts_with('>') {
+        Some(rest)
+    } else {
+        rest.strip_prefix(' ')
+    }
+}
+
+/// Counts the quote markers at the start of `line`, in both the `> >` and `>>`
+/// forms.
+fn quote_depth(line: &str) -> usize {
+    let mut depth = 0;
+    let mut rest = line;
+    while let Some(stripped) = strip_quote_marker(rest) {
+        depth += 1;
+        rest = stripped;
+    }
+    depth
+}
+
+/// Returns why `line` is over one of the safety limits of `options`, if it is.
+fn exceeded_limit(line: &str, options: &ParserOptions) -> Option<String> {
+    let line_length = line.chars().count();
+    if line_length > options.max_line_length {
+        return Some(format!(
+            "line is {} characters long (limit {})",
+            line_length, options.max_line_length
+        ));
+    }
+
+    let delimiters = line
+        .chars()
+        .filter(|c| matches!(c, '*' | '_' | '`' | '~' | '[' | ']' | '(' | ')'))
+        .count();
+    if delimiters > options.max_inline_delimiters {
+        return Some(format!(
+            "line has {} inline delimiters (limit {})",
+            delimiters, options.max_inline_delimiters
+        ));
+    }
+    None
+}
+
+/// Returns the number of an ordered list item marker (`3` for `3. item`),
+/// or `None` when the line is not an ordered list item.
+fn ordered_list_number(line: &str) -> Option<u32> {
+    ordered_list_marker_re()
+        .captures(line)
+        .and_then(|groups| groups[1].parse().ok())
+}
+
+/// Decides whether `line` starts a new block when it directly follows paragraph text.
+///
+/// - Unordered list items, code fences, headers, quotes and horizontal lines interrupt.
+/// - Ordered list items interrupt only when their number is 1.
+/// - Table delimiter rows (`|---|---|`) and indented code (4 spaces) never interrupt.
+///
+/// After a blank line there is no paragraph to interrupt, so this isn't consulted.
+pub(crate) fn interrupts_paragraph(line: &str) -> bool {
+    if line.starts_with("    ") || line.starts_with('\t') {
+        return false;
+    }
+    if is_table_delimiter_row(line) {
+        return false;
+    }
+    let unindented = line.trim_start_matches([' ', '\t']);
+    if ordered_list_marker_re().is_match(unindented) {
+        return ordered_list_number(unindented) == Some(1);
+    }
+    !matches!(
+        tokenize_line(line.to_string()),
+        Ok(Token::Paragraph { .. }) | Ok(Token::BreakLine {})
+    )
+}
+
+/// Checks whether `line` is a table delimiter row such as `| --- | :---: |`.
+fn is_table_delimiter_row(line: &str) -> bool {
+    let trimmed = line.trim().trim_matches('|');
+    trimmed.contains('|')
+        && trimmed.split('|').all(|cell| {
+            let cell = cell.trim().trim_start_matches(':').trim_end_matches(':');
+            !cell.is_empty() && cell.chars().all(|c| c == '-')
+        })
+}
+
+/// Tokenizes a single line of text.
+///
+/// This function takes a markdown string as input and processes it to extract tokens
+/// based on the implemented parsing logic. It is typically used as part of
+/// a larger markdown parsing pipeline.
+///
+/// # Arguments
+///
+/// * `text` - A `String` containing the line of markdown text to be tokenized.
+///
+/// # Example
+///
+/// ```rust
+/// let line = String::from("This is a sample line.");
+/// md2html::tokenize_line(line);
+/// ```
+///
+/// # Errors
+///
+/// Returns a `TokenizeError` when the line can't be tokenized.
+pub fn tokenize_line(line: String) -> Result<Token, TokenizeError> {
+    tokenize_nested_line(line, 0, &ParserOptions::default())
+}
+
+/// Tokenizes a line found inside `depth` enclosing containers (e.g. quotes).
+///
+/// Once `depth` reaches `options.max_nesting_depth` container markers aren't parsed anymore, so they
+/// end up as literal text of the innermost allowed container.
+fn tokenize_nested_line(
+    line: String,
+    depth: usize,
+    options: &ParserOptions,
+) -> Result<Token, TokenizeError> {
+    let token_result;
+    let mut line_copy = line.clone();
+
+    // Not all blocks accept the inline parsing, eg. Code blocks
+
+    // Line is a Header
+    if let Some((level, rest)) = atx_heading(&line_copy).filter(|_| options.headings) {
+        let mut heading = normalize_heading_text(strip_closing_hashes(rest));
+
+        let inline_converted_line = convert_inline_markdown_with_options(&mut heading, options);
+
+        token_result = Token::Header {
+            level,
+            text: inline::from_html(&inline_converted_line),
+        };
+        return Ok(token_result);
+    }
+
+    // Line is a block quote, a lone `>` being an empty line of the quote
+    let quotes = options.blockquotes && depth < options.max_nesting_depth;
+    if quotes && line_copy.trim_end() == ">" {
+        return Ok(Token::Quote {
+            text: Vec::new(),
+            nested_token: Box::new(Token::BreakLine {}),
+        });
+    }
+    if quotes {
+        if let Some(quoted) = strip_quote_marker(&line_copy) {
+            // The nested block converts its own inline markdown
+            let nested_token = tokenize_nested_line(quoted.to_string(), depth + 1, options)?;
+            token_result = match nested_token {
+                Token::Paragraph { text } => Token::Quote {
+                    text,
+                    nested_token: Box::new(Token::None {}),
+                },
+                nested_token => Token::Quote {
+                    text: Vec::new(),
+                    nested_token: Box::new(nested_token),
+                },
+            };
+
+            return Ok(token_result);
+        }
+    }
+
+    // Line is 3 dashes -> HorizLine
+    if options.horizontal_rules && line_copy.trim() == "---" {
+        token_result = Token::HorizLine {};
+        return Ok(token_result);
+    }
+
+    // Line followed by a space is a ListItem, its indentation gives the nesting depth
+    let unindented = line_copy.trim_start_matches([' ', '\t']);
+    let list_depth = indent_depth(&line_copy[..line_copy.len() - unindented.len()]);
+    let bullet_text = unindented
+        .strip_prefix(['-', '*', '+'])
+        .and_then(|rest| rest.strip_prefix(' '));
+    if let Some(item_text) = bullet_text.filter(|_| options.lists) {
+        token_result = Token::UListItem {
+            depth: list_depth,
+            text: list_item_text(item_text, options),
+        };
+        return Ok(token_result);
+    }
+    // Line is a code fence of backticks or tildes, only the first word of its info
+    // string names the language (```rust,no_run)
+    if let Some((fence, info)) = Fence::parse(&line_copy).filter(|_| options.code_fences) {
+        let language = info
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .find(|word| !word.is_empty())
+            // `=html` names an output format rather than a language
+            .filter(|word| !word.starts_with('='))
+            .map(String::from);
+        token_result = Token::CodeBlock { language, fence };
+        return Ok(token_result);
+    }
+
+    // Line starts with a number (at most 9 digits) followed by a . and a space
+    if let Some(groups) = ordered_list_marker_re()
+        .captures(unindented)
+        .filter(|_| options.lists)
+    {
+        token_result = Token::OListItem {
+            depth: list_depth,
+            number: groups[1].parse().unwrap(),
+            text: list_item_text(&unindented[groups.get(0).unwrap().end()..], options),
+        };
+
+        return Ok(token_result);
+    }
+
+    // Line is empty -> BreakLine
+    if line_copy.clone().trim() == "" {
+        token_result = Token::BreakLine {};
+        return Ok(token_result);
+    }
 
     // If none of the above -> Paragraph
-    let inline_converted_line = convert_inline_markdown(&mut line_copy);
+    let inline_converted_line = convert_inline_markdown_with_options(&mut line_copy, options);
     token_result = Token::Paragraph {
-        text: (inline_converted_line),
+        text: inline::from_html(&inline_converted_line),
     };
     Ok(token_result)
 }
@@ -610,274 +3134,2501 @@ pub fn tokenize_line(line: String) -> Result<Token, ()> {
 mod tests {
     use super::*;
 
+    // The conversions of these tests go through `verify::tokenize_verified`, so
+    // every code block they make is checked against its source
+    fn tokenize_text(str_iter: impl Iterator<Item = String>) -> Vec<String> {
+        let lines: Vec<String> = str_iter.collect();
+        verify::tokenize_verified(lines.iter().cloned(), &ParserOptions::default());
+        super::tokenize_text(lines.into_iter())
+    }
+
+    fn tokenize_text_with_options(
+        str_iter: impl Iterator<Item = String>,
+        options: &ParserOptions,
+    ) -> ParseResult {
+        verify::tokenize_verified(str_iter, options)
+    }
+
     #[test]
     fn test_tokenize_header() {
         let token = tokenize_line(String::from("## Header")).unwrap();
         match token {
             Token::Header { level, text } => {
                 assert_eq!(level, 2);
-                assert_eq!(text, "Header");
+                assert_eq!(inline::to_html(&text), "Header");
             }
             _ => panic!("Expected Header token"),
         }
     }
 
     #[test]
-    fn test_header_missing_space() {
-        let line = String::from("##Header");
-        let token = tokenize_line(line.clone()).unwrap();
-        match token {
-            Token::Paragraph { text } => assert_eq!(text, line),
-            _ => panic!("Should not parse header without space"),
-        };
+    fn test_too_many_hashes_are_text() {
+        for line in ["####### Seven", &format!("{} Too deep", "#".repeat(300))] {
+            let token = tokenize_line(line.to_string()).unwrap();
+            assert!(matches!(token, Token::Paragraph { .. }), "{}", line);
+        }
+        assert!(tokenize_line(format!("> > {} x", "#".repeat(256))).is_ok());
+    }
+
+    #[test]
+    fn test_many_hashes_kept_as_text() {
+        let lines = vec![
+            String::from("# Title"),
+            format!("{} <b>x</b>", "#".repeat(256)),
+            String::new(),
+            String::from("After *it*"),
+        ];
+        let result = tokenize_text_with_options(lines.into_iter(), &ParserOptions::default());
+        assert_eq!(
+            result.html_lines,
+            vec![
+                "<h1>Title</h1>".to_string(),
+                format!("<p>{} &lt;b&gt;x&lt;/b&gt;</p>", "#".repeat(256)),
+                "<br/>".to_string(),
+                "<p>After <i>it</i></p>".to_string(),
+            ]
+        );
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_hash_only_lines() {
+        for (line, expected_level) in [("#", 1), ("##", 2), ("#\t", 1), ("###   ", 3)] {
+            match tokenize_line(line.to_string()).unwrap() {
+                Token::Header { level, text } => {
+                    assert_eq!(level, expected_level, "{:?}", line);
+                    assert!(text.is_empty(), "{:?}", line);
+                }
+                token => panic!("Expected Header token for {:?}, got {:?}", line, token),
+            }
+        }
+    }
+
+    #[test]
+    fn test_atx_heading_remainder() {
+        let cases = [
+            ("#", Some((1, ""))),
+            ("##", Some((2, ""))),
+            ("#\t", Some((1, "\t"))),
+            ("###### Six", Some((6, " Six"))),
+            ("#######", None),
+            ("#hashtag", None),
+            ("", None),
+            ("Title #", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(atx_heading(line), expected, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_one_character_lines() {
+        for line in ["-", "*", "+", "1", ">", "#"] {
+            let result = tokenize_text_with_options(
+                std::iter::once(line.to_string()),
+                &ParserOptions::default(),
+            );
+            assert!(!result.html_lines.is_empty(), "{:?}", line);
+        }
+        for line in ["-", "*", "+"] {
+            let token = tokenize_line(line.to_string()).unwrap();
+            assert!(matches!(token, Token::Paragraph { .. }), "{:?}", line);
+        }
+        assert!(matches!(
+            tokenize_line(String::from("- ")).unwrap(),
+            Token::UListItem { .. }
+        ));
+    }
+
+    #[test]
+    fn test_atx_heading_levels_and_closing_hashes() {
+        let cases = [
+            ("###### Six", "<h6>Six</h6>"),
+            ("####### Seven", "<p>####### Seven</p>"),
+            ("# Title #", "<h1>Title</h1>"),
+            ("## Title ##   ", "<h2>Title</h2>"),
+            ("# Title ######", "<h1>Title</h1>"),
+            ("# C#", "<h1>C#</h1>"),
+            ("# Issue #42", "<h1>Issue #42</h1>"),
+            ("# Title \\#", "<h1>Title #</h1>"),
+            ("# `#` #", "<h1><code>#</code></h1>"),
+            ("#", "<h1></h1>"),
+            ("### ###", "<h3></h3>"),
+            ("## ", "<h2></h2>"),
+            ("#\tTab", "<h1>Tab</h1>"),
+        ];
+        for (markdown, html) in cases {
+            let result = tokenize_text_with_options(
+                std::iter::once(markdown.to_string()),
+                &ParserOptions::default(),
+            );
+            assert_eq!(result.html_lines, vec![html.to_string()], "{:?}", markdown);
+        }
+    }
+
+    #[test]
+    fn test_header_missing_space() {
+        let line = String::from("##Header");
+        let token = tokenize_line(line.clone()).unwrap();
+        match token {
+            Token::Paragraph { text } => assert_eq!(inline::to_html(&text), line),
+            _ => panic!("Should not parse header without space"),
+        };
+    }
+
+    #[test]
+    fn test_header_whitespace_is_normalized() {
+        let header_text = |line: &str| match tokenize_line(String::from(line)).unwrap() {
+            Token::Header { text, .. } => inline::to_html(&text),
+            _ => panic!("Expected Header token"),
+        };
+        assert_eq!(header_text("#   Spaced   out title   "), "Spaced out title");
+        assert_eq!(header_text("## Title\t\t"), "Title");
+        assert_eq!(
+            header_text("###  Call  `f(a,  b)`   now "),
+            "Call <code>f(a,  b)</code> now"
+        );
+    }
+
+    #[test]
+    fn test_ulist_missing_space() {
+        let line = String::from("-List item");
+        let token = tokenize_line(line.clone()).unwrap();
+        match token {
+            Token::Paragraph { text } => assert_eq!(inline::to_html(&text), line),
+            _ => panic!("Should not parse header without space"),
+        };
+    }
+
+    #[test]
+    fn test_olist_missing_space() {
+        let line = String::from("1.Ordered item");
+        let token = tokenize_line(line.clone()).unwrap();
+        match token {
+            Token::Paragraph { text } => assert_eq!(inline::to_html(&text), line),
+            _ => panic!("Should not parse ordered list without space"),
+        };
+    }
+
+    #[test]
+    fn test_unclosed_code_block() {
+        let lines = vec![String::from("```"), String::from("code but never ends")];
+        let tokens = tokenize_text(lines.into_iter());
+        assert_eq!(
+            tokens,
+            vec!["<pre><code>", "code but never ends", "</code></pre>"],
+            "An unclosed code block ends with the document"
+        );
+    }
+
+    #[test]
+    fn test_stray_fence_mid_document() {
+        let lines = ["# Title", "Some *text*", "```", "- not a list", "More text"];
+        let result = tokenize_text_with_options(
+            lines.iter().map(|line| line.to_string()),
+            &ParserOptions::default(),
+        );
+        assert_eq!(
+            result.html_lines[2..],
+            ["<pre><code>", "- not a list", "More text", "</code></pre>"]
+        );
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line, 3);
+        assert_eq!(result.warnings[0].kind, WarningKind::UnclosedFence);
+    }
+
+    #[test]
+    fn test_trailing_fence_block_is_not_stray() {
+        let lines = [
+            "Text",
+            "```",
+            "a",
+            "```",
+            "",
+            "```rust",
+            "fn main() {}",
+            "```",
+        ];
+        let result = tokenize_text_with_options(
+            lines.iter().map(|line| line.to_string()),
+            &ParserOptions::default(),
+        );
+        assert!(result.warnings.is_empty());
+        assert_eq!(
+            result.html_lines.last().map(String::as_str),
+            Some("</code></pre>")
+        );
+        assert_eq!(
+            result
+                .html_lines
+                .iter()
+                .filter(|line| *line == "</code></pre>")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_unclosed_bold() {
+        let mut line = String::from("This is **bold text.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(
+            html, "This is **bold text.",
+            "Unclosed bold should remain raw"
+        );
+    }
+
+    #[test]
+    fn test_unclosed_italic() {
+        let mut line = String::from("This is *italic text.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(
+            html, "This is *italic text.",
+            "Unclosed italic should remain raw"
+        );
+    }
+
+    #[test]
+    fn test_malformed_link_missing_closing_paren() {
+        let mut line = String::from("A [link](https://example.com here.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(
+            html, "A [link](https://example.com here.",
+            "Malformed link should not convert"
+        );
+    }
+
+    #[test]
+    fn test_malformed_link_missing_closing_bracket() {
+        let mut line = String::from("A link](https://example.com) here.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(
+            html, "A link](https://example.com) here.",
+            "Malformed link should not convert"
+        );
+    }
+
+    #[test]
+    fn test_misnested_formatting() {
+        let mut line = String::from("This is *italic and **bold*** text**.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(
+            html, "This is <i>italic and <strong>bold</strong></i> text**.",
+            "Misnested formatting should still parse valid nesting"
+        );
+    }
+
+    #[test]
+    fn test_bold_italic() {
+        let convert = |line: &str| convert_inline_markdown(&mut line.to_string());
+        assert_eq!(
+            convert("***really important*** and ***a***"),
+            "<strong><i>really important</i></strong> and <strong><i>a</i></strong>"
+        );
+        assert_eq!(
+            convert("***bold** italic*"),
+            "<i><strong>bold</strong> italic</i>"
+        );
+        assert_eq!(
+            convert("***italic* bold**"),
+            "<strong><i>italic</i> bold</strong>"
+        );
+        assert_eq!(
+            convert("*italic **bold***"),
+            "<i>italic <strong>bold</strong></i>"
+        );
+        assert_eq!(
+            convert("**bold *italic***"),
+            "<strong>bold <i>italic</i></strong>"
+        );
+    }
+
+    #[test]
+    fn test_not_a_horizontal_rule() {
+        let line = String::from("--- not a rule");
+        let token = tokenize_line(line);
+        match token {
+            Ok(Token::Paragraph { .. }) => (), // treat as paragraph
+            _ => panic!("Line with text after --- should not be HorizLine"),
+        }
+    }
+
+    #[test]
+    fn test_empty_or_garbage_line() {
+        let line = String::from("    "); // only spaces
+        let token = tokenize_line(line);
+        match token {
+            Ok(Token::BreakLine {}) => (),
+            _ => panic!("Whitespace-only line should be a BreakLine"),
+        }
+
+        let line = String::from("!@#$%^&*()");
+        let token = tokenize_line(line.clone());
+        match token {
+            Ok(Token::Paragraph { text }) => assert_eq!(inline::to_html(&text), "!@#$%^&amp;*()"),
+            _ => panic!("Garbage line should fallback to Paragraph or raw text"),
+        };
+    }
+
+    #[test]
+    fn test_tokenize_paragraph() {
+        let line = String::from("This is a paragraph.");
+        let token = tokenize_line(line).unwrap();
+        match token {
+            Token::Paragraph { text } => assert_eq!(inline::to_html(&text), "This is a paragraph."),
+            _ => panic!("Expected Paragraph token"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_ulist_item() {
+        let line = String::from("- List item");
+        let token = tokenize_line(line).unwrap();
+        match token {
+            Token::UListItem { text, .. } => assert_eq!(inline::to_html(&text), "List item"),
+            _ => panic!("Expected UListItem token"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_olist_item() {
+        let line = String::from("1. Ordered item");
+        let token = tokenize_line(line).unwrap();
+        match token {
+            Token::OListItem { text, .. } => assert_eq!(inline::to_html(&text), "Ordered item"),
+            _ => panic!("Expected OListItem token"),
+        }
+    }
+
+    #[test]
+    fn test_olist_marker_digit_cap() {
+        let line = String::from("1234567890. Too many digits");
+        let token = tokenize_line(line.clone()).unwrap();
+        match token {
+            Token::Paragraph { text } => assert_eq!(inline::to_html(&text), line),
+            _ => panic!("Markers longer than 9 digits should not start a list"),
+        };
+    }
+
+    #[test]
+    fn test_consecutive_lines_make_one_paragraph() {
+        let lines = [
+            "First *line*",
+            "second line",
+            "",
+            "New paragraph",
+            "```",
+            "code",
+            "lines",
+            "```",
+            "After code",
+            "and more",
+        ]
+        .iter()
+        .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec![
+                "<p>First <i>line</i> second line</p>",
+                "<br/>",
+                "<p>New paragraph</p>",
+                "<pre><code>",
+                "code",
+                "lines",
+                "</code></pre>",
+                "<p>After code and more</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_numbered_sentence_after_paragraph_stays_prose() {
+        let lines = vec![
+            String::from("Some history."),
+            String::from("1984. was the year"),
+        ];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(result, vec!["<p>Some history. 1984. was the year</p>"]);
+    }
+
+    #[test]
+    fn test_first_item_after_paragraph_starts_list() {
+        let lines = vec![String::from("Steps:"), String::from("1. real item")];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(
+            result,
+            vec!["<p>Steps:</p>", "<ol>", "<li>real item</li>", "</ol>"]
+        );
+    }
+
+    #[test]
+    fn test_numbered_list_after_blank_line() {
+        let lines = vec![
+            String::from("Some history."),
+            String::from(""),
+            String::from("1984. first"),
+            String::from("1985. second"),
+        ];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(
+            result,
+            vec![
+                "<p>Some history.</p>",
+                "<br/>",
+                "<ol start=\"1984\">",
+                "<li>first</li>",
+                "<li>second</li>",
+                "</ol>"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paragraph_interruption_rules() {
+        // (line, starts a block right after paragraph text, starts a block after a blank line)
+        let cases = [
+            ("- item", true, true),
+            ("```rust", true, true),
+            ("1. item", true, true),
+            ("2. item", false, true),
+            ("| --- | :---: |", false, false),
+            ("    let x = 1;", false, true),
+            ("plain text", false, false),
+        ];
+        for (line, interrupts, after_blank) in cases {
+            assert_eq!(interrupts_paragraph(line), interrupts, "{}", line);
+
+            let starts_block = |lines: Vec<&str>| {
+                let result = tokenize_text(lines.into_iter().map(String::from));
+                result[1..]
+                    .iter()
+                    .any(|html| !html.starts_with("<p>") && html != "<br/>")
+            };
+            assert_eq!(
+                starts_block(vec!["Paragraph text", line]),
+                interrupts,
+                "{}",
+                line
+            );
+            assert_eq!(
+                starts_block(vec!["Paragraph text", "", line]),
+                after_blank,
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_backslash_hard_break_mid_paragraph() {
+        let lines = vec![String::from("first line\\"), String::from("second line")];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(result, vec!["<p>first line<br/>second line</p>"]);
+    }
+
+    #[test]
+    fn test_soft_break_policies() {
+        let lines = ["one", "two  ", "three", "", "> quoted", "> lines"];
+        let convert = |options: ParserOptions| {
+            tokenize_text_with_options(lines.iter().map(|line| line.to_string()), &options)
+                .html_lines
+        };
+        let result = convert(ParserOptions {
+            soft_break_default: SoftBreak::Break,
+            soft_break_in_quotes: Some(SoftBreak::Space),
+            ..Default::default()
+        });
+        assert_eq!(result[0], "<p>one<br/>two<br/>three</p>");
+        assert_eq!(result[3], "<p>quoted lines</p>");
+        // Quotes follow the default when they have no policy of their own
+        let result = convert(ParserOptions {
+            soft_break_default: SoftBreak::Break,
+            ..Default::default()
+        });
+        assert_eq!(result[3], "<p>quoted<br/>lines</p>");
+        assert_eq!(
+            convert(ParserOptions::default())[0],
+            "<p>one two<br/>three</p>"
+        );
+    }
+
+    #[test]
+    fn test_backslash_at_end_of_paragraph_is_literal() {
+        let lines = vec![
+            String::from("first line\\"),
+            String::from(""),
+            String::from("last line\\"),
+        ];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(
+            result,
+            vec!["<p>first line\\</p>", "<br/>", "<p>last line\\</p>"]
+        );
+    }
+
+    #[test]
+    fn test_backslash_inside_code_block_is_kept() {
+        let lines = vec![
+            String::from("```"),
+            String::from("echo a \\"),
+            String::from("  && echo b"),
+            String::from("```"),
+        ];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(result[1], "echo a \\");
+    }
+
+    fn raw_fence_lines(info: &str) -> impl Iterator<Item = String> {
+        vec![
+            format!("```{}", info),
+            String::from("<div class=\"note\">&copy; *kept*</div>"),
+            String::from("```"),
+        ]
+        .into_iter()
+    }
+
+    #[test]
+    fn test_raw_fence_allowed() {
+        let options = ParserOptions {
+            allow_raw: true,
+            ..Default::default()
+        };
+        for info in ["=html", "raw"] {
+            let result = tokenize_text_with_options(raw_fence_lines(info), &options);
+            assert_eq!(
+                result.html_lines,
+                vec!["<div class=\"note\">&copy; *kept*</div>"]
+            );
+            assert!(result.warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_raw_fence_without_allow_raw() {
+        let result = tokenize_text_with_options(raw_fence_lines("=html"), &Default::default());
+        assert_eq!(
+            result.html_lines,
+            vec![
+                "<pre><code>",
+                "&lt;div class=\"note\"&gt;&amp;copy; *kept*&lt;/div&gt;",
+                "</code></pre>",
+            ]
+        );
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].kind, WarningKind::RawBlock);
+        assert_eq!(result.warnings[0].line, 1);
+    }
+
+    #[test]
+    fn test_plain_fence_with_allow_raw() {
+        let options = ParserOptions {
+            allow_raw: true,
+            ..Default::default()
+        };
+        let result = tokenize_text_with_options(raw_fence_lines("text"), &options);
+        assert_eq!(result.html_lines[0], "<pre><code class=\"language-text\">");
+        assert_eq!(result.html_lines[2], "</code></pre>");
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_trailing_spaces_hard_break() {
+        let lines = [
+            "two spaces  ",
+            "three *spaces*   ",
+            "one space ",
+            "end of paragraph  ",
+            "",
+            "```",
+            "code  ",
+            "kept\\",
+            "```",
+        ]
+        .iter()
+        .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec![
+                "<p>two spaces<br/>three <i>spaces</i><br/>one space end of paragraph</p>",
+                "<br/>",
+                "<pre><code>",
+                "code  ",
+                "kept\\",
+                "</code></pre>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_line_skips_inline_formatting() {
+        let options = ParserOptions {
+            max_line_length: 1_000,
+            ..Default::default()
+        };
+        let line = format!("<b>{}</b>", "**a** ".repeat(1_000));
+        let result = tokenize_text_with_options(vec![line.clone()].into_iter(), &options);
+        assert_eq!(result.html_lines.len(), 1);
+        assert!(!result.html_lines[0].contains("<strong>"));
+        assert!(result.html_lines[0].starts_with("<p>&lt;b&gt;**a** "));
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line, 1);
+        assert!(result.warnings[0].message.contains("characters long"));
+    }
+
+    #[test]
+    fn test_many_delimiters_skip_inline_formatting() {
+        let lines = vec![
+            String::from("fine **line**"),
+            String::new(),
+            "*".repeat(100_000),
+            String::new(),
+            "[".repeat(10_001),
+        ];
+        let result = tokenize_text_with_options(lines.into_iter(), &ParserOptions::default());
+        assert_eq!(result.html_lines[0], "<p>fine <strong>line</strong></p>");
+        assert_eq!(
+            result.html_lines[2],
+            format!("<p>{}</p>", "*".repeat(100_000))
+        );
+        assert_eq!(
+            result.html_lines[4],
+            format!("<p>{}</p>", "[".repeat(10_001))
+        );
+        let warned_lines: Vec<usize> = result.warnings.iter().map(|w| w.line).collect();
+        assert_eq!(warned_lines, vec![3, 5]);
+        assert!(result.warnings[0].message.contains("inline delimiters"));
+    }
+
+    #[test]
+    fn test_unterminated_brackets_at_delimiter_limit() {
+        // 10 000 delimiters, right at the limit so every inline pass still runs
+        let line = format!("{} a < b {}", "[".repeat(5_000), "(".repeat(5_000));
+        let result = tokenize_text_with_options(vec![line].into_iter(), &Default::default());
+        assert!(result.warnings.is_empty());
+        assert_eq!(
+            result.html_lines,
+            vec![format!(
+                "<p>{} a &lt; b {}</p>",
+                "[".repeat(5_000),
+                "(".repeat(5_000)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_many_links_on_one_line() {
+        let line = "[a](b) `c` ![d](e) ".repeat(1_000);
+        let html = convert_inline_markdown(&mut line.to_string());
+        assert_eq!(html.matches("<a href=\"b\">a</a>").count(), 1_000);
+        assert_eq!(html.matches("<code>c</code>").count(), 1_000);
+        assert_eq!(html.matches("<img src=\"e\" alt=\"d\">").count(), 1_000);
+    }
+
+    #[test]
+    fn test_limits_ignore_code_blocks() {
+        let options = ParserOptions {
+            max_line_length: 5,
+            ..Default::default()
+        };
+        let lines = vec![
+            String::from("```"),
+            String::from("a long line of code"),
+            String::from("```"),
+        ];
+        let result = tokenize_text_with_options(lines.into_iter(), &options);
+        assert_eq!(result.html_lines[1], "a long line of code");
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_quote_line_renders_blockquote() {
+        let quote = tokenize_line(String::from("> # Title")).unwrap();
+        assert_eq!(quote.to_string(), "<blockquote><h1>Title</h1></blockquote>");
+        let quote = tokenize_line(String::from("> > - item")).unwrap();
+        assert_eq!(
+            quote.to_string(),
+            "<blockquote><blockquote><ul><li>item</li></ul></blockquote></blockquote>"
+        );
+    }
+
+    #[test]
+    fn test_quote_lines_are_grouped() {
+        let lines = [
+            "> # Title",
+            "> Some *text*",
+            "> on two lines",
+            ">",
+            "> > Nested",
+            "> - one",
+            "> - two",
+            "",
+            "> Another quote",
+        ]
+        .iter()
+        .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec![
+                "<blockquote>",
+                "<h1>Title</h1>",
+                "<p>Some <i>text</i> on two lines</p>",
+                "<blockquote>",
+                "<p>Nested</p>",
+                "</blockquote>",
+                "<ul>",
+                "<li>one</li>",
+                "<li>two</li>",
+                "</ul>",
+                "</blockquote>",
+                "<br/>",
+                "<blockquote>",
+                "<p>Another quote</p>",
+                "</blockquote>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_quote_markers() {
+        let lines = ["> one", ">> two", ">>> three", "> > > *four*", "after"]
+            .iter()
+            .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec![
+                "<blockquote>",
+                "<p>one</p>",
+                "<blockquote>",
+                "<p>two</p>",
+                "<blockquote>",
+                "<p>three <i>four</i></p>",
+                "</blockquote>",
+                "</blockquote>",
+                "</blockquote>",
+                "<p>after</p>",
+            ]
+        );
+        // A `>` needs a space or another `>` after it
+        assert_eq!(
+            tokenize_line(String::from(">not a quote"))
+                .unwrap()
+                .to_string(),
+            "<p>&gt;not a quote</p>"
+        );
+        assert_eq!(quote_depth(">> > >>x"), 4);
+    }
+
+    #[test]
+    fn test_admonitions() {
+        let lines = [
+            "> [!NOTE]",
+            "> Useful information",
+            "> on two lines.",
+            ">",
+            "> - a list",
+            "",
+            "> [!warning]",
+            "> > Nested quote",
+            "",
+            "> > [!TIP]",
+            "> > Deep tip",
+        ]
+        .iter()
+        .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec![
+                "<div class=\"admonition note\"><p class=\"admonition-title\">Note</p>",
+                "<p>Useful information on two lines.</p>",
+                "<ul>",
+                "<li>a list</li>",
+                "</ul>",
+                "</div>",
+                "<br/>",
+                "<div class=\"admonition warning\"><p class=\"admonition-title\">Warning</p>",
+                "<blockquote>",
+                "<p>Nested quote</p>",
+                "</blockquote>",
+                "</div>",
+                "<br/>",
+                "<blockquote>",
+                "<div class=\"admonition tip\"><p class=\"admonition-title\">Tip</p>",
+                "<p>Deep tip</p>",
+                "</div>",
+                "</blockquote>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_admonition_stays_a_quote() {
+        let lines = ["> [!FOO]", "> Text", "", "> Text", "> [!NOTE]"]
+            .iter()
+            .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec![
+                "<blockquote>",
+                "<p>[!FOO] Text</p>",
+                "</blockquote>",
+                "<br/>",
+                "<blockquote>",
+                "<p>Text [!NOTE]</p>",
+                "</blockquote>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_containers() {
+        let lines = [
+            "::: warning",
+            "Mind the **gap**",
+            "- an item",
+            ":::: note open",
+            "Nested",
+            "::::",
+            ":::",
+            "",
+            ":::",
+            "",
+            "```",
+            ":::",
+            "```",
+            ":::",
+            "::: not a class!",
+        ]
+        .iter()
+        .map(|line| line.to_string());
+        let result = tokenize_text_with_options(lines, &ParserOptions::default());
+        assert_eq!(
+            result.html_lines,
+            vec![
+                "<div class=\"warning\">",
+                "<p>Mind the <strong>gap</strong></p>",
+                "<ul>",
+                "<li>an item</li>",
+                "</ul>",
+                "<div class=\"note open\">",
+                "<p>Nested</p>",
+                "</div>",
+                "</div>",
+                "<br/>",
+                "<div>",
+                "<br/>",
+                "<pre><code>",
+                ":::",
+                "</code></pre>",
+                "</div>",
+                "<p>::: not a class!</p>",
+            ]
+        );
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_details() {
+        let lines = [
+            ":::details Click to *expand*",
+            "First paragraph",
+            "",
+            "- an item",
+            "",
+            "```sh",
+            ":::",
+            ":::details not a summary",
+            "```",
+            "::: warning",
+            "Inside",
+            ":::",
+            ":::",
+            "::: details",
+            ":::",
+            ":::detailed",
+            ":::",
+        ]
+        .iter()
+        .map(|line| line.to_string());
+        let result = tokenize_text_with_options(lines, &ParserOptions::default());
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        assert_eq!(
+            result.html_lines,
+            vec![
+                "<details><summary>Click to <i>expand</i></summary>",
+                "<p>First paragraph</p>",
+                "<br/>",
+                "<ul>",
+                "<li>an item</li>",
+                "</ul>",
+                "<br/>",
+                "<pre><code class=\"language-sh\">",
+                ":::",
+                ":::details not a summary",
+                "</code></pre>",
+                "<div class=\"warning\">",
+                "<p>Inside</p>",
+                "</div>",
+                "</details>",
+                "<details>",
+                "</details>",
+                "<div class=\"detailed\">",
+                "</div>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_containers() {
+        let lines = ["Text", "::: note", "::: tip", "# Inside", "- item"]
+            .iter()
+            .map(|line| line.to_string());
+        let options = ParserOptions {
+            sections: true,
+            ..ParserOptions::default()
+        };
+        let result = tokenize_text_with_options(lines, &options);
+        assert_eq!(
+            result.html_lines,
+            vec![
+                "<p>Text</p>",
+                "<div class=\"note\">",
+                "<div class=\"tip\">",
+                "<section id=\"inside\">",
+                "<h1>Inside</h1>",
+                "<ul>",
+                "<li>item</li>",
+                "</ul>",
+                "</section>",
+                "</div>",
+                "</div>",
+            ]
+        );
+        let lines: Vec<usize> = result.warnings.iter().map(|warning| warning.line).collect();
+        assert_eq!(lines, [3, 2]);
+        assert_eq!(result.warnings[0].kind, WarningKind::UnclosedContainer);
+    }
+
+    fn quote_with_attribution(lines: &[&str]) -> Vec<String> {
+        let options = ParserOptions {
+            quote_attribution: true,
+            ..ParserOptions::default()
+        };
+        let lines = lines.iter().map(|line| line.to_string());
+        tokenize_text_with_options(lines, &options).html_lines
+    }
+
+    #[test]
+    fn test_quote_attribution_em_dash() {
+        assert_eq!(
+            quote_with_attribution(&[
+                "> The Analytical Engine weaves",
+                "> algebraic patterns.",
+                "> \u{2014} [Ada Lovelace](https://example.com/ada)",
+            ]),
+            vec![
+                "<blockquote>",
+                "<p>The Analytical Engine weaves algebraic patterns.</p>",
+                "<footer><cite><a href=\"https://example.com/ada\">Ada Lovelace</a></cite></footer>",
+                "</blockquote>",
+            ]
+        );
+        // Off by default
+        let lines = ["> Quote", "> \u{2014} Ada"]
+            .iter()
+            .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec!["<blockquote>", "<p>Quote \u{2014} Ada</p>", "</blockquote>"]
+        );
+    }
+
+    #[test]
+    fn test_quote_attribution_double_hyphen() {
+        assert_eq!(
+            quote_with_attribution(&["> Quote", ">", "> -- *the* source"]),
+            vec![
+                "<blockquote>",
+                "<p>Quote</p>",
+                "<footer><cite><i>the</i> source</cite></footer>",
+                "</blockquote>",
+            ]
+        );
+        assert_eq!(
+            quote_with_attribution(&["> Run it with", "> --verbose"]),
+            vec![
+                "<blockquote>",
+                "<p>Run it with --verbose</p>",
+                "</blockquote>"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quote_attribution_only_on_last_line() {
+        assert_eq!(
+            quote_with_attribution(&["> First", "> -- not a source", "> Last"]),
+            vec![
+                "<blockquote>",
+                "<p>First -- not a source Last</p>",
+                "</blockquote>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nesting_below_limit() {
+        let line = format!("{}text", "> ".repeat(31));
+        let result = tokenize_text_with_options(vec![line].into_iter(), &ParserOptions::default());
+        let html = result.html_lines.join("");
+        assert_eq!(html.matches("<blockquote>").count(), 31);
+        assert!(html.contains("<blockquote><p>text</p></blockquote>"));
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_nesting_at_limit() {
+        let line = format!("{}text", "> ".repeat(32));
+        let result = tokenize_text_with_options(vec![line].into_iter(), &ParserOptions::default());
+        assert_eq!(
+            result.html_lines.join("").matches("<blockquote>").count(),
+            32
+        );
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_nesting_far_beyond_limit() {
+        let options = ParserOptions {
+            max_nesting_depth: 4,
+            ..Default::default()
+        };
+        let line = format!("{}text", "> ".repeat(10_000));
+        let result = tokenize_text_with_options(vec![line].into_iter(), &options);
+        let html = result.html_lines.join("");
+        assert_eq!(html.matches("<blockquote>").count(), 4);
+        assert!(html.contains(&format!(
+            "<blockquote><p>{}text</p></blockquote>",
+            "&gt; ".repeat(9_996)
+        )));
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].message.contains("nesting depth 10000"));
+    }
+
+    #[test]
+    fn test_malformed_front_matter_is_skipped() {
+        let lines = [
+            "---",
+            "title: Post",
+            "tags: [a, b",
+            "  ]",
+            "---",
+            "# Title",
+            "```",
+        ];
+        let result = tokenize_text_with_options(
+            lines.iter().map(|line| line.to_string()),
+            &ParserOptions::default(),
+        );
+        assert_eq!(result.front_matter, None);
+        assert_eq!(result.html_lines[0], "<h1>Title</h1>");
+        let kinds: Vec<(WarningKind, usize)> = result
+            .warnings
+            .iter()
+            .map(|warning| (warning.kind, warning.line))
+            .collect();
+        // The other warnings still point at their line in the whole document
+        assert_eq!(
+            kinds,
+            [
+                (WarningKind::FrontMatter, 4),
+                (WarningKind::UnclosedFence, 7)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_front_matter_warns() {
+        let markdown = "---\ntitle: Post\n\n# Title";
+        let result =
+            tokenize_text_with_options(markdown.lines().map(String::from), &Default::default());
+        assert_eq!(result.front_matter, None);
+        assert_eq!(
+            result.html_lines,
+            vec!["<hr>", "<p>title: Post</p>", "<br/>", "<h1>Title</h1>"]
+        );
+        assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+        assert_eq!(result.warnings[0].kind, WarningKind::FrontMatter);
+        assert_eq!(result.warnings[0].line, 1);
+
+        // A closed block without any pair is a horizontal rule, not front matter
+        let markdown = "---\nHeading\n---";
+        let result =
+            tokenize_text_with_options(markdown.lines().map(String::from), &Default::default());
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn test_front_matter_is_not_rendered() {
+        let markdown = "---\ndate: 2024-06-01\n---\n# Title\n### Jump";
+        let result =
+            tokenize_text_with_options(markdown.lines().map(String::from), &Default::default());
+        assert_eq!(result.html_lines, vec!["<h1>Title</h1>", "<h3>Jump</h3>"]);
+        assert_eq!(result.front_matter.unwrap().get("date"), Some("2024-06-01"));
+        assert_eq!(result.warnings[0].line, 5);
+    }
+
+    #[test]
+    fn test_reference_links() {
+        let markdown = "Use [rustc][1], see [Guide].\n\n[1]: https://rust-lang.org\n[guide]: /guide 'The guide'";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<p>Use <a href=\"https://rust-lang.org\">rustc</a>, see <a href=\"/guide\" title=\"The guide\">Guide</a>.</p>",
+                "<br/>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_reference_definition() {
+        let markdown = "---\ntitle: Docs\n---\nSee [docs].\n\n[docs]: /first\n[Docs]: /second";
+        let result =
+            tokenize_text_with_options(markdown.lines().map(String::from), &Default::default());
+        assert_eq!(
+            result.html_lines[0],
+            "<p>See <a href=\"/first\">docs</a>.</p>"
+        );
+        assert_eq!(
+            result.warnings,
+            vec![ParseWarning {
+                line: 7,
+                span: None,
+                kind: WarningKind::Reference,
+                message: String::from(
+                    "link reference [docs] is already defined on line 6, this definition is ignored"
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unused_reference_definition() {
+        let markdown = "See [docs].\n\n[docs]: /docs\n[old]: /old";
+        let lint = ParserOptions {
+            lint: true,
+            ..Default::default()
+        };
+        let result = parse_with_options(markdown.lines().map(String::from), &lint);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(
+            result.warnings[0].to_string(),
+            "line 4: link reference [old] is defined but never used"
+        );
+        // Unused definitions are only reported with `lint`
+        let result = parse_with_options(markdown.lines().map(String::from), &Default::default());
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_inline_markup_warnings() {
+        let markdown =
+            "---\ntitle: Notes\n---\nInstall it **now and restart\n\n    indented **code\n\n\
+                        - 日本語のテキスト [リンク](https://example.com の説明\n\n\
+                        ```\nno **check** in [code](blocks\n```";
+        let lint = ParserOptions {
+            lint: true,
+            ..Default::default()
+        };
+        let result = parse_with_options(markdown.lines().map(String::from), &lint);
+        let lines: Vec<&str> = markdown.lines().collect();
+        let warnings: Vec<(usize, &str, &str)> = result
+            .warnings
+            .iter()
+            .map(|warning| {
+                let span = warning.span.clone().unwrap();
+                (
+                    warning.line,
+                    &lines[warning.line - 1][span],
+                    warning.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (4, "**", "`**` is never closed, kept as text"),
+                (
+                    8,
+                    "[リンク](https://example.com の説明",
+                    "link destination is never closed, missing `)`"
+                ),
+            ]
+        );
+        assert!(result
+            .warnings
+            .iter()
+            .all(|w| w.kind == WarningKind::Inline));
+        // Inline problems are only reported with `lint`
+        let result = parse_with_options(markdown.lines().map(String::from), &Default::default());
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_reference_definitions_clean() {
+        let markdown = "[Rust][1] and [guide][].\n\n[1]: https://rust-lang.org\n[guide]: /guide";
+        let lint = ParserOptions {
+            lint: true,
+            ..Default::default()
+        };
+        let result = parse_with_options(markdown.lines().map(String::from), &lint);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_heading_outline_compliant() {
+        let markdown = "# Title\n## Usage\n### Flags\n## Install\n```\n# comment\n```";
+        let result = parse_with_options(markdown.lines().map(String::from), &Default::default());
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_heading_outline_level_jump() {
+        let markdown = "# Title\n## Usage\nText\n#### Flags";
+        let result = parse_with_options(markdown.lines().map(String::from), &Default::default());
+        assert_eq!(
+            result.warnings,
+            vec![ParseWarning {
+                line: 4,
+                span: None,
+                kind: WarningKind::HeadingStructure,
+                message: String::from("heading level jumps from h2 to h4, use h3 instead"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_heading_outline_double_h1() {
+        let markdown = "# Title\n## Usage\n# Another title";
+        let result = parse_with_options(markdown.lines().map(String::from), &Default::default());
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].line, 3);
+        assert_eq!(
+            result.warnings[0].to_string(),
+            "line 3: another h1, the document already has one on line 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_codeblock_structure() {
+        let lines = vec![
+            String::from("```"),
+            String::from("# not a header"),
+            String::from("```"),
+        ];
+        let tokens = parse(lines.into_iter());
+        assert_eq!(
+            tokens,
+            vec![
+                Token::CodeBlockStart { language: None },
+                Token::SimpleText {
+                    text: String::from("# not a header")
+                },
+                Token::CodeBlockEnd {},
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blank_lines_in_code_blocks() {
+        let lines = ["```", "", "first", "", "  ", "last", "", "```"];
+        let tokens = parse(lines.iter().map(|line| line.to_string()));
+        let code_line = |text: &str| Token::SimpleText {
+            text: String::from(text),
+        };
+        assert_eq!(
+            tokens,
+            vec![
+                Token::CodeBlockStart { language: None },
+                code_line(""),
+                code_line("first"),
+                code_line(""),
+                code_line("  "),
+                code_line("last"),
+                code_line(""),
+                Token::CodeBlockEnd {},
+            ]
+        );
+        assert!(!render_html(&tokens).contains(&String::from("<br/>")));
+    }
+
+    #[test]
+    fn test_disabled_block_constructs() {
+        let fixture = [
+            "# Title",
+            "",
+            "> quoted",
+            "",
+            "- item",
+            "",
+            "1. first",
+            "",
+            "---",
+            "",
+            "```",
+            "code",
+            "```",
+            "",
+            "| a | b |",
+            "|---|---|",
+            "| 1 | 2 |",
+        ];
+        let convert = |options: &ParserOptions| {
+            tokenize_text_with_options(fixture.iter().map(|line| line.to_string()), options)
+                .html_lines
+                .join("\n")
+        };
+        type Disable = fn(&mut ParserOptions);
+        let constructs: [(Disable, &[&str], &str); 6] = [
+            (|o| o.headings = false, &["<h1"], "<p># Title</p>"),
+            (
+                |o| o.blockquotes = false,
+                &["<blockquote>"],
+                "<p>&gt; quoted</p>",
+            ),
+            (|o| o.lists = false, &["<ul>", "<ol"], "<p>1. first</p>"),
+            (|o| o.horizontal_rules = false, &["<hr>"], "<p>---</p>"),
+            (|o| o.code_fences = false, &["<pre>"], "<p>``` code ```</p>"),
+            (|o| o.tables = false, &["<table>"], "<p>| a | b |"),
+        ];
+        let all_enabled = convert(&ParserOptions::default());
+        for (_, markers, _) in &constructs {
+            assert!(markers.iter().all(|marker| all_enabled.contains(marker)));
+        }
+
+        for (index, (disable, _, paragraph)) in constructs.iter().enumerate() {
+            let mut options = ParserOptions::default();
+            disable(&mut options);
+            let html = convert(&options);
+            assert!(html.contains(paragraph), "{}", html);
+            for (other, (_, markers, _)) in constructs.iter().enumerate() {
+                let kept = markers.iter().all(|marker| html.contains(marker));
+                let dropped = !markers.iter().any(|marker| html.contains(marker));
+                assert!(if other == index { dropped } else { kept }, "{}", html);
+            }
+        }
+    }
+
+    #[test]
+    fn test_highlighted_code_blocks() {
+        let options = ParserOptions {
+            highlight: true,
+            ..Default::default()
+        };
+        let lines = [
+            "```rust",
+            "let s = \"<b>\";",
+            "```",
+            "```klingon",
+            "<b>",
+            "```",
+            "```rust",
+            "fn unclosed() {}",
+        ];
+        let html = tokenize_text_with_options(lines.iter().map(|line| line.to_string()), &options)
+            .html_lines;
+        assert_eq!(html.len(), 9);
+        if highlight::is_available() {
+            assert!(html[0].starts_with("<pre style=\"background-color:"));
+            assert!(html[1].contains("<span style=") && html[1].contains("&lt;b&gt;"));
+            assert!(html[6].starts_with("<pre style="));
+        } else {
+            assert_eq!(html[0], "<pre><code class=\"language-rust\">");
+            assert_eq!(html[1], "let s = \"&lt;b&gt;\";");
+        }
+        assert_eq!(html[2], "</code></pre>");
+        // Unknown languages stay plain code
+        assert_eq!(
+            html[3..6],
+            [
+                "<pre><code class=\"language-klingon\">",
+                "&lt;b&gt;",
+                "</code></pre>"
+            ]
+        );
+        assert_eq!(html[8], "</code></pre>");
+    }
+
+    #[test]
+    fn test_setext_headings() {
+        let lines = [
+            "Title",
+            "=====",
+            "",
+            "A *subtitle*  ",
+            "on two lines",
+            "---",
+            "",
+            "---",
+            "",
+            "Text",
+            "",
+            "==",
+            "",
+            "Not a heading",
+            "= =",
+        ];
+        let result = tokenize_text_with_options(
+            lines.iter().map(|line| line.to_string()),
+            &ParserOptions::default(),
+        );
+        assert_eq!(
+            result.html_lines,
+            vec![
+                "<h1>Title</h1>",
+                "<br/>",
+                "<h2>A <i>subtitle</i> on two lines</h2>",
+                "<br/>",
+                "<hr>",
+                "<br/>",
+                "<p>Text</p>",
+                "<br/>",
+                "<p>==</p>",
+                "<br/>",
+                "<p>Not a heading = =</p>",
+            ]
+        );
+        // The heading starts on its first line of text
+        let result = tokenize_text_with_options(
+            ["# One", "", "Again", "====="]
+                .iter()
+                .map(|line| line.to_string()),
+            &ParserOptions::default(),
+        );
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].kind, WarningKind::HeadingStructure);
+        assert_eq!(result.warnings[0].line, 3);
+    }
+
+    #[test]
+    fn test_indented_code_blocks() {
+        let lines = [
+            "Some code:",
+            "",
+            "    if a < b && c {",
+            "\treturn \"*not* emphasis\";",
+            "",
+            "      }",
+            "",
+            "Text again",
+        ];
+        let result = tokenize_text(lines.iter().map(|line| line.to_string()));
+        assert_eq!(
+            result,
+            vec![
+                "<p>Some code:</p>",
+                "<br/>",
+                "<pre><code>",
+                "if a &lt; b &amp;&amp; c {",
+                "return \"*not* emphasis\";",
+                "",
+                "  }",
+                "</code></pre>",
+                "<br/>",
+                "<p>Text again</p>",
+            ]
+        );
+
+        // Without a blank line above, or inside a list, indented lines aren't code
+        for lines in [
+            vec!["Text", "    continuing the paragraph"],
+            vec!["- item", "", "    continuation of the item"],
+            vec![
+                "1. item",
+                "    - nested item",
+                "",
+                "        deeper continuation",
+            ],
+        ] {
+            let result = tokenize_text(lines.iter().map(|line| line.to_string()));
+            assert!(
+                !result.contains(&String::from("<pre><code>")),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_tilde_and_long_fences() {
+        let lines = [
+            "````markdown",
+            "```rust",
+            "let x = 1;",
+            "```",
+            "````",
+            "~~~",
+            "```",
+            "[a]: /in-code",
+            "~~~~",
+            "*text*",
+        ];
+        let result = tokenize_text_with_options(
+            lines.iter().map(|line| line.to_string()),
+            &ParserOptions::default(),
+        );
+        assert_eq!(
+            result.html_lines,
+            [
+                "<pre><code class=\"language-markdown\">",
+                "```rust",
+                "let x = 1;",
+                "```",
+                "</code></pre>",
+                "<pre><code>",
+                "```",
+                "[a]: /in-code",
+                "</code></pre>",
+                "<p><i>text</i></p>",
+            ]
+        );
+        assert!(result.warnings.is_empty());
+        // A shorter fence doesn't close the block
+        let lines = ["````", "```", "code"];
+        let result = tokenize_text_with_options(
+            lines.iter().map(|line| line.to_string()),
+            &ParserOptions::default(),
+        );
+        assert_eq!(result.html_lines[1], "```");
+        assert_eq!(result.warnings[0].kind, WarningKind::UnclosedFence);
+        assert_eq!(result.warnings[0].line, 1);
+    }
+
+    #[test]
+    fn test_code_block_language_class() {
+        let lines = [
+            "```rust,no_run",
+            "let x = 1;",
+            "```",
+            "``` python  extra words",
+            "```",
+            "```",
+            "plain",
+            "```",
+            "```\"><script>",
+            "```",
+        ];
+        let html = tokenize_text(lines.iter().map(|line| line.to_string()));
+        let starts: Vec<&String> = html
+            .iter()
+            .filter(|line| line.starts_with("<pre>"))
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                "<pre><code class=\"language-rust\">",
+                "<pre><code class=\"language-python\">",
+                "<pre><code>",
+                "<pre><code class=\"language-&quot;&gt;&lt;script&gt;\">",
+            ]
+        );
+        assert_eq!(html[1], "let x = 1;");
+    }
+
+    #[test]
+    fn test_parse_then_render_matches_tokenize_text() {
+        let markdown = "# Title\n\nSome *text*\n1. one\n2. two\n```\ncode\n```\n> quote";
+        let lines = || markdown.lines().map(String::from);
+        assert_eq!(render_html(&parse(lines())), tokenize_text(lines()));
+    }
+
+    #[test]
+    fn test_embeds_in_document() {
+        let options = ParserOptions {
+            embeds: true,
+            ..Default::default()
+        };
+        let lines = vec![
+            String::from("https://youtu.be/dQw4w9WgXcQ"),
+            String::from("{{youtube dQw4w9WgXcQ}}"),
+            String::from("https://example.com/video"),
+            String::new(),
+            String::from("See https://youtu.be/dQw4w9WgXcQ"),
+        ];
+        let embed = "<div class=\"video-embed\"><iframe src=\"https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ\" allowfullscreen></iframe></div>";
+        let result = tokenize_text_with_options(lines.clone().into_iter(), &options);
+        assert_eq!(
+            result.html_lines,
+            vec![
+                embed,
+                embed,
+                "<p><a href=\"https://example.com/video\">https://example.com/video</a></p>",
+                "<br/>",
+                "<p>See https://youtu.be/dQw4w9WgXcQ</p>",
+            ]
+        );
+
+        // Embeds are opt-in
+        let result = tokenize_text(lines.into_iter());
+        assert!(result[0].contains(" {{youtube dQw4w9WgXcQ}} "));
+    }
+
+    #[test]
+    fn test_tokenize_text_codeblock_sequence() {
+        let lines = vec![
+            String::from("```"),
+            String::from("code line 1"),
+            String::from("code line 2"),
+            String::from("```"),
+        ];
+        let tokens = tokenize_text(lines.into_iter());
+        assert_eq!(tokens[0], "<pre><code>");
+        assert_eq!(tokens[1], "code line 1");
+        assert_eq!(tokens[2], "code line 2");
+        assert_eq!(tokens[3], "</code></pre>");
+    }
+
+    #[test]
+    fn test_tokenize_horiz_line() {
+        let line = String::from("---");
+        let token = tokenize_line(line).unwrap();
+        match token {
+            Token::HorizLine {} => (),
+            _ => panic!("Expected HorizLine token"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_break_line() {
+        let line = String::from("");
+        let token = tokenize_line(line).unwrap();
+        match token {
+            Token::BreakLine {} => (),
+            _ => panic!("Expected BreakLine token"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_bold_and_italic() {
+        let mut line = String::from("This is **bold** and *italic*.");
+        let html = super::convert_inline_markdown(&mut line);
+        assert_eq!(html, "This is <strong>bold</strong> and <i>italic</i>.");
+    }
+
+    #[test]
+    fn test_tokenize_link() {
+        let mut line = String::from("A [link](https://example.com) here.");
+        let html = super::convert_inline_markdown(&mut line);
+        assert_eq!(html, "A <a href=\"https://example.com\">link</a> here.");
+    }
+
+    #[test]
+    fn test_convert_inline_markdown_bold() {
+        let mut line = String::from("This is **bold** text.");
+        let result = convert_inline_markdown(&mut line);
+        assert_eq!(result, "This is <strong>bold</strong> text.");
+    }
+
+    #[test]
+    fn test_convert_inline_markdown_italic() {
+        let mut line = String::from("This is *italic* text.");
+        let result = convert_inline_markdown(&mut line);
+        assert_eq!(result, "This is <i>italic</i> text.");
+    }
+
+    #[test]
+    fn test_tokenize_text_simple() {
+        let markdown_lines = vec![
+            String::from("# Header"),
+            String::from("This is a paragraph."),
+            String::from("- List item"),
+        ];
+        let result = tokenize_text(markdown_lines.into_iter());
+        assert_eq!(
+            result,
+            vec![
+                "<h1>Header</h1>",
+                "<p>This is a paragraph.</p>",
+                "<ul>",
+                "<li>List item</li>",
+                "</ul>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_nested_ulist() {
+        let markdown = "- a\n  - b\n    - c\n- d\n    - e\nText";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<ul>",
+                "<li>a",
+                "<ul>",
+                "<li>b",
+                "<ul>",
+                "<li>c</li>",
+                "</ul>",
+                "</li>",
+                "</ul>",
+                "</li>",
+                "<li>d",
+                "<ul>",
+                "<li>e</li>",
+                "</ul>",
+                "</li>",
+                "</ul>",
+                "<p>Text</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_nested_ulist_unwinds_at_end() {
+        let markdown = "- a\n\t- b\n  \t- c";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(result.iter().filter(|l| *l == "<ul>").count(), 3);
+        assert_eq!(result.iter().filter(|l| *l == "</ul>").count(), 3);
+        assert_eq!(result.last().unwrap(), "</ul>");
+    }
+
+    #[test]
+    fn test_tokenize_text_nested_olist_and_mixed() {
+        let markdown = "1. a\n   1. b\n      - c\n2. d\nText";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<ol>",
+                "<li>a",
+                "<ol>",
+                "<li>b",
+                "<ul>",
+                "<li>c</li>",
+                "</ul>",
+                "</li>",
+                "</ol>",
+                "</li>",
+                "<li>d</li>",
+                "</ol>",
+                "<p>Text</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_table() {
+        let markdown = "Intro\n| Name | Qty |  Note |\n|:-----|:---:|------:|\n| **a** | 1 |\n| b | 2 | x | extra\nAfter";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<p>Intro</p>",
+                "<table>",
+                "<thead>",
+                "<tr><th scope=\"col\" style=\"text-align:left\">Name</th><th scope=\"col\" style=\"text-align:center\">Qty</th><th scope=\"col\" style=\"text-align:right\">Note</th></tr>",
+                "</thead>",
+                "<tbody>",
+                "<tr><td style=\"text-align:left\"><strong>a</strong></td><td style=\"text-align:center\">1</td><td style=\"text-align:right\"></td></tr>",
+                "<tr><td style=\"text-align:left\">b</td><td style=\"text-align:center\">2</td><td style=\"text-align:right\">x</td></tr>",
+                "</tbody>",
+                "</table>",
+                "<p>After</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_table_without_body() {
+        let markdown = "a | b\n--- | ---\n\n| not | a table |\n| text |";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<table>",
+                "<thead>",
+                "<tr><th scope=\"col\">a</th><th scope=\"col\">b</th></tr>",
+                "</thead>",
+                "</table>",
+                "<br/>",
+                "<p>| not | a table | | text |</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_task_list() {
+        let markdown = "- [ ] Buy *milk*\n- [X] Call Bob\n  - [x] nested\n- [link](a.md)\n- [y] not a task\n- [ ]no space\n\nDone [ ] or [x] here";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<ul>",
+                "<li><input type=\"checkbox\" disabled> Buy <i>milk</i></li>",
+                "<li><input type=\"checkbox\" checked disabled> Call Bob",
+                "<ul>",
+                "<li><input type=\"checkbox\" checked disabled> nested</li>",
+                "</ul>",
+                "</li>",
+                "<li><a href=\"a.md\">link</a></li>",
+                "<li>[y] not a task</li>",
+                "<li>[ ]no space</li>",
+                "</ul>",
+                "<br/>",
+                "<p>Done [ ] or [x] here</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_olist_start() {
+        let markdown = "5. five\n9. nine\n\n1. one\n2. two\n   3. three";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<ol start=\"5\">",
+                "<li>five</li>",
+                "<li>nine</li>",
+                "</ol>",
+                "<br/>",
+                "<ol>",
+                "<li>one</li>",
+                "<li>two",
+                "<ol start=\"3\">",
+                "<li>three</li>",
+                "</ol>",
+                "</li>",
+                "</ol>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_list_kind_switch_at_same_depth() {
+        let markdown = "- a\n  - b\n  1. c\n- d\n1. e";
+        let result = tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(
+            result,
+            vec![
+                "<ul>",
+                "<li>a",
+                "<ul>",
+                "<li>b</li>",
+                "</ul>",
+                "<ol>",
+                "<li>c</li>",
+                "</ol>",
+                "</li>",
+                "<li>d</li>",
+                "</ul>",
+                "<ol>",
+                "<li>e</li>",
+                "</ol>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_text_ulist_is_wrapped() {
+        let markdown_lines = vec![
+            String::from("- a"),
+            String::from("- b"),
+            String::from(""),
+            String::from("Some text"),
+            String::from("1. one"),
+            String::from("After"),
+        ];
+        let result = tokenize_text(markdown_lines.into_iter());
+        assert_eq!(
+            result,
+            vec![
+                "<ul>",
+                "<li>a</li>",
+                "<li>b</li>",
+                "</ul>",
+                "<br/>",
+                "<p>Some text</p>",
+                "<ol>",
+                "<li>one</li>",
+                "</ol>",
+                "<p>After</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_code_basic() {
+        let mut line = String::from("This is `code`.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(html, "This is <code>code</code>.");
+    }
+
+    #[test]
+    fn test_inline_code_multiple() {
+        let mut line = String::from("`a` and `b` are variables.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(html, "<code>a</code> and <code>b</code> are variables.");
+    }
+
+    #[test]
+    fn test_inline_code_many_spans() {
+        let mut line = String::from("`a`, `b`, `c` and `d <e>` with **`f`** and ``g ` h``.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(
+            html,
+            "<code>a</code>, <code>b</code>, <code>c</code> and <code>d &lt;e&gt;</code> \
+             with <strong><code>f</code></strong> and <code>g ` h</code>."
+        );
+    }
+
+    #[test]
+    fn test_inline_code_adjacent_spans() {
+        // A run of two backticks doesn't close a span opened by one
+        let mut line = String::from("`a``b`");
+        assert_eq!(convert_inline_markdown(&mut line), "<code>a``b</code>");
+        let mut line = String::from("`a`-`b``c`");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<code>a</code>-<code>b``c</code>"
+        );
+        let mut line = String::from("`a` `b`");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<code>a</code> <code>b</code>"
+        );
+    }
+
+    #[test]
+    fn test_inline_code_lone_backtick() {
+        let mut line = String::from("Press the ` key, then **save**.");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "Press the ` key, then <strong>save</strong>."
+        );
+    }
+
+    #[test]
+    fn test_inline_code_mismatched_runs() {
+        // Runs only pair with a run of the same length, the others are literal
+        let mut line = String::from("``not code` here");
+        assert_eq!(convert_inline_markdown(&mut line), "``not code` here");
+        let mut line = String::from("`a`` b ``c`");
+        assert_eq!(convert_inline_markdown(&mut line), "<code>a`` b ``c</code>");
+        let mut line = String::from("`` a ` b ``` c ``");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<code>a ` b ``` c</code>"
+        );
+    }
+
+    #[test]
+    fn test_inline_code_many_unmatched_runs() {
+        // Runs of 1 to 300 backticks, none of them closed: 45k backticks of text
+        let line: String = (1..=300).map(|n| "`".repeat(n) + " x ").collect();
+        assert_eq!(convert_inline_markdown(&mut line.clone()), line);
+        let run = "`".repeat(301);
+        let mut closed = format!("{}{}a{}", line, run, run);
+        assert_eq!(
+            convert_inline_markdown(&mut closed),
+            format!("{}<code>a</code>", line)
+        );
+    }
+
+    #[test]
+    fn test_inline_code_space_stripping() {
+        let cases = [
+            ("`` ` ``", "<code>`</code>"),
+            ("`` `foo` ``", "<code>`foo`</code>"),
+            ("` code `", "<code>code</code>"),
+            ("`  two  `", "<code> two </code>"),
+            ("` leading`", "<code> leading</code>"),
+            ("`   `", "<code>   </code>"),
+        ];
+        for (markdown, html) in cases {
+            let mut line = String::from(markdown);
+            assert_eq!(convert_inline_markdown(&mut line), html, "{}", markdown);
+        }
+    }
+
+    #[test]
+    fn test_inline_code_with_special_chars() {
+        let mut line = String::from("Use `x = y + z;` in your code.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(html, "Use <code>x = y + z;</code> in your code.");
+    }
+
+    #[test]
+    fn test_inline_code_unclosed() {
+        let mut line = String::from("This is `not closed.");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(html, "This is `not closed.");
+    }
+
+    #[test]
+    fn test_character_references() {
+        let mut line = String::from("Wait&hellip; 1990&ndash;2000 &#8212; &notareference;");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(
+            html,
+            "Wait&hellip; 1990&ndash;2000 &#8212; &amp;notareference;"
+        );
+    }
+
+    #[test]
+    fn test_character_reference_inside_inline_code() {
+        let mut line = String::from("Type `&copy;` to get &copy;");
+        let html = convert_inline_markdown(&mut line);
+        assert_eq!(html, "Type <code>&amp;copy;</code> to get &copy;");
+    }
+
+    #[test]
+    fn test_character_reference_inside_code_block() {
+        let lines = vec![
+            String::from("```"),
+            String::from("a &amp;&amp; b"),
+            String::from("```"),
+        ];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(result[1], "a &amp;amp;&amp;amp; b");
+    }
+
+    #[test]
+    fn test_code_block_escapes_html() {
+        let lines = vec![
+            String::from("```c"),
+            String::from("#include <stdio.h>"),
+            String::from("if (a > b && c) puts(\"&copy;\");"),
+            String::from("```"),
+        ];
+        let result = tokenize_text(lines.into_iter());
+        assert_eq!(result[1], "#include &lt;stdio.h&gt;");
+        assert_eq!(
+            result[2],
+            "if (a &gt; b &amp;&amp; c) puts(\"&amp;copy;\");"
+        );
+    }
+
+    #[test]
+    fn test_inline_code_escapes_html() {
+        let mut line = String::from("A `Vec<String>` and `a && b`, not &copy; `&amp;`");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "A <code>Vec&lt;String&gt;</code> and <code>a &amp;&amp; b</code>, \
+             not &copy; <code>&amp;amp;</code>"
+        );
+        // The content of code is never formatted
+        let mut line = String::from("`**not bold** [x](y)` **bold**");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<code>**not bold** [x](y)</code> <strong>bold</strong>"
+        );
+    }
+
+    #[test]
+    fn test_formatting_skips_code_spans() {
+        let mut line = String::from(
+            "*deref* `let x = *ptr;` then `a[0](b)`, `__init__`, `~~old~~` and `![i](x.png)`",
+        );
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<i>deref</i> <code>let x = *ptr;</code> then <code>a[0](b)</code>, \
+             <code>__init__</code>, <code>~~old~~</code> and <code>![i](x.png)</code>"
+        );
+        // A delimiter inside code never closes the emphasis around it
+        let mut line = String::from("*a `b* c` d*");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<i>a <code>b* c</code> d</i>"
+        );
+    }
+
+    #[test]
+    fn test_windows_glob_is_literal() {
+        let mut line = String::from("**Open** C:\\Users\\me\\*.md *now*");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<strong>Open</strong> C:\\Users\\me\\*.md <i>now</i>"
+        );
     }
 
     #[test]
-    fn test_ulist_missing_space() {
-        let line = String::from("-List item");
-        let token = tokenize_line(line.clone()).unwrap();
-        match token {
-            Token::Paragraph { text } => assert_eq!(text, line),
-            _ => panic!("Should not parse header without space"),
-        };
+    fn test_posix_glob_is_literal() {
+        let mut line = String::from("*Match* src/**/*.rs and *see src/*.md*");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<i>Match</i> src/**/*.rs and <i>see src/*.md</i>"
+        );
     }
 
     #[test]
-    fn test_olist_missing_space() {
-        let line = String::from("1.Ordered item");
-        let token = tokenize_line(line.clone()).unwrap();
-        match token {
-            Token::Paragraph { text } => assert_eq!(text, line),
-            _ => panic!("Should not parse ordered list without space"),
-        };
+    fn test_file_uri_is_literal() {
+        let mut line = String::from("**Saved to file:///C:/temp/*.log**, *done*.");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<strong>Saved to file:///C:/temp/*.log</strong>, <i>done</i>."
+        );
     }
 
     #[test]
-    fn test_unclosed_code_block() {
-        let lines = vec![String::from("```"), String::from("code but never ends")];
-        let tokens = tokenize_text(lines.into_iter());
-        assert!(
-            tokens.last().unwrap() != "</code></pre>",
-            "Should not close unclosed code block"
+    fn test_literal_paths_opt_out() {
+        let options = ParserOptions {
+            literal_paths: false,
+            ..Default::default()
+        };
+        let mut line = String::from("src/*/x/*.rs");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &options),
+            "src/<i>/x/</i>.rs"
         );
     }
 
     #[test]
-    fn test_unclosed_bold() {
-        let mut line = String::from("This is **bold text.");
-        let html = convert_inline_markdown(&mut line);
+    fn test_text_is_escaped() {
+        let lines = [
+            "# A <b>title</b>",
+            "5 < 10 && x > 3 is **true** &copy;",
+            "- see [a & b](/x?a=1&b=2) <i>raw</i>",
+            "> quoted <script>",
+        ]
+        .iter()
+        .map(|line| line.to_string());
         assert_eq!(
-            html, "This is **bold text.",
-            "Unclosed bold should remain raw"
+            tokenize_text(lines),
+            vec![
+                "<h1>A &lt;b&gt;title&lt;/b&gt;</h1>",
+                "<p>5 &lt; 10 &amp;&amp; x &gt; 3 is <strong>true</strong> &copy;</p>",
+                "<ul>",
+                "<li>see <a href=\"/x?a=1&amp;b=2\">a &amp; b</a> &lt;i&gt;raw&lt;/i&gt;</li>",
+                "</ul>",
+                "<blockquote>",
+                "<p>quoted &lt;script&gt;</p>",
+                "</blockquote>",
+            ]
         );
     }
 
     #[test]
-    fn test_unclosed_italic() {
-        let mut line = String::from("This is *italic text.");
-        let html = convert_inline_markdown(&mut line);
+    fn test_underscore_emphasis() {
+        let convert = |line: &str| convert_inline_markdown(&mut line.to_string());
         assert_eq!(
-            html, "This is *italic text.",
-            "Unclosed italic should remain raw"
+            convert("_italic_ and __bold__"),
+            "<i>italic</i> and <strong>bold</strong>"
+        );
+        assert_eq!(
+            convert("_italic with **bold**_"),
+            "<i>italic with <strong>bold</strong></i>"
+        );
+        assert_eq!(
+            convert("__bold with *italic*__ and **bold _too_**"),
+            "<strong>bold with <i>italic</i></strong> and <strong>bold <i>too</i></strong>"
         );
+        assert_eq!(convert("(_aside_)."), "(<i>aside</i>).");
     }
 
     #[test]
-    fn test_malformed_link_missing_closing_paren() {
-        let mut line = String::from("A [link](https://example.com here.");
-        let html = convert_inline_markdown(&mut line);
+    fn test_underscores_inside_words() {
+        let convert = |line: &str| convert_inline_markdown(&mut line.to_string());
         assert_eq!(
-            html, "A [link](https://example.com here.",
-            "Malformed link should not convert"
+            convert("call snake_case_name or __init__ on MAX_VALUE_2"),
+            "call snake_case_name or <strong>init</strong> on MAX_VALUE_2"
+        );
+        assert_eq!(
+            convert("a_b _c_d and _ spaced _"),
+            "a_b _c_d and _ spaced _"
         );
+        assert_eq!(convert("see my_file_"), "see my_file_");
     }
 
     #[test]
-    fn test_malformed_link_missing_closing_bracket() {
-        let mut line = String::from("A link](https://example.com) here.");
-        let html = convert_inline_markdown(&mut line);
+    fn test_strikethrough() {
+        let convert = |line: &str| convert_inline_markdown(&mut line.to_string());
+        assert_eq!(convert("~~deprecated~~ API"), "<del>deprecated</del> API");
         assert_eq!(
-            html, "A link](https://example.com) here.",
-            "Malformed link should not convert"
+            convert("**bold ~~and struck~~**"),
+            "<strong>bold <del>and struck</del></strong>"
+        );
+        assert_eq!(convert("~~*both*~~"), "<del><i>both</i></del>");
+        assert_eq!(
+            convert("`~~code~~` \\~~escaped~~"),
+            "<code>~~code~~</code> ~~escaped~~"
         );
     }
 
     #[test]
-    fn test_misnested_formatting() {
-        let mut line = String::from("This is *italic and **bold*** text**.");
-        let html = convert_inline_markdown(&mut line);
+    fn test_strikethrough_literal_tildes() {
+        let convert = |line: &str| convert_inline_markdown(&mut line.to_string());
+        assert_eq!(convert("~1 km and ~2 km"), "~1 km and ~2 km");
+        assert_eq!(convert("~~unclosed"), "~~unclosed");
+        assert_eq!(convert("a ~~ b ~~ c"), "a ~~ b ~~ c");
+        assert_eq!(convert("~~~rust"), "~~~rust");
+        assert_eq!(convert("~~~not~~~"), "~~~not~~~");
+        assert_eq!(convert("\\~\\~not struck\\~\\~"), "~~not struck~~");
+    }
+
+    #[test]
+    fn test_backslash_escapes() {
+        let convert = |line: &str| convert_inline_markdown(&mut line.to_string());
+        assert_eq!(convert("\\*not bold\\*"), "*not bold*");
+        assert_eq!(convert("**\\*** \\_x\\_"), "<strong>*</strong> _x_");
+        assert_eq!(convert("\\[not a link\\](url)"), "[not a link](url)");
+        assert_eq!(convert("\\`not code\\`"), "`not code`");
+        assert_eq!(convert("a\\\\b \\< \\& \\\""), "a&#92;b &lt; &amp; &quot;");
+        // Only punctuation can be escaped
+        assert_eq!(convert("C:\\Users \\d"), "C:\\Users \\d");
+    }
+
+    #[test]
+    fn test_backslash_escapes_untouched_in_code_spans() {
+        let mut line = String::from("`a\\*b` and \\`c\\*`");
         assert_eq!(
-            html, "This is <i>italic and <strong>bold</strong></i> text**.",
-            "Misnested formatting should still parse valid nesting"
+            convert_inline_markdown(&mut line),
+            "<code>a\\*b</code> and `c*`"
         );
     }
 
     #[test]
-    fn test_not_a_horizontal_rule() {
-        let line = String::from("--- not a rule");
-        let token = tokenize_line(line);
-        match token {
-            Ok(Token::Paragraph { .. }) => (), // treat as paragraph
-            _ => panic!("Line with text after --- should not be HorizLine"),
-        }
+    fn test_backslash_escapes_block_markers() {
+        let lines = [
+            "\\# not a heading",
+            "",
+            "\\- not an item",
+            "",
+            "ends with\\\\",
+            "next",
+        ]
+        .iter()
+        .map(|line| line.to_string());
+        assert_eq!(
+            tokenize_text(lines),
+            vec![
+                "<p># not a heading</p>",
+                "<br/>",
+                "<p>- not an item</p>",
+                "<br/>",
+                "<p>ends with&#92; next</p>",
+            ]
+        );
     }
 
     #[test]
-    fn test_empty_or_garbage_line() {
-        let line = String::from("    "); // only spaces
-        let token = tokenize_line(line);
-        match token {
-            Ok(Token::BreakLine {}) => (),
-            _ => panic!("Whitespace-only line should be a BreakLine"),
-        }
-
-        let line = String::from("!@#$%^&*()");
-        let token = tokenize_line(line.clone());
-        match token {
-            Ok(Token::Paragraph { text }) => assert_eq!(line, text),
-            _ => panic!("Garbage line should fallback to Paragraph or raw text"),
+    fn test_allow_raw_html() {
+        let options = ParserOptions {
+            allow_raw_html: true,
+            ..Default::default()
         };
+        let mut line = String::from("Press <kbd>Ctrl</kbd> & **C**");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &options),
+            "Press <kbd>Ctrl</kbd> & <strong>C</strong>"
+        );
     }
 
     #[test]
-    fn test_tokenize_paragraph() {
-        let line = String::from("This is a paragraph.");
-        let token = tokenize_line(line).unwrap();
-        match token {
-            Token::Paragraph { text } => assert_eq!(text, "This is a paragraph."),
-            _ => panic!("Expected Paragraph token"),
-        }
+    fn test_angle_autolinks() {
+        let mut line = String::from("<https://example.com/*a*> or <me@example.org>, not <br/>");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<a href=\"https://example.com/*a*\">https://example.com/*a*</a> or \
+             <a href=\"mailto:me@example.org\">me@example.org</a>, not &lt;br/&gt;"
+        );
     }
 
     #[test]
-    fn test_tokenize_ulist_item() {
-        let line = String::from("- List item");
-        let token = tokenize_line(line).unwrap();
-        match token {
-            Token::UListItem { text } => assert_eq!(text, "List item"),
-            _ => panic!("Expected UListItem token"),
-        }
+    fn test_bare_urls_are_opt_in() {
+        let options = ParserOptions {
+            autolink_bare_urls: true,
+            ..Default::default()
+        };
+        let mut line =
+            String::from("Read https://x.org/a_(b). Or www.rust-lang.org, (https://y.org)!");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &options),
+            "Read <a href=\"https://x.org/a_(b)\">https://x.org/a_(b)</a>. \
+             Or <a href=\"http://www.rust-lang.org\">www.rust-lang.org</a>, \
+             (<a href=\"https://y.org\">https://y.org</a>)!"
+        );
+        let mut line = String::from("[site](https://x.org) https://x.org");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &options),
+            "<a href=\"https://x.org\">site</a> <a href=\"https://x.org\">https://x.org</a>"
+        );
+        let mut line = String::from("Read https://x.org.");
+        assert_eq!(convert_inline_markdown(&mut line), "Read https://x.org.");
     }
 
     #[test]
-    fn test_tokenize_olist_item() {
-        let line = String::from("1. Ordered item");
-        let token = tokenize_line(line).unwrap();
-        match token {
-            Token::OListItem { text } => assert_eq!(text, "Ordered item"),
-            _ => panic!("Expected OListItem token"),
-        }
+    fn test_link_titles() {
+        let mut line = String::from(
+            "[a](https://x.com \"My Title\") [b](/b 'it\\'s') [c](/c (Note)) [d](/d \"say \\\"hi\\\"\")",
+        );
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<a href=\"https://x.com\" title=\"My Title\">a</a> \
+             <a href=\"/b\" title=\"it's\">b</a> \
+             <a href=\"/c\" title=\"Note\">c</a> \
+             <a href=\"/d\" title=\"say &quot;hi&quot;\">d</a>"
+        );
+        let mut line = String::from("[plain](https://x.com/a b)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<a href=\"https://x.com/a b\">plain</a>"
+        );
     }
 
     #[test]
-    fn test_tokenize_text_codeblock_sequence() {
-        let lines = vec![
-            String::from("```"),
-            String::from("code line 1"),
-            String::from("code line 2"),
-            String::from("```"),
-        ];
-        let tokens = tokenize_text(lines.into_iter());
-        assert_eq!(tokens[0], "<pre><code>");
-        assert_eq!(tokens[1], "code line 1");
-        assert_eq!(tokens[2], "code line 2");
-        assert_eq!(tokens[3], "</code></pre>");
+    fn test_link_url_quote_is_escaped() {
+        let mut line = String::from("[x](a\"onmouseover=\"alert(1)) [y](/q?a=1&b='2')");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<a href=\"a&quot;onmouseover=&quot;alert(1)\">x</a> \
+             <a href=\"/q?a=1&amp;b=&#39;2&#39;\">y</a>"
+        );
+        let options = ParserOptions {
+            allow_raw_html: true,
+            ..Default::default()
+        };
+        let mut line = String::from("[x](a\"b&c)");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &options),
+            "<a href=\"a&quot;b&amp;c\">x</a>"
+        );
     }
 
     #[test]
-    fn test_tokenize_horiz_line() {
-        let line = String::from("---");
-        let token = tokenize_line(line).unwrap();
-        match token {
-            Token::HorizLine {} => (),
-            _ => panic!("Expected HorizLine token"),
-        }
+    fn test_image_url_quote_is_escaped() {
+        let mut line = String::from("![x](a\"onerror=\"alert(1).png)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"a&quot;onerror=&quot;alert(1).png\" alt=\"x\">"
+        );
+        let options = ParserOptions {
+            media_embeds: true,
+            ..Default::default()
+        };
+        let mut line = String::from("![x](a\"b.mp4)");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &options),
+            "<video controls src=\"a&quot;b.mp4\">x</video>"
+        );
     }
 
     #[test]
-    fn test_tokenize_break_line() {
-        let line = String::from("");
-        let token = tokenize_line(line).unwrap();
-        match token {
-            Token::BreakLine {} => (),
-            _ => panic!("Expected BreakLine token"),
-        }
+    fn test_image_title() {
+        let mut line = String::from("![chart](img.png \"Sales\") ![chart](img.png =640x 'Sized')");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"img.png\" alt=\"chart\" title=\"Sales\"> \
+             <img src=\"img.png\" alt=\"chart\" title=\"Sized\" width=\"640\">"
+        );
     }
 
     #[test]
-    fn test_tokenize_bold_and_italic() {
-        let mut line = String::from("This is **bold** and *italic*.");
-        let html = super::convert_inline_markdown(&mut line);
-        assert_eq!(html, "This is <strong>bold</strong> and <i>italic</i>.");
+    fn test_image_alt_text() {
+        let mut line = String::from("![](a.png) ![say \"hi\" *now*](b.png)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"a.png\" alt=\"\"> <img src=\"b.png\" alt=\"say &quot;hi&quot; now\">"
+        );
     }
 
     #[test]
-    fn test_tokenize_link() {
-        let mut line = String::from("A [link](https://example.com) here.");
-        let html = super::convert_inline_markdown(&mut line);
-        assert_eq!(html, "A <a href=\"https://example.com\">link</a> here.");
+    fn test_image_inside_link() {
+        let mut line = String::from("[![build](badge.svg)](https://ci.example.com)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<a href=\"https://ci.example.com\"><img src=\"badge.svg\" alt=\"build\"></a>"
+        );
     }
 
     #[test]
-    fn test_convert_inline_markdown_bold() {
-        let mut line = String::from("This is **bold** text.");
-        let result = convert_inline_markdown(&mut line);
-        assert_eq!(result, "This is <strong>bold</strong> text.");
+    fn test_image_dimensions() {
+        let mut line = String::from("![chart](img.png =640x480)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"img.png\" alt=\"chart\" width=\"640\" height=\"480\">"
+        );
     }
 
     #[test]
-    fn test_convert_inline_markdown_italic() {
-        let mut line = String::from("This is *italic* text.");
-        let result = convert_inline_markdown(&mut line);
-        assert_eq!(result, "This is <i>italic</i> text.");
+    fn test_image_width_only() {
+        let mut line = String::from("![chart](img.png =640x)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"img.png\" alt=\"chart\" width=\"640\">"
+        );
     }
 
     #[test]
-    fn test_tokenize_text_simple() {
-        let markdown_lines = vec![
-            String::from("# Header"),
-            String::from("This is a paragraph."),
-            String::from("- List item"),
-        ];
-        let result = tokenize_text(markdown_lines.into_iter());
+    fn test_image_height_only() {
+        let mut line = String::from("![chart](img.png =x480)");
         assert_eq!(
-            result,
-            vec![
-                "<h1>Header</h1>",
-                "<p>This is a paragraph.</p>",
-                "<li>List item</li>",
-            ]
+            convert_inline_markdown(&mut line),
+            "<img src=\"img.png\" alt=\"chart\" height=\"480\">"
         );
     }
 
     #[test]
-    fn test_inline_code_basic() {
-        let mut line = String::from("This is `code`.");
-        let html = convert_inline_markdown(&mut line);
-        assert_eq!(html, "This is <code>code</code>.");
+    fn test_image_malformed_dimensions_stay_in_url() {
+        let mut line = String::from("![chart](img.png =wide)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"img.png =wide\" alt=\"chart\">"
+        );
+        let mut line = String::from("![chart](img.png =x)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"img.png =x\" alt=\"chart\">"
+        );
+    }
+
+    fn media_options() -> ParserOptions {
+        ParserOptions {
+            media_embeds: true,
+            ..Default::default()
+        }
     }
 
     #[test]
-    fn test_inline_code_multiple() {
-        let mut line = String::from("`a` and `b` are variables.");
-        let html = convert_inline_markdown(&mut line);
-        // Only the first inline code will be replaced due to current implementation
-        // Adjust this test if you improve the function to handle multiple
-        assert!(html.contains("<code>a</code>") || html.contains("<code>b</code>"));
+    fn test_media_video_extension() {
+        let mut line = String::from("![demo](clip.mp4)");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &media_options()),
+            "<video controls src=\"clip.mp4\">demo</video>"
+        );
     }
 
     #[test]
-    fn test_inline_code_with_special_chars() {
-        let mut line = String::from("Use `x = y + z;` in your code.");
-        let html = convert_inline_markdown(&mut line);
-        assert_eq!(html, "Use <code>x = y + z;</code> in your code.");
+    fn test_media_audio_extension() {
+        let mut line = String::from("![podcast](ep1.ogg)");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &media_options()),
+            "<audio controls src=\"ep1.ogg\">podcast</audio>"
+        );
     }
 
     #[test]
-    fn test_inline_code_unclosed() {
-        let mut line = String::from("This is `not closed.");
-        let html = convert_inline_markdown(&mut line);
-        assert_eq!(html, "This is `not closed.");
+    fn test_media_uppercase_extension() {
+        let mut line = String::from("![demo](media/CLIP.WEBM?t=10 =640x)");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &media_options()),
+            "<video controls src=\"media/CLIP.WEBM?t=10\" width=\"640\">demo</video>"
+        );
+    }
+
+    #[test]
+    fn test_media_normal_image_unaffected() {
+        let mut line = String::from("![logo](logo.png)");
+        assert_eq!(
+            convert_inline_markdown_with_options(&mut line, &media_options()),
+            "<img src=\"logo.png\" alt=\"logo\">"
+        );
+        let mut line = String::from("![demo](clip.mp4)");
+        assert_eq!(
+            convert_inline_markdown(&mut line),
+            "<img src=\"clip.mp4\" alt=\"demo\">"
+        );
     }
 
     #[test]
@@ -888,4 +5639,55 @@ mod tests {
         // Should handle double backticks as code delimiter
         assert!(html.contains("<code>code with `backtick` inside</code>"));
     }
+
+    #[test]
+    fn test_definition_list() {
+        let markdown = "Glossary\n\nTerm *one*  \n: First **def**\n: Second [def](d.md)\n\n\
+                        Term two\n: Its def\n\nAfter";
+        assert_eq!(
+            tokenize_text(markdown.lines().map(String::from)),
+            [
+                "<p>Glossary</p>",
+                "<br/>",
+                "<dl>",
+                "<dt>Term <i>one</i></dt>",
+                "<dd>First <strong>def</strong></dd>",
+                "<dd>Second <a href=\"d.md\">def</a></dd>",
+                "<dt>Term two</dt>",
+                "<dd>Its def</dd>",
+                "</dl>",
+                "<br/>",
+                "<p>After</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_definition_without_term() {
+        let html = |markdown: &str| tokenize_text(markdown.lines().map(String::from));
+        assert_eq!(html(": orphan"), ["<p>: orphan</p>"]);
+        assert_eq!(
+            html("# Title\n: not a definition"),
+            ["<h1>Title</h1>", "<p>: not a definition</p>"]
+        );
+        assert_eq!(
+            html("Term\n\n: too far"),
+            ["<p>Term</p>", "<br/>", "<p>: too far</p>"]
+        );
+        assert_eq!(
+            html("```\nTerm\n: code\n```"),
+            ["<pre><code>", "Term", ": code", "</code></pre>"]
+        );
+        // Only the last line of a paragraph is the term
+        assert_eq!(
+            html("Some text\nTerm\n: def"),
+            [
+                "<p>Some text</p>",
+                "<dl>",
+                "<dt>Term</dt>",
+                "<dd>def</dd>",
+                "</dl>"
+            ]
+        );
+    }
 }
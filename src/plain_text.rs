@@ -0,0 +1,108 @@
+use crate::inline::{self, Inline};
+use crate::sections::without_heading_anchor;
+
+/// Renders a line of converter output as plain text.
+///
+/// This function removes every HTML tag produced by the converter (e.g. `<strong>`,
+/// `<a href="...">`) and decodes the few entities the converter may emit, leaving
+/// only the text a reader would see.
+///
+/// # Arguments
+///
+/// * `html` - A line of HTML as produced by `tokenize_text`.
+///
+/// # Returns
+///
+/// A `String` containing the visible text of the line.
+///
+/// # Example
+///
+/// ```rust
+/// let text = md2html::plain_text::strip_html("<h2><strong>List</strong> Example</h2>");
+/// assert_eq!(text, "List Example");
+/// ```
+pub fn strip_html(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut inside_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => inside_tag = true,
+            '>' if inside_tag => inside_tag = false,
+            _ if !inside_tag => result.push(c),
+            _ => (),
+        }
+    }
+    decode_entities(&result)
+}
+
+/// The text a reader sees of inline elements: markup is dropped, entities
+/// decoded and whitespace collapsed.
+///
+/// This is the text of headings wherever it is reused outside the page body,
+/// e.g. page titles, navigation links or the search index.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::inline::from_html;
+///
+/// let html = "Using <strong>md2html</strong> with <code>a&lt;b&gt;</code> &amp; <a href=\"x.html\">links</a>";
+/// assert_eq!(md2html::plain_text::plain_text(&from_html(html)), "Using md2html with a<b> & links");
+/// ```
+pub fn plain_text(inlines: &[Inline]) -> String {
+    normalize_whitespace(&decode_entities(&inline::to_plain_text(inlines)))
+}
+
+/// The plain text of a line of converter output such as a heading
+/// (`<h1>Using <strong>md2html</strong></h1>`), see `plain_text`.
+///
+/// Unlike `strip_html`, text of inline code that merely looks like a tag is kept,
+/// and the permalink anchor of a heading (see `sections::add_heading_anchors`) is
+/// left out.
+pub fn heading_text(html: &str) -> String {
+    plain_text(&inline::from_html(&without_heading_anchor(html)))
+}
+
+/// Decodes the basic HTML entities (`&lt;`, `&gt;`, `&quot;`, `&#39;` and `&amp;`).
+///
+/// `&amp;` is decoded last so that `&amp;lt;` becomes the literal text `&lt;`.
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Collapses every run of whitespace in `text` into a single space.
+pub fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_html_nested_tags() {
+        let html = "<p>This is <i>italic and <strong>bold</strong></i> text.</p>";
+        assert_eq!(strip_html(html), "This is italic and bold text.");
+    }
+
+    #[test]
+    fn test_strip_html_link() {
+        let html = "<li><a href=\"#intro\">Introduction</a></li>";
+        assert_eq!(strip_html(html), "Introduction");
+    }
+
+    #[test]
+    fn test_heading_text_keeps_code() {
+        let html = "<h1>Using <strong>md2html</strong> with <code>Vec<T></code> and  <a href=\"x.md\">a link</a></h1>";
+        assert_eq!(heading_text(html), "Using md2html with Vec<T> and a link");
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        assert_eq!(normalize_whitespace("  a \t b\n c "), "a b c");
+    }
+}
@@ -0,0 +1,280 @@
+use crate::fences::FenceTracker;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+/// The target of a link reference definition (`[label]: url "title"`).
+///
+/// # Fields
+///
+/// - `url`: Where the references link to.
+/// - `title`: The title of the links, if the definition has one.
+/// - `line`: The 1-based line of the definition (0 when it isn't collected from a
+///   document, see `parse_definition`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkDefinition {
+    pub url: String,
+    pub title: Option<String>,
+    pub line: usize,
+}
+
+/// A label defined again after its first definition, which is the one used.
+///
+/// # Fields
+///
+/// - `label`: The normalized label.
+/// - `first_line`: The 1-based line of the definition in use.
+/// - `line`: The 1-based line of the ignored definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateDefinition {
+    pub label: String,
+    pub first_line: usize,
+    pub line: usize,
+}
+
+/// The output of `collect_definitions`.
+///
+/// # Fields
+///
+/// - `definitions`: The definitions in use, by normalized label.
+/// - `is_definition`: For every line, whether it is a definition.
+/// - `duplicates`: The definitions ignored because their label was already defined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectedDefinitions {
+    pub definitions: LinkDefinitions,
+    pub is_definition: Vec<bool>,
+    pub duplicates: Vec<DuplicateDefinition>,
+}
+
+/// The link reference definitions of a document, by normalized label.
+pub type LinkDefinitions = HashMap<String, LinkDefinition>;
+
+/// Normalizes a link label: references match their definition whatever the case
+/// and the spacing of the label.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Parses a link reference definition line such as
+/// `[rustc]: https://rust-lang.org "The compiler"`, returning its label and target.
+///
+/// The URL may be wrapped in `<>`, and the title written in double quotes, single
+/// quotes or parentheses.
+pub fn parse_definition(line: &str) -> Option<(String, LinkDefinition)> {
+    let definition_re = Regex::new(
+        r#"^ {0,3}\[([^\[\]]+)\]:\s*(<[^<>]*>|\S+)(?:\s+(?:"((?:[^"\\]|\\.)*)"|'((?:[^'\\]|\\.)*)'|\(([^()]*)\)))?\s*$"#,
+    )
+    .unwrap();
+    let groups = definition_re.captures(line)?;
+    let label = normalize_label(&groups[1]);
+    // `[^label]: text` defines a footnote
+    if label.is_empty() || label.starts_with('^') {
+        return None;
+    }
+    let url = groups[2].trim_start_matches('<').trim_end_matches('>');
+    let title = (3..=5)
+        .find_map(|index| groups.get(index))
+        .map(|title| title.as_str().to_string());
+    Some((
+        label,
+        LinkDefinition {
+            url: url.to_string(),
+            title,
+            line: 0,
+        },
+    ))
+}
+
+/// Collects the link reference definitions of a document, skipping fenced code
+/// blocks. When a label is defined twice the first definition wins, the others
+/// are listed in `CollectedDefinitions::duplicates`.
+pub fn collect_definitions(lines: &[String]) -> CollectedDefinitions {
+    let mut definitions = LinkDefinitions::new();
+    let mut is_definition = vec![false; lines.len()];
+    let mut duplicates = Vec::new();
+    let mut fences = FenceTracker::default();
+    for (index, line) in lines.iter().enumerate() {
+        if fences.is_code(line) {
+            continue;
+        }
+        if let Some((label, definition)) = parse_definition(line) {
+            is_definition[index] = true;
+            match definitions.get(&label) {
+                Some(first) => duplicates.push(DuplicateDefinition {
+                    label,
+                    first_line: first.line,
+                    line: index + 1,
+                }),
+                None => {
+                    let definition = LinkDefinition {
+                        line: index + 1,
+                        ..definition
+                    };
+                    definitions.insert(label, definition);
+                }
+            }
+        }
+    }
+    CollectedDefinitions {
+        definitions,
+        is_definition,
+        duplicates,
+    }
+}
+
+/// The definitions of `definitions` whose label isn't in `used_labels`, by line.
+pub fn unused_definitions<'a>(
+    definitions: &'a LinkDefinitions,
+    used_labels: &HashSet<String>,
+) -> Vec<(&'a str, &'a LinkDefinition)> {
+    let mut unused: Vec<(&str, &LinkDefinition)> = definitions
+        .iter()
+        .filter(|(label, _)| !used_labels.contains(*label))
+        .map(|(label, definition)| (label.as_str(), definition))
+        .collect();
+    unused.sort_by_key(|(_, definition)| definition.line);
+    unused
+}
+
+/// Rewrites the reference links of `line` into inline links, so the inline
+/// converter handles them like any other link.
+///
+/// Full (`[text][label]`), collapsed (`[label][]`) and shortcut (`[label]`)
+/// references are resolved, images (`![alt][label]`) too. References to labels
+/// that aren't defined are kept as they are, and so are code spans.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::references::{collect_definitions, resolve_references};
+///
+/// let lines = vec![String::from("[1]: https://www.rust-lang.org \"Rust\"")];
+/// let definitions = collect_definitions(&lines).definitions;
+/// assert_eq!(
+///     resolve_references("See [rustc][1] and [nothing][2]", &definitions),
+///     "See [rustc](https://www.rust-lang.org \"Rust\") and [nothing][2]"
+/// );
+/// ```
+pub fn resolve_references(line: &str, definitions: &LinkDefinitions) -> String {
+    resolve_references_tracking(line, definitions, &mut HashSet::new())
+}
+
+/// Same as `resolve_references`, adding the label of every resolved reference to
+/// `used_labels`.
+pub fn resolve_references_tracking(
+    line: &str,
+    definitions: &LinkDefinitions,
+    used_labels: &mut HashSet<String>,
+) -> String {
+    if definitions.is_empty() {
+        return line.to_string();
+    }
+    let reference_re = Regex::new(r"\[([^\[\]]*)\](?:\[([^\[\]]*)\])?").unwrap();
+
+    // Odd pieces are the inside of code spans
+    let mut resolved = String::with_capacity(line.len());
+    for (index, piece) in line.split('`').enumerate() {
+        if index > 0 {
+            resolved.push('`');
+        }
+        if index % 2 == 1 {
+            resolved.push_str(piece);
+            continue;
+        }
+        let mut last_end = 0;
+        for groups in reference_re.captures_iter(piece) {
+            let found = groups.get(0).unwrap();
+            // Inline links and definitions are not references
+            let rest = &piece[found.end()..];
+            if groups.get(2).is_none() && (rest.starts_with('(') || rest.starts_with(':')) {
+                continue;
+            }
+            let text = &groups[1];
+            let label = match groups.get(2) {
+                Some(label) if !label.as_str().trim().is_empty() => label.as_str(),
+                _ => text,
+            };
+            let label = normalize_label(label);
+            let Some(definition) = definitions.get(&label) else {
+                continue;
+            };
+            used_labels.insert(label);
+            resolved.push_str(&piece[last_end..found.start()]);
+            resolved.push_str(&format!("[{}]({}", text, definition.url));
+            if let Some(title) = &definition.title {
+                resolved.push_str(&format!(" \"{}\"", title.replace('"', "\\\"")));
+            }
+            resolved.push(')');
+            last_end = found.end();
+        }
+        resolved.push_str(&piece[last_end..]);
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions(lines: &[&str]) -> LinkDefinitions {
+        let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        collect_definitions(&lines).definitions
+    }
+
+    #[test]
+    fn test_reference_kinds() {
+        let definitions = definitions(&[
+            "[Docs]: <https://docs.rs> 'API docs'",
+            "[logo]: img/logo.png",
+        ]);
+        assert_eq!(
+            resolve_references("[read][docs], [DOCS][], [docs] and ![Logo]", &definitions),
+            "[read](https://docs.rs \"API docs\"), [DOCS](https://docs.rs \"API docs\"), \
+             [docs](https://docs.rs \"API docs\") and ![Logo](img/logo.png)"
+        );
+    }
+
+    #[test]
+    fn test_unresolved_and_code_kept() {
+        let definitions = definitions(&["[a]: /a"]);
+        assert_eq!(
+            resolve_references("[x][b] `[a]` [a](/other) [ ]", &definitions),
+            "[x][b] `[a]` [a](/other) [ ]"
+        );
+    }
+
+    #[test]
+    fn test_first_definition_wins_and_fences_skipped() {
+        let lines: Vec<String> = ["```", "[a]: /in-code", "```", "[A]: /first", "[a]: /second"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        let collected = collect_definitions(&lines);
+        assert_eq!(collected.definitions["a"].url, "/first");
+        assert_eq!(
+            collected.is_definition,
+            vec![false, false, false, true, true]
+        );
+        assert_eq!(
+            collected.duplicates,
+            vec![DuplicateDefinition {
+                label: String::from("a"),
+                first_line: 4,
+                line: 5,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unused_definitions() {
+        let definitions = definitions(&["[b]: /b", "[a]: /a", "[c]: /c"]);
+        let mut used_labels = HashSet::new();
+        resolve_references_tracking("[B] and [x][c]", &definitions, &mut used_labels);
+        let unused = unused_definitions(&definitions, &used_labels);
+        assert_eq!(unused.len(), 1);
+        assert_eq!((unused[0].0, unused[0].1.line), ("a", 2));
+    }
+}
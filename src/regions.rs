@@ -0,0 +1,248 @@
+use crate::fences::FenceTracker;
+
+/// What becomes of a line of the document, see `classify_lines`.
+///
+/// # Variants
+///
+/// - `Markdown`: The line is converted as usual.
+/// - `Stripped`: The line is in a region to strip, or is one of its markers, and
+///   is left out of the output.
+/// - `Kept`: The line is in a region to keep, or is one of its markers, and is
+///   output as it is, without being converted nor escaped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionLine {
+    Markdown,
+    Stripped,
+    Kept,
+}
+
+/// A region marker without its counterpart, found by `classify_lines`.
+///
+/// # Fields
+///
+/// - `line`: The 1-based line of the marker.
+/// - `message`: What is wrong with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionProblem {
+    pub line: usize,
+    pub message: String,
+}
+
+/// The lines of a document sorted out by `classify_lines`.
+///
+/// # Fields
+///
+/// - `lines`: What becomes of every line, in order.
+/// - `problems`: The unbalanced markers, in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Regions {
+    pub lines: Vec<RegionLine>,
+    pub problems: Vec<RegionProblem>,
+}
+
+/// Parses a region marker, an HTML comment alone on its line such as
+/// `<!-- toc:start -->` or `<!-- toc:end -->`, into its name and whether it
+/// starts the region. Whitespace inside the comment doesn't matter.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::regions::parse_marker;
+///
+/// assert_eq!(parse_marker("<!-- toc:start -->"), Some(("toc", true)));
+/// assert_eq!(parse_marker("  <!--badges : end-->"), Some(("badges", false)));
+/// assert_eq!(parse_marker("<!-- a comment -->"), None);
+/// ```
+pub fn parse_marker(line: &str) -> Option<(&str, bool)> {
+    let comment = line.trim().strip_prefix("<!--")?.strip_suffix("-->")?;
+    let (name, position) = comment.split_once(':')?;
+    let name = name.trim();
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return None;
+    }
+    match position.trim() {
+        "start" => Some((name, true)),
+        "end" => Some((name, false)),
+        _ => None,
+    }
+}
+
+/// Sorts out the `lines` of a document: the regions named in `strip`, from their
+/// `<!-- name:start -->` marker to their `<!-- name:end -->` one, are left out,
+/// and the regions named in `keep` are output as they are.
+///
+/// Markers inside a region are part of its content, and so are the markers in
+/// code blocks when `code_fences` is set. A start marker never closed, or an end
+/// marker without a start, is reported and its lines are converted as usual.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::regions::{classify_lines, RegionLine};
+///
+/// let lines: Vec<String> = ["# Tool", "<!-- toc:start -->", "- [Usage](#usage)", "<!-- toc:end -->"]
+///     .iter()
+///     .map(|line| line.to_string())
+///     .collect();
+/// let regions = classify_lines(&lines, &[String::from("toc")], &[], true);
+/// assert_eq!(regions.lines[0], RegionLine::Markdown);
+/// assert!(regions.lines[1..].iter().all(|line| *line == RegionLine::Stripped));
+/// assert!(regions.problems.is_empty());
+/// ```
+pub fn classify_lines(
+    lines: &[String],
+    strip: &[String],
+    keep: &[String],
+    code_fences: bool,
+) -> Regions {
+    let mut regions = Regions {
+        lines: vec![RegionLine::Markdown; lines.len()],
+        problems: Vec::new(),
+    };
+    if strip.is_empty() && keep.is_empty() {
+        return regions;
+    }
+    let kind = |name: &str| {
+        if strip.iter().any(|strip| strip == name) {
+            Some(RegionLine::Stripped)
+        } else if keep.iter().any(|keep| keep == name) {
+            Some(RegionLine::Kept)
+        } else {
+            None
+        }
+    };
+
+    let mut fences = FenceTracker::default();
+    // The name, kind and first line of the region being read
+    let mut open: Option<(&str, RegionLine, usize)> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let marker = parse_marker(line).filter(|(name, _)| kind(name).is_some());
+        match (open, marker) {
+            (Some((name, region, start)), Some((end_name, false))) if end_name == name => {
+                regions.lines[start..=i].fill(region);
+                open = None;
+            }
+            (Some(_), _) => (),
+            // Code blocks only matter outside regions, whose content is never parsed
+            _ if code_fences && fences.is_code(line) => (),
+            (None, Some((name, true))) => open = kind(name).map(|region| (name, region, i)),
+            (None, Some((name, false))) => regions.problems.push(RegionProblem {
+                line: i + 1,
+                message: format!("`{}:end` has no matching `{}:start`", name, name),
+            }),
+            (None, None) => (),
+        }
+    }
+    if let Some((name, _, start)) = open {
+        regions.problems.push(RegionProblem {
+            line: start + 1,
+            message: format!(
+                "`{}:start` is never closed by a `{}:end`, the region is converted",
+                name, name
+            ),
+        });
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tokenize_text_with_options, ParserOptions, WarningKind};
+
+    fn region_options(strip: &[&str], keep: &[&str]) -> ParserOptions {
+        ParserOptions {
+            strip_regions: strip.iter().map(|name| name.to_string()).collect(),
+            keep_regions: keep.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stripped_region() {
+        let markdown = "# Tool\n<!--toc:start   -->\n- [Install](#install)\n\
+                        <!-- toc : end -->\nText\n\n<!-- other:start -->";
+        let result = tokenize_text_with_options(
+            markdown.lines().map(String::from),
+            &region_options(&["toc"], &[]),
+        );
+        assert_eq!(
+            result.html_lines,
+            [
+                "<h1>Tool</h1>",
+                "<p>Text</p>",
+                "<br/>",
+                "<p>&lt;!-- other:start --&gt;</p>"
+            ]
+        );
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_kept_region_not_converted() {
+        let markdown = "Intro\n<!-- badges:start -->\n| [![CI](ci.svg)](ci) | **beta** |\n\
+                        |---|---|\n<!-- badges:end -->\n\n```\n<!-- badges:start -->\n```";
+        let result = tokenize_text_with_options(
+            markdown.lines().map(String::from),
+            &region_options(&[], &["badges"]),
+        );
+        assert_eq!(
+            result.html_lines[..5],
+            [
+                "<p>Intro</p>",
+                "<!-- badges:start -->",
+                "| [![CI](ci.svg)](ci) | **beta** |",
+                "|---|---|",
+                "<!-- badges:end -->",
+            ]
+        );
+        // The marker in the code block is code, not an unclosed region
+        assert!(result
+            .html_lines
+            .contains(&String::from("&lt;!-- badges:start --&gt;")));
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unbalanced_markers() {
+        let lines: Vec<String> = [
+            "<!-- toc:end -->",
+            "Text",
+            "<!-- toc:start -->",
+            "<!-- badges:start -->",
+            "* item",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        let strip = [String::from("toc"), String::from("badges")];
+        let regions = classify_lines(&lines, &strip, &[], true);
+        assert!(regions
+            .lines
+            .iter()
+            .all(|line| *line == RegionLine::Markdown));
+        assert_eq!(
+            regions.problems,
+            [
+                RegionProblem {
+                    line: 1,
+                    message: String::from("`toc:end` has no matching `toc:start`"),
+                },
+                RegionProblem {
+                    line: 3,
+                    message: String::from(
+                        "`toc:start` is never closed by a `toc:end`, the region is converted"
+                    ),
+                },
+            ]
+        );
+
+        let result =
+            tokenize_text_with_options(lines.into_iter(), &region_options(&["toc"], &["badges"]));
+        assert_eq!(result.warnings.len(), 2);
+        assert_eq!(result.warnings[1].line, 3);
+        assert_eq!(result.warnings[1].kind, WarningKind::Region);
+        assert!(result.html_lines.contains(&String::from("<li>item</li>")));
+    }
+}
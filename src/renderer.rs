@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// What ends every line of HTML written by an `HtmlRenderer`.
+///
+/// # Variants
+///
+/// - `Lf`: `\n`, the default.
+/// - `CrLf`: `\r\n`, for tools on Windows that expect it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The characters ending a line.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::CrLf),
+            _ => Err(format!(
+                "unknown line ending \"{}\", expected lf or crlf",
+                name
+            )),
+        }
+    }
+}
+
+/// Writes the HTML lines of a converted document, see `Converter::renderer`.
+///
+/// # Fields
+///
+/// - `line_ending`: What ends every line, the last one included.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::renderer::{HtmlRenderer, LineEnding};
+/// use md2html::Converter;
+///
+/// let converter = Converter::new().renderer(HtmlRenderer {
+///     line_ending: LineEnding::CrLf,
+/// });
+/// assert_eq!(converter.convert_str("# Title\nText"), "<h1>Title</h1>\r\n<p>Text</p>\r\n");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HtmlRenderer {
+    pub line_ending: LineEnding,
+}
+
+impl HtmlRenderer {
+    /// Joins `lines` into a single string, every line followed by the line ending.
+    pub fn join_lines(&self, lines: &[String]) -> String {
+        let line_ending = self.line_ending.as_str();
+        let mut html = String::with_capacity(
+            lines
+                .iter()
+                .map(|line| line.len() + line_ending.len())
+                .sum(),
+        );
+        for line in lines {
+            html.push_str(line);
+            html.push_str(line_ending);
+        }
+        html
+    }
+
+    /// Writes `lines` to `output`, every line followed by the line ending, then
+    /// flushes it.
+    ///
+    /// Returns the number of bytes written.
+    pub fn write_lines<W: Write>(&self, mut output: W, lines: &[String]) -> io::Result<usize> {
+        let line_ending = self.line_ending.as_str();
+        let mut bytes_written = 0;
+        for line in lines {
+            output.write_all(line.as_bytes())?;
+            output.write_all(line_ending.as_bytes())?;
+            bytes_written += line.len() + line_ending.len();
+        }
+        output.flush()?;
+        Ok(bytes_written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line_ending() {
+        assert_eq!("lf".parse(), Ok(LineEnding::Lf));
+        assert_eq!("crlf".parse(), Ok(LineEnding::CrLf));
+        assert!("cr"
+            .parse::<LineEnding>()
+            .unwrap_err()
+            .contains("lf or crlf"));
+    }
+
+    #[test]
+    fn test_write_lines_counts_line_endings() {
+        let lines = vec![String::from("<p>a</p>"), String::from("<p>b</p>")];
+        for (line_ending, expected) in [
+            (LineEnding::Lf, "<p>a</p>\n<p>b</p>\n"),
+            (LineEnding::CrLf, "<p>a</p>\r\n<p>b</p>\r\n"),
+        ] {
+            let renderer = HtmlRenderer { line_ending };
+            let mut output = Vec::new();
+            let bytes_written = renderer.write_lines(&mut output, &lines).unwrap();
+            assert_eq!(output, expected.as_bytes());
+            assert_eq!(bytes_written, expected.len());
+            assert_eq!(renderer.join_lines(&lines), expected);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::plain_text::heading_text;
+
+/// Default number of characters kept from a page body in the search index.
+pub const DEFAULT_BODY_LIMIT: usize = 5000;
+
+/// A single page entry of the client-side search index.
+///
+/// Every field holds plain text (no HTML), so the index can be fed directly to
+/// lunr/fuse-style search libraries.
+///
+/// # Fields
+///
+/// - `title`: The text of the first `<h1>`, or the fallback title when the page has none.
+/// - `url`: The URL of the generated page, relative to the output directory.
+/// - `headings`: The text of every heading of the page, in document order.
+/// - `body`: The plain text of the page without headings and code blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchPage {
+    pub title: String,
+    pub url: String,
+    pub headings: Vec<String>,
+    pub body: String,
+}
+
+/// Builds the search index entry of a converted page.
+///
+/// # Arguments
+///
+/// * `fallback_title` - The title used when the page has no `<h1>` (usually the file name).
+/// * `url` - The URL of the generated page.
+/// * `html_lines` - The converted page, as returned by `tokenize_text`.
+/// * `body_limit` - The maximum number of characters kept from the body.
+///
+/// # Example
+///
+/// ```rust
+/// let html = md2html::tokenize_text(vec![String::from("# Intro"), String::from("Hello **world**")].into_iter());
+/// let page = md2html::search_index::build_search_page("intro", "intro.html", &html, 100);
+/// assert_eq!(page.title, "Intro");
+/// assert_eq!(page.body, "Hello world");
+/// ```
+pub fn build_search_page(
+    fallback_title: &str,
+    url: &str,
+    html_lines: &[String],
+    body_limit: usize,
+) -> SearchPage {
+    let mut title: Option<String> = None;
+    let mut headings: Vec<String> = Vec::new();
+    let mut body_parts: Vec<String> = Vec::new();
+    let mut inside_code_block = false;
+
+    for line in html_lines {
+        // Code blocks are noise for a text search so we leave them out
+        if line.starts_with("<pre") {
+            inside_code_block = true;
+        }
+        if inside_code_block {
+            if line.ends_with("</code></pre>") {
+                inside_code_block = false;
+            }
+            continue;
+        }
+
+        let text = heading_text(line);
+        if text.is_empty() {
+            continue;
+        }
+
+        if is_heading(line) {
+            if title.is_none() && line.starts_with("<h1") {
+                title = Some(text.clone());
+            }
+            headings.push(text);
+        } else {
+            body_parts.push(text);
+        }
+    }
+
+    SearchPage {
+        title: title.unwrap_or_else(|| fallback_title.to_string()),
+        url: url.to_string(),
+        headings,
+        body: truncate_chars(&body_parts.join(" "), body_limit),
+    }
+}
+
+/// Serializes the search index as a JSON array of page objects.
+///
+/// # Example
+///
+/// ```rust
+/// let page = md2html::search_index::SearchPage {
+///     title: String::from("Intro"),
+///     url: String::from("intro.html"),
+///     headings: vec![String::from("Intro")],
+///     body: String::from("Hello"),
+/// };
+/// let json = md2html::search_index::to_json(&[page]);
+/// assert!(json.contains("\"url\": \"intro.html\""));
+/// ```
+pub fn to_json(pages: &[SearchPage]) -> String {
+    let mut json = String::from("[\n");
+    for (i, page) in pages.iter().enumerate() {
+        let headings: Vec<String> = page.headings.iter().map(|h| json_string(h)).collect();
+        json += "  {\n";
+        json += &format!("    \"title\": {},\n", json_string(&page.title));
+        json += &format!("    \"url\": {},\n", json_string(&page.url));
+        json += &format!("    \"headings\": [{}],\n", headings.join(", "));
+        json += &format!("    \"body\": {}\n", json_string(&page.body));
+        json += if i + 1 < pages.len() {
+            "  },\n"
+        } else {
+            "  }\n"
+        };
+    }
+    json += "]\n";
+    json
+}
+
+/// Checks whether a rendered HTML line is a heading (`<h1>` to `<h6>`).
+pub(crate) fn is_heading(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    bytes.len() > 3 && line.starts_with("<h") && bytes[2].is_ascii_digit() && bytes[3] == b'>'
+}
+
+/// Keeps at most `limit` characters of `text`, cutting on a char boundary.
+fn truncate_chars(text: &str, limit: usize) -> String {
+    match text.char_indices().nth(limit) {
+        Some((byte_index, _)) => text[..byte_index].trim_end().to_string(),
+        None => text.to_string(),
+    }
+}
+
+/// Quotes and escapes `text` as a JSON string literal.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize_text;
+
+    fn to_lines(text: &str) -> impl Iterator<Item = String> + '_ {
+        text.lines().map(String::from)
+    }
+
+    fn fixture_pages(body_limit: usize) -> Vec<SearchPage> {
+        let fixture = [
+            (
+                "index",
+                "index.html",
+                "# Welcome\n\nThis is the **home** page with a [link](guide/setup.html).",
+            ),
+            (
+                "setup",
+                "guide/setup.html",
+                "# Setup\n\n## Install\n\nRun the *installer*.\n\n```\nlet x = <y>;\n```\n\n## Configure\n\nEdit the file.",
+            ),
+            (
+                "notes",
+                "notes.html",
+                "Plain notes without a title and \"quotes\".\n\n- first `item`\n- second item",
+            ),
+        ];
+        fixture
+            .iter()
+            .map(|(stem, url, markdown)| {
+                let html = tokenize_text(to_lines(markdown));
+                build_search_page(stem, url, &html, body_limit)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_search_index_titles_and_urls() {
+        let pages = fixture_pages(DEFAULT_BODY_LIMIT);
+        let titles: Vec<&str> = pages.iter().map(|p| p.title.as_str()).collect();
+        let urls: Vec<&str> = pages.iter().map(|p| p.url.as_str()).collect();
+        assert_eq!(titles, vec!["Welcome", "Setup", "notes"]);
+        assert_eq!(urls, vec!["index.html", "guide/setup.html", "notes.html"]);
+        assert_eq!(pages[1].headings, vec!["Setup", "Install", "Configure"]);
+    }
+
+    #[test]
+    fn test_search_index_title_is_plain_text() {
+        let html = tokenize_text(to_lines(
+            "# Using **md2html** with `Vec<T>` and [links](x.md)\n## *Fast* &amp; small",
+        ));
+        let page = build_search_page("fallback", "a.html", &html, DEFAULT_BODY_LIMIT);
+        assert_eq!(page.title, "Using md2html with Vec<T> and links");
+        assert_eq!(page.headings[1], "Fast & small");
+        assert!(to_json(&[page]).contains("\"title\": \"Using md2html with Vec<T> and links\""));
+    }
+
+    #[test]
+    fn test_search_index_excludes_code_blocks() {
+        let pages = fixture_pages(DEFAULT_BODY_LIMIT);
+        assert_eq!(pages[1].body, "Run the installer. Edit the file.");
+        assert!(!pages[1].body.contains("let x"));
+    }
+
+    #[test]
+    fn test_search_index_has_no_html_in_text_fields() {
+        for page in fixture_pages(DEFAULT_BODY_LIMIT) {
+            let mut fields = vec![page.title, page.body];
+            fields.extend(page.headings);
+            for field in fields {
+                assert!(
+                    !field.contains('<') && !field.contains('>'),
+                    "HTML leaked into search text: {}",
+                    field
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_index_body_truncated() {
+        let pages = fixture_pages(10);
+        assert_eq!(pages[0].body, "This is th");
+        assert!(pages.iter().all(|p| p.body.chars().count() <= 10));
+    }
+
+    #[test]
+    fn test_search_index_json_structure() {
+        let json = to_json(&fixture_pages(DEFAULT_BODY_LIMIT));
+        assert!(json.starts_with("[\n  {\n    \"title\": \"Welcome\",\n"));
+        assert!(json.contains("\"url\": \"guide/setup.html\""));
+        assert!(json.contains("\"headings\": [\"Setup\", \"Install\", \"Configure\"]"));
+        assert!(json.contains("without a title and \\\"quotes\\\"."));
+        assert_eq!(json.matches("\"title\":").count(), 3);
+        assert!(json.ends_with("  }\n]\n"));
+    }
+}
@@ -0,0 +1,620 @@
+use crate::escape;
+use crate::inline::{self, Inline};
+use crate::plain_text::strip_html;
+use crate::search_index::is_heading;
+use crate::{ParserOptions, Token};
+use regex::Regex;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// How the permalink anchors of `add_heading_anchors` start.
+const ANCHOR_START: &str = "<a class=\"anchor\" ";
+
+/// The text of the heading permalinks by default, like GitHub's link icon.
+pub const DEFAULT_ANCHOR_SYMBOL: &str = "#";
+
+/// How heading text is turned into an id, see `slugify_with_options`.
+///
+/// # Variants
+///
+/// - `Default`: md2html's own rules, described on `slugify`.
+/// - `GitHub`: The ids GitHub gives to headings of rendered markdown files, so links
+///   copied from GitHub keep working on the converted pages.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SlugStyle {
+    #[default]
+    Default,
+    GitHub,
+}
+
+impl FromStr for SlugStyle {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        match style {
+            "default" => Ok(SlugStyle::Default),
+            "github" => Ok(SlugStyle::GitHub),
+            _ => Err(format!(
+                "unknown slug style \"{}\", expected default or github",
+                style
+            )),
+        }
+    }
+}
+
+/// Turns heading text into an id usable in URLs (`Getting **Started**!` -> `getting-started`).
+///
+/// HTML tags are removed, letters are lowercased, spaces become `-` and every other
+/// character that isn't a letter, a digit, `-` or `_` is dropped, like GitHub does.
+/// Letters of any script are kept (`## Überblick` -> `überblick`), emoji and
+/// punctuation are not.
+///
+/// # Example
+///
+/// ```rust
+/// assert_eq!(md2html::sections::slugify("Getting <strong>Started</strong>!"), "getting-started");
+/// assert_eq!(md2html::sections::slugify("日本語の見出し 🎌"), "日本語の見出し-");
+/// ```
+pub fn slugify(text: &str) -> String {
+    slugify_with_options(text, &ParserOptions::default())
+}
+
+/// Turns heading text into an id, honoring `options`.
+///
+/// This is the configurable version of `slugify`. With `ascii_slugs` the id is
+/// plain ASCII: common Latin diacritics are transliterated (`ü` -> `u`, `ß` ->
+/// `ss`) and the other non-ASCII letters are percent-encoded.
+///
+/// With `slug_style` set to `SlugStyle::GitHub` the id is the one GitHub gives the
+/// heading. It differs from the default one on rare characters only: GitHub keeps
+/// every combining mark (the U+FE0F of `❤️`) and connector punctuation (`‿`), and
+/// drops symbols such as `Ⓐ`.
+///
+/// # Example
+///
+/// ```rust
+/// let options = md2html::ParserOptions {
+///     ascii_slugs: true,
+///     ..Default::default()
+/// };
+/// assert_eq!(md2html::sections::slugify_with_options("Größe", &options), "grosse");
+/// assert_eq!(md2html::sections::slugify_with_options("見出し", &options), "%E8%A6%8B%E5%87%BA%E3%81%97");
+/// ```
+pub fn slugify_with_options(text: &str, options: &ParserOptions) -> String {
+    let slug = match options.slug_style {
+        SlugStyle::Default => strip_html(text)
+            .trim()
+            .chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                '-' | '_' => Some(c),
+                c if c.is_alphanumeric() || is_combining_mark(c) => Some(c),
+                _ => None,
+            })
+            .flat_map(char::to_lowercase)
+            .collect(),
+        SlugStyle::GitHub => github_slug(&strip_html(text)),
+    };
+    match options.ascii_slugs {
+        true => ascii_slug(&slug),
+        false => slug,
+    }
+}
+
+/// The slug GitHub computes: the text is lowercased, every character that isn't a
+/// letter, a mark, a number, connector punctuation, `-` or a space is dropped, and
+/// spaces become `-`.
+fn github_slug(text: &str) -> String {
+    let removed_re = Regex::new(r"[^\p{L}\p{M}\p{N}\p{Pc} -]").unwrap();
+    removed_re
+        .replace_all(text.trim(), "")
+        .to_lowercase()
+        .replace(' ', "-")
+}
+
+/// Whether `c` is a combining diacritical mark, written after the letter it
+/// accents in decomposed text (`u` followed by U+0308 for `ü`).
+fn is_combining_mark(c: char) -> bool {
+    ('\u{0300}'..='\u{036F}').contains(&c)
+}
+
+/// Makes a lowercase slug plain ASCII: Latin letters lose their diacritics and the
+/// other non-ASCII characters are percent-encoded.
+fn ascii_slug(slug: &str) -> String {
+    let mut ascii = String::with_capacity(slug.len());
+    for c in slug.chars() {
+        if c.is_ascii() {
+            ascii.push(c);
+        } else if is_combining_mark(c) {
+            continue;
+        } else if let Some(transliteration) = transliterate(c) {
+            ascii.push_str(transliteration);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                ascii += &format!("%{:02X}", byte);
+            }
+        }
+    }
+    ascii
+}
+
+/// The ASCII spelling of a lowercase Latin letter with a diacritic, if it is a
+/// common one.
+fn transliterate(c: char) -> Option<&'static str> {
+    let ascii = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => "i",
+        'ł' | 'ľ' | 'ĺ' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ř' => "r",
+        'ß' => "ss",
+        'ś' | 'š' | 'ş' | 'ș' => "s",
+        'ť' | 'ţ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ų' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+/// Wraps every heading, and the content up to the next heading of the same or a
+/// higher level, in a `<section>`.
+///
+/// Sections are nested the same way headings are, a level skip (`h2` followed by
+/// `h4`) simply nests the `h4` section inside the `h2` one. The heading id is put on
+/// the `<section>` element, the heading itself gets no id. Content before the first
+/// heading is left unwrapped, and every section still open is closed at the end.
+///
+/// # Example
+///
+/// ```rust
+/// let tokens = md2html::parse(vec![String::from("# Intro"), String::from("Text")].into_iter());
+/// let html = md2html::render_html(&md2html::sections::wrap_sections(tokens));
+/// assert_eq!(
+///     html,
+///     vec!["<section id=\"intro\">", "<h1>Intro</h1>", "<p>Text</p>", "</section>"]
+/// );
+/// ```
+pub fn wrap_sections(tokens: Vec<Token>) -> Vec<Token> {
+    wrap_sections_with_options(tokens, &ParserOptions::default())
+}
+
+/// Wraps headings and their content in sections, with the ids of
+/// `slugify_with_options`.
+///
+/// This is the configurable version of `wrap_sections`.
+pub fn wrap_sections_with_options(tokens: Vec<Token>, options: &ParserOptions) -> Vec<Token> {
+    let mut result = Vec::new();
+    let mut open_levels: Vec<u8> = Vec::new();
+    let mut used_ids: Vec<String> = Vec::new();
+    // How many sections were open when each `:::` container started, the sections
+    // of its headings close with it
+    let mut containers: Vec<usize> = Vec::new();
+
+    for token in tokens {
+        let outside = containers.last().copied().unwrap_or(0);
+        if let Token::Header { level, text } = &token {
+            while open_levels.len() > outside
+                && open_levels.last().is_some_and(|open| open >= level)
+            {
+                open_levels.pop();
+                result.push(Token::SectionEnd {});
+            }
+            open_levels.push(*level);
+            result.push(Token::SectionStart {
+                id: unique_id(
+                    slugify_with_options(&inline::to_html(text), options),
+                    &mut used_ids,
+                ),
+            });
+        }
+        match token {
+            Token::ContainerStart { .. } | Token::DetailsStart { .. } => {
+                containers.push(open_levels.len())
+            }
+            Token::ContainerEnd {} | Token::DetailsEnd {} => {
+                for _ in open_levels.drain(containers.pop().unwrap_or(0)..) {
+                    result.push(Token::SectionEnd {});
+                }
+            }
+            _ => (),
+        }
+        result.push(token);
+    }
+
+    for _ in open_levels {
+        result.push(Token::SectionEnd {});
+    }
+    result
+}
+
+/// Appends a permalink to the section to every heading of `tokens`, an
+/// `<a class="anchor" href="#id" aria-hidden="true">` showing `symbol`.
+///
+/// The tokens must already be wrapped in sections, the links point at their ids.
+/// The anchors aren't part of the heading text reused elsewhere: ids, the table
+/// of contents (inserted before them), titles and the search index leave them out.
+///
+/// # Example
+///
+/// ```rust
+/// let tokens = md2html::parse(vec![String::from("## Setup")].into_iter());
+/// let tokens = md2html::sections::add_heading_anchors(md2html::sections::wrap_sections(tokens), "¶");
+/// assert_eq!(
+///     md2html::render_html(&tokens)[1],
+///     "<h2>Setup<a class=\"anchor\" href=\"#setup\" aria-hidden=\"true\">¶</a></h2>"
+/// );
+/// ```
+pub fn add_heading_anchors(mut tokens: Vec<Token>, symbol: &str) -> Vec<Token> {
+    for i in 1..tokens.len() {
+        let Token::SectionStart { id } = &tokens[i - 1] else {
+            continue;
+        };
+        let anchor = format!(
+            "{}href=\"#{}\" aria-hidden=\"true\">{}</a>",
+            ANCHOR_START,
+            id,
+            escape::escape_text(symbol)
+        );
+        if let Token::Header { text, .. } = &mut tokens[i] {
+            text.push(Inline::Html(anchor));
+        }
+    }
+    tokens
+}
+
+/// The rendered heading `html` without the permalink of `add_heading_anchors`.
+pub(crate) fn without_heading_anchor(html: &str) -> Cow<'_, str> {
+    let Some(start) = html.rfind(ANCHOR_START) else {
+        return Cow::Borrowed(html);
+    };
+    match html[start..].find("</a>") {
+        Some(length) => Cow::Owned(format!(
+            "{}{}",
+            &html[..start],
+            &html[start + length + "</a>".len()..]
+        )),
+        None => Cow::Borrowed(html),
+    }
+}
+
+/// Returns the ids `wrap_sections` gives to the headings of rendered HTML, in
+/// document order.
+///
+/// # Example
+///
+/// ```rust
+/// let html = md2html::tokenize_text(vec![String::from("# Setup"), String::from("## Setup")].into_iter());
+/// assert_eq!(md2html::sections::heading_ids(&html), vec!["setup", "setup-1"]);
+/// ```
+pub fn heading_ids(html_lines: &[String]) -> Vec<String> {
+    heading_ids_with_options(html_lines, &ParserOptions::default())
+}
+
+/// Returns the ids `wrap_sections_with_options` gives to the headings of rendered
+/// HTML, in document order.
+///
+/// This is the configurable version of `heading_ids`, the HTML must have been
+/// rendered with the same `options`.
+pub fn heading_ids_with_options(html_lines: &[String], options: &ParserOptions) -> Vec<String> {
+    let mut used_ids: Vec<String> = Vec::new();
+    for line in html_lines.iter().filter(|line| is_heading(line)) {
+        unique_id(
+            slugify_with_options(&without_heading_anchor(line), options),
+            &mut used_ids,
+        );
+    }
+    used_ids
+}
+
+/// Makes `id` unique among `used_ids` by appending `-1`, `-2`, ... and records it.
+///
+/// Headings without any text left for an id (`#`, `# 🎉`) get `section`, since an
+/// empty `id` attribute isn't valid HTML.
+fn unique_id(id: String, used_ids: &mut Vec<String>) -> String {
+    let id = match id.is_empty() {
+        true => String::from("section"),
+        false => id,
+    };
+    let mut unique = id.clone();
+    let mut suffix = 1;
+    while used_ids.contains(&unique) {
+        unique = format!("{}-{}", id, suffix);
+        suffix += 1;
+    }
+    used_ids.push(unique.clone());
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, render_html};
+
+    fn sections_html(markdown: &str) -> Vec<String> {
+        let tokens = parse(markdown.lines().map(String::from));
+        render_html(&wrap_sections(tokens))
+    }
+
+    #[test]
+    fn test_sections_two_levels() {
+        let html = sections_html("# Guide\nIntro\n## Install\nSteps\n## Usage\nRun it");
+        assert_eq!(
+            html,
+            vec![
+                "<section id=\"guide\">",
+                "<h1>Guide</h1>",
+                "<p>Intro</p>",
+                "<section id=\"install\">",
+                "<h2>Install</h2>",
+                "<p>Steps</p>",
+                "</section>",
+                "<section id=\"usage\">",
+                "<h2>Usage</h2>",
+                "<p>Run it</p>",
+                "</section>",
+                "</section>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sections_level_skip() {
+        let html = sections_html("## Setup\n#### Details\nText\n## Next");
+        assert_eq!(
+            html,
+            vec![
+                "<section id=\"setup\">",
+                "<h2>Setup</h2>",
+                "<section id=\"details\">",
+                "<h4>Details</h4>",
+                "<p>Text</p>",
+                "</section>",
+                "</section>",
+                "<section id=\"next\">",
+                "<h2>Next</h2>",
+                "</section>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sections_content_before_first_heading() {
+        let html = sections_html("Preamble\n# Title\n# Title");
+        assert_eq!(
+            html,
+            vec![
+                "<p>Preamble</p>",
+                "<section id=\"title\">",
+                "<h1>Title</h1>",
+                "</section>",
+                "<section id=\"title-1\">",
+                "<h1>Title</h1>",
+                "</section>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sections_inside_containers() {
+        let html = sections_html("# Guide\n::: note\n## Aside\nText\n:::\n## Usage");
+        assert_eq!(
+            html,
+            vec![
+                "<section id=\"guide\">",
+                "<h1>Guide</h1>",
+                "<div class=\"note\">",
+                "<section id=\"aside\">",
+                "<h2>Aside</h2>",
+                "<p>Text</p>",
+                "</section>",
+                "</div>",
+                "<section id=\"usage\">",
+                "<h2>Usage</h2>",
+                "</section>",
+                "</section>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(
+            slugify("Code <i>Example</i>: part_2"),
+            "code-example-part_2"
+        );
+        assert_eq!(slugify("Über Größe"), "über-größe");
+    }
+
+    #[test]
+    fn test_sections_of_headings_without_id_text() {
+        let html = sections_html("#\n# 🎉\n## ##");
+        let ids: Vec<&String> = html
+            .iter()
+            .filter(|line| line.starts_with("<section"))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "<section id=\"section\">",
+                "<section id=\"section-1\">",
+                "<section id=\"section-2\">"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slugify_non_ascii_headings() {
+        let ascii = ParserOptions {
+            ascii_slugs: true,
+            ..Default::default()
+        };
+        let slugs = |heading: &str| (slugify(heading), slugify_with_options(heading, &ascii));
+        assert_eq!(
+            slugs("Überblick: Straße & Café"),
+            (
+                String::from("überblick-straße--café"),
+                String::from("uberblick-strasse--cafe")
+            )
+        );
+        // Decomposed letters (`U` and a combining diaeresis) give the same ASCII id
+        assert_eq!(slugs("U\u{0308}berblick").1, "uberblick");
+        assert_eq!(
+            slugs("日本語の見出し"),
+            (
+                String::from("日本語の見出し"),
+                String::from("%E6%97%A5%E6%9C%AC%E8%AA%9E%E3%81%AE%E8%A6%8B%E5%87%BA%E3%81%97")
+            )
+        );
+        assert_eq!(
+            slugs("🚀 Launch day 🎉"),
+            (String::from("-launch-day-"), String::from("-launch-day-"))
+        );
+    }
+
+    #[test]
+    fn test_heading_ids_match_sections() {
+        let markdown = "# Überblick\n## 日本語の見出し\n## Überblick 🎉";
+        for ascii_slugs in [false, true] {
+            let options = ParserOptions {
+                sections: true,
+                ascii_slugs,
+                ..Default::default()
+            };
+            let html =
+                crate::tokenize_text_with_options(markdown.lines().map(String::from), &options)
+                    .html_lines;
+            let section_ids: Vec<String> = html
+                .iter()
+                .filter_map(|line| line.strip_prefix("<section id=\""))
+                .map(|rest| rest.trim_end_matches("\">").to_string())
+                .collect();
+            assert_eq!(heading_ids_with_options(&html, &options), section_ids);
+        }
+    }
+
+    #[test]
+    fn test_github_slug_style() {
+        let github = ParserOptions {
+            slug_style: SlugStyle::GitHub,
+            ..Default::default()
+        };
+        // Heading, default id, GitHub id (the anchor github.com gives the heading)
+        let headings = [
+            ("Hello World", "hello-world", "hello-world"),
+            ("What's new?", "whats-new", "whats-new"),
+            ("C++ & Rust", "c--rust", "c--rust"),
+            (
+                "1. Getting started",
+                "1-getting-started",
+                "1-getting-started",
+            ),
+            (
+                "My Heading -- Part 2",
+                "my-heading----part-2",
+                "my-heading----part-2",
+            ),
+            (
+                "snake_case and kebab-case",
+                "snake_case-and-kebab-case",
+                "snake_case-and-kebab-case",
+            ),
+            (
+                "Über <code>code</code> <strong>bold</strong>",
+                "über-code-bold",
+                "über-code-bold",
+            ),
+            (
+                "2024 — Year in review",
+                "2024--year-in-review",
+                "2024--year-in-review",
+            ),
+            ("🎉 Party 🎉", "-party-", "-party-"),
+            ("❤\u{FE0F} Love", "-love", "\u{FE0F}-love"),
+            (
+                "1\u{FE0F}\u{20E3} Keycap",
+                "1-keycap",
+                "1\u{FE0F}\u{20E3}-keycap",
+            ),
+            ("Ⓐ circled", "ⓐ-circled", "-circled"),
+            ("foo‿bar", "foobar", "foo‿bar"),
+            ("हिन्दी", "हिनदी", "हिन्दी"),
+            ("ΟΔΟΣ Ⅻ x²", "οδοσ-ⅻ-x²", "οδος-ⅻ-x²"),
+        ];
+        for (heading, default_id, github_id) in headings {
+            assert_eq!(slugify(heading), default_id, "{}", heading);
+            assert_eq!(
+                slugify_with_options(heading, &github),
+                github_id,
+                "{}",
+                heading
+            );
+        }
+
+        // Duplicates are numbered the same way, and sections use the style
+        let tokens = parse(["# ❤\u{FE0F}", "# ❤\u{FE0F}"].iter().map(|s| s.to_string()));
+        let html = render_html(&wrap_sections_with_options(tokens, &github));
+        assert_eq!(html[0], "<section id=\"\u{FE0F}\">");
+        assert_eq!(html[3], "<section id=\"\u{FE0F}-1\">");
+
+        assert_eq!("github".parse(), Ok(SlugStyle::GitHub));
+        assert!("gitlab".parse::<SlugStyle>().is_err());
+    }
+
+    #[test]
+    fn test_heading_anchors() {
+        let options = ParserOptions {
+            heading_anchors: true,
+            toc: true,
+            ..Default::default()
+        };
+        let markdown = "# Guide\n## Set *up* & run\n## Set up & run";
+        let html = crate::tokenize_text_with_options(markdown.lines().map(String::from), &options)
+            .html_lines;
+        // The table of contents shows the heading text only
+        assert!(html.contains(&String::from(
+            "<li><a href=\"#set-up--run\">Set up &amp; run</a></li>"
+        )));
+        assert!(!html
+            .iter()
+            .any(|line| line.starts_with("<li>") && line.contains("anchor")));
+        assert!(html.contains(&String::from(
+            "<h2>Set <i>up</i> &amp; run<a class=\"anchor\" href=\"#set-up--run\" \
+             aria-hidden=\"true\">#</a></h2>"
+        )));
+        assert!(html.contains(&String::from(
+            "<h2>Set up &amp; run<a class=\"anchor\" href=\"#set-up--run-1\" \
+             aria-hidden=\"true\">#</a></h2>"
+        )));
+        assert_eq!(
+            heading_ids_with_options(&html, &options),
+            ["guide", "set-up--run", "set-up--run-1"]
+        );
+
+        // Any symbol, escaped, and never part of the heading text
+        let options = ParserOptions {
+            heading_anchors: true,
+            anchor_symbol: String::from("<link>"),
+            ..Default::default()
+        };
+        let html =
+            crate::tokenize_text_with_options(["# Intro"].map(String::from).into_iter(), &options)
+                .html_lines;
+        assert_eq!(
+            html[1],
+            "<h1>Intro<a class=\"anchor\" href=\"#intro\" aria-hidden=\"true\">&lt;link&gt;</a></h1>"
+        );
+        assert_eq!(crate::plain_text::heading_text(&html[1]), "Intro");
+        assert_eq!(heading_ids_with_options(&html, &options), ["intro"]);
+    }
+}
@@ -3,7 +3,7 @@ macro_rules! log {
     (debug, $($arg:tt)*) => {
         // If this is a release do not log debug messages
         if(cfg!(debug_assertions)) {
-            println!(
+            eprintln!(
                 "[{}] {} {}",
                 chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
                 "[DEBUG]".bold().white(),
@@ -12,7 +12,7 @@ macro_rules! log {
         }
     };
     (info, $($arg:tt)*) => {
-        println!(
+        eprintln!(
             "[{}] {} {}",
             chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
             "[INFO]".bold().green(),
@@ -20,7 +20,7 @@ macro_rules! log {
         );
     };
     (warning, $($arg:tt)*) => {
-        println!(
+        eprintln!(
             "[{}] {} {}",
             chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
             "[WARNING]".bold().truecolor(255, 165, 0),
@@ -28,7 +28,7 @@ macro_rules! log {
         );
     };
     (error, $($arg:tt)*) => {
-        println!(
+        eprintln!(
             "[{}] {} {}",
             chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
             "[ERROR]".bold().red(),
@@ -0,0 +1,404 @@
+use crate::batch::sort_inputs;
+use crate::search_index::json_string;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How many cells the table comparing the changed lines of a file may have, about
+/// 2000 lines on each side. Bigger changes are shown as every old line removed and
+/// every new line added.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A line of a changed file, see `line_diff`.
+///
+/// # Variants
+///
+/// - `Removed`: A line of the old file missing from the new one, with its 1-based
+///   line number in the old file.
+/// - `Added`: A line of the new file that wasn't in the old one, with its line
+///   number in the new file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Removed { line: usize, text: String },
+    Added { line: usize, text: String },
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffLine::Removed { line, text } => write!(f, "- {} | {}", line, text),
+            DiffLine::Added { line, text } => write!(f, "+ {} | {}", line, text),
+        }
+    }
+}
+
+/// How a line of two compared files lines up, see `align_lines`.
+///
+/// # Variants
+///
+/// - `Same`: The 0-based index of a line of the old file, and of the same line
+///   kept in the new file.
+/// - `Removed`: The index of a line of the old file missing from the new one.
+/// - `Added`: The index of a line of the new file that wasn't in the old one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineOp {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// What became of an output file from one conversion to the next.
+///
+/// # Variants
+///
+/// - `Identical`: The file has the same lines in both.
+/// - `Changed`: The file is in both but its lines differ, see `line_diff`.
+/// - `Added`: The file is only in the new output.
+/// - `Removed`: The file is only in the old output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileChange {
+    Identical,
+    Changed(Vec<DiffLine>),
+    Added,
+    Removed,
+}
+
+impl FileChange {
+    /// The name of the change in reports: `identical`, `changed`, `added` or
+    /// `removed`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileChange::Identical => "identical",
+            FileChange::Changed(_) => "changed",
+            FileChange::Added => "added",
+            FileChange::Removed => "removed",
+        }
+    }
+}
+
+/// An output file compared by `diff_trees`, `path` being relative to the output
+/// directories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    pub change: FileChange,
+}
+
+/// Reads every `.html` file under `dir`, with its path relative to `dir`, in the
+/// order of `batch::sort_inputs`.
+pub fn read_html_files(dir: &Path) -> io::Result<Vec<(PathBuf, String)>> {
+    let mut paths = Vec::new();
+    collect_html_files(dir, &mut paths)?;
+    sort_inputs(&mut paths);
+    paths
+        .into_iter()
+        .map(|path| {
+            let html = fs::read_to_string(&path)?;
+            Ok((path.strip_prefix(dir).unwrap().to_path_buf(), html))
+        })
+        .collect()
+}
+
+fn collect_html_files(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_html_files(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "html") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Compares the files of two outputs, given as their relative path and content,
+/// returning every file of either in the order of `batch::sort_inputs`.
+///
+/// Files are compared line by line, `\n` and `\r\n` line endings alike and with
+/// or without a newline at the end, so only a change of the HTML itself shows.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::site_diff::{diff_trees, DiffLine, FileChange};
+///
+/// let old = [("a.html".into(), String::from("<p>A</p>\r\n")), ("b.html".into(), String::from("<p>B</p>\n<p>C</p>"))];
+/// let new = [("a.html".into(), String::from("<p>A</p>")), ("b.html".into(), String::from("<p>B</p>\n<p>D</p>\n"))];
+/// let diffs = diff_trees(&old, &new);
+/// assert_eq!(diffs[0].change, FileChange::Identical);
+/// assert_eq!(
+///     diffs[1].change,
+///     FileChange::Changed(vec![
+///         DiffLine::Removed { line: 2, text: String::from("<p>C</p>") },
+///         DiffLine::Added { line: 2, text: String::from("<p>D</p>") },
+///     ])
+/// );
+/// ```
+pub fn diff_trees(old: &[(PathBuf, String)], new: &[(PathBuf, String)]) -> Vec<FileDiff> {
+    let old_files: HashMap<&PathBuf, &String> =
+        old.iter().map(|(path, html)| (path, html)).collect();
+    let new_files: HashMap<&PathBuf, &String> =
+        new.iter().map(|(path, html)| (path, html)).collect();
+    let mut paths: Vec<PathBuf> = old_files
+        .keys()
+        .chain(
+            new_files
+                .keys()
+                .filter(|path| !old_files.contains_key(*path)),
+        )
+        .map(|path| path.to_path_buf())
+        .collect();
+    sort_inputs(&mut paths);
+    paths
+        .into_iter()
+        .map(|path| {
+            let change = match (old_files.get(&path), new_files.get(&path)) {
+                (Some(old), Some(new)) => {
+                    let old_lines: Vec<&str> = old.lines().collect();
+                    let new_lines: Vec<&str> = new.lines().collect();
+                    match old_lines == new_lines {
+                        true => FileChange::Identical,
+                        false => FileChange::Changed(line_diff(&old_lines, &new_lines)),
+                    }
+                }
+                (Some(_), None) => FileChange::Removed,
+                _ => FileChange::Added,
+            };
+            FileDiff { path, change }
+        })
+        .collect()
+}
+
+/// The lines removed from `old` and added in `new`, the smallest such set for
+/// changes of up to a few thousand lines (see `align_lines`). Removed lines come
+/// before the lines added in their place.
+pub fn line_diff(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    align_lines(old, new)
+        .into_iter()
+        .filter_map(|op| match op {
+            LineOp::Same(_, _) => None,
+            LineOp::Removed(i) => Some(DiffLine::Removed {
+                line: i + 1,
+                text: old[i].to_string(),
+            }),
+            LineOp::Added(j) => Some(DiffLine::Added {
+                line: j + 1,
+                text: new[j].to_string(),
+            }),
+        })
+        .collect()
+}
+
+/// Lines up `old` and `new` along their longest common subsequence of lines, in
+/// order: every line of `old` is kept or removed, every line of `new` kept or
+/// added. Removed lines come before the lines added in their place.
+///
+/// The common first and last lines are matched right away, and the lines in
+/// between compared with a table of up to `MAX_DIFF_CELLS` cells. A bigger change
+/// is every old line of it removed and every new line added.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::site_diff::{align_lines, LineOp};
+///
+/// assert_eq!(
+///     align_lines(&["a", "b", "c"], &["a", "c", "d"]),
+///     [LineOp::Same(0, 0), LineOp::Removed(1), LineOp::Same(2, 1), LineOp::Added(2)]
+/// );
+/// ```
+pub fn align_lines(old: &[&str], new: &[&str]) -> Vec<LineOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let (n, m) = (old_middle.len(), new_middle.len());
+    let mut ops: Vec<LineOp> = (0..prefix).map(|i| LineOp::Same(i, i)).collect();
+    if (n + 1) * (m + 1) > MAX_DIFF_CELLS {
+        ops.extend((0..n).map(|i| LineOp::Removed(prefix + i)));
+        ops.extend((0..m).map(|j| LineOp::Added(prefix + j)));
+    } else {
+        // common[i][j]: the longest common subsequence of old_middle[i..] and
+        // new_middle[j..]
+        let mut common = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                common[i][j] = match old_middle[i] == new_middle[j] {
+                    true => common[i + 1][j + 1] + 1,
+                    false => common[i + 1][j].max(common[i][j + 1]),
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                ops.push(LineOp::Same(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+                ops.push(LineOp::Removed(prefix + i));
+                i += 1;
+            } else {
+                ops.push(LineOp::Added(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    ops.extend((0..suffix).map(|k| LineOp::Same(old.len() - suffix + k, new.len() - suffix + k)));
+    ops
+}
+
+/// The counts of the changes of `diffs`, e.g. `3 identical, 1 changed, 1 added,
+/// 0 removed`.
+pub fn summary(diffs: &[FileDiff]) -> String {
+    format!(
+        "{} identical, {} changed, {} added, {} removed",
+        count(diffs, "identical"),
+        count(diffs, "changed"),
+        count(diffs, "added"),
+        count(diffs, "removed")
+    )
+}
+
+/// How many of `diffs` are the change named `name`.
+fn count(diffs: &[FileDiff], name: &str) -> usize {
+    diffs
+        .iter()
+        .filter(|diff| diff.change.name() == name)
+        .count()
+}
+
+/// The comparison as a JSON object, `{"files": [{"path": ..., "status": "changed",
+/// "diff": ["- 2 | ...", "+ 2 | ..."]}], "identical": 3, "changed": 1, "added": 1,
+/// "removed": 0}`. Only changed files have a `diff`.
+pub fn to_json(diffs: &[FileDiff]) -> String {
+    let files: Vec<String> = diffs
+        .iter()
+        .map(|diff| {
+            let lines = match &diff.change {
+                FileChange::Changed(lines) => {
+                    let lines: Vec<String> = lines
+                        .iter()
+                        .map(|line| json_string(&line.to_string()))
+                        .collect();
+                    format!(", \"diff\": [{}]", lines.join(", "))
+                }
+                _ => String::new(),
+            };
+            format!(
+                "{{\"path\": {}, \"status\": \"{}\"{}}}",
+                json_string(&diff.path.display().to_string()),
+                diff.change.name(),
+                lines
+            )
+        })
+        .collect();
+    format!(
+        "{{\"files\": [{}], \"identical\": {}, \"changed\": {}, \"added\": {}, \"removed\": {}}}\n",
+        files.join(", "),
+        count(diffs, "identical"),
+        count(diffs, "changed"),
+        count(diffs, "added"),
+        count(diffs, "removed")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<&str> {
+        text.lines().collect()
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let old = lines("<h1>T</h1>\n<p>a</p>\n<p>b</p>\n<p>c</p>\n<p>end</p>");
+        let new = lines("<h1>T</h1>\n<p>b</p>\n<p>c2</p>\n<p>new</p>\n<p>end</p>");
+        let diff: Vec<String> = line_diff(&old, &new)
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(
+            diff,
+            [
+                "- 2 | <p>a</p>",
+                "- 4 | <p>c</p>",
+                "+ 3 | <p>c2</p>",
+                "+ 4 | <p>new</p>"
+            ]
+        );
+        assert!(line_diff(&old, &old).is_empty());
+        assert_eq!(line_diff(&[], &["a"]).len(), 1);
+    }
+
+    #[test]
+    fn test_fixture_pair() {
+        let dir = std::env::temp_dir().join("md2html-site-diff");
+        let _ = fs::remove_dir_all(&dir);
+        let (old_dir, new_dir) = (dir.join("old"), dir.join("new"));
+        for (path, html) in [
+            ("index.html", "<h1>Home</h1>\n<p>Welcome</p>\n"),
+            ("guide/setup.html", "<h1>Setup</h1>\r\n<p>Run it</p>\r\n"),
+            ("old.html", "<p>Gone</p>\n"),
+            ("notes.txt", "not html"),
+        ] {
+            fs::create_dir_all(old_dir.join(path).parent().unwrap()).unwrap();
+            fs::write(old_dir.join(path), html).unwrap();
+        }
+        for (path, html) in [
+            ("index.html", "<h1>Home</h1>\n<p>Welcome back</p>\n"),
+            ("guide/setup.html", "<h1>Setup</h1>\n<p>Run it</p>"),
+            ("guide/faq.html", "<h1>FAQ</h1>\n"),
+        ] {
+            fs::create_dir_all(new_dir.join(path).parent().unwrap()).unwrap();
+            fs::write(new_dir.join(path), html).unwrap();
+        }
+
+        let old = read_html_files(&old_dir).unwrap();
+        let new = read_html_files(&new_dir).unwrap();
+        let diffs = diff_trees(&old, &new);
+        let changes: Vec<(String, &str)> = diffs
+            .iter()
+            .map(|diff| (diff.path.display().to_string(), diff.change.name()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (
+                    format!("guide{}faq.html", std::path::MAIN_SEPARATOR),
+                    "added"
+                ),
+                (
+                    format!("guide{}setup.html", std::path::MAIN_SEPARATOR),
+                    "identical"
+                ),
+                (String::from("index.html"), "changed"),
+                (String::from("old.html"), "removed"),
+            ]
+        );
+        assert_eq!(
+            summary(&diffs),
+            "1 identical, 1 changed, 1 added, 1 removed"
+        );
+        let json = to_json(&diffs);
+        assert!(
+            json.contains(
+                "{\"path\": \"index.html\", \"status\": \"changed\", \"diff\": \
+                 [\"- 2 | <p>Welcome</p>\", \"+ 2 | <p>Welcome back</p>\"]}"
+            ),
+            "{}",
+            json
+        );
+        assert!(json.ends_with("\"identical\": 1, \"changed\": 1, \"added\": 1, \"removed\": 1}\n"));
+    }
+}
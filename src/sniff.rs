@@ -0,0 +1,127 @@
+use std::path::Path;
+
+/// How many bytes at the start of an input `sniff` looks at.
+pub const SNIFF_LENGTH: usize = 8 * 1024;
+
+/// Extensions of files that are never markdown, refused before reading them as text.
+pub const BINARY_EXTENSIONS: &[&str] = &[
+    "7z", "a", "avi", "bin", "bmp", "class", "dll", "doc", "docx", "dylib", "exe", "gif", "gz",
+    "ico", "jar", "jpeg", "jpg", "mov", "mp3", "mp4", "o", "odt", "ogg", "otf", "pdf", "png", "so",
+    "tar", "ttf", "wasm", "wav", "webm", "webp", "woff", "woff2", "xls", "xlsx", "zip",
+];
+
+/// What the start of an input looks like, see `sniff`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Content {
+    /// Text without NUL bytes, converted as markdown.
+    Text,
+    /// Text encoded as UTF-16, which has to be converted to UTF-8 first.
+    Utf16,
+    /// Anything else containing NUL bytes: an image, an archive, a PDF...
+    Binary,
+}
+
+/// Guesses whether `bytes`, the start of an input, is text from its first
+/// `SNIFF_LENGTH` bytes.
+///
+/// Markdown never contains NUL bytes, while nearly every binary format does. UTF-16
+/// is recognized by its byte order mark, or by the NUL bytes ASCII characters
+/// leave at every other position.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::sniff::{sniff, Content};
+///
+/// assert_eq!(sniff("# Title\n".as_bytes()), Content::Text);
+/// assert_eq!(sniff(b"%PDF-1.7\n\x00\x01\x02"), Content::Binary);
+/// assert_eq!(sniff(b"#\x00 \x00T\x00"), Content::Utf16);
+/// ```
+pub fn sniff(bytes: &[u8]) -> Content {
+    let head = &bytes[..bytes.len().min(SNIFF_LENGTH)];
+    if head.starts_with(&[0xFF, 0xFE]) || head.starts_with(&[0xFE, 0xFF]) {
+        return Content::Utf16;
+    }
+    if !head.contains(&0) {
+        return Content::Text;
+    }
+    let nuls = |start: usize| {
+        head.iter()
+            .skip(start)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let (even, odd) = (nuls(0), nuls(1));
+    let (most, fewest) = (even.max(odd), even.min(odd));
+    if most * 2 >= head.len() / 2 && fewest * 10 <= most {
+        Content::Utf16
+    } else {
+        Content::Binary
+    }
+}
+
+/// Whether the extension of `path` is one of `BINARY_EXTENSIONS`, ignoring case.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::sniff::has_binary_extension;
+/// use std::path::Path;
+///
+/// assert!(has_binary_extension(Path::new("images/Logo.PNG")));
+/// assert!(!has_binary_extension(Path::new("README.md")));
+/// ```
+pub fn has_binary_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            BINARY_EXTENSIONS
+                .iter()
+                .any(|binary| binary.eq_ignore_ascii_case(ext))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_header_is_binary() {
+        let png = [
+            0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, b'I', b'H',
+            b'D', b'R', 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0x06, 0x00, 0x00,
+            0x00,
+        ];
+        assert_eq!(sniff(&png), Content::Binary);
+    }
+
+    #[test]
+    fn test_utf8_text() {
+        let text = "# Größe\n\nPlain *markdown*, 日本語 and emoji 🎉\n";
+        assert_eq!(sniff(text.as_bytes()), Content::Text);
+        assert_eq!(sniff(b""), Content::Text);
+        // A UTF-8 byte order mark is still text
+        assert_eq!(sniff(b"\xEF\xBB\xBF# Title"), Content::Text);
+    }
+
+    #[test]
+    fn test_utf16() {
+        let text = "# Title\n\nSome *text*.\n";
+        let little_endian: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let big_endian: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(sniff(&little_endian), Content::Utf16);
+        assert_eq!(sniff(&big_endian), Content::Utf16);
+
+        let mut with_bom = vec![0xFF, 0xFE];
+        with_bom.extend(&little_endian);
+        assert_eq!(sniff(&with_bom), Content::Utf16);
+    }
+
+    #[test]
+    fn test_only_the_start_is_sniffed() {
+        let mut bytes = vec![b'a'; SNIFF_LENGTH];
+        bytes.push(0);
+        assert_eq!(sniff(&bytes), Content::Text);
+    }
+}
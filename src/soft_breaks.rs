@@ -0,0 +1,136 @@
+use crate::inline::Inline;
+use crate::ParserOptions;
+use std::str::FromStr;
+
+/// What the line break between two lines of a paragraph becomes once they're
+/// merged, when the first line doesn't end with a hard break (two spaces or `\`).
+///
+/// # Variants
+///
+/// - `Space`: The lines reflow, the break becomes a space.
+/// - `Break`: The line structure is kept, the break becomes a `<br/>`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SoftBreak {
+    #[default]
+    Space,
+    Break,
+}
+
+impl SoftBreak {
+    /// The inline content put between the merged lines.
+    pub fn inline(self) -> Inline {
+        match self {
+            SoftBreak::Space => Inline::Text(String::from(" ")),
+            SoftBreak::Break => Inline::Break,
+        }
+    }
+}
+
+impl FromStr for SoftBreak {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "space" => Ok(SoftBreak::Space),
+            "break" => Ok(SoftBreak::Break),
+            _ => Err(format!(
+                "unknown soft break policy \"{}\", expected space or break",
+                policy
+            )),
+        }
+    }
+}
+
+/// A block a paragraph can be inside of, for `resolve`.
+///
+/// # Variants
+///
+/// - `Quote`: A blockquote or callout, following `ParserOptions::soft_break_in_quotes`.
+/// - `Container`: A `:::` container or collapsible section, which follows the
+///   policy of the blocks around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoftBreakScope {
+    Quote,
+    Container,
+}
+
+/// The soft break policy of a paragraph inside the `scopes` blocks, innermost last:
+/// the policy of the innermost block that has one, `soft_break_default` otherwise.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::soft_breaks::{resolve, SoftBreak, SoftBreakScope};
+///
+/// let options = md2html::ParserOptions {
+///     soft_break_in_quotes: Some(SoftBreak::Break),
+///     ..Default::default()
+/// };
+/// assert_eq!(resolve(&[], &options), SoftBreak::Space);
+/// assert_eq!(resolve(&[SoftBreakScope::Quote, SoftBreakScope::Container], &options), SoftBreak::Break);
+/// ```
+pub fn resolve(scopes: &[SoftBreakScope], options: &ParserOptions) -> SoftBreak {
+    scopes
+        .iter()
+        .rev()
+        .find_map(|scope| match scope {
+            SoftBreakScope::Quote => options.soft_break_in_quotes,
+            SoftBreakScope::Container => None,
+        })
+        .unwrap_or(options.soft_break_default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SoftBreakScope::{Container, Quote};
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!("space".parse(), Ok(SoftBreak::Space));
+        assert_eq!("break".parse(), Ok(SoftBreak::Break));
+        assert!("br"
+            .parse::<SoftBreak>()
+            .unwrap_err()
+            .contains("space or break"));
+    }
+
+    #[test]
+    fn test_resolve_innermost_policy() {
+        let defaults = ParserOptions::default();
+        let quotes_break = ParserOptions {
+            soft_break_in_quotes: Some(SoftBreak::Break),
+            ..Default::default()
+        };
+        let default_break = ParserOptions {
+            soft_break_default: SoftBreak::Break,
+            ..Default::default()
+        };
+        let quotes_space = ParserOptions {
+            soft_break_default: SoftBreak::Break,
+            soft_break_in_quotes: Some(SoftBreak::Space),
+            ..Default::default()
+        };
+        let cases = [
+            (&defaults, vec![], SoftBreak::Space),
+            (&defaults, vec![Quote], SoftBreak::Space),
+            (&quotes_break, vec![], SoftBreak::Space),
+            (&quotes_break, vec![Container], SoftBreak::Space),
+            (&quotes_break, vec![Quote], SoftBreak::Break),
+            (
+                &quotes_break,
+                vec![Container, Quote, Quote],
+                SoftBreak::Break,
+            ),
+            (&quotes_break, vec![Quote, Container], SoftBreak::Break),
+            (&default_break, vec![Container], SoftBreak::Break),
+            // Quotes without a policy of their own follow the default
+            (&default_break, vec![Quote], SoftBreak::Break),
+            (&quotes_space, vec![Quote], SoftBreak::Space),
+            (&quotes_space, vec![Container], SoftBreak::Break),
+        ];
+        for (options, scopes, expected) in cases {
+            assert_eq!(resolve(&scopes, options), expected, "{:?}", scopes);
+        }
+    }
+}
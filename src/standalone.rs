@@ -0,0 +1,591 @@
+use crate::book::relative_url;
+use crate::entities::escape_attribute;
+use crate::escape::{escape_attr, escape_text};
+use crate::plain_text::heading_text;
+use std::str::FromStr;
+
+/// The id of the `<main>` element, targeted by the "skip to content" link.
+pub const CONTENT_ID: &str = "content";
+
+/// Hides the skip link until it receives keyboard focus.
+const VISUALLY_HIDDEN_CSS: &str = ".visually-hidden:not(:focus){position:absolute;width:1px;height:1px;overflow:hidden;clip:rect(0 0 0 0);white-space:nowrap}";
+
+/// Options of a standalone HTML page.
+///
+/// # Fields
+///
+/// - `title`: The text of the `<title>` element.
+/// - `lang`: The language of the page, its `<html lang>`, `en` when `None`.
+/// - `header_html`: HTML injected at the top of the page, inside a `<header>` landmark.
+/// - `footer_html`: HTML injected at the bottom of the page, inside a `<footer>` landmark.
+/// - `sidebar_html`: Navigation HTML put between the header and the content, such as
+///   the chapter tree of a book (see `book::sidebar_html`).
+/// - `page_nav_html`: Navigation HTML put after the content, such as the previous and
+///   next page links (see `page_nav_html`).
+/// - `meta`: The description and open graph `<meta>` tags, left out when `None`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PageOptions {
+    pub title: String,
+    pub lang: Option<String>,
+    pub header_html: Option<String>,
+    pub footer_html: Option<String>,
+    pub sidebar_html: Option<String>,
+    pub page_nav_html: Option<String>,
+    pub meta: Option<PageMeta>,
+}
+
+/// The `<meta>` tags of a standalone page, shown by link previews.
+///
+/// # Fields
+///
+/// - `title`: The `og:title`, when it differs from the page title.
+/// - `description`: The `description` and `og:description`, as plain text.
+/// - `image`: The URL of the `og:image`.
+/// - `author`: The `author` of the page.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PageMeta {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub author: Option<String>,
+}
+
+/// The longest description derived from the content, in characters.
+pub const DESCRIPTION_LENGTH: usize = 160;
+
+/// A page of a batch conversion, as linked to by the previous/next navigation.
+///
+/// # Fields
+///
+/// - `title`: The title of the page, as plain text.
+/// - `url`: The URL of the page, relative to the output directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLink {
+    pub title: String,
+    pub url: String,
+}
+
+/// Wraps converted HTML into a complete, screen-reader friendly page.
+///
+/// The content is put inside `<main id="content">`, preceded by a visually-hidden
+/// "Skip to content" link targeting it, which is the first focusable element of the
+/// page. The injected header and footer HTML get their own `<header>`/`<footer>`
+/// landmarks, and are left out entirely when not set.
+///
+/// # Example
+///
+/// ```rust
+/// let html = md2html::tokenize_text(vec![String::from("# Intro")].into_iter());
+/// let options = md2html::standalone::PageOptions {
+///     title: String::from("Intro"),
+///     ..Default::default()
+/// };
+/// let page = md2html::standalone::wrap_page(&html, &options);
+/// assert_eq!(page[0], "<!DOCTYPE html>");
+/// assert!(page.contains(&String::from("<main id=\"content\">")));
+/// ```
+pub fn wrap_page(html_lines: &[String], options: &PageOptions) -> Vec<String> {
+    let mut page = vec![
+        String::from("<!DOCTYPE html>"),
+        format!(
+            "<html lang=\"{}\">",
+            escape_attr(options.lang.as_deref().unwrap_or("en"))
+        ),
+        String::from("<head>"),
+        String::from("<meta charset=\"utf-8\">"),
+        String::from("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">"),
+        format!("<title>{}</title>", escape_text(&options.title)),
+    ];
+    if let Some(meta) = &options.meta {
+        page.extend(meta_tags(&options.title, meta));
+    }
+    page.extend([
+        format!("<style>{}</style>", VISUALLY_HIDDEN_CSS),
+        String::from("</head>"),
+        String::from("<body>"),
+        format!(
+            "<a class=\"visually-hidden\" href=\"#{}\">Skip to content</a>",
+            CONTENT_ID
+        ),
+    ]);
+
+    if let Some(header_html) = &options.header_html {
+        page.push(String::from("<header>"));
+        page.extend(header_html.lines().map(String::from));
+        page.push(String::from("</header>"));
+    }
+
+    if let Some(sidebar_html) = &options.sidebar_html {
+        page.extend(sidebar_html.lines().map(String::from));
+    }
+
+    page.push(format!("<main id=\"{}\">", CONTENT_ID));
+    page.extend(html_lines.iter().cloned());
+    page.push(String::from("</main>"));
+
+    if let Some(page_nav_html) = &options.page_nav_html {
+        page.extend(page_nav_html.lines().map(String::from));
+    }
+
+    if let Some(footer_html) = &options.footer_html {
+        page.push(String::from("<footer>"));
+        page.extend(footer_html.lines().map(String::from));
+        page.push(String::from("</footer>"));
+    }
+
+    page.push(String::from("</body>"));
+    page.push(String::from("</html>"));
+    page
+}
+
+/// Renders the `<meta>` tags of `meta`, `title` being the `og:title` fallback.
+///
+/// The values are plain text, so every `&` is escaped, not only the ones of
+/// invalid references.
+fn meta_tags(title: &str, meta: &PageMeta) -> Vec<String> {
+    let title = meta.title.as_deref().unwrap_or(title);
+    let mut tags = Vec::new();
+    if let Some(description) = &meta.description {
+        tags.push(format!(
+            "<meta name=\"description\" content=\"{}\">",
+            escape_attr(description)
+        ));
+    }
+    tags.push(format!(
+        "<meta property=\"og:title\" content=\"{}\">",
+        escape_attr(title)
+    ));
+    if let Some(description) = &meta.description {
+        tags.push(format!(
+            "<meta property=\"og:description\" content=\"{}\">",
+            escape_attr(description)
+        ));
+    }
+    if let Some(image) = &meta.image {
+        tags.push(format!(
+            "<meta property=\"og:image\" content=\"{}\">",
+            escape_attr(image)
+        ));
+    }
+    if let Some(author) = &meta.author {
+        tags.push(format!(
+            "<meta name=\"author\" content=\"{}\">",
+            escape_attr(author)
+        ));
+    }
+    tags
+}
+
+/// Returns the plain text of the first paragraph of `html_lines` outside quotes,
+/// cut on a word boundary to at most `DESCRIPTION_LENGTH` characters, or `None` when
+/// there is no paragraph.
+///
+/// # Example
+///
+/// ```rust
+/// let html = vec![String::from("<h1>Intro</h1>"), String::from("<p>Some <em>text</em></p>")];
+/// assert_eq!(md2html::standalone::page_description(&html).as_deref(), Some("Some text"));
+/// assert_eq!(md2html::standalone::page_description(&html[..1]), None);
+/// ```
+pub fn page_description(html_lines: &[String]) -> Option<String> {
+    // Paragraphs of quotes are someone else's words
+    let mut quote_depth = 0;
+    let start = html_lines.iter().position(|line| {
+        if line.starts_with("<blockquote") {
+            quote_depth += 1;
+        } else if line.starts_with("</blockquote>") {
+            quote_depth -= 1;
+        }
+        quote_depth == 0 && line.starts_with("<p>")
+    })?;
+    // A wrapped paragraph spans several lines
+    let mut paragraph = Vec::new();
+    for line in &html_lines[start..] {
+        paragraph.push(line.as_str());
+        if line.ends_with("</p>") {
+            break;
+        }
+    }
+    let text = heading_text(&paragraph.join(" "));
+    if text.is_empty() {
+        return None;
+    }
+    Some(truncate_words(&text, DESCRIPTION_LENGTH))
+}
+
+/// Cuts `text` to at most `length` characters on a word boundary, ending it with
+/// `…` when it is cut.
+fn truncate_words(text: &str, length: usize) -> String {
+    if text.chars().count() <= length {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    for word in text.split(' ') {
+        let separator = usize::from(!truncated.is_empty());
+        if truncated.chars().count() + separator + word.chars().count() + 1 > length {
+            break;
+        }
+        if separator == 1 {
+            truncated.push(' ');
+        }
+        truncated.push_str(word);
+    }
+    if truncated.is_empty() {
+        // A single word longer than the limit
+        truncated = text.chars().take(length - 1).collect();
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// The `<div>` a fragment is wrapped in with `--wrap-div`, to embed it in an
+/// existing page.
+///
+/// It is parsed from `class` or `#id.class`: an optional `#id` followed by the
+/// classes, separated by dots (`#content.article-body.wide`). A spec without `#`
+/// is a class list, so `article-body` is `<div class="article-body">`.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::standalone::WrapDiv;
+///
+/// let wrap_div: WrapDiv = "#content.article-body".parse().unwrap();
+/// assert_eq!(wrap_div.id.as_deref(), Some("content"));
+/// assert_eq!(wrap_div.classes, vec!["article-body"]);
+/// assert!("#".parse::<WrapDiv>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrapDiv {
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+}
+
+impl FromStr for WrapDiv {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        let (id, classes) = match spec.strip_prefix('#') {
+            Some(rest) => match rest.split_once('.') {
+                Some((id, classes)) => (Some(id), classes),
+                None => (Some(rest), ""),
+            },
+            None => (None, spec),
+        };
+        if id.is_some_and(str::is_empty) {
+            return Err(format!("empty id in the wrapper \"{}\"", spec));
+        }
+        let classes: Vec<String> = classes
+            .split('.')
+            .filter(|class| !class.is_empty())
+            .map(String::from)
+            .collect();
+        if id.is_none() && classes.is_empty() {
+            return Err(String::from("the wrapper needs a class or an #id"));
+        }
+        let mut names = id.into_iter().chain(classes.iter().map(String::as_str));
+        if let Some(name) = names.find(|name| name.contains(char::is_whitespace)) {
+            return Err(format!("\"{}\" can't contain spaces", name));
+        }
+        Ok(WrapDiv {
+            id: id.map(String::from),
+            classes,
+        })
+    }
+}
+
+/// Wraps the lines of a fragment into the `<div>` of `wrap_div`.
+///
+/// # Example
+///
+/// ```rust
+/// let wrap_div = "article-body".parse().unwrap();
+/// let html = md2html::standalone::wrap_fragment(vec![String::from("<p>Text</p>")], &wrap_div);
+/// assert_eq!(html, vec!["<div class=\"article-body\">", "<p>Text</p>", "</div>"]);
+/// ```
+pub fn wrap_fragment(html_lines: Vec<String>, wrap_div: &WrapDiv) -> Vec<String> {
+    let mut open = String::from("<div");
+    if let Some(id) = &wrap_div.id {
+        open += &format!(" id=\"{}\"", escape_attribute(id));
+    }
+    if !wrap_div.classes.is_empty() {
+        open += &format!(
+            " class=\"{}\"",
+            escape_attribute(&wrap_div.classes.join(" "))
+        );
+    }
+    open.push('>');
+
+    let mut lines = Vec::with_capacity(html_lines.len() + 2);
+    lines.push(open);
+    lines.extend(html_lines);
+    lines.push(String::from("</div>"));
+    lines
+}
+
+/// Returns the plain text of the first `<h1>` of `html_lines` (see
+/// `plain_text::heading_text`), or `fallback` when there is none.
+///
+/// # Example
+///
+/// ```rust
+/// let html = vec![String::from("<p>Text</p>"), String::from("<h1>The <em>Guide</em></h1>")];
+/// assert_eq!(md2html::standalone::page_title("guide", &html), "The Guide");
+/// assert_eq!(md2html::standalone::page_title("guide", &[]), "guide");
+/// ```
+pub fn page_title(fallback: &str, html_lines: &[String]) -> String {
+    html_lines
+        .iter()
+        .find(|line| line.starts_with("<h1>"))
+        .map(|line| heading_text(line))
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Renders the "Previous"/"Next" links of `pages[current]`, in a batch of ordered
+/// pages.
+///
+/// Links are relative to the current page. The first page has no "Previous" link
+/// and the last one no "Next" link, so a batch of a single page has no navigation
+/// at all and `None` is returned.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::standalone::{page_nav_html, PageLink};
+///
+/// let pages = vec![
+///     PageLink { title: String::from("Intro"), url: String::from("intro.html") },
+///     PageLink { title: String::from("Usage"), url: String::from("usage.html") },
+/// ];
+/// let nav = page_nav_html(&pages, 0).unwrap();
+/// assert!(nav.contains("<a href=\"usage.html\" rel=\"next\">Next: Usage →</a>"));
+/// assert!(!nav.contains("rel=\"prev\""));
+/// ```
+pub fn page_nav_html(pages: &[PageLink], current: usize) -> Option<String> {
+    let current_url = &pages[current].url;
+    let previous = current.checked_sub(1).map(|index| &pages[index]);
+    let next = pages.get(current + 1);
+    if previous.is_none() && next.is_none() {
+        return None;
+    }
+
+    let mut lines = vec![String::from(
+        "<nav class=\"page-nav\" aria-label=\"Previous and next pages\">",
+    )];
+    if let Some(page) = previous {
+        lines.push(format!(
+            "<a href=\"{}\" rel=\"prev\">← Previous: {}</a>",
+            relative_url(current_url, &page.url),
+            escape_text(&page.title)
+        ));
+    }
+    if let Some(page) = next {
+        lines.push(format!(
+            "<a href=\"{}\" rel=\"next\">Next: {} →</a>",
+            relative_url(current_url, &page.url),
+            escape_text(&page.title)
+        ));
+    }
+    lines.push(String::from("</nav>"));
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_landmarks() {
+        let options = PageOptions {
+            title: String::from("Notes & <Ideas>"),
+            lang: None,
+            header_html: Some(String::from("<a href=\"/\">Home</a>")),
+            footer_html: Some(String::from("<p>MIT licensed</p>")),
+            sidebar_html: None,
+            page_nav_html: None,
+            meta: None,
+        };
+        let page = wrap_page(&[String::from("<p>Text</p>")], &options);
+        assert_eq!(
+            page[5..],
+            [
+                "<title>Notes &amp; &lt;Ideas&gt;</title>",
+                &format!("<style>{}</style>", VISUALLY_HIDDEN_CSS),
+                "</head>",
+                "<body>",
+                "<a class=\"visually-hidden\" href=\"#content\">Skip to content</a>",
+                "<header>",
+                "<a href=\"/\">Home</a>",
+                "</header>",
+                "<main id=\"content\">",
+                "<p>Text</p>",
+                "</main>",
+                "<footer>",
+                "<p>MIT licensed</p>",
+                "</footer>",
+                "</body>",
+                "</html>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_meta_tags_escaped() {
+        let options = PageOptions {
+            title: String::from("Notes"),
+            meta: Some(PageMeta {
+                title: None,
+                description: Some(String::from("The \"best\" <notes> & more")),
+                image: Some(String::from("img/a\"b.png")),
+                author: None,
+            }),
+            ..Default::default()
+        };
+        let page = wrap_page(&[], &options);
+        assert_eq!(
+            page[6..10],
+            [
+                "<meta name=\"description\" content=\"The &quot;best&quot; &lt;notes&gt; &amp; more\">",
+                "<meta property=\"og:title\" content=\"Notes\">",
+                "<meta property=\"og:description\" content=\"The &quot;best&quot; &lt;notes&gt; &amp; more\">",
+                "<meta property=\"og:image\" content=\"img/a&quot;b.png\">",
+            ]
+        );
+        assert!(!wrap_page(&[], &PageOptions::default())
+            .iter()
+            .any(|line| line.starts_with("<meta property")));
+    }
+
+    #[test]
+    fn test_page_description_truncated() {
+        let words = "word ".repeat(50);
+        let html = vec![
+            String::from("<h1>Title</h1>"),
+            format!("<p>{}", words.trim_end()),
+            String::from("and more</p>"),
+            String::from("<p>Second</p>"),
+        ];
+        let description = page_description(&html).unwrap();
+        assert_eq!(description.chars().count(), 160);
+        assert!(description.ends_with("word word…"));
+        assert_eq!(truncate_words(&"x".repeat(200), 10), "xxxxxxxxx…");
+        assert_eq!(truncate_words("short text", 10), "short text");
+    }
+
+    #[test]
+    fn test_page_title_is_plain_text() {
+        let html = crate::tokenize_text(
+            [
+                "# Using **md2html** with `<code>` and [links](x.md)",
+                "Text",
+            ]
+            .iter()
+            .map(|line| line.to_string()),
+        );
+        let title = page_title("fallback", &html);
+        assert_eq!(title, "Using md2html with <code> and links");
+        let options = PageOptions {
+            title,
+            ..Default::default()
+        };
+        assert!(wrap_page(&html, &options).contains(&String::from(
+            "<title>Using md2html with &lt;code&gt; and links</title>"
+        )));
+    }
+
+    #[test]
+    fn test_skip_link_target_resolves() {
+        let page = wrap_page(&[], &PageOptions::default());
+        let skip_link = page.iter().find(|line| line.contains("Skip to content"));
+        let target = skip_link
+            .and_then(|line| line.split("href=\"#").nth(1))
+            .and_then(|rest| rest.split('"').next())
+            .unwrap();
+        let id = format!("id=\"{}\"", target);
+        assert_eq!(page.iter().filter(|line| line.contains(&id)).count(), 1);
+        assert!(page
+            .iter()
+            .any(|line| line.starts_with("<main") && line.contains(&id)));
+        assert!(!page.contains(&String::from("<header>")));
+        assert!(!page.contains(&String::from("<footer>")));
+    }
+
+    #[test]
+    fn test_page_nav_three_pages() {
+        let page = |title: &str, url: &str| PageLink {
+            title: String::from(title),
+            url: String::from(url),
+        };
+        let pages = vec![
+            page("Intro", "intro.html"),
+            page("Setup & Install", "guide/setup.html"),
+            page("Usage", "usage.html"),
+        ];
+
+        assert_eq!(
+            page_nav_html(&pages, 0).unwrap(),
+            "<nav class=\"page-nav\" aria-label=\"Previous and next pages\">\n\
+             <a href=\"guide/setup.html\" rel=\"next\">Next: Setup &amp; Install →</a>\n\
+             </nav>"
+        );
+        assert_eq!(
+            page_nav_html(&pages, 1).unwrap(),
+            "<nav class=\"page-nav\" aria-label=\"Previous and next pages\">\n\
+             <a href=\"../intro.html\" rel=\"prev\">← Previous: Intro</a>\n\
+             <a href=\"../usage.html\" rel=\"next\">Next: Usage →</a>\n\
+             </nav>"
+        );
+        assert_eq!(
+            page_nav_html(&pages, 2).unwrap(),
+            "<nav class=\"page-nav\" aria-label=\"Previous and next pages\">\n\
+             <a href=\"guide/setup.html\" rel=\"prev\">← Previous: Setup &amp; Install</a>\n\
+             </nav>"
+        );
+        assert_eq!(page_nav_html(&pages[..1], 0), None);
+    }
+
+    #[test]
+    fn test_parse_wrap_div() {
+        let parse = |spec: &str| spec.parse::<WrapDiv>();
+        assert_eq!(
+            parse("article-body"),
+            Ok(WrapDiv {
+                id: None,
+                classes: vec![String::from("article-body")],
+            })
+        );
+        assert_eq!(
+            parse("#content.article-body.wide"),
+            Ok(WrapDiv {
+                id: Some(String::from("content")),
+                classes: vec![String::from("article-body"), String::from("wide")],
+            })
+        );
+        assert_eq!(parse("#content").unwrap().classes, Vec::<String>::new());
+        assert_eq!(parse(".a.b").unwrap().classes, vec!["a", "b"]);
+        assert!(parse("").is_err());
+        assert!(parse("#.body").is_err());
+        assert!(parse("article body").is_err());
+    }
+
+    #[test]
+    fn test_wrap_fragment() {
+        let lines = vec![String::from("<p>Text</p>")];
+        let wrap = |spec: &str| wrap_fragment(lines.clone(), &spec.parse().unwrap());
+        assert_eq!(
+            wrap("article-body"),
+            ["<div class=\"article-body\">", "<p>Text</p>", "</div>"]
+        );
+        assert_eq!(
+            wrap("#content.article-body.wide"),
+            [
+                "<div id=\"content\" class=\"article-body wide\">",
+                "<p>Text</p>",
+                "</div>"
+            ]
+        );
+        assert_eq!(wrap("#a\"b")[0], "<div id=\"a&quot;b\">");
+    }
+}
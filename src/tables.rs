@@ -0,0 +1,240 @@
+use crate::inline::{self, Inline};
+use crate::{convert_inline_markdown_with_options, find_code_span, ParserOptions};
+use std::fmt;
+
+/// The alignment of a table column, given by the colons of the delimiter row.
+///
+/// # Variants
+///
+/// - `None`: No colon (`---`), the browser default.
+/// - `Left`: A colon on the left (`:---`).
+/// - `Center`: Colons on both sides (`:---:`).
+/// - `Right`: A colon on the right (`---:`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// A cell of a table row.
+///
+/// # Fields
+///
+/// - `header`: Whether the cell is in the header row (`<th scope="col">`) or not
+///   (`<td>`).
+/// - `align`: The alignment of the cell's column.
+/// - `text`: The content of the cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableCell {
+    pub header: bool,
+    pub align: Alignment,
+    pub text: Vec<Inline>,
+}
+
+impl fmt::Display for TableCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // A header cell labels the cells of its column for screen readers
+        let (tag, scope) = match self.header {
+            true => ("th", " scope=\"col\""),
+            false => ("td", ""),
+        };
+        let style = match self.align {
+            Alignment::None => "",
+            Alignment::Left => " style=\"text-align:left\"",
+            Alignment::Center => " style=\"text-align:center\"",
+            Alignment::Right => " style=\"text-align:right\"",
+        };
+        write!(
+            f,
+            "<{0}{1}{2}>{3}</{0}>",
+            tag,
+            scope,
+            style,
+            inline::to_html(&self.text)
+        )
+    }
+}
+
+/// Splits a table row into the raw text of its cells.
+///
+/// The outer pipes are optional, and `\|` is a pipe inside a cell rather than a
+/// cell separator. Pipes inside code spans don't separate cells either.
+///
+/// # Example
+///
+/// ```rust
+/// let cells = md2html::tables::split_row("| a | b \\| c |");
+/// assert_eq!(cells, vec!["a", "b | c"]);
+/// ```
+pub fn split_row(line: &str) -> Vec<String> {
+    let mut line = line.trim();
+    line = line.strip_prefix('|').unwrap_or(line);
+    if line.ends_with('|') && !line.ends_with("\\|") {
+        line = &line[..line.len() - 1];
+    }
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            // A code span is copied whole, the pipes inside it don't end the cell
+            let run = rest.len() - rest.trim_start_matches('`').len();
+            let len = match find_code_span(rest) {
+                Some((0, _, _, span_end)) => span_end,
+                _ => run,
+            };
+            cell += &rest[..len].replace("\\|", "|");
+            rest = &rest[len..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("\\|") {
+            cell.push('|');
+            rest = after;
+            continue;
+        }
+        match c {
+            '|' => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    cells.push(cell);
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// Parses a table delimiter row such as `| --- | :---: |` into the alignment of
+/// every column, or returns `None` if `line` isn't a delimiter row.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::tables::{parse_delimiter_row, Alignment};
+///
+/// assert_eq!(
+///     parse_delimiter_row("|:--|:-:|--:|---|"),
+///     Some(vec![Alignment::Left, Alignment::Center, Alignment::Right, Alignment::None])
+/// );
+/// assert_eq!(parse_delimiter_row("- item"), None);
+/// ```
+pub fn parse_delimiter_row(line: &str) -> Option<Vec<Alignment>> {
+    if !line.contains('|') {
+        return None;
+    }
+    split_row(line)
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None,
+            })
+        })
+        .collect()
+}
+
+/// Returns the alignments of the table whose header row is `line` and delimiter
+/// row is `next_line`, if they start a table: both contain pipes and they have
+/// the same number of cells.
+pub fn table_start(line: &str, next_line: &str) -> Option<Vec<Alignment>> {
+    if !line.contains('|') {
+        return None;
+    }
+    let alignments = parse_delimiter_row(next_line)?;
+    (split_row(line).len() == alignments.len()).then_some(alignments)
+}
+
+/// Builds the cells of a table row, converting their inline markdown.
+///
+/// Rows with more cells than the table has columns are truncated, and rows with
+/// fewer are padded with empty cells.
+pub fn row_cells(
+    line: &str,
+    alignments: &[Alignment],
+    header: bool,
+    options: &ParserOptions,
+) -> Vec<TableCell> {
+    let mut cells = split_row(line);
+    cells.resize(alignments.len(), String::new());
+    cells
+        .into_iter()
+        .zip(alignments)
+        .map(|(mut text, &align)| TableCell {
+            header,
+            align,
+            text: inline::from_html(&convert_inline_markdown_with_options(&mut text, options)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_cells_pad_and_truncate() {
+        let alignments = [Alignment::None, Alignment::Right];
+        let options = ParserOptions::default();
+        let padded = row_cells("| only |", &alignments, false, &options);
+        assert_eq!(padded.len(), 2);
+        assert_eq!(
+            padded[1].to_string(),
+            "<td style=\"text-align:right\"></td>"
+        );
+        let truncated = row_cells("a | **b** | c", &alignments, false, &options);
+        assert_eq!(truncated.len(), 2);
+        assert_eq!(
+            truncated[1].to_string(),
+            "<td style=\"text-align:right\"><strong>b</strong></td>"
+        );
+    }
+
+    #[test]
+    fn test_split_row_keeps_code_span_pipes() {
+        assert_eq!(split_row("| **bold** | `a|b` |"), vec!["**bold**", "`a|b`"]);
+        assert_eq!(split_row("| ``x | `y` `` | z |"), vec!["``x | `y` ``", "z"]);
+        // A backtick without a closing run is plain text
+        assert_eq!(split_row("| a ` b | c |"), vec!["a ` b", "c"]);
+        assert_eq!(split_row("|  | b |"), vec!["", "b"]);
+    }
+
+    #[test]
+    fn test_row_cells_inline_markdown() {
+        let alignments = [Alignment::None; 4];
+        let options = ParserOptions::default();
+        let cells: Vec<String> = row_cells(
+            "| **bold** | [docs](docs.html) | `a|b` |   |",
+            &alignments,
+            false,
+            &options,
+        )
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect();
+        assert_eq!(
+            cells,
+            vec![
+                "<td><strong>bold</strong></td>",
+                "<td><a href=\"docs.html\">docs</a></td>",
+                "<td><code>a|b</code></td>",
+                "<td></td>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_table_start_needs_matching_cells() {
+        assert!(table_start("| a | b |", "|---|---|").is_some());
+        assert!(table_start("a | b", "--- | ---").is_some());
+        assert!(table_start("| a | b |", "|---|").is_none());
+        assert!(table_start("No pipes", "|---|").is_none());
+        assert!(table_start("| a |", "| text |").is_none());
+    }
+}
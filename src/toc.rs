@@ -0,0 +1,274 @@
+use crate::inline::{self, Inline};
+use crate::Token;
+
+/// The line asking for the table of contents to be put where it is.
+pub const TOC_MARKER: &str = "[TOC]";
+
+/// Whether `tokens` has a `[TOC]` marker: a paragraph made of the marker alone.
+///
+/// # Example
+///
+/// ```rust
+/// let tokens = md2html::parse(["# Guide", "", "[TOC]"].iter().map(|line| line.to_string()));
+/// assert!(md2html::toc::has_marker(&tokens));
+/// let tokens = md2html::parse(["See [TOC] below"].iter().map(|line| line.to_string()));
+/// assert!(!md2html::toc::has_marker(&tokens));
+/// ```
+pub fn has_marker(tokens: &[Token]) -> bool {
+    marker_position(tokens).is_some()
+}
+
+/// Whether `text` is the `[TOC]` marker alone. Like a heading, a marker line ends
+/// the paragraph above it and is never merged with the lines around it.
+pub(crate) fn is_marker(text: &[Inline]) -> bool {
+    matches!(text, [Inline::Text(marker)] if marker == TOC_MARKER)
+}
+
+/// The index of the `[TOC]` marker in `tokens`.
+fn marker_position(tokens: &[Token]) -> Option<usize> {
+    tokens
+        .iter()
+        .position(|token| matches!(token, Token::Paragraph { text } if is_marker(text)))
+}
+
+/// Inserts a table of contents of the headings of `tokens` up to the `<h{depth}>`
+/// ones, as a `<nav class="toc" aria-label="Table of contents">` holding nested
+/// lists of links.
+///
+/// The tokens must already be wrapped in sections (see
+/// `sections::wrap_sections_with_options`): the links point at the ids of the
+/// sections. The table replaces the `[TOC]` marker if there is one, otherwise it
+/// goes right before the first heading. Without headings to list, the marker is
+/// simply removed.
+///
+/// # Example
+///
+/// ```rust
+/// let tokens = md2html::parse(["# Guide", "## Setup", "### Linux"].iter().map(|line| line.to_string()));
+/// let tokens = md2html::sections::wrap_sections(tokens);
+/// let html = md2html::render_html(&md2html::toc::insert_toc(tokens, 2));
+/// assert_eq!(
+///     html[..8],
+///     [
+///         "<nav class=\"toc\" aria-label=\"Table of contents\">",
+///         "<ul>",
+///         "<li><a href=\"#guide\">Guide</a>",
+///         "<ul>",
+///         "<li><a href=\"#setup\">Setup</a></li>",
+///         "</ul>",
+///         "</li>",
+///         "</ul>",
+///     ]
+/// );
+/// ```
+pub fn insert_toc(mut tokens: Vec<Token>, depth: u8) -> Vec<Token> {
+    let mut entries: Vec<(u8, String, String)> = Vec::new();
+    for pair in tokens.windows(2) {
+        if let [Token::SectionStart { id }, Token::Header { level, text }] = pair {
+            if *level <= depth {
+                let text = inline::to_plain_text(text).replace('\n', " ");
+                entries.push((*level, id.clone(), text));
+            }
+        }
+    }
+
+    let toc = toc_html(&entries)
+        .into_iter()
+        .map(|html| Token::RawHtml { html });
+    match marker_position(&tokens) {
+        Some(marker) => {
+            tokens.splice(marker..=marker, toc);
+        }
+        None => {
+            let first_heading = tokens
+                .iter()
+                .position(|token| matches!(token, Token::SectionStart { .. }))
+                .unwrap_or(0);
+            tokens.splice(first_heading..first_heading, toc);
+        }
+    }
+    tokens
+}
+
+/// The HTML lines of a table of contents listing `entries`, `(level, id, text)`
+/// triples. A heading deeper than the one before it opens a nested list, however
+/// many levels it skips.
+fn toc_html(entries: &[(u8, String, String)]) -> Vec<String> {
+    if entries.is_empty() {
+        return Vec::new();
+    }
+    let mut html = vec![
+        String::from("<nav class=\"toc\" aria-label=\"Table of contents\">"),
+        String::from("<ul>"),
+    ];
+    let mut open_levels: Vec<u8> = vec![entries[0].0];
+    for (i, (level, id, text)) in entries.iter().enumerate() {
+        let link = format!("<li><a href=\"#{}\">{}</a>", id, text);
+        match entries.get(i + 1) {
+            Some((next_level, _, _)) if next_level > level => {
+                html.push(link);
+                html.push(String::from("<ul>"));
+                open_levels.push(*next_level);
+            }
+            next => {
+                html.push(link + "</li>");
+                let next_level = next.map_or(0, |(next_level, _, _)| *next_level);
+                while open_levels.len() > 1
+                    && open_levels.last().is_some_and(|open| *open > next_level)
+                {
+                    open_levels.pop();
+                    html.push(String::from("</ul>"));
+                    html.push(String::from("</li>"));
+                }
+            }
+        }
+    }
+    html.push(String::from("</ul>"));
+    html.push(String::from("</nav>"));
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tokenize_text_with_options, ParserOptions};
+
+    fn toc_options(depth: u8) -> ParserOptions {
+        ParserOptions {
+            toc: true,
+            toc_depth: depth,
+            ..Default::default()
+        }
+    }
+
+    fn html(markdown: &str, options: &ParserOptions) -> Vec<String> {
+        tokenize_text_with_options(markdown.lines().map(String::from), options).html_lines
+    }
+
+    #[test]
+    fn test_toc_before_first_heading() {
+        let markdown = "Intro text\n\n# Guide\n## Install\n### Linux\n#### Packages\n\
+                        ## Usage *and* `flags`\n# Appendix";
+        let html = html(markdown, &toc_options(3));
+        assert_eq!(
+            html[..19],
+            [
+                "<p>Intro text</p>",
+                "<br/>",
+                "<nav class=\"toc\" aria-label=\"Table of contents\">",
+                "<ul>",
+                "<li><a href=\"#guide\">Guide</a>",
+                "<ul>",
+                "<li><a href=\"#install\">Install</a>",
+                "<ul>",
+                "<li><a href=\"#linux\">Linux</a></li>",
+                "</ul>",
+                "</li>",
+                "<li><a href=\"#usage-and-flags\">Usage and flags</a></li>",
+                "</ul>",
+                "</li>",
+                "<li><a href=\"#appendix\">Appendix</a></li>",
+                "</ul>",
+                "</nav>",
+                "<section id=\"guide\">",
+                "<h1>Guide</h1>",
+            ]
+        );
+        // Every link points at a section of the page
+        for line in html.iter().filter(|line| line.starts_with("<li>")) {
+            let id = line.split('#').nth(1).unwrap().split('"').next().unwrap();
+            assert!(html.contains(&format!("<section id=\"{}\">", id)), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_toc_marker() {
+        let markdown = "# Changelog\n\n[TOC]\n\n## v2\n## v2\n#### Fixed";
+        let expected_toc = [
+            "<nav class=\"toc\" aria-label=\"Table of contents\">",
+            "<ul>",
+            "<li><a href=\"#changelog\">Changelog</a>",
+            "<ul>",
+            "<li><a href=\"#v2\">v2</a></li>",
+            "<li><a href=\"#v2-1\">v2</a></li>",
+            "</ul>",
+            "</li>",
+            "</ul>",
+            "</nav>",
+        ];
+        // The marker works without `toc`, and lists headings up to h3 by default
+        let html = html(markdown, &ParserOptions::default());
+        assert_eq!(
+            html[..3],
+            ["<section id=\"changelog\">", "<h1>Changelog</h1>", "<br/>"]
+        );
+        assert_eq!(html[3..13], expected_toc);
+        assert!(!html.iter().any(|line| line.contains("[TOC]")));
+
+        let html = html_with_depth(markdown, 4);
+        assert!(html.contains(&String::from("<li><a href=\"#fixed\">Fixed</a></li>")));
+    }
+
+    fn html_with_depth(markdown: &str, depth: u8) -> Vec<String> {
+        html(markdown, &toc_options(depth))
+    }
+
+    #[test]
+    fn test_toc_marker_in_text_and_code() {
+        let options = ParserOptions::default();
+        for markdown in ["# A\nSee [TOC]", "# A\n```\n[TOC]\n```", "# A\n`[TOC]`"] {
+            assert!(
+                !html(markdown, &options)
+                    .iter()
+                    .any(|line| line.contains("<nav")),
+                "{}",
+                markdown
+            );
+        }
+    }
+
+    #[test]
+    fn test_toc_marker_after_paragraph_text() {
+        let html = html("intro\n[TOC]\nmore\n# Guide", &ParserOptions::default());
+        // The marker ends the paragraph above it, and the text below starts another
+        assert_eq!(
+            html[..8],
+            [
+                "<p>intro</p>",
+                "<nav class=\"toc\" aria-label=\"Table of contents\">",
+                "<ul>",
+                "<li><a href=\"#guide\">Guide</a></li>",
+                "</ul>",
+                "</nav>",
+                "<p>more</p>",
+                "<section id=\"guide\">",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_toc_skipped_levels_and_empty() {
+        let html = html_with_depth("### Deep\n# Top\n### Skip", 6);
+        assert_eq!(
+            html[..10],
+            [
+                "<nav class=\"toc\" aria-label=\"Table of contents\">",
+                "<ul>",
+                "<li><a href=\"#deep\">Deep</a></li>",
+                "<li><a href=\"#top\">Top</a>",
+                "<ul>",
+                "<li><a href=\"#skip\">Skip</a></li>",
+                "</ul>",
+                "</li>",
+                "</ul>",
+                "</nav>",
+            ]
+        );
+        // Without headings there is no table, and the marker goes away
+        assert_eq!(html_with_depth("Text", 3), ["<p>Text</p>"]);
+        assert_eq!(
+            html_with_depth("Text\n\n[TOC]", 3),
+            ["<p>Text</p>", "<br/>"]
+        );
+        assert_eq!(html_with_depth("#### Deep", 3)[0], "<section id=\"deep\">");
+    }
+}
@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+
+/// The no-break space word processors put in pasted text.
+const NO_BREAK_SPACE: char = '\u{00A0}';
+
+/// Whether `c` is an invisible character that only gets in the way of the
+/// markup: a zero-width space, a word joiner or a byte order mark.
+///
+/// The zero-width joiner and non-joiner aren't, they change how emoji sequences
+/// (`👩‍💻`) and some scripts are displayed.
+pub fn is_zero_width(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}')
+}
+
+/// Removes the zero-width characters (see `is_zero_width`) of `line`, returning the
+/// line and how many were removed.
+///
+/// # Example
+///
+/// ```rust
+/// let (line, removed) = md2html::unicode::strip_zero_width("zero\u{200B}width\u{FEFF}");
+/// assert_eq!(line, "zerowidth");
+/// assert_eq!(removed, 2);
+/// ```
+pub fn strip_zero_width(line: &str) -> (Cow<'_, str>, usize) {
+    let removed = line.chars().filter(|c| is_zero_width(*c)).count();
+    match removed {
+        0 => (Cow::Borrowed(line), 0),
+        _ => (Cow::Owned(line.replace(is_zero_width, "")), removed),
+    }
+}
+
+/// Turns the no-break spaces of `line` that are part of its block structure into
+/// plain spaces: the indentation, and the space after a heading, list or quote
+/// marker (`#\u{A0}Title`, `-\u{A0}item`). The ones in the text are kept.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::unicode::block_spaces;
+///
+/// assert_eq!(block_spaces("\u{A0}>\u{A0}-\u{A0}an\u{A0}item"), " > - an\u{A0}item");
+/// assert_eq!(block_spaces("1.\u{A0}one"), "1. one");
+/// assert_eq!(block_spaces("Price:\u{A0}5\u{A0}€"), "Price:\u{A0}5\u{A0}€");
+/// ```
+pub fn block_spaces(line: &str) -> Cow<'_, str> {
+    if !line.contains(NO_BREAK_SPACE) {
+        return Cow::Borrowed(line);
+    }
+    let mut spaced = String::with_capacity(line.len());
+    let mut rest = line;
+    loop {
+        // Indentation
+        let indent = rest.len() - rest.trim_start_matches([' ', '\t', NO_BREAK_SPACE]).len();
+        spaced.extend(
+            rest[..indent]
+                .chars()
+                .map(|c| if c == NO_BREAK_SPACE { ' ' } else { c }),
+        );
+        rest = &rest[indent..];
+
+        let marker = block_marker_length(rest);
+        match rest[marker..].strip_prefix(NO_BREAK_SPACE) {
+            Some(after) if marker > 0 => {
+                spaced.push_str(&rest[..marker]);
+                spaced.push(' ');
+                rest = after;
+            }
+            _ => break,
+        }
+    }
+    spaced.push_str(rest);
+    Cow::Owned(spaced)
+}
+
+/// The length of the block marker `text` starts with: a run of `#`, a `-`, `*` or
+/// `+` bullet, an ordered list number (`12.` or `12)`) or a `>`.
+fn block_marker_length(text: &str) -> usize {
+    let hashes = text.len() - text.trim_start_matches('#').len();
+    if hashes > 0 {
+        return hashes;
+    }
+    if text.starts_with(['-', '*', '+', '>']) {
+        return 1;
+    }
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match text[digits..].starts_with(['.', ')']) {
+        true if digits > 0 => digits + 1,
+        _ => 0,
+    }
+}
+
+/// `text` in Unicode normalization form C, where a letter and its combining
+/// accent (`e` + U+0301) become the single precomposed character (`é`).
+///
+/// # Example
+///
+/// ```rust
+/// assert_eq!(md2html::unicode::nfc("Cafe\u{301}"), "Café");
+/// ```
+pub fn nfc(text: &str) -> String {
+    text.nfc().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tokenize_text_with_options, ParserOptions, WarningKind};
+
+    fn convert(markdown: &str, options: &ParserOptions) -> crate::ParseResult {
+        tokenize_text_with_options(markdown.lines().map(String::from), options)
+    }
+
+    #[test]
+    fn test_no_break_spaces_after_markers() {
+        let markdown = "#\u{A0}Pasted\u{A0}heading\n\n-\u{A0}first\u{A0}item\n\
+                        -\u{A0}second\n\n>\u{A0}quoted\n\nKeep\u{A0}this";
+        let result = convert(markdown, &ParserOptions::default());
+        assert_eq!(
+            result.html_lines,
+            [
+                "<h1>Pasted\u{A0}heading</h1>",
+                "<br/>",
+                "<ul>",
+                "<li>first\u{A0}item</li>",
+                "<li>second</li>",
+                "</ul>",
+                "<br/>",
+                "<blockquote>",
+                "<p>quoted</p>",
+                "</blockquote>",
+                "<br/>",
+                "<p>Keep\u{A0}this</p>",
+            ]
+        );
+        // Code keeps its no-break spaces
+        let result = convert("```\n#\u{A0}not a heading\n```", &ParserOptions::default());
+        assert_eq!(result.html_lines[1], "#\u{A0}not a heading");
+    }
+
+    #[test]
+    fn test_zero_width_characters_removed() {
+        let markdown = "Title\n\nzero\u{200B}width\u{200B} spaces and a \u{FEFF}mark\n\
+                        👩\u{200D}💻 stays\n\n**bo\u{200B}ld**";
+        let result = convert(markdown, &ParserOptions::default());
+        assert_eq!(
+            result.html_lines,
+            [
+                "<p>Title</p>",
+                "<br/>",
+                "<p>zerowidth spaces and a mark 👩\u{200D}💻 stays</p>",
+                "<br/>",
+                "<p><strong>bold</strong></p>",
+            ]
+        );
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].kind, WarningKind::ZeroWidth);
+        assert_eq!(result.warnings[0].line, 3);
+        assert_eq!(
+            result.warnings[0].message,
+            "4 zero-width characters removed, the first on this line"
+        );
+    }
+
+    #[test]
+    fn test_normalize_unicode() {
+        let markdown = "# Cafe\u{301}\n\nre\u{301}sume\u{301}\n\n```\ne\u{301}\n```";
+        let options = ParserOptions {
+            normalize_unicode: true,
+            ..Default::default()
+        };
+        let html = convert(markdown, &options).html_lines;
+        assert_eq!(html[..3], ["<h1>Café</h1>", "<br/>", "<p>résumé</p>"]);
+        // Code is left as typed
+        assert_eq!(html[5], "e\u{301}");
+        let html = convert(markdown, &ParserOptions::default()).html_lines;
+        assert_eq!(html[0], "<h1>Cafe\u{301}</h1>");
+    }
+}
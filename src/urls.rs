@@ -0,0 +1,371 @@
+use crate::inline::Inline;
+use crate::tables::TableCell;
+use crate::Token;
+use std::fmt;
+use std::sync::Arc;
+
+/// Where a URL given to a URL rewriter is emitted.
+///
+/// # Variants
+///
+/// - `Link`: The `href` of a link (`[text](url)` or a reference link).
+/// - `Image`: The `src` of an image (`![alt](url)`).
+/// - `Autolink`: The `href` of a link showing its own URL (`<https://example.com>`
+///   or a bare URL with `ParserOptions::autolink_bare_urls`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UrlContext {
+    Link,
+    Image,
+    Autolink,
+}
+
+/// A function rewriting the URLs of links and images, see `Converter::url_rewriter`.
+///
+/// Rewriters get the URL as it is written in the HTML attribute and return the URL
+/// to write instead. Two rewriters are equal when they are the same function.
+#[derive(Clone)]
+pub struct UrlRewriter(Arc<RewriteFn>);
+
+type RewriteFn = dyn Fn(UrlContext, &str) -> String + Send + Sync;
+
+impl UrlRewriter {
+    /// Wraps `rewrite` as a rewriter.
+    pub fn new(rewrite: impl Fn(UrlContext, &str) -> String + Send + Sync + 'static) -> Self {
+        UrlRewriter(Arc::new(rewrite))
+    }
+
+    /// Rewrites `url`, emitted in `context`.
+    pub fn rewrite(&self, context: UrlContext, url: &str) -> String {
+        (self.0)(context, url)
+    }
+}
+
+impl fmt::Debug for UrlRewriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UrlRewriter")
+    }
+}
+
+impl PartialEq for UrlRewriter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Whether `url` is relative to the page: no scheme (`https:`, `mailto:`), not
+/// rooted (`/docs`, `//cdn.example.com`) and not only a fragment or a query.
+fn is_relative(url: &str) -> bool {
+    let scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains(['/', '?', '#']));
+    !url.is_empty() && !scheme && !url.starts_with(['/', '#', '?'])
+}
+
+/// A built-in rewriter pointing relative links to markdown files at the HTML pages
+/// converted from them (`setup.md#install` -> `setup.html#install`).
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::urls::{md_to_html, UrlContext};
+///
+/// assert_eq!(md_to_html(UrlContext::Link, "guide/setup.md?v=2#install"), "guide/setup.html?v=2#install");
+/// assert_eq!(md_to_html(UrlContext::Link, "https://example.com/README.md"), "https://example.com/README.md");
+/// assert_eq!(md_to_html(UrlContext::Image, "diagram.md"), "diagram.md");
+/// ```
+pub fn md_to_html(context: UrlContext, url: &str) -> String {
+    if context != UrlContext::Link || !is_relative(url) {
+        return url.to_string();
+    }
+    let path_end = url.find(['?', '#']).unwrap_or(url.len());
+    let (path, rest) = url.split_at(path_end);
+    match path.strip_suffix(".md") {
+        Some(stem) if !stem.is_empty() && !stem.ends_with('/') => {
+            format!("{}.html{}", stem, rest)
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// A built-in rewriter prefixing relative URLs with `base`, for pages served below
+/// a path (`https://example.com/docs/`) or images served by a CDN.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::urls::{base_url, UrlContext};
+///
+/// let rewrite = base_url("/docs");
+/// assert_eq!(rewrite(UrlContext::Link, "setup.html"), "/docs/setup.html");
+/// assert_eq!(rewrite(UrlContext::Link, "#usage"), "#usage");
+/// assert_eq!(rewrite(UrlContext::Image, "https://example.com/logo.png"), "https://example.com/logo.png");
+/// ```
+pub fn base_url(base: &str) -> impl Fn(UrlContext, &str) -> String + Send + Sync + 'static {
+    let base = base.trim_end_matches('/').to_string();
+    move |_, url| match is_relative(url) {
+        true => format!("{}/{}", base, url.trim_start_matches("./")),
+        false => url.to_string(),
+    }
+}
+
+/// Applies `rewriters` in order to the URL of every link and image of `tokens`.
+///
+/// URLs written as raw HTML (media players, `<a>` tags kept with
+/// `ParserOptions::allow_raw_html`) are left as they are, so is code.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::urls::{self, UrlRewriter};
+///
+/// let tokens = md2html::parse(vec![String::from("See [setup](setup.md).")].into_iter());
+/// let rewriters = [UrlRewriter::new(urls::md_to_html), UrlRewriter::new(urls::base_url("/docs/"))];
+/// let html = md2html::render_html(&urls::rewrite_urls(tokens, &rewriters));
+/// assert_eq!(html, vec!["<p>See <a href=\"/docs/setup.html\">setup</a>.</p>"]);
+/// ```
+pub fn rewrite_urls(tokens: Vec<Token>, rewriters: &[UrlRewriter]) -> Vec<Token> {
+    if rewriters.is_empty() {
+        return tokens;
+    }
+    tokens
+        .into_iter()
+        .map(|token| rewrite_token(token, rewriters))
+        .collect()
+}
+
+/// Rewrites the URLs of a single token.
+fn rewrite_token(token: Token, rewriters: &[UrlRewriter]) -> Token {
+    match token {
+        Token::Header { level, text } => Token::Header {
+            level,
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::Paragraph { text } => Token::Paragraph {
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::UListItem { depth, text } => Token::UListItem {
+            depth,
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::ListItemStart { text } => Token::ListItemStart {
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::OListItem {
+            depth,
+            number,
+            text,
+        } => Token::OListItem {
+            depth,
+            number,
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::TableRow { cells } => Token::TableRow {
+            cells: cells
+                .into_iter()
+                .map(|cell| TableCell {
+                    text: rewrite_inlines(cell.text, rewriters),
+                    ..cell
+                })
+                .collect(),
+        },
+        Token::QuoteAttribution { text } => Token::QuoteAttribution {
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::DefinitionTerm { text } => Token::DefinitionTerm {
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::Definition { text } => Token::Definition {
+            text: rewrite_inlines(text, rewriters),
+        },
+        Token::Quote { text, nested_token } => Token::Quote {
+            text: rewrite_inlines(text, rewriters),
+            nested_token: Box::new(rewrite_token(*nested_token, rewriters)),
+        },
+        // SimpleText only holds raw code block lines
+        token => token,
+    }
+}
+
+/// Rewrites the URLs of the links and images of `inlines`.
+fn rewrite_inlines(inlines: Vec<Inline>, rewriters: &[UrlRewriter]) -> Vec<Inline> {
+    let rewrite = |context: UrlContext, url: String| {
+        rewriters
+            .iter()
+            .fold(url, |url, rewriter| rewriter.rewrite(context, &url))
+    };
+    inlines
+        .into_iter()
+        .map(|inline| match inline {
+            Inline::Link {
+                children,
+                url,
+                title,
+            } => {
+                // An autolink shows its own URL
+                let context = match children.as_slice() {
+                    [Inline::Text(text)] if *text == url => UrlContext::Autolink,
+                    _ => UrlContext::Link,
+                };
+                Inline::Link {
+                    children: rewrite_inlines(children, rewriters),
+                    url: rewrite(context, url),
+                    title,
+                }
+            }
+            Inline::Image { alt, url, title } => Inline::Image {
+                alt,
+                url: rewrite(UrlContext::Image, url),
+                title,
+            },
+            Inline::Strong(children) => Inline::Strong(rewrite_inlines(children, rewriters)),
+            Inline::Emph(children) => Inline::Emph(rewrite_inlines(children, rewriters)),
+            Inline::Strikethrough(children) => {
+                Inline::Strikethrough(rewrite_inlines(children, rewriters))
+            }
+            inline => inline,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render_html, tokenize_text_with_options, Converter, ParserOptions};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_rewriters_apply_in_order() {
+        let markdown = "See [setup](guide/setup.md#install) and [home](https://example.com/a.md).";
+        let md_then_base = Converter::new()
+            .url_rewriter(md_to_html)
+            .url_rewriter(base_url("https://example.com/docs/"));
+        assert_eq!(
+            md_then_base.convert_str(markdown),
+            "<p>See <a href=\"https://example.com/docs/guide/setup.html#install\">setup</a> \
+             and <a href=\"https://example.com/a.md\">home</a>.</p>\n"
+        );
+
+        // The other way around, the prefixed URL isn't relative anymore
+        let base_then_md = Converter::new()
+            .url_rewriter(base_url("https://example.com/docs/"))
+            .url_rewriter(md_to_html);
+        assert!(base_then_md
+            .convert_str(markdown)
+            .contains("href=\"https://example.com/docs/guide/setup.md#install\""));
+
+        // Each rewriter sees the output of the one before it
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (first, second) = (calls.clone(), calls.clone());
+        let html = Converter::new()
+            .url_rewriter(move |_, url: &str| {
+                first.lock().unwrap().push(format!("first {}", url));
+                format!("{}?a", url)
+            })
+            .url_rewriter(move |_, url: &str| {
+                second.lock().unwrap().push(format!("second {}", url));
+                format!("{}&b", url)
+            })
+            .convert_str("[x](page)");
+        assert_eq!(html, "<p><a href=\"page?a&b\">x</a></p>\n");
+        assert_eq!(*calls.lock().unwrap(), ["first page", "second page?a"]);
+    }
+
+    #[test]
+    fn test_custom_rewriter_sees_every_url_category() {
+        let uppercase_host = |context: UrlContext, url: &str| {
+            let Some((scheme, rest)) = url.split_once("://") else {
+                return url.to_string();
+            };
+            let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            let marker = match context {
+                UrlContext::Link => "link",
+                UrlContext::Image => "image",
+                UrlContext::Autolink => "autolink",
+            };
+            format!("{}://{}{}#{}", scheme, host.to_uppercase(), path, marker)
+        };
+        let converter = Converter::new()
+            .options(ParserOptions {
+                autolink_bare_urls: true,
+                ..Default::default()
+            })
+            .url_rewriter(uppercase_host);
+        let html = converter.convert_str(
+            "[Docs](https://docs.rs/md2html) ![Logo](https://cdn.example.com/logo.png)\n\
+             <https://example.com/a> and www.rust-lang.org\n\n\
+             | [Table](https://table.example/x) |\n|---|\n\n\
+             > - **[Quoted](http://quote.example)**\n\n\
+             ```\n[code](https://not.rewritten)\n```",
+        );
+        for rewritten in [
+            "<a href=\"https://DOCS.RS/md2html#link\">Docs</a>",
+            "<img src=\"https://CDN.EXAMPLE.COM/logo.png#image\" alt=\"Logo\">",
+            "<a href=\"https://EXAMPLE.COM/a#autolink\">https://example.com/a</a>",
+            "href=\"https://TABLE.EXAMPLE/x#link\"",
+            "href=\"http://QUOTE.EXAMPLE#link\"",
+            "[code](https://not.rewritten)",
+        ] {
+            assert!(html.contains(rewritten), "{} in {}", rewritten, html);
+        }
+        assert!(html.contains("WWW.RUST-LANG.ORG"), "{}", html);
+    }
+
+    #[test]
+    fn test_rewritten_urls_in_endnotes() {
+        let options = ParserOptions {
+            link_endnotes: true,
+            ..Default::default()
+        };
+        let tokens = crate::parse_with_options(
+            ["See [setup](setup.md)."]
+                .iter()
+                .map(|line| line.to_string()),
+            &options,
+        )
+        .tokens;
+        let tokens = rewrite_urls(tokens, &[UrlRewriter::new(md_to_html)]);
+        let html = render_html(&crate::endnotes::links_to_endnotes(tokens));
+        assert!(
+            html.contains(&String::from("<li id=\"endnote-1\">setup.html</li>")),
+            "{:?}",
+            html
+        );
+
+        // Without rewriters nothing changes
+        let lines = || {
+            ["[a](b.md) ![c](d.png)"]
+                .iter()
+                .map(|line| line.to_string())
+        };
+        assert_eq!(
+            render_html(&rewrite_urls(crate::parse(lines()), &[])),
+            tokenize_text_with_options(lines(), &ParserOptions::default()).html_lines
+        );
+    }
+
+    #[test]
+    fn test_relative_urls() {
+        assert!(is_relative("setup.md"));
+        assert!(is_relative("./img/logo.png"));
+        for url in [
+            "",
+            "/docs",
+            "//cdn.example.com/x",
+            "#top",
+            "?q=1",
+            "mailto:a@b.c",
+            "https://x",
+        ] {
+            assert!(!is_relative(url), "{}", url);
+        }
+        assert_eq!(
+            base_url("/docs/")(UrlContext::Image, "./img/logo.png"),
+            "/docs/img/logo.png"
+        );
+        assert_eq!(md_to_html(UrlContext::Link, "docs/.md"), "docs/.md");
+        assert_eq!(
+            md_to_html(UrlContext::Link, "notes.markdown"),
+            "notes.markdown"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use crate::site_diff::{self, DiffLine};
+use crate::{ParseWarning, WarningKind};
+use std::ops::Range;
+
+/// Where the content of a code block is in the markdown source, recorded by
+/// `parse_with_options` for every `<pre><code>` block it renders.
+///
+/// # Fields
+///
+/// - `lines`: The 0-based lines of the code, without the fences, counting the front
+///   matter lines.
+/// - `indented`: Whether it's an indented code block, whose lines lose their
+///   indentation of four spaces or a tab.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlockLines {
+    pub lines: Range<usize>,
+    pub indented: bool,
+}
+
+/// Extracts the text of every `<pre><code>` block of `html`, in order: the tags
+/// inside (e.g. the `<span>` of highlighted code) are removed and the entities
+/// decoded, so the result is the code as typed.
+///
+/// # Example
+///
+/// ```rust
+/// let html = "<p>Run</p>\n<pre><code class=\"language-sh\">\necho &quot;a &amp;&amp; b&quot;\n</code></pre>";
+/// assert_eq!(md2html::verify::extract_code_blocks(html), ["echo \"a && b\""]);
+/// ```
+pub fn extract_code_blocks(html: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut rest = html;
+    while let Some(start) = find_pre(rest) {
+        rest = &rest[start..];
+        let Some(content_start) = rest
+            .find("<code")
+            .and_then(|code| rest[code..].find('>').map(|end| code + end + 1))
+        else {
+            break;
+        };
+        let Some(content_end) = rest.find("</code></pre>") else {
+            break;
+        };
+        let content = &rest[content_start..content_end.max(content_start)];
+        // The opening and closing tags are on lines of their own
+        let content = content.strip_prefix('\n').unwrap_or(content);
+        let content = content.strip_suffix('\n').unwrap_or(content);
+        blocks.push(decode_entities(&strip_tags(content)));
+        rest = &rest[content_end + "</code></pre>".len()..];
+    }
+    blocks
+}
+
+/// The position of the next `<pre>` or `<pre ...>` tag of `html`.
+fn find_pre(html: &str) -> Option<usize> {
+    html.match_indices("<pre")
+        .map(|(i, _)| i)
+        .find(|&i| html[i + "<pre".len()..].starts_with(['>', ' ']))
+}
+
+/// Removes the tags of `html`. Inside code every `<` of the text is escaped, so
+/// every `<` starts a tag.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut inside_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => inside_tag = true,
+            '>' if inside_tag => inside_tag = false,
+            c if !inside_tag => text.push(c),
+            _ => (),
+        }
+    }
+    text
+}
+
+/// Decodes the entities escaping code: `&lt;`, `&gt;`, `&quot;`, `&#39;`, `&amp;`
+/// and the numeric references (`&#8212;`, `&#x2014;`). Other text, a lone `&`
+/// included, is left as it is.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::verify::decode_entities;
+///
+/// assert_eq!(decode_entities("a &lt;b&gt; &amp;lt; &#x2014; &#39;c&#39; & d"), "a <b> &lt; \u{2014} 'c' & d");
+/// ```
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "amp" => '&',
+                "apos" => '\'',
+                name => {
+                    let code_point = match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => name
+                            .strip_prefix('#')
+                            .and_then(|decimal| decimal.parse().ok()),
+                    };
+                    code_point.and_then(char::from_u32)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        match reference {
+            Some((c, length)) => {
+                decoded.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The code of `block` as typed in the `source` lines.
+fn source_code(source: &[String], block: &CodeBlockLines) -> String {
+    let lines = source.get(block.lines.clone()).unwrap_or_default();
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| match block.indented {
+            true => crate::strip_code_indent(line).unwrap_or_default(),
+            false => line.as_str(),
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Checks that the code blocks of `html_lines` hold the code of the `blocks` of
+/// `source` byte for byte, once extracted (see `extract_code_blocks`).
+///
+/// Every block that doesn't is reported with a diff of its lines, as a
+/// `WarningKind::CodeBlock` warning pointing at its first source line.
+///
+/// # Example
+///
+/// ```rust
+/// use md2html::verify::{check_code_blocks, CodeBlockLines};
+///
+/// let source = vec![String::from("```"), String::from("a < b"), String::from("```")];
+/// let blocks = [CodeBlockLines { lines: 1..2, indented: false }];
+/// let html = ["<pre><code>", "a &lt; b", "</code></pre>"].map(String::from);
+/// assert!(check_code_blocks(&source, &blocks, &html).is_empty());
+///
+/// let html = ["<pre><code>", "a &lt;b", "</code></pre>"].map(String::from);
+/// let warnings = check_code_blocks(&source, &blocks, &html);
+/// assert_eq!(warnings[0].line, 2);
+/// assert!(warnings[0].message.ends_with("- 1 | \"a < b\"\n+ 1 | \"a <b\""));
+/// ```
+pub fn check_code_blocks(
+    source: &[String],
+    blocks: &[CodeBlockLines],
+    html_lines: &[String],
+) -> Vec<ParseWarning> {
+    let rendered = extract_code_blocks(&html_lines.join("\n"));
+    let mut warnings = Vec::new();
+    for (index, block) in blocks.iter().enumerate() {
+        let line = block.lines.start + 1;
+        let Some(actual) = rendered.get(index) else {
+            warnings.push(code_block_warning(
+                line,
+                format!(
+                    "code block missing from the output, {} blocks rendered for {}",
+                    rendered.len(),
+                    blocks.len()
+                ),
+            ));
+            break;
+        };
+        let expected = source_code(source, block);
+        if *actual != expected {
+            warnings.push(code_block_warning(
+                line,
+                format!(
+                    "code block output differs from its source (- source, + output):\n{}",
+                    diff(&expected, actual)
+                ),
+            ));
+        }
+    }
+    if rendered.len() > blocks.len() {
+        warnings.push(code_block_warning(
+            1,
+            format!(
+                "{} code blocks rendered for {} in the source",
+                rendered.len(),
+                blocks.len()
+            ),
+        ));
+    }
+    warnings
+}
+
+fn code_block_warning(line: usize, message: String) -> ParseWarning {
+    ParseWarning {
+        line,
+        span: None,
+        kind: WarningKind::CodeBlock,
+        message,
+    }
+}
+
+/// The lines of `expected` and `actual` that differ (see `site_diff::line_diff`),
+/// numbered from the first line of the block and quoted so whitespace shows.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.split('\n').collect();
+    let actual: Vec<&str> = actual.split('\n').collect();
+    let lines: Vec<String> = site_diff::line_diff(&expected, &actual)
+        .iter()
+        .map(|line| match line {
+            DiffLine::Removed { line, text } => format!("- {} | {:?}", line, text),
+            DiffLine::Added { line, text } => format!("+ {} | {:?}", line, text),
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Converts `lines` like `tokenize_text_with_options` with `verify_code_blocks`
+/// on, and asserts that none of the code blocks differs from its source.
+#[cfg(test)]
+pub(crate) fn tokenize_verified(
+    lines: impl Iterator<Item = String>,
+    options: &crate::ParserOptions,
+) -> crate::ParseResult {
+    let options = crate::ParserOptions {
+        verify_code_blocks: true,
+        ..options.clone()
+    };
+    let result = crate::tokenize_text_with_options(lines, &options);
+    let mismatches: Vec<&ParseWarning> = result
+        .warnings
+        .iter()
+        .filter(|warning| warning.kind == WarningKind::CodeBlock)
+        .collect();
+    assert!(mismatches.is_empty(), "{:?}", mismatches);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParserOptions;
+
+    /// Converts `markdown` with the verification on and returns the code of its
+    /// blocks as extracted.
+    fn round_trip(markdown: &str) -> Vec<String> {
+        let result = tokenize_verified(
+            markdown.lines().map(String::from),
+            &ParserOptions::default(),
+        );
+        extract_code_blocks(&result.html_lines.join("\n"))
+    }
+
+    #[test]
+    fn test_code_blocks_round_trip() {
+        let markdown = "Text\n\n```html\n<a href=\"x?a=1&amp;b=2\">&copy;</a> &#169;\n\
+                        trailing spaces   \n\n\n\tindented\twith tabs\t\n```\n\n\
+                        ~~~\n  \n~~~\n\n    indented & <code>\n    \n    \tafter a blank\n\n\
+                        ```\nnever closed\n";
+        assert_eq!(
+            round_trip(markdown),
+            [
+                "<a href=\"x?a=1&amp;b=2\">&copy;</a> &#169;\n\
+                 trailing spaces   \n\n\n\tindented\twith tabs\t",
+                "  ",
+                "indented & <code>\n\n\tafter a blank",
+                "never closed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unicode_cleanup_leaves_code_alone() {
+        let markdown = "#\u{A0}Title\n\n```\nzero\u{200B}width\n-\u{A0}e\u{301}\n```";
+        let options = ParserOptions {
+            normalize_unicode: true,
+            ..Default::default()
+        };
+        let result = tokenize_verified(markdown.lines().map(String::from), &options);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        assert_eq!(result.html_lines[3], "zero\u{200B}width");
+    }
+
+    #[test]
+    fn test_mismatch_reported_with_diff() {
+        let source: Vec<String> = ["    a\tb", "", "    c"].map(String::from).to_vec();
+        let blocks = [CodeBlockLines {
+            lines: 0..3,
+            indented: true,
+        }];
+        let html = ["<pre><code>", "a b", "", "c ", "</code></pre>"].map(String::from);
+        let warnings = check_code_blocks(&source, &blocks, &html);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 1);
+        assert_eq!(
+            warnings[0].message,
+            "code block output differs from its source (- source, + output):\n\
+             - 1 | \"a\\tb\"\n+ 1 | \"a b\"\n- 3 | \"c\"\n+ 3 | \"c \""
+        );
+        // A line added to the output is the only one reported, not every line after it
+        let html = ["<pre><code>", "a\tb", "extra", "", "c", "</code></pre>"].map(String::from);
+        let warnings = check_code_blocks(&source, &blocks, &html);
+        assert!(
+            warnings[0].message.ends_with(":\n+ 2 | \"extra\""),
+            "{}",
+            warnings[0].message
+        );
+        let warnings = check_code_blocks(&source, &blocks, &[]);
+        assert_eq!(
+            warnings[0].message,
+            "code block missing from the output, 0 blocks rendered for 1"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+/// Soft-wraps rendered HTML lines so they fit in `width` columns where possible.
+///
+/// Lines are only broken on spaces of the text content, replacing the space with a
+/// newline (which HTML renders the same way). A line is never broken inside a tag or
+/// one of its attributes, inside inline `<code>`, or anywhere in a `<pre>` block. A
+/// single word longer than `width` stays on its own overlong line.
+///
+/// # Arguments
+///
+/// * `html_lines` - The rendered HTML, as returned by `render_html`.
+/// * `width` - The maximum number of characters per line.
+///
+/// # Example
+///
+/// ```rust
+/// let html = vec![String::from("<p>one two three four</p>")];
+/// let wrapped = md2html::wrap::wrap_html(&html, 14);
+/// assert_eq!(wrapped, vec!["<p>one two", "three four</p>"]);
+/// ```
+pub fn wrap_html(html_lines: &[String], width: usize) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut inside_pre = false;
+    for line in html_lines {
+        if line.starts_with("<pre") {
+            inside_pre = true;
+        }
+        if inside_pre {
+            result.push(line.clone());
+            if line.ends_with("</pre>") {
+                inside_pre = false;
+            }
+            continue;
+        }
+        result.extend(wrap_line(line, width));
+    }
+    result
+}
+
+/// Wraps a single line of HTML, see `wrap_html`.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let mut wrapped = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    // Byte index in `current` of the last space we are allowed to break on
+    let mut last_break: Option<usize> = None;
+
+    // State machine over the generated HTML
+    let mut tag = String::new();
+    let mut inside_tag = false;
+    let mut attribute_quote: Option<char> = None;
+    let mut code_depth = 0;
+
+    for c in line.chars() {
+        if inside_tag {
+            tag.push(c);
+            match attribute_quote {
+                Some(quote) if c == quote => attribute_quote = None,
+                Some(_) => (),
+                None if c == '"' || c == '\'' => attribute_quote = Some(c),
+                None if c == '>' => {
+                    inside_tag = false;
+                    if tag.starts_with("<code") {
+                        code_depth += 1;
+                    } else if tag.starts_with("</code") && code_depth > 0 {
+                        code_depth -= 1;
+                    }
+                }
+                None => (),
+            }
+        } else if c == '<' {
+            inside_tag = true;
+            tag = String::from("<");
+        } else if c == ' ' && code_depth == 0 {
+            last_break = Some(current.len());
+        }
+
+        current.push(c);
+        current_width += 1;
+
+        if current_width > width {
+            if let Some(break_index) = last_break {
+                wrapped.push(current[..break_index].to_string());
+                current = current[break_index + 1..].to_string();
+                current_width = current.chars().count();
+                last_break = None;
+            }
+        }
+    }
+    wrapped.push(current);
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_never_breaks_inside_tags() {
+        let html = vec![String::from(
+            "<p>Read the <a href=\"https://example.com/a page\">setup guide</a> and the <a href=\"#faq\">frequently asked questions</a> first.</p>",
+        )];
+        let wrapped = wrap_html(&html, 40);
+        assert_eq!(
+            wrapped,
+            vec![
+                "<p>Read the",
+                "<a href=\"https://example.com/a page\">setup",
+                "guide</a> and the",
+                "<a href=\"#faq\">frequently asked",
+                "questions</a> first.</p>",
+            ]
+        );
+        assert_eq!(wrapped.join(" "), html[0]);
+    }
+
+    #[test]
+    fn test_wrap_keeps_inline_code_together() {
+        let html = vec![String::from(
+            "<p>Run <code>cargo build --release</code> now</p>",
+        )];
+        let wrapped = wrap_html(&html, 12);
+        assert_eq!(
+            wrapped,
+            vec!["<p>Run", "<code>cargo build --release</code>", "now</p>"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_leaves_code_blocks_alone() {
+        let long_line = "let value = compute(first_argument, second_argument, third_argument);";
+        let html = vec![
+            String::from("<pre><code>"),
+            String::from(long_line),
+            String::from("</code></pre>"),
+            String::from("<p>short text after</p>"),
+        ];
+        let wrapped = wrap_html(&html, 20);
+        assert_eq!(wrapped[1], long_line);
+        assert_eq!(wrapped[3..], ["<p>short text", "after</p>"]);
+    }
+}
@@ -0,0 +1,459 @@
+//! Runs the md2html binary the way scripts do, for the behavior that only exists
+//! on the command line.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// A fresh temporary folder for the test `name`.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("md2html-cli-{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs md2html with `args`, piping `stdin` into it.
+fn md2html(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md2html"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_stdin_filename_in_diagnostics() {
+    let markdown = "# Notes\n```\nnever closed";
+    let args = ["--input", "-", "--output", "-", "--message-format", "short"];
+    let stderr = |output: Output| String::from_utf8(output.stderr).unwrap();
+
+    let named = stderr(md2html(
+        &[&args[..], &["--stdin-filename", "docs/notes.md"]].concat(),
+        markdown,
+    ));
+    assert!(
+        named.contains("docs/notes.md:2:1: warning: code fence is never closed"),
+        "{}",
+        named
+    );
+    let unnamed = stderr(md2html(&args, markdown));
+    assert!(
+        unnamed.contains("<stdin>:2:1: warning: code fence is never closed"),
+        "{}",
+        unnamed
+    );
+}
+
+#[test]
+fn test_stdin_filename_output_name_and_title() {
+    let dir = temp_dir("stdin-output");
+    let output_dir = dir.join("site");
+    let output = output_dir.to_str().unwrap();
+    let args = ["--input", "-", "--output", output, "--standalone"];
+
+    md2html(
+        &[&args[..], &["--stdin-filename", "notes.md"]].concat(),
+        "Text",
+    );
+    let page = fs::read_to_string(output_dir.join("notes.html")).unwrap();
+    assert!(page.contains("<title>notes</title>"), "{}", page);
+    assert!(!output_dir.join("out.html").exists());
+
+    md2html(&args, "Text");
+    let page = fs::read_to_string(output_dir.join("out.html")).unwrap();
+    assert!(!page.contains("<title>notes</title>"), "{}", page);
+}
+
+#[test]
+fn test_stdin_filename_date_from_existing_file() {
+    let dir = temp_dir("stdin-date");
+    let source = dir.join("notes.md");
+    fs::write(&source, "Text").unwrap();
+    let footer = dir.join("footer.html");
+    fs::write(&footer, "<p>Updated {{date}}</p>").unwrap();
+    let args = [
+        "--input",
+        "-",
+        "--output",
+        "-",
+        "--standalone",
+        "--footer",
+        footer.to_str().unwrap(),
+    ];
+    let stdout = |output: Output| String::from_utf8(output.stdout).unwrap();
+
+    // The modification time of the named file is the fallback date
+    let page = stdout(md2html(
+        &[&args[..], &["--stdin-filename", source.to_str().unwrap()]].concat(),
+        "Text",
+    ));
+    assert!(!page.contains("<p>Updated </p>"), "{}", page);
+    assert!(page.contains("<p>Updated 2"), "{}", page);
+
+    // A file that doesn't exist, or no file at all, gives no date
+    let missing = dir.join("missing.md");
+    for extra in [vec!["--stdin-filename", missing.to_str().unwrap()], vec![]] {
+        let page = stdout(md2html(&[&args[..], &extra[..]].concat(), "Text"));
+        assert!(page.contains("<p>Updated </p>"), "{}", page);
+    }
+}
+
+#[test]
+fn test_stdin_max_input_size() {
+    let args = ["--input", "-", "--output", "-", "--max-input-size", "16"];
+
+    let output = md2html(&args, "A paragraph longer than sixteen bytes");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("<stdin> is over the --max-input-size limit of 16 bytes"),
+        "{}",
+        stderr
+    );
+    assert!(output.stdout.is_empty());
+
+    let output = md2html(&args, "Sixteen bytes ok");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"<p>Sixteen bytes ok</p>\n");
+}
+
+#[test]
+fn test_line_ending() {
+    let args = ["--input", "-", "--output", "-"];
+    let output = md2html(&[&args[..], &["--line-ending", "crlf"]].concat(), "# A\nb");
+    assert_eq!(output.stdout, b"<h1>A</h1>\r\n<p>b</p>\r\n");
+    let output = md2html(&args, "# A\nb");
+    assert_eq!(output.stdout, b"<h1>A</h1>\n<p>b</p>\n");
+
+    let output = md2html(&[&args[..], &["--line-ending", "cr"]].concat(), "");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_inline_diagnostic_columns() {
+    let markdown = "# Notes\n\n日本語 **強調 and [リンク](https://example.com\n";
+    let args = [
+        "--input",
+        "-",
+        "--output",
+        "-",
+        "--lint",
+        "--message-format",
+    ];
+    let stderr = |output: Output| String::from_utf8(output.stderr).unwrap();
+
+    let pretty = stderr(md2html(&[&args[..], &["pretty"]].concat(), markdown));
+    assert!(
+        pretty.contains(
+            "  3 | 日本語 **強調 and [リンク](https://example.com\n\
+             \x20   |        ^^\n"
+        ),
+        "{}",
+        pretty
+    );
+    assert!(
+        // 日本語, 強調 and リンク are two columns wide each
+        pretty.contains(&format!("    | {}{}\n", " ".repeat(18), "^".repeat(28))),
+        "{}",
+        pretty
+    );
+
+    let json = stderr(md2html(&[&args[..], &["json"]].concat(), markdown));
+    assert!(
+        json.contains(
+            "\"line\": 3, \"column\": 5, \"length\": 2, \"byte_start\": 10, \"byte_end\": 12"
+        ),
+        "{}",
+        json
+    );
+    assert!(
+        json.contains("\"column\": 14, \"length\": 25, \"byte_start\": 23, \"byte_end\": 54"),
+        "{}",
+        json
+    );
+}
+
+#[test]
+fn test_directory_write_failures_reported() {
+    let dir = temp_dir("write-failures");
+    let input_dir = dir.join("docs");
+    let output_dir = dir.join("site");
+    fs::create_dir_all(&input_dir).unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(input_dir.join(format!("{}.md", name)), "# Page").unwrap();
+    }
+    // A folder where b.html goes makes that page, and only it, fail
+    fs::create_dir_all(output_dir.join("b.html/keep")).unwrap();
+    let report_path = dir.join("report.json");
+
+    let output = md2html(
+        &[
+            "--input",
+            input_dir.to_str().unwrap(),
+            "--output",
+            output_dir.to_str().unwrap(),
+            "--write-report",
+            report_path.to_str().unwrap(),
+        ],
+        "",
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("2 file(s) written, 1 failed:"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("b.html: "), "{}", stderr);
+    assert!(output_dir.join("a.html").is_file());
+    assert!(output_dir.join("c.html").is_file());
+    assert!(!output_dir.join(".b.html.md2html-tmp").exists());
+    let report = fs::read_to_string(&report_path).unwrap();
+    assert!(report.contains("\"aborted\": false"), "{}", report);
+    assert!(report.contains("b.html\", \"error\": "), "{}", report);
+
+    // One failure in a row is already too many
+    let output = md2html(
+        &[
+            "--input",
+            input_dir.to_str().unwrap(),
+            "--output",
+            output_dir.to_str().unwrap(),
+            "--max-write-failures",
+            "1",
+        ],
+        "",
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("aborting after 1 consecutive write failures"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("1 file(s) written, 1 failed:"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_directory_manifest_titles_in_page_nav() {
+    let dir = temp_dir("manifest");
+    let input_dir = dir.join("docs");
+    fs::create_dir_all(input_dir.join("guide")).unwrap();
+    fs::write(
+        input_dir.join("a.md"),
+        "---\ndate: 2024-06-01\n---\n# Start\n",
+    )
+    .unwrap();
+    fs::write(input_dir.join("b.md"), "Intro\n\nSetext *title*\n===\n").unwrap();
+    fs::write(input_dir.join("guide/c.md"), "No title, [back](../a.md)\n").unwrap();
+
+    // The pages are the same whether they're written as they're converted or,
+    // with --strict, once the links are checked
+    let mut pages = Vec::new();
+    for (name, extra) in [("site", "--max-open-files"), ("strict", "--strict")] {
+        let output_dir = dir.join(name);
+        let mut args = vec![
+            "--input",
+            input_dir.to_str().unwrap(),
+            "--output",
+            output_dir.to_str().unwrap(),
+            "--page-nav",
+            "--search-index",
+            extra,
+        ];
+        if extra == "--max-open-files" {
+            args.push("1");
+        }
+        let output = md2html(&args, "");
+        assert!(output.status.success(), "{:?}", output);
+        pages.push(
+            ["a.html", "b.html", "guide/c.html", "search-index.json"]
+                .map(|page| fs::read_to_string(output_dir.join(page)).unwrap()),
+        );
+    }
+    assert_eq!(pages[0], pages[1]);
+    let [_, b, _, search_index] = &pages[0];
+    assert!(b.contains("Start"), "{}", b);
+    assert!(b.contains("guide/c.html"), "{}", b);
+    assert!(
+        search_index.contains("\"title\": \"Setext title\""),
+        "{}",
+        search_index
+    );
+    assert!(
+        search_index.contains("\"title\": \"c\""),
+        "{}",
+        search_index
+    );
+}
+
+#[test]
+fn test_search_index_fixture() {
+    let input_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/search_site");
+    let output_dir = temp_dir("search-index").join("site");
+    let output = md2html(
+        &[
+            "--input",
+            input_dir.to_str().unwrap(),
+            "--output",
+            output_dir.to_str().unwrap(),
+            "--search-index",
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    // The URLs are where the pages are written, the permalink of notes.md included
+    let search_index = fs::read_to_string(output_dir.join("search-index.json")).unwrap();
+    assert_eq!(
+        search_index,
+        r#"[
+  {
+    "title": "Setup",
+    "url": "guide/setup.html",
+    "headings": ["Setup", "Install", "Configure"],
+    "body": "Run cargo install md2html, any version from 1.2 on works. Put an md2html.toml file next to your markdown files."
+  },
+  {
+    "title": "Welcome",
+    "url": "index.html",
+    "headings": ["Welcome"],
+    "body": "Start with the setup guide, or read the release notes."
+  },
+  {
+    "title": "Release notes",
+    "url": "releases/index.html",
+    "headings": ["Next"],
+    "body": "Version 2 adds a search index & page navigation. More output formats."
+  }
+]
+"#
+    );
+    for url in ["guide/setup.html", "index.html", "releases/index.html"] {
+        assert!(output_dir.join(url).is_file(), "{}", url);
+    }
+    assert!(!output_dir.join("notes.html").exists());
+    assert!(!search_index.contains('<'), "{}", search_index);
+}
+
+#[test]
+fn test_diff_against_previous_output() {
+    let dir = temp_dir("diff-against");
+    let (input_dir, old_dir) = (dir.join("docs"), dir.join("site"));
+    fs::create_dir_all(&input_dir).unwrap();
+    for (name, markdown) in [("same", "# Same\n"), ("edited", "# Edited\n\nOld text\n")] {
+        fs::write(input_dir.join(format!("{}.md", name)), markdown).unwrap();
+    }
+    let convert = |extra: &[&str]| {
+        let mut args = vec![
+            "--input",
+            input_dir.to_str().unwrap(),
+            "--output",
+            old_dir.to_str().unwrap(),
+        ];
+        args.extend(extra);
+        md2html(&args, "")
+    };
+    assert!(convert(&[]).status.success());
+    // Line endings and the final newline of the old output don't count
+    let same = fs::read_to_string(old_dir.join("same.html")).unwrap();
+    fs::write(
+        old_dir.join("same.html"),
+        same.trim_end().replace('\n', "\r\n"),
+    )
+    .unwrap();
+
+    fs::write(input_dir.join("edited.md"), "# Edited\n\nNew text\n").unwrap();
+    fs::write(input_dir.join("added.md"), "# Added\n").unwrap();
+    fs::write(old_dir.join("removed.html"), "<p>Removed</p>\n").unwrap();
+    let report_path = dir.join("diff.json");
+    let output = convert(&[
+        "--diff-against",
+        old_dir.to_str().unwrap(),
+        "--report",
+        report_path.to_str().unwrap(),
+    ]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stdout,
+        "added     added.html\n\
+         changed   edited.html\n    \
+         - 3 | <p>Old text</p>\n    \
+         + 3 | <p>New text</p>\n\
+         removed   removed.html\n\
+         identical same.html\n\
+         1 identical, 1 changed, 1 added, 1 removed\n"
+    );
+    // Nothing was written
+    assert!(!old_dir.join("added.html").exists());
+    let report = fs::read_to_string(&report_path).unwrap();
+    assert!(
+        report.contains("{\"path\": \"removed.html\", \"status\": \"removed\"}"),
+        "{}",
+        report
+    );
+
+    let output = md2html(
+        &["diff", old_dir.to_str().unwrap(), old_dir.to_str().unwrap()],
+        "",
+    );
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_emit_anchors_without_sections() {
+    let dir = temp_dir("emit-anchors");
+    let input = dir.join("CHANGELOG.md");
+    fs::write(
+        &input,
+        "# Changelog\n## v1.2.0\n### Fixed\n## v1.1.0\n### Fixed",
+    )
+    .unwrap();
+    let anchors = dir.join("anchors.json");
+    let output = md2html(
+        &[
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            dir.join("CHANGELOG.html").to_str().unwrap(),
+            "--emit-anchors",
+            anchors.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr.contains("[WARNING]"), "{}", stderr);
+
+    // Every id of the map is a section of the page
+    let html = fs::read_to_string(dir.join("CHANGELOG.html")).unwrap();
+    let anchors = fs::read_to_string(anchors).unwrap();
+    for id in ["changelog", "v120", "fixed", "v110", "fixed-1"] {
+        assert!(
+            anchors.contains(&format!("\"id\": \"{}\"", id)),
+            "{}",
+            anchors
+        );
+        assert!(
+            html.contains(&format!("<section id=\"{}\">", id)),
+            "{}",
+            html
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Uses `md2html::Converter` the way programs embedding the library do: one
+//! converter built once and reused for strings, readers and files.
+
+use md2html::renderer::LineEnding;
+use md2html::{ConvertError, Converter, HtmlRenderer, ParserOptions};
+use std::fs::{self, File};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// A fresh temporary folder for the test `name`.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("md2html-converter-{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_builder_settings_reach_the_page() {
+    let converter = Converter::new()
+        .options(ParserOptions {
+            sections: true,
+            ..Default::default()
+        })
+        .standalone(true)
+        .title("Docs")
+        .reproducible(true)
+        .renderer(HtmlRenderer {
+            line_ending: LineEnding::CrLf,
+        });
+    let html = converter.convert_str("# Usage\nRun it.");
+    assert!(html.contains("<title>Docs</title>\r\n"), "{}", html);
+    assert!(html.contains("<section id=\"usage\">\r\n"), "{}", html);
+    assert!(html.ends_with("</html>\r\n"), "{}", html);
+    assert!(!html.replace("\r\n", "").contains('\n'), "{}", html);
+}
+
+#[test]
+fn test_one_converter_for_every_file() {
+    let dir = temp_dir("batch");
+    let converter = Converter::new().standalone(true).reproducible(true);
+    for (name, markdown) in [("intro", "Some *text*"), ("guide/setup", "# Setup\nRun it")] {
+        let input = dir.join(format!("{}.md", name));
+        fs::create_dir_all(input.parent().unwrap()).unwrap();
+        fs::write(&input, markdown).unwrap();
+        let output = dir.join("site").join(format!("{}.html", name));
+        let stats = converter.convert_file(&input, &output).unwrap();
+        assert_eq!(stats.bytes_written as u64, output.metadata().unwrap().len());
+    }
+
+    // The file name is the title of a page without a heading
+    let intro = fs::read_to_string(dir.join("site/intro.html")).unwrap();
+    assert!(intro.contains("<title>intro</title>"), "{}", intro);
+    assert!(intro.contains("<p>Some <i>text</i></p>"), "{}", intro);
+    let setup = fs::read_to_string(dir.join("site/guide/setup.html")).unwrap();
+    assert!(setup.contains("<title>Setup</title>"), "{}", setup);
+}
+
+#[test]
+fn test_reader_writer_matches_convert_str() {
+    let dir = temp_dir("reader-writer");
+    let markdown = "---\ntitle: Notes\n---\n# Notes\n\n- a\n- b\n\n```\nx < y\n```\n";
+    fs::write(dir.join("notes.md"), markdown).unwrap();
+    let converter = Converter::new();
+
+    let mut html = Vec::new();
+    let stats = converter
+        .convert_reader_writer(File::open(dir.join("notes.md")).unwrap(), &mut html)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(html).unwrap(),
+        converter.convert_str(markdown)
+    );
+    assert!(stats.warnings.is_empty(), "{:?}", stats.warnings);
+    assert_eq!(
+        stats.front_matter.unwrap().metadata().title.as_deref(),
+        Some("Notes")
+    );
+
+    // md2html::convert is the same conversion with the default settings
+    let mut converted = Vec::new();
+    md2html::convert(
+        Cursor::new(markdown),
+        &mut converted,
+        &ParserOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(converted).unwrap(),
+        converter.convert_str(markdown)
+    );
+}
+
+#[test]
+fn test_unreadable_input_writes_nothing() {
+    let dir = temp_dir("unreadable");
+    fs::write(dir.join("binary.md"), [b'#', b' ', 0xff, 0xfe]).unwrap();
+    let output = dir.join("site/binary.html");
+
+    let result = Converter::new().convert_file(&dir.join("binary.md"), &output);
+    assert!(matches!(result, Err(ConvertError::Read(_))), "{:?}", result);
+    assert!(!output.exists());
+    let result = Converter::new().convert_file(&dir.join("missing.md"), &output);
+    assert!(matches!(result, Err(ConvertError::Read(_))), "{:?}", result);
+}
+
+#[test]
+fn test_standalone_page_landmarks() {
+    let converter = Converter::new()
+        .options(ParserOptions {
+            toc: true,
+            ..Default::default()
+        })
+        .standalone(true)
+        .header_html("<a href=\"/\">Home</a>")
+        .footer_html("<p>MIT licensed</p>")
+        .reproducible(true);
+    let html = converter.convert_str("# Flags\n\n| Flag | Default |\n|---|---|\n| --toc | off |");
+    let lines: Vec<&str> = html.lines().collect();
+    let position = |line: &str| {
+        lines
+            .iter()
+            .position(|found| *found == line)
+            .unwrap_or_else(|| panic!("{} missing from {}", line, html))
+    };
+
+    // The skip link is the first thing of the body and targets the main content
+    let skip_link = position("<a class=\"visually-hidden\" href=\"#content\">Skip to content</a>");
+    assert_eq!(lines[skip_link - 1], "<body>");
+    let main = position("<main id=\"content\">");
+    let toc = position("<nav class=\"toc\" aria-label=\"Table of contents\">");
+    assert!(position("<header>") < main && main < toc, "{}", html);
+    assert!(toc < position("</main>") && position("</main>") < position("<footer>"));
+    assert!(
+        html.contains("<tr><th scope=\"col\">Flag</th><th scope=\"col\">Default</th></tr>"),
+        "{}",
+        html
+    );
+}
@@ -0,0 +1,166 @@
+//! Converts the markdown documents of `tests/fixtures/` and compares the result with
+//! the HTML files next to them.
+//!
+//! When the output changes on purpose, regenerate the expected files with
+//! `MD2HTML_UPDATE_GOLDEN=1 cargo test --test golden` and review their diff.
+
+use md2html::site_diff::{align_lines, LineOp};
+use md2html::soft_breaks::SoftBreak;
+use md2html::{Converter, ParserOptions, WarningKind};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Set to rewrite the golden files instead of comparing against them.
+const UPDATE_ENV: &str = "MD2HTML_UPDATE_GOLDEN";
+
+/// Unchanged lines shown around every difference.
+const CONTEXT_LINES: usize = 3;
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// The converter of a fixture, so the documents also cover some of the options.
+fn converter(name: &str) -> Converter {
+    match name {
+        "blog_post" => Converter::new().standalone(true).reproducible(true),
+        "spec" => Converter::new().options(ParserOptions {
+            sections: true,
+            ..Default::default()
+        }),
+        "chat_log" => Converter::new().options(ParserOptions {
+            soft_break_in_quotes: Some(SoftBreak::Break),
+            ..Default::default()
+        }),
+        _ => Converter::new(),
+    }
+}
+
+/// Checks the fixture `name`.md against `name`.html, or rewrites `name`.html in
+/// update mode.
+fn check_fixture(name: &str) {
+    let dir = fixtures_dir();
+    let markdown = fs::read_to_string(dir.join(format!("{}.md", name))).unwrap();
+    // The code blocks of every fixture are checked against their source too
+    let converter = converter(name);
+    let converter = converter.clone().options(ParserOptions {
+        verify_code_blocks: true,
+        ..converter.parser_options().clone()
+    });
+    let document = converter.convert_document(&markdown, None);
+    let mismatches: Vec<String> = document
+        .warnings
+        .iter()
+        .filter(|warning| warning.kind == WarningKind::CodeBlock)
+        .map(|warning| warning.to_string())
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    let actual: String = converter
+        .render(&document)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect();
+    let golden_path = dir.join(format!("{}.html", name));
+
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        fs::write(&golden_path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden_path).unwrap_or_else(|err| {
+        panic!(
+            "can't read {}: {}\nrun `{}=1 cargo test --test golden` to create it",
+            golden_path.display(),
+            err,
+            UPDATE_ENV
+        )
+    });
+    if actual != expected {
+        panic!(
+            "{} doesn't match the converted {}.md (- expected, + actual):\n{}\n\
+             run `{}=1 cargo test --test golden` if the change is intended",
+            golden_path.display(),
+            name,
+            diff(&expected, &actual),
+            UPDATE_ENV
+        );
+    }
+}
+
+/// A line diff of `expected` and `actual` (see `site_diff::align_lines`), showing
+/// the removed (`-`) and added (`+`) lines with a few unchanged lines around them.
+fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let lines: Vec<(char, usize, &str)> = align_lines(&old, &new)
+        .into_iter()
+        .map(|op| match op {
+            LineOp::Same(i, _) => (' ', i + 1, old[i]),
+            LineOp::Removed(i) => ('-', i + 1, old[i]),
+            LineOp::Added(j) => ('+', j + 1, new[j]),
+        })
+        .collect();
+
+    let changed: Vec<usize> = (0..lines.len()).filter(|&k| lines[k].0 != ' ').collect();
+    let shown = |k: usize| {
+        changed
+            .iter()
+            .any(|&c| k + CONTEXT_LINES >= c && k <= c + CONTEXT_LINES)
+    };
+    let mut output = String::new();
+    let mut skipped = false;
+    for (k, (sign, line_number, line)) in lines.iter().enumerate() {
+        if !shown(k) {
+            skipped = true;
+            continue;
+        }
+        if skipped && !output.is_empty() {
+            output.push_str("...\n");
+        }
+        skipped = false;
+        output.push_str(&format!("{} {:>4} | {}\n", sign, line_number, line));
+    }
+    if expected.ends_with('\n') != actual.ends_with('\n') {
+        output.push_str("(the files differ in their final newline)\n");
+    }
+    output
+}
+
+#[test]
+fn golden_readme() {
+    check_fixture("readme");
+}
+
+#[test]
+fn golden_blog_post() {
+    check_fixture("blog_post");
+}
+
+#[test]
+fn golden_spec() {
+    check_fixture("spec");
+}
+
+#[test]
+fn golden_chat_log() {
+    check_fixture("chat_log");
+}
+
+#[test]
+fn test_diff_is_readable() {
+    let expected = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    let actual = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj";
+    assert_eq!(
+        diff(expected, actual).lines().collect::<Vec<&str>>(),
+        [
+            "     2 | b",
+            "     3 | c",
+            "     4 | d",
+            "-    5 | e",
+            "+    5 | E",
+            "     6 | f",
+            "     7 | g",
+            "     8 | h",
+            "(the files differ in their final newline)",
+        ]
+    );
+}